@@ -0,0 +1,189 @@
+// Tokens for the write API. A token is 32 random bytes, hex encoded, and only its SHA-256 is
+// stored, so a leaked site directory doesn't leak working tokens. Tokens are random enough that
+// an unsalted hash is as good as anything slower.
+// Each token has its own rate limit, counted in one minute windows. Like cooldowns, the counts are
+// only kept in memory.
+use crate::core::storage::config::Config;
+use crate::core::storage::site_storage;
+use crate::core::structs::api_token::{ApiToken, Scope};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::string::String;
+use std::time::{Duration, SystemTime};
+use std::vec::Vec;
+
+const TOKEN_LENGTH: usize = 32;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+pub(crate) fn hash_token(token: &str) -> String {
+    return hex::encode(Sha256::digest(token.as_bytes()));
+}
+
+pub(crate) fn find_token<'a>(tokens: &'a [ApiToken], token: &str) -> Option<&'a ApiToken> {
+    let hash: String = hash_token(token);
+    return tokens.iter().find(|known| *known.get_hash() == hash);
+}
+
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    // names end up in post owners and cooldown keys, so they're kept plain
+    return !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+}
+
+pub(crate) fn add_token(
+    config: &Config,
+    name: &str,
+    scopes: Vec<Scope>,
+    rate_limit: u32,
+) -> Result<String, Error> {
+    // returns the new token, which can't be had again afterwards. adding a name that's already
+    // there replaces its token
+    if !is_valid_name(name) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "token names are letters, digits, - and _",
+        ));
+    }
+
+    let mut token: [u8; TOKEN_LENGTH] = [0; TOKEN_LENGTH];
+    getrandom::fill(&mut token).map_err(Error::other)?;
+    let token: String = hex::encode(token);
+
+    let mut tokens: Vec<ApiToken> = site_storage::read_api_tokens(config)?;
+    tokens.retain(|other| other.get_name() != name);
+    tokens.push(ApiToken::new(
+        name.to_string(),
+        hash_token(&token),
+        scopes,
+        rate_limit,
+    ));
+    site_storage::write_api_tokens(config, &tokens)?;
+
+    return Ok(token);
+}
+
+pub(crate) fn remove_token(config: &Config, name: &str) -> Result<bool, Error> {
+    let mut tokens: Vec<ApiToken> = site_storage::read_api_tokens(config)?;
+    let length: usize = tokens.len();
+    tokens.retain(|other| other.get_name() != name);
+    site_storage::write_api_tokens(config, &tokens)?;
+
+    return Ok(tokens.len() != length);
+}
+
+// how many requests each token has made in its current window
+pub(crate) struct RateLimits {
+    windows: HashMap<String, (SystemTime, u32)>, // by token name, when the window started
+}
+
+impl RateLimits {
+    pub fn new() -> RateLimits {
+        let rate_limits: RateLimits = RateLimits {
+            windows: HashMap::new(),
+        };
+
+        return rate_limits;
+    }
+
+    pub fn check(&mut self, token: &ApiToken, now: SystemTime) -> Result<(), u64> {
+        // counts the request if it's allowed, otherwise gives the seconds until it would be
+        if token.get_rate_limit() == 0 {
+            return Ok(());
+        }
+
+        let window: &mut (SystemTime, u32) = self
+            .windows
+            .entry(token.get_name().clone())
+            .or_insert((now, 0));
+        let elapsed: Duration = now.duration_since(window.0).unwrap_or_default();
+        if elapsed >= RATE_WINDOW {
+            *window = (now, 0);
+        }
+
+        if window.1 >= token.get_rate_limit() {
+            let remaining: Duration = RATE_WINDOW.saturating_sub(elapsed);
+            return Err(remaining.as_secs() + (remaining.subsec_nanos() > 0) as u64);
+        }
+        window.1 += 1;
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::fixtures::TestRoot;
+
+    fn test_config(name: &str) -> TestRoot {
+        let config = TestRoot::new("api_tokens", name);
+        config.connect().unwrap();
+        return config;
+    }
+
+    fn token(rate_limit: u32) -> ApiToken {
+        return ApiToken::new(
+            "bot".to_string(),
+            hash_token("token"),
+            vec![Scope::Post],
+            rate_limit,
+        );
+    }
+
+    #[test]
+    fn test_hash_token() {
+        assert_eq!(
+            hash_token("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_add_find_remove() {
+        let config = test_config("add_find_remove");
+        let first = add_token(&config, "bot", vec![Scope::Post], 10).unwrap();
+        assert_eq!(first.len(), TOKEN_LENGTH * 2);
+
+        let tokens = site_storage::read_api_tokens(&config).unwrap();
+        assert_eq!(find_token(&tokens, &first).unwrap().get_name(), "bot");
+        assert!(find_token(&tokens, "guess").is_none());
+        assert!(!tokens[0].get_hash().contains(&first));
+
+        // adding the same name again replaces the old token
+        let second = add_token(&config, "bot", vec![Scope::Ban], 0).unwrap();
+        let tokens = site_storage::read_api_tokens(&config).unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert!(find_token(&tokens, &first).is_none());
+        assert!(find_token(&tokens, &second).unwrap().has_scope(Scope::Ban));
+
+        assert_eq!(
+            add_token(&config, "a;b", Vec::new(), 0).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert!(remove_token(&config, "bot").unwrap());
+        assert!(!remove_token(&config, "bot").unwrap());
+    }
+
+    #[test]
+    fn test_rate_limits() {
+        let mut rate_limits = RateLimits::new();
+        let now = SystemTime::now();
+        let limited = token(2);
+
+        assert_eq!(rate_limits.check(&limited, now), Ok(()));
+        assert_eq!(rate_limits.check(&limited, now), Ok(()));
+        assert_eq!(
+            rate_limits.check(&limited, now + Duration::from_millis(20500)),
+            Err(40)
+        );
+        assert_eq!(rate_limits.check(&limited, now + RATE_WINDOW), Ok(()));
+
+        let unlimited = token(0);
+        for _ in 0..100 {
+            assert_eq!(rate_limits.check(&unlimited, now), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,666 @@
+// Higher level attachment handling, tying the upload checks and media processing to storage.
+// This is the upload path: validate, strip metadata, check it against the banned images, store
+// the original, store its thumbnails, then attach it.
+use crate::core::media::file_type::FileType;
+use crate::core::media::perceptual;
+use crate::core::media::sanitize;
+use crate::core::media::thumbnail;
+use crate::core::media::validation::{self, UploadError, ValidUpload};
+use crate::core::storage::attachment_storage;
+use crate::core::storage::config::Config;
+use crate::core::structs::attachment::Attachment;
+use crate::core::structs::banned_images::BannedImages;
+use crate::core::structs::board::Board;
+use crate::core::structs::board_config::BoardConfig;
+use crate::core::structs::post::Post;
+use image::DynamicImage;
+use std::collections::HashMap;
+use std::io::Error;
+use std::string::String;
+use std::vec::Vec;
+
+// a file as it came in from the poster, before anything has been checked
+pub(crate) struct UploadedFile<'a> {
+    pub original_name: &'a str,
+    pub data: &'a [u8],
+    pub spoiler: bool,
+}
+
+pub(crate) fn attach(
+    config: &Config,
+    board: &Board,
+    banned_images: &BannedImages,
+    post: &mut Post,
+    post_id: u64,
+    file: &UploadedFile,
+) -> Result<(), UploadError> {
+    let board_config: &BoardConfig = board.get_config();
+    let limit: u8 = board_config.get_max_files();
+    if post.get_attachments().len() >= limit as usize {
+        return Err(UploadError::TooManyFiles { limit });
+    }
+
+    let original_name: &str = file.original_name;
+    let mut upload: ValidUpload = validation::validate(original_name, file.data, board_config)?;
+    let file_type: FileType = upload.get_file_type();
+
+    let stripped: Vec<u8>;
+    let data: &[u8] = match board_config.get_strip_metadata() {
+        true => {
+            stripped = sanitize::strip_metadata(file.data, file_type)
+                .map_err(|_| UploadError::Unreadable(file_type))?;
+            // validated again, so the size and hash describe what is actually stored
+            upload = validation::validate(original_name, &stripped, board_config)?;
+            &stripped
+        }
+        false => file.data,
+    };
+
+    // images are decoded once, for both the banned image check and the thumbnails.
+    // thumbnails are made before anything is written, so a bad image never leaves files behind
+    let mut perceptual_hash: Option<u64> = None;
+    let mut thumbnails: Vec<(thumbnail::ThumbnailSize, Vec<u8>)> = Vec::new();
+    if file_type.is_image() {
+        let image: DynamicImage = file_type
+            .decode(data)
+            .map_err(|_| UploadError::Unreadable(file_type))?;
+
+        let hash: u64 = perceptual::dhash(&image);
+        if banned_images.find_match(hash).is_some() {
+            return Err(UploadError::Banned);
+        }
+
+        perceptual_hash = Some(hash);
+        thumbnails = thumbnail::generate_all(&image, board_config)
+            .map_err(|_| UploadError::Unreadable(file_type))?;
+    }
+
+    // stored names only need to be unique per board, and post IDs already are
+    let stored_name: String = format!(
+        "{}-{}.{}",
+        post_id,
+        post.get_attachments().len(),
+        file_type.get_extension()
+    );
+
+    store(config, board.get_name(), &stored_name, data, &thumbnails)
+        .map_err(|error| UploadError::Storage(error.kind()))?;
+    if let Some(hash) = perceptual_hash {
+        attachment_storage::write_perceptual_hash(config, board.get_name(), &stored_name, hash)
+            .map_err(|error| UploadError::Storage(error.kind()))?;
+    }
+
+    let mut attachment: Attachment =
+        upload.into_attachment(original_name.to_string(), stored_name, file.spoiler);
+    attachment.modify_perceptual_hash(perceptual_hash);
+    post.add_attachment(attachment, limit);
+
+    return Ok(());
+}
+
+fn store(
+    config: &Config,
+    board: &str,
+    stored_name: &str,
+    data: &[u8],
+    thumbnails: &[(thumbnail::ThumbnailSize, Vec<u8>)],
+) -> Result<(), Error> {
+    attachment_storage::write_attachment(config, board, stored_name, data)?;
+
+    for (size, thumbnail_data) in thumbnails {
+        let written =
+            attachment_storage::write_thumbnail(config, board, stored_name, *size, thumbnail_data);
+        if let Err(error) = written {
+            // don't leave an original behind without its thumbnails
+            attachment_storage::remove_attachment(config, board, stored_name)?;
+            return Err(error);
+        }
+    }
+
+    return Ok(());
+}
+
+pub(crate) fn regenerate_thumbnails(
+    config: &Config,
+    board: &Board,
+) -> Result<(u64, Vec<String>), Error> {
+    // rebuilds every thumbnail on a board, for when the thumbnail bounds in its config change.
+    // returns how many attachments were regenerated, along with any that couldn't be decoded
+    let mut regenerated: u64 = 0;
+    let mut failed: Vec<String> = Vec::new();
+
+    for stored_name in attachment_storage::list_attachments(config, board.get_name())? {
+        let data: Vec<u8> =
+            attachment_storage::read_attachment(config, board.get_name(), &stored_name)?;
+        let file_type: FileType = match FileType::detect(&data) {
+            Some(file_type) if file_type.is_image() => file_type,
+            _ => continue,
+        };
+
+        let thumbnails = match file_type
+            .decode(&data)
+            .and_then(|image| thumbnail::generate_all(&image, board.get_config()))
+        {
+            Ok(thumbnails) => thumbnails,
+            Err(_) => {
+                failed.push(stored_name);
+                continue;
+            }
+        };
+
+        for (size, thumbnail_data) in thumbnails {
+            attachment_storage::write_thumbnail(
+                config,
+                board.get_name(),
+                &stored_name,
+                size,
+                &thumbnail_data,
+            )?;
+        }
+        regenerated += 1;
+    }
+
+    return Ok((regenerated, failed));
+}
+
+pub(crate) fn get_perceptual_hash(
+    config: &Config,
+    board: &str,
+    stored_name: &str,
+) -> Result<Option<u64>, Error> {
+    let hashes: HashMap<String, u64> = attachment_storage::read_perceptual_hashes(config, board)?;
+    return Ok(hashes.get(stored_name).copied());
+}
+
+pub(crate) fn find_similar(
+    config: &Config,
+    boards: &[String],
+    hash: u64,
+    threshold: u32,
+) -> Result<Vec<(String, String, u32)>, Error> {
+    // every indexed image on the given boards within `threshold` bits of the hash, closest first.
+    // each match is the board, the stored name, and the distance
+    let mut similar: Vec<(String, String, u32)> = Vec::new();
+
+    for board in boards {
+        for (stored_name, other) in attachment_storage::read_perceptual_hashes(config, board)? {
+            let distance: u32 = perceptual::distance(hash, other);
+            if distance <= threshold {
+                similar.push((board.clone(), stored_name, distance));
+            }
+        }
+    }
+
+    similar.sort_by(|a, b| (a.2, &a.0, &a.1).cmp(&(b.2, &b.0, &b.1)));
+    return Ok(similar);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::media::thumbnail::ThumbnailSize;
+    use crate::core::storage::board_storage;
+    use crate::core::storage::fixtures::TestRoot;
+    use image::{DynamicImage, ImageFormat};
+    use std::io::Cursor;
+
+    const BOARD: &str = "test";
+    const POST_ID: u64 = 12;
+
+    fn test_config(name: &str) -> TestRoot {
+        return TestRoot::with_boards("attachments", name, &[BOARD]);
+    }
+
+    fn post_init() -> Post {
+        let a_post = Post::new(
+            "me".to_string(),
+            "Anonymous".to_string(),
+            None,
+            "look at this".to_string(),
+            POST_ID,
+        );
+        return a_post;
+    }
+
+    fn file<'a>(original_name: &'a str, data: &'a [u8], spoiler: bool) -> UploadedFile<'a> {
+        return UploadedFile {
+            original_name,
+            data,
+            spoiler,
+        };
+    }
+
+    fn blocks(width: u32, height: u32) -> Vec<u8> {
+        // a few big blocks, which survive being resized
+        let image = image::GrayImage::from_fn(width, height, |x, y| {
+            let level = (x * 5 / width + y * 3 / height) % 2 * 255;
+            image::Luma([level as u8])
+        });
+        let mut data: Vec<u8> = Vec::new();
+        DynamicImage::ImageLuma8(image)
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        return data;
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        DynamicImage::new_rgb8(width, height)
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        return data;
+    }
+
+    fn thumbnail_dimensions(config: &Config, stored_name: &str, size: ThumbnailSize) -> (u32, u32) {
+        let data = attachment_storage::read_thumbnail(config, BOARD, stored_name, size).unwrap();
+        let image = image::load_from_memory(&data).unwrap();
+        return (image.width(), image.height());
+    }
+
+    #[test]
+    fn test_attach_image() {
+        let config = test_config("attach_image");
+        let board = board_storage::read_board(&config, BOARD).unwrap();
+        let mut post = post_init();
+        let data = png(500, 500);
+
+        attach(
+            &config,
+            &board,
+            &BannedImages::new(0),
+            &mut post,
+            POST_ID,
+            &file("cat.png", &data, false),
+        )
+        .unwrap();
+
+        let attachment = &post.get_attachments()[0];
+        assert_eq!(attachment.get_stored_name(), "12-0.png");
+        assert_eq!(attachment.get_original_name(), "cat.png");
+        assert_eq!(
+            attachment_storage::read_attachment(&config, BOARD, "12-0.png").unwrap(),
+            data
+        );
+        assert_eq!(
+            thumbnail_dimensions(&config, "12-0.png", ThumbnailSize::Op),
+            (250, 250)
+        );
+        assert_eq!(
+            thumbnail_dimensions(&config, "12-0.png", ThumbnailSize::Reply),
+            (125, 125)
+        );
+    }
+
+    #[test]
+    fn test_attach_non_image() {
+        let config = test_config("attach_non_image");
+        let board = board_storage::read_board(&config, BOARD).unwrap();
+        let mut post = post_init();
+
+        attach(
+            &config,
+            &board,
+            &BannedImages::new(0),
+            &mut post,
+            POST_ID,
+            &file("paper.pdf", b"%PDF-1.7\n", true),
+        )
+        .unwrap();
+
+        assert!(post.get_attachments()[0].get_spoiler());
+        assert_eq!(
+            attachment_storage::list_attachments(&config, BOARD).unwrap(),
+            vec!["12-0.pdf"]
+        );
+        assert!(
+            attachment_storage::read_thumbnail(&config, BOARD, "12-0.pdf", ThumbnailSize::Op)
+                .is_err()
+        );
+    }
+
+    fn png_with_text() -> Vec<u8> {
+        // a tEXt chunk spliced in right after the IHDR chunk
+        let data = png(1, 1);
+        let text: &[u8] = b"\0\0\0\x0etEXtComment\0secret\x64\xaa\xc5\x29";
+        return [&data[..33], text, &data[33..]].concat();
+    }
+
+    #[test]
+    fn test_attach_strips_metadata() {
+        let config = test_config("strips_metadata");
+        let board = board_storage::read_board(&config, BOARD).unwrap();
+        let mut post = post_init();
+        let data = png_with_text();
+
+        attach(
+            &config,
+            &board,
+            &BannedImages::new(0),
+            &mut post,
+            POST_ID,
+            &file("cat.png", &data, false),
+        )
+        .unwrap();
+
+        let stored = attachment_storage::read_attachment(&config, BOARD, "12-0.png").unwrap();
+        assert_eq!(stored, png(1, 1));
+        assert_eq!(post.get_attachments()[0].get_size(), stored.len() as u64);
+        assert_eq!(
+            *post.get_attachments()[0].get_hash(),
+            validation::hash(&stored)
+        );
+    }
+
+    #[test]
+    fn test_attach_keeps_metadata() {
+        let config = test_config("keeps_metadata");
+        let mut board = board_storage::read_board(&config, BOARD).unwrap();
+        let mut board_config = board.get_config().clone();
+        board_config.modify_strip_metadata(false);
+        board.modify_config(board_config);
+        let mut post = post_init();
+        let data = png_with_text();
+
+        attach(
+            &config,
+            &board,
+            &BannedImages::new(0),
+            &mut post,
+            POST_ID,
+            &file("cat.png", &data, false),
+        )
+        .unwrap();
+
+        let stored = attachment_storage::read_attachment(&config, BOARD, "12-0.png").unwrap();
+        assert_eq!(stored, data);
+    }
+
+    #[test]
+    fn test_attach_rejected() {
+        let config = test_config("attach_rejected");
+        let board = board_storage::read_board(&config, BOARD).unwrap();
+        let mut post = post_init();
+
+        let result = attach(
+            &config,
+            &board,
+            &BannedImages::new(0),
+            &mut post,
+            POST_ID,
+            &file("cat.jpg", &png(1, 1), false),
+        );
+        assert!(matches!(result, Err(UploadError::ExtensionMismatch { .. })));
+        assert!(post.get_attachments().is_empty());
+        assert!(attachment_storage::list_attachments(&config, BOARD)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_attach_too_many() {
+        let config = test_config("attach_too_many");
+        let mut board = board_storage::read_board(&config, BOARD).unwrap();
+        let mut board_config = board.get_config().clone();
+        board_config.modify_max_files(1);
+        board.modify_config(board_config);
+        let mut post = post_init();
+
+        attach(
+            &config,
+            &board,
+            &BannedImages::new(0),
+            &mut post,
+            POST_ID,
+            &file("a.png", &png(1, 1), false),
+        )
+        .unwrap();
+        assert_eq!(
+            attach(
+                &config,
+                &board,
+                &BannedImages::new(0),
+                &mut post,
+                POST_ID,
+                &file("b.png", &png(1, 1), false)
+            ),
+            Err(UploadError::TooManyFiles { limit: 1 })
+        );
+        assert_eq!(post.get_attachments().len(), 1);
+    }
+
+    #[test]
+    fn test_regenerate_thumbnails() {
+        let config = test_config("regenerate");
+        let mut board = board_storage::read_board(&config, BOARD).unwrap();
+        let mut post = post_init();
+        attach(
+            &config,
+            &board,
+            &BannedImages::new(0),
+            &mut post,
+            POST_ID,
+            &file("cat.png", &png(500, 500), false),
+        )
+        .unwrap();
+        attach(
+            &config,
+            &board,
+            &BannedImages::new(0),
+            &mut post,
+            POST_ID,
+            &file("paper.pdf", b"%PDF-1.7\n", false),
+        )
+        .unwrap();
+        attachment_storage::write_attachment(
+            &config,
+            BOARD,
+            "13-0.png",
+            b"\x89PNG\r\n\x1a\nbroken",
+        )
+        .unwrap();
+
+        let mut board_config = board.get_config().clone();
+        board_config.modify_op_thumbnail_bound(100);
+        board_config.modify_reply_thumbnail_bound(50);
+        board.modify_config(board_config);
+
+        let (regenerated, failed) = regenerate_thumbnails(&config, &board).unwrap();
+        assert_eq!(regenerated, 1);
+        assert_eq!(failed, vec!["13-0.png".to_string()]);
+        assert_eq!(
+            thumbnail_dimensions(&config, "12-0.png", ThumbnailSize::Op),
+            (100, 100)
+        );
+        assert_eq!(
+            thumbnail_dimensions(&config, "12-0.png", ThumbnailSize::Reply),
+            (50, 50)
+        );
+    }
+
+    #[test]
+    fn test_attach_records_perceptual_hash() {
+        let config = test_config("perceptual_hash");
+        let board = board_storage::read_board(&config, BOARD).unwrap();
+        let mut post = post_init();
+        let data = blocks(64, 64);
+
+        attach(
+            &config,
+            &board,
+            &BannedImages::new(0),
+            &mut post,
+            POST_ID,
+            &file("a.png", &data, false),
+        )
+        .unwrap();
+        attach(
+            &config,
+            &board,
+            &BannedImages::new(0),
+            &mut post,
+            POST_ID,
+            &file("b.pdf", b"%PDF-1.7\n", false),
+        )
+        .unwrap();
+
+        let hash = post.get_attachments()[0].get_perceptual_hash();
+        assert!(hash.is_some());
+        assert_eq!(post.get_attachments()[1].get_perceptual_hash(), None);
+        assert_eq!(
+            get_perceptual_hash(&config, BOARD, "12-0.png").unwrap(),
+            hash
+        );
+        assert_eq!(
+            get_perceptual_hash(&config, BOARD, "12-1.pdf").unwrap(),
+            None
+        );
+    }
+
+    fn sideways_jpeg(width: u32, height: u32) -> (Vec<u8>, DynamicImage) {
+        // a JPEG with an EXIF orientation of 6, the way phones store portrait photos, along
+        // with the image as it's meant to be shown
+        let image = image::load_from_memory(&blocks(width, height)).unwrap();
+        let mut jpeg: Vec<u8> = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+            .unwrap();
+        let exif: &[u8] =
+            b"Exif\0\0MM\0\x2A\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0\x06\0\0\0\0\0\0";
+        let app1 = [
+            &[0xFF, 0xE1],
+            &(exif.len() as u16 + 2).to_be_bytes()[..],
+            exif,
+        ]
+        .concat();
+
+        let shown = FileType::Jpeg.decode(&jpeg).unwrap().rotate90();
+        return ([&jpeg[..2], &app1, &jpeg[2..]].concat(), shown);
+    }
+
+    #[test]
+    fn test_attach_applies_orientation() {
+        // the stored file keeps its orientation, everything made from it is upright
+        let config = test_config("orientation");
+        let mut board = board_storage::read_board(&config, BOARD).unwrap();
+        let mut post = post_init();
+        let (data, shown) = sideways_jpeg(400, 200);
+
+        attach(
+            &config,
+            &board,
+            &BannedImages::new(0),
+            &mut post,
+            POST_ID,
+            &file("portrait.jpg", &data, false),
+        )
+        .unwrap();
+
+        assert_eq!(post.get_attachments()[0].get_dimensions(), Some((200, 400)));
+        assert_eq!(
+            post.get_attachments()[0].get_perceptual_hash(),
+            Some(perceptual::dhash(&shown))
+        );
+        assert_eq!(
+            thumbnail_dimensions(&config, "12-0.jpg", ThumbnailSize::Op),
+            (125, 250)
+        );
+
+        let mut board_config = board.get_config().clone();
+        board_config.modify_op_thumbnail_bound(100);
+        board.modify_config(board_config);
+        regenerate_thumbnails(&config, &board).unwrap();
+        assert_eq!(
+            thumbnail_dimensions(&config, "12-0.jpg", ThumbnailSize::Op),
+            (50, 100)
+        );
+    }
+
+    #[test]
+    fn test_attach_banned() {
+        let config = test_config("banned");
+        let board = board_storage::read_board(&config, BOARD).unwrap();
+        let mut post = post_init();
+        let mut banned_images = BannedImages::new(8);
+        let original = FileType::Png.decode(&blocks(64, 64)).unwrap();
+        banned_images.add_hash(perceptual::dhash(&original), "spam".to_string());
+
+        // a resized copy is still caught
+        let result = attach(
+            &config,
+            &board,
+            &banned_images,
+            &mut post,
+            POST_ID,
+            &file("a.png", &blocks(200, 200), false),
+        );
+        assert_eq!(result, Err(UploadError::Banned));
+        assert!(post.get_attachments().is_empty());
+        assert!(attachment_storage::list_attachments(&config, BOARD)
+            .unwrap()
+            .is_empty());
+
+        attach(
+            &config,
+            &board,
+            &banned_images,
+            &mut post,
+            POST_ID,
+            &file("b.png", &png(64, 64), false),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_find_similar() {
+        let config = test_config("find_similar");
+        board_storage::initialize(&config, "other").unwrap();
+        let board = board_storage::read_board(&config, BOARD).unwrap();
+        let other = board_storage::read_board(&config, "other").unwrap();
+        let mut post = post_init();
+
+        attach(
+            &config,
+            &board,
+            &BannedImages::new(0),
+            &mut post,
+            POST_ID,
+            &file("a.png", &blocks(64, 64), false),
+        )
+        .unwrap();
+        attach(
+            &config,
+            &board,
+            &BannedImages::new(0),
+            &mut post,
+            POST_ID,
+            &file("b.png", &png(64, 64), false),
+        )
+        .unwrap();
+        attach(
+            &config,
+            &other,
+            &BannedImages::new(0),
+            &mut post,
+            3,
+            &file("c.png", &blocks(128, 128), false),
+        )
+        .unwrap();
+
+        let hash = post.get_attachments()[0].get_perceptual_hash().unwrap();
+        let boards = vec![BOARD.to_string(), "other".to_string()];
+        let similar = find_similar(&config, &boards, hash, 8).unwrap();
+
+        // the scaled up copy hashes identically, so ties are ordered by board then name
+        assert_eq!(
+            similar,
+            vec![
+                ("other".to_string(), "3-2.png".to_string(), 0),
+                (BOARD.to_string(), "12-0.png".to_string(), 0)
+            ]
+        );
+        assert_eq!(
+            find_similar(&config, &boards[..1], hash, 8).unwrap().len(),
+            1
+        );
+    }
+}
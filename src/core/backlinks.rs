@@ -0,0 +1,262 @@
+// Backlinks, the reverse of quote links. When a post quotes >>123, post 123 gets a backlink to it.
+// Backlinks are stored on the quoted post, so reading a post is enough to show who replied to it,
+// and they work the same within a thread, across threads, and across boards.
+// Deleted posts don't quote anything, so deleting a post takes its backlinks away again.
+use crate::core::markup::parser::{self, Node};
+use crate::core::markup::render::LinkResolver;
+use crate::core::storage::config::Config;
+use crate::core::storage::post_storage;
+use crate::core::structs::post::Post;
+use std::io::Error;
+use std::string::String;
+use std::vec::Vec;
+
+pub(crate) fn get_quoted(board: &str, text: &str) -> Vec<(String, u64)> {
+    // every post the text links to, as board and post ID, without duplicates
+    let mut quoted: Vec<(String, u64)> = Vec::new();
+    collect_quoted(board, &parser::parse(text), &mut quoted);
+    return quoted;
+}
+
+fn collect_quoted(board: &str, nodes: &[Node], quoted: &mut Vec<(String, u64)>) {
+    for node in nodes {
+        let target: (String, u64) = match node {
+            Node::PostLink(post) => (board.to_string(), *post),
+            Node::BoardLink {
+                board: other,
+                post: Some(post),
+            } => (other.clone(), *post),
+            Node::Greentext(children) | Node::Spoiler(children) => {
+                collect_quoted(board, children, quoted);
+                continue;
+            }
+            _ => continue,
+        };
+
+        if !quoted.contains(&target) {
+            quoted.push(target);
+        }
+    }
+}
+
+pub(crate) fn link_post(config: &Config, board: &str, id: u64, post: &Post) -> Result<(), Error> {
+    // for a new or undeleted post: adds a backlink to every existing post it quotes
+    if post.get_deleted() {
+        return Ok(());
+    }
+
+    for (target_board, target) in get_quoted(board, post.get_text()) {
+        update_target(config, &target_board, target, (board, id), |quoted| {
+            quoted.add_backlink(board.to_string(), id)
+        })?;
+    }
+
+    return Ok(());
+}
+
+pub(crate) fn unlink_post(config: &Config, board: &str, id: u64, text: &str) -> Result<(), Error> {
+    // for a deleted post, or the old text of an edited one: takes its backlinks away again
+    for (target_board, target) in get_quoted(board, text) {
+        update_target(config, &target_board, target, (board, id), |quoted| {
+            quoted.remove_backlink(board, id)
+        })?;
+    }
+
+    return Ok(());
+}
+
+pub(crate) fn relink_post(
+    config: &Config,
+    board: &str,
+    id: u64,
+    old_text: &str,
+    post: &Post,
+) -> Result<(), Error> {
+    // for an edited post, anything still quoted is just linked again
+    unlink_post(config, board, id, old_text)?;
+    return link_post(config, board, id, post);
+}
+
+fn update_target(
+    config: &Config,
+    board: &str,
+    target: u64,
+    source: (&str, u64),
+    update: impl Fn(&mut Post) -> bool,
+) -> Result<(), Error> {
+    // posts quoting themselves, or posts that don't exist, don't get backlinks
+    if (board, target) == source || !post_storage::post_exists(config, board, target) {
+        return Ok(());
+    }
+
+    let mut quoted: Post = post_storage::read_post(config, board, target)?;
+    if update(&mut quoted) {
+        post_storage::write_post(config, board, target, &quoted)?;
+    }
+
+    return Ok(());
+}
+
+// resolves post links against storage, for rendering stored posts
+pub(crate) struct StorageLinks<'a> {
+    config: &'a Config,
+}
+
+impl<'a> StorageLinks<'a> {
+    pub fn new(config: &'a Config) -> StorageLinks<'a> {
+        return StorageLinks { config };
+    }
+}
+
+impl LinkResolver for StorageLinks<'_> {
+    fn find_thread(&self, board: &str, post: u64) -> Option<u64> {
+        if !self.board_exists(board) || !post_storage::post_exists(self.config, board, post) {
+            return None;
+        }
+
+        return match post_storage::read_post(self.config, board, post) {
+            Ok(found) if !found.get_deleted() => Some(found.get_parent()),
+            _ => None,
+        };
+    }
+
+    fn board_exists(&self, board: &str) -> bool {
+        // board names in links are only ever alphanumeric, but they still end up as paths
+        if board.is_empty() || !board.chars().all(char::is_alphanumeric) {
+            return false;
+        }
+        return self.config.get_board_path(board).is_dir();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::markup::render;
+    use crate::core::storage::fixtures::TestRoot;
+    use crate::core::structs::revision::Editor;
+
+    const BOARD: &str = "test";
+    const OTHER: &str = "\u{3B1}";
+
+    fn test_config(name: &str) -> TestRoot {
+        return TestRoot::with_boards("backlinks", name, &[BOARD, OTHER]);
+    }
+
+    fn post_init(text: &str, parent: u64) -> Post {
+        let a_post = Post::new(
+            "me".to_string(),
+            "Anonymous".to_string(),
+            None,
+            text.to_string(),
+            parent,
+        );
+        return a_post;
+    }
+
+    fn create(config: &Config, board: &str, id: u64, text: &str, parent: u64) {
+        let post = post_init(text, parent);
+        post_storage::write_post(config, board, id, &post).unwrap();
+        link_post(config, board, id, &post).unwrap();
+    }
+
+    fn backlinks(config: &Config, board: &str, id: u64) -> Vec<(String, u64)> {
+        let post = post_storage::read_post(config, board, id).unwrap();
+        return post.get_backlinks().clone();
+    }
+
+    #[test]
+    fn test_get_quoted() {
+        assert_eq!(
+            get_quoted(
+                BOARD,
+                ">>1 >>1 >>>/\u{3B1}/2\n>green >>3\n[spoiler]>>4[/spoiler] [code]>>5[/code] >>>/test/1"
+            ),
+            vec![
+                (BOARD.to_string(), 1),
+                (OTHER.to_string(), 2),
+                (BOARD.to_string(), 3),
+                (BOARD.to_string(), 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_link_post() {
+        let config = test_config("link");
+        create(&config, BOARD, 1, "op", 1);
+        create(&config, OTHER, 1, "other op", 1);
+        create(&config, BOARD, 2, ">>1 >>2 >>99", 1);
+        create(&config, BOARD, 3, ">>1\n>>>/\u{3B1}/1", 3);
+
+        assert_eq!(
+            backlinks(&config, BOARD, 1),
+            vec![(BOARD.to_string(), 2), (BOARD.to_string(), 3)]
+        );
+        assert!(backlinks(&config, BOARD, 2).is_empty());
+        assert_eq!(backlinks(&config, OTHER, 1), vec![(BOARD.to_string(), 3)]);
+        assert!(!post_storage::post_exists(&config, BOARD, 99));
+    }
+
+    #[test]
+    fn test_link_deleted_post() {
+        let config = test_config("link_deleted");
+        create(&config, BOARD, 1, "op", 1);
+        let mut post = post_init(">>1", 1);
+        post.delete();
+
+        link_post(&config, BOARD, 2, &post).unwrap();
+        assert!(backlinks(&config, BOARD, 1).is_empty());
+    }
+
+    #[test]
+    fn test_unlink_post() {
+        let config = test_config("unlink");
+        create(&config, BOARD, 1, "op", 1);
+        create(&config, BOARD, 2, ">>1", 1);
+        create(&config, BOARD, 3, ">>1", 1);
+
+        unlink_post(&config, BOARD, 2, ">>1").unwrap();
+        assert_eq!(backlinks(&config, BOARD, 1), vec![(BOARD.to_string(), 3)]);
+    }
+
+    #[test]
+    fn test_relink_post() {
+        let config = test_config("relink");
+        create(&config, BOARD, 1, "op", 1);
+        create(&config, BOARD, 2, "reply", 1);
+        create(&config, BOARD, 3, ">>1", 1);
+
+        let mut post = post_storage::read_post(&config, BOARD, 3).unwrap();
+        let old_text = post.get_text().clone();
+        post.modify_text(">>2".to_string(), Editor::Poster, 10);
+        relink_post(&config, BOARD, 3, &old_text, &post).unwrap();
+
+        assert!(backlinks(&config, BOARD, 1).is_empty());
+        assert_eq!(backlinks(&config, BOARD, 2), vec![(BOARD.to_string(), 3)]);
+    }
+
+    #[test]
+    fn test_storage_links() {
+        let config = test_config("storage_links");
+        create(&config, BOARD, 1, "op", 1);
+        create(&config, BOARD, 2, "reply", 1);
+        let mut deleted = post_init("gone", 1);
+        deleted.delete();
+        post_storage::write_post(&config, BOARD, 3, &deleted).unwrap();
+
+        let links = StorageLinks::new(&config);
+        assert_eq!(links.find_thread(BOARD, 2), Some(1));
+        assert_eq!(links.find_thread(BOARD, 3), None);
+        assert_eq!(links.find_thread(BOARD, 4), None);
+        assert_eq!(links.find_thread("nope", 1), None);
+        assert!(links.board_exists(OTHER));
+        assert!(!links.board_exists(".."));
+
+        assert_eq!(
+            render::render_text(">>2 >>3", BOARD, 1, &links),
+            "<a href=\"#p2\" class=\"quotelink\">&gt;&gt;2</a> \
+             <span class=\"deadlink\">&gt;&gt;3</span>"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+// Poster bans. Bans are on IPs and cover the whole site. Moderators ban the poster of a post rather
+// than naming an IP, since IPs are never shown to them, and banning an IP again just replaces the
+// old ban. Expired bans are dropped whenever the list is written.
+use crate::core::creation;
+use crate::core::storage::config::Config;
+use crate::core::storage::post_storage;
+use crate::core::storage::site_storage;
+use crate::core::structs::ban::Ban;
+use crate::core::structs::post::Post;
+use std::io::{Error, ErrorKind};
+use std::time::SystemTime;
+use std::vec::Vec;
+
+pub(crate) fn find_ban<'a>(bans: &'a [Ban], ip: &str, now: SystemTime) -> Option<&'a Ban> {
+    return bans
+        .iter()
+        .find(|ban| ban.get_ip() == ip && !ban.is_expired(now));
+}
+
+pub(crate) fn ban_poster(
+    config: &Config,
+    board: &str,
+    post: u64,
+    reason: &str,
+    expires: Option<SystemTime>,
+    now: SystemTime,
+) -> Result<Ban, Error> {
+    // posts from before owners had IPs in them can't be banned over
+    let read: Post = post_storage::read_post(config, board, post)?;
+    let ip: &str = creation::owner_ip(read.get_owner()).ok_or(Error::new(
+        ErrorKind::InvalidData,
+        format!("/{}/{} has no IP to ban", board, post),
+    ))?;
+
+    let ban: Ban = Ban::new(ip.to_string(), reason.to_string(), expires);
+    let mut bans: Vec<Ban> = site_storage::read_bans(config)?;
+    bans.retain(|other| other.get_ip() != ip && !other.is_expired(now));
+    bans.push(ban.clone());
+    site_storage::write_bans(config, &bans)?;
+
+    return Ok(ban);
+}
+
+pub(crate) fn unban(config: &Config, ip: &str, now: SystemTime) -> Result<bool, Error> {
+    let mut bans: Vec<Ban> = site_storage::read_bans(config)?;
+    let banned: bool = find_ban(&bans, ip, now).is_some();
+    bans.retain(|other| other.get_ip() != ip && !other.is_expired(now));
+    site_storage::write_bans(config, &bans)?;
+
+    return Ok(banned);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::fixtures::TestRoot;
+    use std::time::Duration;
+
+    const BOARD: &str = "test";
+    const IP: &str = "192.0.2.1";
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    fn test_config(name: &str) -> TestRoot {
+        let config = TestRoot::new("bans", name);
+        site_storage::initialize(&config).unwrap();
+        return config;
+    }
+
+    fn write_post(config: &Config, id: u64, owner: &str) {
+        let post = Post::new(
+            owner.to_string(),
+            "Anonymous".to_string(),
+            None,
+            "text".to_string(),
+            id,
+        );
+        post_storage::write_post(config, BOARD, id, &post).unwrap();
+    }
+
+    #[test]
+    fn test_find_ban() {
+        let now = SystemTime::now();
+        let bans = vec![
+            Ban::new("192.0.2.2".to_string(), "old".to_string(), Some(now)),
+            Ban::new(IP.to_string(), "spam".to_string(), None),
+        ];
+
+        assert_eq!(find_ban(&bans, IP, now).unwrap().get_reason(), "spam");
+        assert!(find_ban(&bans, "192.0.2.2", now).is_none());
+        assert!(find_ban(&bans, "192.0.2.3", now).is_none());
+    }
+
+    #[test]
+    fn test_ban_poster() {
+        let config = test_config("ban_poster");
+        let now = SystemTime::now();
+        write_post(&config, 1, &creation::owner(IP, "cookie"));
+        write_post(&config, 2, "cookie");
+
+        let ban = ban_poster(&config, BOARD, 1, "spam", Some(now + HOUR), now).unwrap();
+        assert_eq!(ban.get_ip(), IP);
+        // banning again replaces the first ban
+        ban_poster(&config, BOARD, 1, "more spam", None, now).unwrap();
+        let bans = site_storage::read_bans(&config).unwrap();
+        assert_eq!(bans.len(), 1);
+        assert_eq!(find_ban(&bans, IP, now).unwrap().get_reason(), "more spam");
+
+        assert_eq!(
+            ban_poster(&config, BOARD, 2, "spam", None, now)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(
+            ban_poster(&config, BOARD, 3, "spam", None, now)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_unban() {
+        let config = test_config("unban");
+        let now = SystemTime::now();
+        write_post(&config, 1, &creation::owner(IP, "cookie"));
+        ban_poster(&config, BOARD, 1, "spam", None, now).unwrap();
+
+        assert!(unban(&config, IP, now).unwrap());
+        assert!(!unban(&config, IP, now).unwrap());
+        assert!(site_storage::read_bans(&config).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,281 @@
+// The catalog, every active thread at a glance. Threads can be sorted by bump order, by when they
+// were made, by reply count or by their last reply, and stickies stay on top whatever the order,
+// in their usual order among themselves.
+// Filtering matches the subject and the OP's text, ignoring case, and happens before any replies
+// are read, so a narrow filter only costs the OPs.
+// Counts only take in what's still up, the same as what the thread shows.
+use crate::core::storage::config::Config;
+use crate::core::storage::post_storage;
+use crate::core::storage::thread_storage;
+use crate::core::structs::board::Board;
+use crate::core::structs::post::Post;
+use crate::core::structs::thread::Thread;
+use std::cmp::Reverse;
+use std::io::Error;
+use std::string::String;
+use std::time::SystemTime;
+use std::vec::Vec;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum CatalogSort {
+    Bump,
+    Created,
+    Replies,
+    LastReply,
+}
+
+pub(crate) const ALL_SORTS: [CatalogSort; 4] = [
+    CatalogSort::Bump,
+    CatalogSort::Created,
+    CatalogSort::Replies,
+    CatalogSort::LastReply,
+];
+
+impl CatalogSort {
+    pub fn parse(name: &str) -> Option<CatalogSort> {
+        return ALL_SORTS
+            .iter()
+            .find(|sort| sort.get_name() == name)
+            .copied();
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        return match self {
+            CatalogSort::Bump => "bump",
+            CatalogSort::Created => "created",
+            CatalogSort::Replies => "replies",
+            CatalogSort::LastReply => "last_reply",
+        };
+    }
+}
+
+// a thread as it shows up in the catalog
+pub(crate) struct CatalogThread {
+    pub thread: Thread,
+    pub posts: Vec<(u64, Post)>, // what's still up, the OP first
+    pub sticky: bool,
+}
+
+impl CatalogThread {
+    pub fn get_op(&self) -> &Post {
+        return &self.posts[0].1;
+    }
+
+    pub fn get_reply_count(&self) -> usize {
+        return self.posts.len() - 1;
+    }
+
+    pub fn get_image_count(&self) -> usize {
+        // files in the replies, the OP's aren't counted
+        return self.posts[1..]
+            .iter()
+            .map(|(_, post)| post.get_attachments().len())
+            .sum();
+    }
+
+    pub fn get_bumped(&self) -> SystemTime {
+        // threads from before bump times were kept go by their newest post
+        return match self.thread.get_bumped() {
+            Some(bumped) => *bumped,
+            None => self.get_last_reply(),
+        };
+    }
+
+    pub fn get_last_reply(&self) -> SystemTime {
+        // the OP, for a thread without replies
+        return *self.posts.last().unwrap().1.get_created();
+    }
+}
+
+pub(crate) fn matches(subject: &str, text: &str, filter: &str) -> bool {
+    let filter: String = filter.trim().to_lowercase();
+    return subject.to_lowercase().contains(&filter) || text.to_lowercase().contains(&filter);
+}
+
+pub(crate) fn read_catalog(
+    config: &Config,
+    board: &Board,
+    sort: CatalogSort,
+    filter: &str,
+    now: SystemTime,
+) -> Result<Vec<CatalogThread>, Error> {
+    // deleted threads are left out, and so are deleted replies
+    let board_name: &str = board.get_name();
+    let mut threads: Vec<CatalogThread> = Vec::new();
+    for parent in board.get_thread_order(now) {
+        let thread: Thread = thread_storage::read_thread(config, board_name, parent)?;
+        if thread.get_deleted() {
+            continue;
+        }
+        let op: Post = post_storage::read_post(config, board_name, parent)?;
+        if !matches(thread.get_name(), op.get_text(), filter) {
+            continue;
+        }
+
+        let mut posts: Vec<(u64, Post)> = vec![(parent, op)];
+        for id in &thread.get_children()[1..] {
+            let post: Post = post_storage::read_post(config, board_name, *id)?;
+            if !post.get_deleted() {
+                posts.push((*id, post));
+            }
+        }
+
+        let sticky: bool = board
+            .get_sticky()
+            .iter()
+            .any(|sticky| sticky.get_parent() == parent && !sticky.is_expired(now));
+        threads.push(CatalogThread {
+            thread,
+            posts,
+            sticky,
+        });
+    }
+
+    // the thread order has the stickies first already, only what's after them is sorted. newest
+    // and busiest first, and the sort is stable, so ties stay in bump order
+    let stickies: usize = threads.iter().take_while(|thread| thread.sticky).count();
+    let rest: &mut [CatalogThread] = &mut threads[stickies..];
+    match sort {
+        CatalogSort::Bump => (),
+        CatalogSort::Created => rest.sort_by_key(|thread| Reverse(thread.thread.get_parent())),
+        CatalogSort::Replies => rest.sort_by_key(|thread| Reverse(thread.get_reply_count())),
+        CatalogSort::LastReply => rest.sort_by_key(|thread| Reverse(thread.get_last_reply())),
+    }
+
+    return Ok(threads);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::board_storage;
+    use crate::core::storage::fixtures::{write_thread, TestRoot};
+    use crate::core::structs::attachment::Attachment;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const BOARD: &str = "test";
+
+    fn test_config(name: &str) -> TestRoot {
+        return TestRoot::with_boards("catalog", name, &[BOARD]);
+    }
+
+    fn read(config: &Config, board: &Board, sort: CatalogSort, filter: &str) -> Vec<u64> {
+        return read_catalog(config, board, sort, filter, SystemTime::now())
+            .unwrap()
+            .iter()
+            .map(|thread| thread.thread.get_parent())
+            .collect();
+    }
+
+    #[test]
+    fn test_sort_names() {
+        for sort in ALL_SORTS {
+            assert_eq!(CatalogSort::parse(sort.get_name()), Some(sort));
+        }
+        assert_eq!(CatalogSort::parse("Bump"), None);
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("Cooking General", "", "cooking"));
+        assert!(matches("", "what are you EATING", " eating "));
+        assert!(matches("anything", "at all", ""));
+        assert!(!matches("cooking", "general", "baking"));
+    }
+
+    fn make_post(seconds: u64, files: usize) -> Post {
+        let mut post = Post::new(String::new(), String::new(), None, String::new(), 1);
+        let created = UNIX_EPOCH + Duration::from_secs(seconds);
+        post.restore_timestamps(created, created);
+        for file in 0..files {
+            post.add_attachment(
+                Attachment::new(
+                    "a.pdf".to_string(),
+                    format!("{}-{}.pdf", seconds, file),
+                    "application/pdf".to_string(),
+                    1,
+                    None,
+                    String::new(),
+                    false,
+                ),
+                u8::MAX,
+            );
+        }
+        return post;
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut catalog_thread = CatalogThread {
+            thread: Thread::new(String::new(), 1),
+            posts: vec![
+                (1, make_post(1, 1)),
+                (2, make_post(2, 2)),
+                (3, make_post(5, 0)),
+            ],
+            sticky: false,
+        };
+        assert_eq!(
+            *catalog_thread.get_op().get_created(),
+            UNIX_EPOCH + Duration::from_secs(1)
+        );
+        assert_eq!(catalog_thread.get_reply_count(), 2);
+        assert_eq!(catalog_thread.get_image_count(), 2);
+        assert_eq!(
+            catalog_thread.get_last_reply(),
+            UNIX_EPOCH + Duration::from_secs(5)
+        );
+        assert_eq!(
+            catalog_thread.get_bumped(),
+            UNIX_EPOCH + Duration::from_secs(5)
+        );
+
+        catalog_thread.thread.modify_bumped(UNIX_EPOCH);
+        assert_eq!(catalog_thread.get_bumped(), UNIX_EPOCH);
+    }
+
+    #[test]
+    fn test_read_catalog() {
+        // bump order is 9, 5, 1, and 1 is stickied
+        let config = test_config("read");
+        let mut board = board_storage::read_board(&config, BOARD).unwrap();
+        write_thread(&config, &mut board, "cooking", &[1, 2, 3, 4]);
+        write_thread(&config, &mut board, "baking", &[5, 10]);
+        write_thread(&config, &mut board, "general", &[9]);
+        write_thread(&config, &mut board, "gone", &[11]);
+        let mut deleted = thread_storage::read_thread(&config, BOARD, 11).unwrap();
+        deleted.delete();
+        thread_storage::write_thread(&config, BOARD, &deleted).unwrap();
+        board.add_sticky(1, 0, None);
+
+        assert_eq!(read(&config, &board, CatalogSort::Bump, ""), vec![1, 9, 5]);
+        assert_eq!(
+            read(&config, &board, CatalogSort::Created, ""),
+            vec![1, 9, 5]
+        );
+        assert_eq!(
+            read(&config, &board, CatalogSort::LastReply, ""),
+            vec![1, 5, 9]
+        );
+        assert_eq!(
+            read(&config, &board, CatalogSort::Replies, ""),
+            vec![1, 5, 9]
+        );
+        assert_eq!(read(&config, &board, CatalogSort::Bump, "KING"), vec![1, 5]);
+        assert_eq!(read(&config, &board, CatalogSort::Bump, "op 9"), vec![9]);
+        assert_eq!(
+            read(&config, &board, CatalogSort::Bump, "gone"),
+            Vec::<u64>::new()
+        );
+
+        board.remove_sticky(1);
+        assert_eq!(
+            read(&config, &board, CatalogSort::Replies, ""),
+            vec![1, 5, 9]
+        );
+        assert_eq!(
+            read(&config, &board, CatalogSort::Created, ""),
+            vec![9, 5, 1]
+        );
+    }
+}
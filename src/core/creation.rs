@@ -0,0 +1,657 @@
+// Making new threads and replies. This is the whole write path for a new post: bans, the posting
+// rules and cooldowns, the name, tripcode and poster ID, the files, storage and backlinks, and finally
+// the board's thread lists, with bumping and archiving.
+// Everything that can be checked up front is checked before anything is written, and a post whose
+// files can't all be stored leaves none of them behind. Post IDs come from the board's counter, so
+// callers have to make sure only one post is made on a board at a time.
+use crate::core::attachments;
+use crate::core::backlinks;
+use crate::core::bans;
+use crate::core::deletion;
+use crate::core::media::validation::UploadError;
+use crate::core::own_posts;
+use crate::core::poster_id;
+use crate::core::posting::{self, Cooldowns, PostingError, Submission};
+use crate::core::replies::{self, ReplyError};
+use crate::core::secrets;
+use crate::core::storage::attachment_storage;
+use crate::core::storage::board_storage;
+use crate::core::storage::config::Config;
+use crate::core::storage::post_storage;
+use crate::core::storage::site_storage;
+use crate::core::storage::thread_storage;
+use crate::core::structs::ban::Ban;
+use crate::core::structs::banned_images::BannedImages;
+use crate::core::structs::board::Board;
+use crate::core::structs::post::Post;
+use crate::core::structs::thread::{Thread, ThreadError};
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::string::String;
+use std::time::SystemTime;
+use std::vec::Vec;
+
+// a post on its way in. the submission's poster is what cooldowns are keyed on, which should be
+// something a poster can't just throw away, like their IP
+pub(crate) struct NewPost<'a> {
+    pub submission: Submission<'a>,
+    pub thread: u64, // the thread being replied to, unused for new threads
+    pub ip: &'a str,
+    pub cookie: &'a str,   // the owner cookie, for managing the post later
+    pub password: &'a str, // the deletion password, empty for none
+    pub sage: bool,        // replies without bumping the thread
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum CreateError {
+    Banned(Ban),
+    Posting(PostingError),
+    Upload(UploadError),
+    ThreadNotFound,
+    Thread(ThreadError),
+    Storage(ErrorKind),
+}
+
+impl fmt::Display for CreateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            CreateError::Banned(ban) => match ban.get_expires() {
+                Some(_) => write!(
+                    f,
+                    "You're banned from posting for now. Reason: {}",
+                    ban.get_reason()
+                ),
+                None => write!(
+                    f,
+                    "You're banned from posting. Reason: {}",
+                    ban.get_reason()
+                ),
+            },
+            CreateError::Posting(error) => error.fmt(f),
+            CreateError::Upload(error) => error.fmt(f),
+            CreateError::ThreadNotFound => write!(f, "That thread doesn't exist."),
+            CreateError::Thread(error) => error.fmt(f),
+            CreateError::Storage(_) => {
+                write!(f, "The post could not be saved, please try again.")
+            }
+        };
+    }
+}
+
+impl std::error::Error for CreateError {}
+
+impl From<Error> for CreateError {
+    fn from(error: Error) -> CreateError {
+        return CreateError::Storage(error.kind());
+    }
+}
+
+impl From<PostingError> for CreateError {
+    fn from(error: PostingError) -> CreateError {
+        return CreateError::Posting(error);
+    }
+}
+
+impl From<UploadError> for CreateError {
+    fn from(error: UploadError) -> CreateError {
+        return CreateError::Upload(error);
+    }
+}
+
+impl From<ReplyError> for CreateError {
+    fn from(error: ReplyError) -> CreateError {
+        return match error {
+            ReplyError::Thread(error) => CreateError::Thread(error),
+            ReplyError::Storage(kind) => CreateError::Storage(kind),
+        };
+    }
+}
+
+pub(crate) fn owner(ip: &str, cookie: &str) -> String {
+    // how a post's owner is stored, neither part can have a semicolon in it
+    return format!("{};{}", ip, cookie);
+}
+
+pub(crate) fn owner_cookie(owner: &str) -> &str {
+    // posts made before owners had IPs in them are only the cookie
+    return owner.rsplit(';').next().unwrap_or(owner);
+}
+
+pub(crate) fn owner_ip(owner: &str) -> Option<&str> {
+    // None for posts made before owners had IPs in them
+    return owner.split_once(';').map(|(ip, _)| ip);
+}
+
+pub(crate) fn create_post(
+    config: &Config,
+    board: &mut Board,
+    cooldowns: &mut Cooldowns,
+    new_post: &NewPost,
+    now: SystemTime,
+) -> Result<(u64, u64), CreateError> {
+    // returns the thread the post ended up in and the post's own ID
+    let submission: &Submission = &new_post.submission;
+    let new_thread: bool = submission.new_thread;
+    let board_name: String = board.get_name().clone();
+    if let Some(ban) = bans::find_ban(&site_storage::read_bans(config)?, new_post.ip, now) {
+        return Err(CreateError::Banned(ban.clone()));
+    }
+    posting::check(board.get_config(), cooldowns, submission, now)?;
+
+    if !new_thread {
+        check_thread(config, board, new_post.thread)?;
+    }
+
+    // the counter only moves once the files are in
+    let id: u64 = board.get_count() + 1;
+    let parent: u64 = match new_thread {
+        true => id,
+        false => new_post.thread,
+    };
+    let mut post: Post = make_post(config, board, new_post, parent, now)?;
+
+    let banned_images: BannedImages = site_storage::read_banned_images(config)?;
+    for file in submission.files {
+        let attached: Result<(), UploadError> =
+            attachments::attach(config, board, &banned_images, &mut post, id, file);
+        if let Err(error) = attached {
+            remove_files(config, &board_name, &post)?;
+            return Err(CreateError::Upload(error));
+        }
+    }
+
+    board.increment_count();
+    post_storage::write_post(config, &board_name, id, &post)?;
+    backlinks::link_post(config, &board_name, id, &post)?;
+
+    match new_thread {
+        true => {
+            let subject: String = submission.subject.trim().to_string();
+            let mut thread: Thread = Thread::new(subject, id);
+            thread.modify_bumped(now);
+            thread_storage::write_thread(config, &board_name, &thread)?;
+            board.bump(id);
+            archive_overflow(config, board, now)?;
+        }
+        false => {
+            // sage and archived threads don't bump
+            let bumps: bool = !new_post.sage && board.get_active().contains(&parent);
+            replies::add_reply(config, board, parent, id, bumps.then_some(now))?;
+            if bumps {
+                board.bump(parent);
+            }
+        }
+    }
+
+    cooldowns.record(submission.poster, new_thread, now);
+    board_storage::write_board(config, board)?;
+
+    return Ok((parent, id));
+}
+
+fn check_thread(config: &Config, board: &Board, thread: u64) -> Result<(), CreateError> {
+    // replies can only go to a live thread with room left, which has to be checked before any
+    // files go in. a thread can be over the limit if the board's limit was lowered after it filled
+    let board_name: &str = board.get_name();
+    if !thread_storage::thread_exists(config, board_name, thread) {
+        return Err(CreateError::ThreadNotFound);
+    }
+
+    let mut read: Thread = thread_storage::read_thread(config, board_name, thread)?;
+    if read.get_deleted() {
+        return Err(CreateError::ThreadNotFound);
+    }
+    if read.get_locked() {
+        return Err(CreateError::Thread(ThreadError::Locked));
+    }
+    let limit: u16 = board.get_config().get_max_replies();
+    if !read.get_cyclic() && read.get_reply_count() >= limit as usize {
+        // the same as hitting the limit by replying, the thread locks
+        read.lock();
+        thread_storage::write_thread(config, board_name, &read)?;
+        return Err(CreateError::Thread(ThreadError::ReplyLimit { limit }));
+    }
+
+    return Ok(());
+}
+
+fn make_post(
+    config: &Config,
+    board: &Board,
+    new_post: &NewPost,
+    parent: u64,
+    now: SystemTime,
+) -> Result<Post, Error> {
+    let submission: &Submission = &new_post.submission;
+    let tripcode_secret: Vec<u8> = secrets::current(config, secrets::TRIPCODE, None, now)?;
+    let (name, tripcode): (String, Option<String>) =
+        posting::poster_name(board.get_config(), submission.name, &tripcode_secret);
+
+    let mut post: Post = Post::new(
+        owner(new_post.ip, new_post.cookie),
+        name,
+        tripcode,
+        submission.text.to_string(),
+        parent,
+    );
+    post.modify_deletion_password(own_posts::hash_password(new_post.password));
+
+    if board.get_config().get_poster_ids() {
+        let secret: Vec<u8> = poster_id::current_secret(config, now)?;
+        post.modify_poster_id(poster_id::assign(
+            board.get_config(),
+            &secret,
+            new_post.ip,
+            board.get_name(),
+            parent,
+        ));
+    }
+
+    return Ok(post);
+}
+
+fn remove_files(config: &Config, board: &str, post: &Post) -> Result<(), Error> {
+    for attachment in post.get_attachments() {
+        attachment_storage::remove_attachment(config, board, attachment.get_stored_name())?;
+    }
+    return Ok(());
+}
+
+fn archive_overflow(config: &Config, board: &mut Board, now: SystemTime) -> Result<(), Error> {
+    // archived threads are locked, and threads falling off the archive are deleted, which leaves
+    // them to be purged once the retention window is up
+    let board_name: String = board.get_name().clone();
+    let (archived, dropped): (Vec<u64>, Vec<u64>) = board.archive_overflow(now);
+
+    for parent in archived {
+        let mut thread: Thread = thread_storage::read_thread(config, &board_name, parent)?;
+        thread.lock();
+        thread_storage::write_thread(config, &board_name, &thread)?;
+    }
+
+    for parent in dropped {
+        if !post_storage::post_exists(config, &board_name, parent) {
+            continue;
+        }
+        let mut op: Post = post_storage::read_post(config, &board_name, parent)?;
+        if !op.get_deleted() {
+            deletion::delete_post(config, &board_name, parent, &mut op)?;
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::attachments::UploadedFile;
+    use crate::core::storage::fixtures::TestRoot;
+    use crate::core::structs::board_config::BoardConfig;
+    use image::{DynamicImage, ImageFormat};
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    const BOARD: &str = "test";
+    const IP: &str = "192.0.2.1";
+    const COOKIE: &str = "cookie";
+    const PDF: &[u8] = b"%PDF-1.7\n";
+
+    fn test_config(name: &str) -> TestRoot {
+        let config = TestRoot::new("creation", name);
+        config.connect().unwrap();
+        board_storage::initialize(&config, BOARD).unwrap();
+        return config;
+    }
+
+    fn board(config: &Config, change: impl Fn(&mut BoardConfig)) -> Board {
+        // no cooldowns, so tests can post as fast as they like
+        let mut board = board_storage::read_board(config, BOARD).unwrap();
+        let mut board_config = board.get_config().clone();
+        board_config.modify_reply_cooldown(0);
+        board_config.modify_thread_cooldown(0);
+        board_config.modify_op_requires_image(false);
+        change(&mut board_config);
+        board.modify_config(board_config);
+        return board;
+    }
+
+    fn new_post<'a>(
+        text: &'a str,
+        thread: Option<u64>,
+        files: &'a [UploadedFile<'a>],
+    ) -> NewPost<'a> {
+        return NewPost {
+            submission: Submission {
+                poster: IP,
+                name: "",
+                subject: "subject",
+                text,
+                files,
+                new_thread: thread.is_none(),
+            },
+            thread: thread.unwrap_or_default(),
+            ip: IP,
+            cookie: COOKIE,
+            password: "",
+            sage: false,
+        };
+    }
+
+    fn create(config: &Config, board: &mut Board, text: &str, thread: Option<u64>) -> (u64, u64) {
+        let mut cooldowns = Cooldowns::new();
+        return create_post(
+            config,
+            board,
+            &mut cooldowns,
+            &new_post(text, thread, &[]),
+            SystemTime::now(),
+        )
+        .unwrap();
+    }
+
+    fn file(data: &[u8]) -> UploadedFile<'_> {
+        return UploadedFile {
+            original_name: "file.pdf",
+            data,
+            spoiler: false,
+        };
+    }
+
+    #[test]
+    fn test_owner() {
+        let owner = owner(IP, COOKIE);
+        assert_eq!(owner, "192.0.2.1;cookie");
+        assert_eq!(owner_cookie(&owner), COOKIE);
+        assert_eq!(owner_cookie("::1;cookie"), COOKIE);
+        assert_eq!(owner_cookie(COOKIE), COOKIE);
+        assert_eq!(owner_ip(&owner), Some(IP));
+        assert_eq!(owner_ip("::1;cookie"), Some("::1"));
+        assert_eq!(owner_ip(COOKIE), None);
+    }
+
+    #[test]
+    fn test_create_thread() {
+        let config = test_config("thread");
+        let mut board = board(&config, |_| ());
+
+        assert_eq!(create(&config, &mut board, "op", None), (1, 1));
+
+        let post = post_storage::read_post(&config, BOARD, 1).unwrap();
+        assert_eq!(post.get_text(), "op");
+        assert_eq!(post.get_name(), "Anonymous");
+        assert_eq!(owner_cookie(post.get_owner()), COOKIE);
+        assert_eq!(*post.get_poster_id(), None);
+        let thread = thread_storage::read_thread(&config, BOARD, 1).unwrap();
+        assert_eq!(thread.get_name(), "subject");
+        assert!(thread.get_bumped().is_some());
+
+        let saved = board_storage::read_board(&config, BOARD).unwrap();
+        assert_eq!(saved.get_count(), 1);
+        assert_eq!(*saved.get_active(), vec![1]);
+    }
+
+    #[test]
+    fn test_create_reply() {
+        let config = test_config("reply");
+        let mut board = board(&config, |_| ());
+        create(&config, &mut board, "first", None);
+        create(&config, &mut board, "second", None);
+
+        assert_eq!(create(&config, &mut board, ">>1", Some(1)), (1, 3));
+        assert_eq!(
+            *thread_storage::read_thread(&config, BOARD, 1)
+                .unwrap()
+                .get_children(),
+            vec![1, 3]
+        );
+        assert_eq!(
+            *post_storage::read_post(&config, BOARD, 1)
+                .unwrap()
+                .get_backlinks(),
+            vec![(BOARD.to_string(), 3)]
+        );
+        // replying bumped the thread
+        assert_eq!(*board.get_active(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_create_reply_sage() {
+        let config = test_config("sage");
+        let mut board = board(&config, |_| ());
+        create(&config, &mut board, "first", None);
+        create(&config, &mut board, "second", None);
+
+        let bumped = *thread_storage::read_thread(&config, BOARD, 1)
+            .unwrap()
+            .get_bumped();
+        let mut sage = new_post("sage", Some(1), &[]);
+        sage.sage = true;
+        create_post(
+            &config,
+            &mut board,
+            &mut Cooldowns::new(),
+            &sage,
+            SystemTime::now(),
+        )
+        .unwrap();
+        assert_eq!(*board.get_active(), vec![2, 1]);
+        assert_eq!(
+            *thread_storage::read_thread(&config, BOARD, 1)
+                .unwrap()
+                .get_bumped(),
+            bumped
+        );
+    }
+
+    #[test]
+    fn test_create_reply_rejected() {
+        let config = test_config("rejected");
+        let mut board = board(&config, |_| ());
+        create(&config, &mut board, "op", None);
+        let mut thread = thread_storage::read_thread(&config, BOARD, 1).unwrap();
+        thread.lock();
+        thread_storage::write_thread(&config, BOARD, &thread).unwrap();
+        let mut cooldowns = Cooldowns::new();
+        let now = SystemTime::now();
+
+        let locked = new_post("reply", Some(1), &[]);
+        assert_eq!(
+            create_post(&config, &mut board, &mut cooldowns, &locked, now),
+            Err(CreateError::Thread(ThreadError::Locked))
+        );
+        let missing = new_post("reply", Some(7), &[]);
+        assert_eq!(
+            create_post(&config, &mut board, &mut cooldowns, &missing, now),
+            Err(CreateError::ThreadNotFound)
+        );
+        let empty = new_post("", Some(1), &[]);
+        assert_eq!(
+            create_post(&config, &mut board, &mut cooldowns, &empty, now),
+            Err(CreateError::Posting(PostingError::Empty))
+        );
+        assert_eq!(board.get_count(), 1);
+    }
+
+    #[test]
+    fn test_create_reply_limit_lowered() {
+        // a thread that's over a lowered limit turns the reply away before anything is written
+        let config = test_config("limit_lowered");
+        let mut board = board(&config, |_| ());
+        create(&config, &mut board, "op", None);
+        create(&config, &mut board, "reply", Some(1));
+        create(&config, &mut board, "reply", Some(1));
+        let mut board_config = board.get_config().clone();
+        board_config.modify_max_replies(1);
+        board.modify_config(board_config);
+
+        let over = new_post(">>1", Some(1), &[]);
+        assert_eq!(
+            create_post(
+                &config,
+                &mut board,
+                &mut Cooldowns::new(),
+                &over,
+                SystemTime::now()
+            ),
+            Err(CreateError::Thread(ThreadError::ReplyLimit { limit: 1 }))
+        );
+        assert_eq!(board.get_count(), 3);
+        assert!(!post_storage::post_exists(&config, BOARD, 4));
+        assert!(post_storage::read_post(&config, BOARD, 1)
+            .unwrap()
+            .get_backlinks()
+            .is_empty());
+        assert!(thread_storage::read_thread(&config, BOARD, 1)
+            .unwrap()
+            .get_locked());
+    }
+
+    #[test]
+    fn test_create_cooldown() {
+        let config = test_config("cooldown");
+        let mut board = board(&config, |board_config| {
+            board_config.modify_reply_cooldown(60)
+        });
+        let mut cooldowns = Cooldowns::new();
+        let now = SystemTime::now();
+
+        create_post(
+            &config,
+            &mut board,
+            &mut cooldowns,
+            &new_post("op", None, &[]),
+            now,
+        )
+        .unwrap();
+        assert!(matches!(
+            create_post(
+                &config,
+                &mut board,
+                &mut cooldowns,
+                &new_post("reply", Some(1), &[]),
+                now
+            ),
+            Err(CreateError::Posting(PostingError::Cooldown { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_create_banned() {
+        let config = test_config("banned");
+        let mut board = board(&config, |_| ());
+        let now = SystemTime::now();
+        let ban = Ban::new(IP.to_string(), "spam".to_string(), None);
+        site_storage::write_bans(&config, std::slice::from_ref(&ban)).unwrap();
+
+        assert_eq!(
+            create_post(
+                &config,
+                &mut board,
+                &mut Cooldowns::new(),
+                &new_post("op", None, &[]),
+                now
+            ),
+            Err(CreateError::Banned(ban))
+        );
+        assert_eq!(board.get_count(), 0);
+
+        // an expired ban doesn't stop anything
+        let expired = Ban::new(IP.to_string(), "spam".to_string(), Some(now));
+        site_storage::write_bans(&config, &[expired]).unwrap();
+        assert_eq!(create(&config, &mut board, "op", None), (1, 1));
+    }
+
+    #[test]
+    fn test_create_files() {
+        let config = test_config("files");
+        let mut board = board(&config, |_| ());
+        let mut png: Vec<u8> = Vec::new();
+        DynamicImage::new_rgb8(4, 4)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let image = UploadedFile {
+            original_name: "image.png",
+            data: &png,
+            spoiler: false,
+        };
+        let files = [file(PDF), image];
+
+        let mut cooldowns = Cooldowns::new();
+        let now = SystemTime::now();
+        create_post(
+            &config,
+            &mut board,
+            &mut cooldowns,
+            &new_post("", None, &files),
+            now,
+        )
+        .unwrap();
+        let post = post_storage::read_post(&config, BOARD, 1).unwrap();
+        assert_eq!(post.get_attachments().len(), 2);
+        assert_eq!(post.get_attachments()[1].get_stored_name(), "1-1.png");
+
+        // one bad file means none of them are kept
+        let files = [file(PDF), file(b"not a pdf")];
+        assert!(matches!(
+            create_post(
+                &config,
+                &mut board,
+                &mut cooldowns,
+                &new_post("", None, &files),
+                now
+            ),
+            Err(CreateError::Upload(_))
+        ));
+        assert!(attachment_storage::read_attachment(&config, BOARD, "2-0.pdf").is_err());
+        assert_eq!(board.get_count(), 1);
+    }
+
+    #[test]
+    fn test_create_poster_ids() {
+        let config = test_config("poster_ids");
+        let mut board = board(&config, |board_config| board_config.modify_poster_ids(true));
+        create(&config, &mut board, "op", None);
+        create(&config, &mut board, "reply", Some(1));
+        create(&config, &mut board, "other thread", None);
+
+        let id = |post: u64| -> Option<String> {
+            return post_storage::read_post(&config, BOARD, post)
+                .unwrap()
+                .get_poster_id()
+                .clone();
+        };
+        assert!(id(1).is_some());
+        assert_eq!(id(1), id(2));
+        assert_ne!(id(1), id(3));
+    }
+
+    #[test]
+    fn test_create_archive_overflow() {
+        let config = test_config("overflow");
+        let mut board = board(&config, |board_config| {
+            board_config.modify_active_count(1);
+            board_config.modify_archive_count(1);
+        });
+        create(&config, &mut board, "first", None);
+        create(&config, &mut board, "second", None);
+
+        assert_eq!(*board.get_archive(), vec![1]);
+        assert!(thread_storage::read_thread(&config, BOARD, 1)
+            .unwrap()
+            .get_locked());
+
+        create(&config, &mut board, "third", None);
+        assert_eq!(*board.get_active(), vec![3]);
+        assert_eq!(*board.get_archive(), vec![2]);
+        assert!(post_storage::read_post(&config, BOARD, 1)
+            .unwrap()
+            .get_deleted());
+
+        // a sticky that has run out doesn't keep its place
+        board.add_sticky(3, 0, Some(SystemTime::now() - Duration::from_secs(1)));
+        create(&config, &mut board, "fourth", None);
+        assert_eq!(*board.get_active(), vec![4]);
+        assert_eq!(*board.get_archive(), vec![3]);
+    }
+}
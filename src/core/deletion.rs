@@ -0,0 +1,482 @@
+// Soft deletion and purging. Deleting a post only hides it, moderators can still restore it until
+// the board's retention window runs out. After that a purge removes it from storage for good,
+// along with its files. Deleting an OP deletes its whole thread, and purging it purges every post
+// in the thread, deleted or not, and takes it off the board's lists.
+// Moderators can also take a single file off a post, leaving the rest of it up. That one is for
+// good, the file and its thumbnails are removed straight away.
+use crate::core::backlinks;
+use crate::core::storage::attachment_storage;
+use crate::core::storage::board_storage;
+use crate::core::storage::config::Config;
+use crate::core::storage::post_storage;
+use crate::core::storage::thread_storage;
+use crate::core::structs::board::Board;
+use crate::core::structs::post::Post;
+use crate::core::structs::thread::Thread;
+use std::io::{Error, ErrorKind};
+use std::time::{Duration, SystemTime};
+use std::vec::Vec;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+pub(crate) fn delete_post(
+    config: &Config,
+    board: &str,
+    id: u64,
+    post: &mut Post,
+) -> Result<(), Error> {
+    post.delete();
+    post_storage::write_post(config, board, id, post)?;
+    backlinks::unlink_post(config, board, id, post.get_text())?;
+
+    return set_thread_deleted(config, board, id, post, true);
+}
+
+pub(crate) fn restore_post(
+    config: &Config,
+    board: &str,
+    id: u64,
+    post: &mut Post,
+) -> Result<(), Error> {
+    post.undelete();
+    post_storage::write_post(config, board, id, post)?;
+    backlinks::link_post(config, board, id, post)?;
+
+    return set_thread_deleted(config, board, id, post, false);
+}
+
+pub(crate) fn delete_file(
+    config: &Config,
+    board: &str,
+    id: u64,
+    post: &mut Post,
+    stored_name: &str,
+) -> Result<bool, Error> {
+    // false if the post doesn't have that file. the post is written first, so it never points
+    // at a file that's gone
+    if post.remove_attachment(stored_name).is_none() {
+        return Ok(false);
+    }
+    post_storage::write_post(config, board, id, post)?;
+    attachment_storage::remove_attachment(config, board, stored_name)?;
+
+    return Ok(true);
+}
+
+fn set_thread_deleted(
+    config: &Config,
+    board: &str,
+    id: u64,
+    post: &Post,
+    deleted: bool,
+) -> Result<(), Error> {
+    // only an OP takes its thread along with it
+    if id != post.get_parent() || !thread_storage::thread_exists(config, board, id) {
+        return Ok(());
+    }
+
+    let mut thread: Thread = thread_storage::read_thread(config, board, id)?;
+    match deleted {
+        true => thread.delete(),
+        false => thread.undelete(),
+    }
+    return thread_storage::write_thread(config, board, &thread);
+}
+
+pub(crate) fn is_expired(post: &Post, retention_days: u16, now: SystemTime) -> bool {
+    let retention: Duration = Duration::from_secs(retention_days as u64 * SECONDS_PER_DAY);
+
+    return match post.get_deleted_at() {
+        Some(deleted) => now.duration_since(*deleted).unwrap_or_default() >= retention,
+        None => false,
+    };
+}
+
+pub(crate) fn purge(
+    config: &Config,
+    board: &mut Board,
+    now: SystemTime,
+) -> Result<Vec<u64>, Error> {
+    // purges every post whose retention window has run out, returning the IDs of everything
+    // removed. posts are listed oldest first, so a thread goes before any of its replies
+    let board_name: String = board.get_name().to_string();
+    let retention_days: u16 = board.get_config().get_retention_days();
+    let mut purged: Vec<u64> = Vec::new();
+    let mut unlisted: bool = false;
+
+    for id in post_storage::list_posts(config, &board_name)? {
+        // replies in a thread that was already purged are gone by now
+        if !post_storage::post_exists(config, &board_name, id) {
+            continue;
+        }
+        let post: Post = post_storage::read_post(config, &board_name, id)?;
+        if !is_expired(&post, retention_days, now) {
+            continue;
+        }
+
+        match id == post.get_parent() {
+            true => {
+                purge_thread(config, &board_name, id, &post, &mut purged)?;
+                // a purged thread can't be left in the lists, or every page showing them breaks
+                unlisted |= board.remove_active(id);
+                unlisted |= board.remove_archive(id);
+                unlisted |= board.remove_sticky(id);
+            }
+            false => {
+                purge_post(config, &board_name, id, &post)?;
+                purged.push(id);
+                remove_from_thread(config, &board_name, id, post.get_parent())?;
+            }
+        }
+    }
+
+    // stickies that ran out are already ignored everywhere, they're dropped here so the list
+    // doesn't keep growing
+    unlisted |= !board.remove_expired_stickies(now).is_empty();
+    if unlisted {
+        board_storage::write_board(config, board)?;
+    }
+    return Ok(purged);
+}
+
+fn purge_thread(
+    config: &Config,
+    board: &str,
+    id: u64,
+    post: &Post,
+    purged: &mut Vec<u64>,
+) -> Result<(), Error> {
+    let children: Vec<u64> = match thread_storage::thread_exists(config, board, id) {
+        true => thread_storage::read_thread(config, board, id)?
+            .get_children()
+            .clone(),
+        false => vec![id],
+    };
+
+    for child in children {
+        if child == id {
+            purge_post(config, board, id, post)?;
+        } else if post_storage::post_exists(config, board, child) {
+            let reply: Post = post_storage::read_post(config, board, child)?;
+            purge_post(config, board, child, &reply)?;
+        } else {
+            continue;
+        }
+        purged.push(child);
+    }
+
+    if thread_storage::thread_exists(config, board, id) {
+        thread_storage::remove_thread(config, board, id)?;
+    }
+    return Ok(());
+}
+
+fn purge_post(config: &Config, board: &str, id: u64, post: &Post) -> Result<(), Error> {
+    // files that are already gone don't stop the purge
+    for attachment in post.get_attachments() {
+        match attachment_storage::remove_attachment(config, board, attachment.get_stored_name()) {
+            Err(error) if error.kind() != ErrorKind::NotFound => return Err(error),
+            _ => (),
+        }
+    }
+
+    // replies purged along with their thread were never deleted, so their quotes still left
+    // backlinks behind
+    if !post.get_deleted() {
+        backlinks::unlink_post(config, board, id, post.get_text())?;
+    }
+
+    return post_storage::remove_post(config, board, id);
+}
+
+fn remove_from_thread(config: &Config, board: &str, id: u64, parent: u64) -> Result<(), Error> {
+    if !thread_storage::thread_exists(config, board, parent) {
+        return Ok(());
+    }
+
+    let mut thread: Thread = thread_storage::read_thread(config, board, parent)?;
+    if thread.remove_child(id) {
+        thread_storage::write_thread(config, board, &thread)?;
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::index;
+    use crate::core::media::thumbnail::ThumbnailSize;
+    use crate::core::storage::fixtures::TestRoot;
+    use crate::core::structs::attachment::Attachment;
+
+    const BOARD: &str = "test";
+    const DAY: Duration = Duration::from_secs(SECONDS_PER_DAY);
+
+    fn test_config(name: &str) -> TestRoot {
+        return TestRoot::with_boards("deletion", name, &[BOARD]);
+    }
+
+    fn create(config: &Config, id: u64, text: &str, parent: u64) {
+        // every post gets a file named after it
+        let mut post = Post::new(
+            "me".to_string(),
+            "Anonymous".to_string(),
+            None,
+            text.to_string(),
+            parent,
+        );
+        let stored_name: String = format!("{}-0.pdf", id);
+        post.add_attachment(
+            Attachment::new(
+                "a.pdf".to_string(),
+                stored_name.clone(),
+                "application/pdf".to_string(),
+                9,
+                None,
+                "hash".to_string(),
+                false,
+            ),
+            4,
+        );
+        attachment_storage::write_attachment(config, BOARD, &stored_name, b"%PDF-1.7\n").unwrap();
+        post_storage::write_post(config, BOARD, id, &post).unwrap();
+        backlinks::link_post(config, BOARD, id, &post).unwrap();
+
+        let mut thread: Thread = match id == parent {
+            true => Thread::new("thread".to_string(), id),
+            false => thread_storage::read_thread(config, BOARD, parent).unwrap(),
+        };
+        if id != parent {
+            thread.add_child(id, 100).unwrap();
+        }
+        thread_storage::write_thread(config, BOARD, &thread).unwrap();
+    }
+
+    fn read(config: &Config, id: u64) -> Post {
+        return post_storage::read_post(config, BOARD, id).unwrap();
+    }
+
+    fn delete(config: &Config, id: u64) {
+        let mut post = read(config, id);
+        delete_post(config, BOARD, id, &mut post).unwrap();
+    }
+
+    fn has_file(config: &Config, id: u64) -> bool {
+        return attachment_storage::read_attachment(config, BOARD, &format!("{}-0.pdf", id))
+            .is_ok();
+    }
+
+    #[test]
+    fn test_delete_post() {
+        let config = test_config("delete");
+        create(&config, 1, "op", 1);
+        create(&config, 2, ">>1", 1);
+
+        delete(&config, 2);
+        assert!(read(&config, 2).get_deleted());
+        assert!(read(&config, 1).get_backlinks().is_empty());
+        assert!(!thread_storage::read_thread(&config, BOARD, 1)
+            .unwrap()
+            .get_deleted());
+        assert!(has_file(&config, 2));
+    }
+
+    #[test]
+    fn test_delete_op() {
+        let config = test_config("delete_op");
+        create(&config, 1, "op", 1);
+        create(&config, 2, "reply", 1);
+
+        delete(&config, 1);
+        assert!(thread_storage::read_thread(&config, BOARD, 1)
+            .unwrap()
+            .get_deleted());
+        assert!(!read(&config, 2).get_deleted());
+
+        let mut post = read(&config, 1);
+        restore_post(&config, BOARD, 1, &mut post).unwrap();
+        assert!(!read(&config, 1).get_deleted());
+        assert!(!thread_storage::read_thread(&config, BOARD, 1)
+            .unwrap()
+            .get_deleted());
+    }
+
+    #[test]
+    fn test_restore_post() {
+        let config = test_config("restore");
+        create(&config, 1, "op", 1);
+        create(&config, 2, ">>1", 1);
+        delete(&config, 2);
+
+        let mut post = read(&config, 2);
+        restore_post(&config, BOARD, 2, &mut post).unwrap();
+        assert!(!read(&config, 2).get_deleted());
+        assert_eq!(
+            *read(&config, 1).get_backlinks(),
+            vec![(BOARD.to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_delete_file() {
+        let config = test_config("delete_file");
+        create(&config, 1, "op", 1);
+        let mut post = read(&config, 1);
+        post.add_attachment(
+            Attachment::new(
+                "b.pdf".to_string(),
+                "1-1.pdf".to_string(),
+                "application/pdf".to_string(),
+                9,
+                None,
+                "hash".to_string(),
+                false,
+            ),
+            4,
+        );
+        post_storage::write_post(&config, BOARD, 1, &post).unwrap();
+        attachment_storage::write_attachment(&config, BOARD, "1-1.pdf", b"%PDF-1.7\n").unwrap();
+        attachment_storage::write_thumbnail(&config, BOARD, "1-0.pdf", ThumbnailSize::Op, b"")
+            .unwrap();
+
+        let mut post = read(&config, 1);
+        assert!(delete_file(&config, BOARD, 1, &mut post, "1-0.pdf").unwrap());
+        let names: Vec<String> = read(&config, 1)
+            .get_attachments()
+            .iter()
+            .map(|attachment| attachment.get_stored_name().clone())
+            .collect();
+        assert_eq!(names, vec!["1-1.pdf".to_string()]);
+        assert!(!has_file(&config, 1));
+        assert!(
+            attachment_storage::read_thumbnail(&config, BOARD, "1-0.pdf", ThumbnailSize::Op)
+                .is_err()
+        );
+        assert!(attachment_storage::read_attachment(&config, BOARD, "1-1.pdf").is_ok());
+        assert!(!read(&config, 1).get_deleted());
+
+        // a file the post doesn't have is left alone
+        let mut post = read(&config, 1);
+        assert!(!delete_file(&config, BOARD, 1, &mut post, "1-0.pdf").unwrap());
+        assert!(!delete_file(&config, BOARD, 1, &mut post, "2-0.pdf").unwrap());
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let mut post = Post::new(
+            "me".to_string(),
+            "Anonymous".to_string(),
+            None,
+            String::new(),
+            1,
+        );
+        let now = SystemTime::now();
+        assert!(!is_expired(&post, 0, now));
+
+        post.restore_deleted(Some(now - DAY * 7));
+        assert!(is_expired(&post, 7, now));
+        assert!(!is_expired(&post, 8, now));
+        // a deletion from the future, from a clock going backwards
+        assert!(!is_expired(&post, 1, now - DAY * 8));
+    }
+
+    #[test]
+    fn test_purge_reply() {
+        let config = test_config("purge_reply");
+        create(&config, 1, "op", 1);
+        create(&config, 2, "reply", 1);
+        create(&config, 3, "reply", 1);
+        delete(&config, 2);
+        let mut board = board_storage::read_board(&config, BOARD).unwrap();
+
+        // still within the window
+        assert!(purge(&config, &mut board, SystemTime::now())
+            .unwrap()
+            .is_empty());
+        assert!(post_storage::post_exists(&config, BOARD, 2));
+
+        let later = SystemTime::now() + DAY * 7;
+        assert_eq!(purge(&config, &mut board, later).unwrap(), vec![2]);
+        assert!(!post_storage::post_exists(&config, BOARD, 2));
+        assert!(!has_file(&config, 2));
+        assert!(has_file(&config, 3));
+        assert_eq!(
+            *thread_storage::read_thread(&config, BOARD, 1)
+                .unwrap()
+                .get_children(),
+            vec![1, 3]
+        );
+    }
+
+    #[test]
+    fn test_purge_thread() {
+        let config = test_config("purge_thread");
+        create(&config, 1, "op", 1);
+        create(&config, 2, "reply", 1);
+        create(&config, 3, "other op", 3);
+        create(&config, 4, ">>3", 1);
+        delete(&config, 1);
+        let mut board = board_storage::read_board(&config, BOARD).unwrap();
+
+        let later = SystemTime::now() + DAY * 7;
+        assert_eq!(purge(&config, &mut board, later).unwrap(), vec![1, 2, 4]);
+        for id in [1, 2, 4] {
+            assert!(!post_storage::post_exists(&config, BOARD, id));
+            assert!(!has_file(&config, id));
+        }
+        assert!(!thread_storage::thread_exists(&config, BOARD, 1));
+
+        // the other thread is untouched, apart from losing its backlink
+        assert!(read(&config, 3).get_backlinks().is_empty());
+        assert!(has_file(&config, 3));
+    }
+
+    #[test]
+    fn test_purge_listed_thread() {
+        // the purged thread comes off the board, so the index still reads
+        let config = test_config("purge_listed");
+        create(&config, 1, "op", 1);
+        create(&config, 2, "other op", 2);
+        delete(&config, 1);
+        let mut board = board_storage::read_board(&config, BOARD).unwrap();
+        board.bump(2);
+        board.bump(1);
+        board.add_sticky(1, 0, None);
+        board_storage::write_board(&config, &board).unwrap();
+
+        let later = SystemTime::now() + DAY * 7;
+        assert_eq!(purge(&config, &mut board, later).unwrap(), vec![1]);
+        let board = board_storage::read_board(&config, BOARD).unwrap();
+        assert_eq!(*board.get_active(), vec![2]);
+        assert!(board.get_sticky().is_empty());
+
+        let page = index::read_page(&config, &board, 1, index::PER_PAGE, 5, later)
+            .unwrap()
+            .unwrap();
+        assert_eq!(page.threads.len(), 1);
+        assert_eq!(page.threads[0].thread.get_parent(), 2);
+    }
+
+    #[test]
+    fn test_purge_expired_sticky() {
+        let config = test_config("purge_sticky");
+        create(&config, 1, "op", 1);
+        create(&config, 2, "other op", 2);
+        let mut board = board_storage::read_board(&config, BOARD).unwrap();
+        let now = SystemTime::now();
+        board.bump(1);
+        board.bump(2);
+        board.add_sticky(1, 0, Some(now + DAY));
+        board.add_sticky(2, 0, None);
+        board_storage::write_board(&config, &board).unwrap();
+
+        // nothing's purged, but the sticky that ran out comes off the board
+        assert!(purge(&config, &mut board, now + DAY * 2)
+            .unwrap()
+            .is_empty());
+        let board = board_storage::read_board(&config, BOARD).unwrap();
+        assert_eq!(board.get_sticky().len(), 1);
+        assert_eq!(board.get_sticky()[0].get_parent(), 2);
+        assert_eq!(*board.get_active(), vec![2, 1]);
+    }
+}
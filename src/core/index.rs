@@ -0,0 +1,182 @@
+// The board index, split into pages, each thread with its OP and newest few replies. Threads go in
+// the board's thread order, stickies first. Only live threads are counted, so every page but the
+// last is full even while deleted threads are still in the active list.
+// A page only reads the posts it shows. Which threads are live and what's in them comes from the
+// threads themselves, and replies are read newest first until there are enough live ones, so the
+// older replies in a long thread are never touched.
+use crate::core::storage::config::Config;
+use crate::core::storage::post_storage;
+use crate::core::storage::thread_storage;
+use crate::core::structs::board::Board;
+use crate::core::structs::post::Post;
+use crate::core::structs::thread::Thread;
+use std::io::Error;
+use std::time::SystemTime;
+use std::vec::Vec;
+
+pub(crate) const PER_PAGE: usize = 15; // threads on each page of the index, and of the JSON API
+pub(crate) const INDEX_REPLIES: usize = 5; // replies shown under each thread on the index
+
+// a thread as it shows up on the index
+pub(crate) struct IndexThread {
+    pub thread: Thread,
+    pub posts: Vec<(u64, Post)>, // the OP, then the newest replies that are still up
+    pub omitted: usize,          // replies left out, counting deleted ones
+}
+
+pub(crate) struct IndexPage {
+    pub threads: Vec<IndexThread>,
+    pub page: usize, // from 1
+    pub page_count: usize,
+}
+
+pub(crate) fn get_page_count(threads: usize, per_page: usize) -> usize {
+    // an empty board still has its first page
+    return threads.div_ceil(per_page.max(1)).max(1);
+}
+
+pub(crate) fn read_page(
+    config: &Config,
+    board: &Board,
+    page: usize,
+    per_page: usize,
+    replies: usize,
+    now: SystemTime,
+) -> Result<Option<IndexPage>, Error> {
+    // None for a page past the last one
+    let board_name: &str = board.get_name();
+    let mut live: Vec<Thread> = Vec::new();
+    for parent in board.get_thread_order(now) {
+        let thread: Thread = thread_storage::read_thread(config, board_name, parent)?;
+        if !thread.get_deleted() {
+            live.push(thread);
+        }
+    }
+
+    let page_count: usize = get_page_count(live.len(), per_page);
+    if page == 0 || page > page_count {
+        return Ok(None);
+    }
+
+    let mut threads: Vec<IndexThread> = Vec::new();
+    for thread in live.into_iter().skip((page - 1) * per_page).take(per_page) {
+        threads.push(read_thread(config, board_name, thread, replies)?);
+    }
+
+    return Ok(Some(IndexPage {
+        threads,
+        page,
+        page_count,
+    }));
+}
+
+fn read_thread(
+    config: &Config,
+    board: &str,
+    thread: Thread,
+    replies: usize,
+) -> Result<IndexThread, Error> {
+    let parent: u64 = thread.get_parent();
+    let mut shown: Vec<(u64, Post)> = Vec::new();
+    let mut read: usize = 0;
+    for id in thread.get_children()[1..].iter().rev() {
+        if shown.len() == replies {
+            break;
+        }
+        let post: Post = post_storage::read_post(config, board, *id)?;
+        read += 1;
+        if !post.get_deleted() {
+            shown.push((*id, post));
+        }
+    }
+
+    let mut posts: Vec<(u64, Post)> =
+        vec![(parent, post_storage::read_post(config, board, parent)?)];
+    posts.extend(shown.into_iter().rev());
+    let omitted: usize = thread.get_reply_count() - read;
+
+    return Ok(IndexThread {
+        thread,
+        posts,
+        omitted,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::board_storage;
+    use crate::core::storage::fixtures::{write_thread, TestRoot};
+
+    const BOARD: &str = "test";
+
+    fn test_config(name: &str) -> TestRoot {
+        return TestRoot::with_boards("index", name, &[BOARD]);
+    }
+
+    fn ids(index_thread: &IndexThread) -> Vec<u64> {
+        return index_thread.posts.iter().map(|(id, _)| *id).collect();
+    }
+
+    #[test]
+    fn test_get_page_count() {
+        assert_eq!(get_page_count(0, 15), 1);
+        assert_eq!(get_page_count(15, 15), 1);
+        assert_eq!(get_page_count(16, 15), 2);
+        assert_eq!(get_page_count(3, 0), 3);
+    }
+
+    #[test]
+    fn test_read_page() {
+        // bump order is 20, 10, 7, 1, and 1 is stickied. 7 is deleted
+        let config = test_config("read_page");
+        let mut board = board_storage::read_board(&config, BOARD).unwrap();
+        write_thread(&config, &mut board, "", &[1, 2, 3, 4, 5, 6]);
+        write_thread(&config, &mut board, "", &[7]);
+        write_thread(&config, &mut board, "", &[10, 11]);
+        write_thread(&config, &mut board, "", &[20]);
+        let mut deleted = thread_storage::read_thread(&config, BOARD, 7).unwrap();
+        deleted.delete();
+        thread_storage::write_thread(&config, BOARD, &deleted).unwrap();
+        board.add_sticky(1, 0, None);
+        let now = SystemTime::now();
+
+        let first = read_page(&config, &board, 1, 2, 2, now).unwrap().unwrap();
+        assert_eq!((first.page, first.page_count), (1, 2));
+        assert_eq!(ids(&first.threads[0]), vec![1, 5, 6]);
+        assert_eq!(first.threads[0].omitted, 3);
+        assert_eq!(ids(&first.threads[1]), vec![20]);
+        assert_eq!(first.threads[1].omitted, 0);
+
+        let second = read_page(&config, &board, 2, 2, 2, now).unwrap().unwrap();
+        assert_eq!(second.threads.len(), 1);
+        assert_eq!(ids(&second.threads[0]), vec![10, 11]);
+
+        assert!(read_page(&config, &board, 3, 2, 2, now).unwrap().is_none());
+        assert!(read_page(&config, &board, 0, 2, 2, now).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_page_deleted_replies() {
+        // deleted replies are skipped over, and the ones shown are still the newest live ones
+        let config = test_config("deleted_replies");
+        let mut board = board_storage::read_board(&config, BOARD).unwrap();
+        write_thread(&config, &mut board, "", &[1, 2, 3, 4]);
+        let mut reply = post_storage::read_post(&config, BOARD, 4).unwrap();
+        reply.delete();
+        post_storage::write_post(&config, BOARD, 4, &reply).unwrap();
+
+        let page = read_page(&config, &board, 1, 15, 2, SystemTime::now())
+            .unwrap()
+            .unwrap();
+        assert_eq!(ids(&page.threads[0]), vec![1, 2, 3]);
+        assert_eq!(page.threads[0].omitted, 0);
+
+        // an empty board still has a first page
+        let empty = board_storage::read_board(&config, BOARD).unwrap();
+        let page = read_page(&config, &empty, 1, 15, 2, SystemTime::now())
+            .unwrap()
+            .unwrap();
+        assert!(page.threads.is_empty());
+    }
+}
@@ -0,0 +1,4 @@
+// This module turns the raw text of a post into something safe to display. The text is stored
+// exactly as posted, and only parsed when it's rendered.
+pub mod parser;
+pub mod render;
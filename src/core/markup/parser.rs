@@ -0,0 +1,419 @@
+// Post markup parsing. Post text goes in, a tree of nodes comes out, and nothing in the tree is
+// ever trusted markup: text nodes are still raw text, and it's up to the renderer to escape them.
+// Supported markup:
+// >greentext            a whole line starting with '>'
+// >>123                 a link to a post, either in the same thread or another one on the board
+// >>>/board/123         a link to a post on another board, or just >>>/board/ for the board itself
+// [spoiler]..[/spoiler] can be nested, and can span lines
+// [code]..[/code]       can span lines, nothing inside is parsed
+// http(s)://...         bare URLs are linked automatically
+// Unclosed tags are left alone as plain text.
+use std::string::String;
+use std::vec::Vec;
+
+const CODE_OPEN: &str = "[code]";
+const CODE_CLOSE: &str = "[/code]";
+const SPOILER_OPEN: &str = "[spoiler]";
+const SPOILER_CLOSE: &str = "[/spoiler]";
+const POST_LINK: &str = ">>";
+const BOARD_LINK: &str = ">>>/";
+const URL_SCHEMES: [&str; 2] = ["http://", "https://"];
+const URL_TRAILING: &[char] = &['.', ',', ';', ':', '!', '?', ')', '\'', '"'];
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Node {
+    Text(String),
+    LineBreak,
+    Greentext(Vec<Node>),
+    PostLink(u64),
+    BoardLink { board: String, post: Option<u64> },
+    Spoiler(Vec<Node>),
+    Code(String),
+    Url(String),
+}
+
+pub(crate) fn parse(text: &str) -> Vec<Node> {
+    let text: String = text.replace("\r\n", "\n");
+    return parse_nodes(&text, true);
+}
+
+fn parse_nodes(text: &str, block: bool) -> Vec<Node> {
+    // block mode is the whole post, where lines can be greentext and tags can span lines.
+    // inside a greentext line, everything has to finish on that line
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut index: usize = 0;
+    let mut line_start: bool = block;
+
+    while index < text.len() {
+        let rest: &str = &text[index..];
+
+        if let Some((node, length)) = parse_tag(rest, block) {
+            nodes.push(node);
+            index += length;
+            line_start = false;
+            continue;
+        }
+
+        if line_start && rest.starts_with('>') && parse_link(rest).is_none() {
+            let length: usize = rest.find('\n').unwrap_or(rest.len());
+            nodes.push(Node::Greentext(parse_nodes(&rest[..length], false)));
+            index += length;
+            line_start = false;
+            continue;
+        }
+
+        if let Some((node, length)) = parse_link(rest).or_else(|| parse_url(rest)) {
+            nodes.push(node);
+            index += length;
+            line_start = false;
+            continue;
+        }
+
+        let next: char = rest.chars().next().unwrap();
+        match next {
+            '\n' => {
+                nodes.push(Node::LineBreak);
+                line_start = block;
+            }
+            _ => {
+                push_text(&mut nodes, next);
+                line_start = false;
+            }
+        }
+        index += next.len_utf8();
+    }
+
+    return nodes;
+}
+
+pub(crate) fn rewrite_post_links(text: &str, rewrite: &dyn Fn(u64) -> Option<String>) -> String {
+    // replaces >>123 links wherever parse would find one, for when posts change IDs.
+    // everything else is copied exactly as it was, line endings and all
+    let mut rewritten: String = String::with_capacity(text.len());
+    rewrite_nodes(text, true, rewrite, &mut rewritten);
+    return rewritten;
+}
+
+fn rewrite_nodes(
+    text: &str,
+    block: bool,
+    rewrite: &dyn Fn(u64) -> Option<String>,
+    out: &mut String,
+) {
+    // walks the text the same way parse_nodes does
+    let mut index: usize = 0;
+    let mut line_start: bool = block;
+
+    while index < text.len() {
+        let rest: &str = &text[index..];
+
+        if let Some((node, length)) = parse_tag(rest, block) {
+            match node {
+                Node::Spoiler(_) => {
+                    out.push_str(SPOILER_OPEN);
+                    let inner: &str = &rest[SPOILER_OPEN.len()..length - SPOILER_CLOSE.len()];
+                    rewrite_nodes(inner, block, rewrite, out);
+                    out.push_str(SPOILER_CLOSE);
+                }
+                _ => out.push_str(&rest[..length]),
+            }
+            index += length;
+            line_start = false;
+            continue;
+        }
+
+        if line_start && rest.starts_with('>') && parse_link(rest).is_none() {
+            let length: usize = rest.find('\n').unwrap_or(rest.len());
+            rewrite_nodes(&rest[..length], false, rewrite, out);
+            index += length;
+            line_start = false;
+            continue;
+        }
+
+        if let Some((node, length)) = parse_link(rest).or_else(|| parse_url(rest)) {
+            match node {
+                Node::PostLink(post) => match rewrite(post) {
+                    Some(replacement) => out.push_str(&replacement),
+                    None => out.push_str(&rest[..length]),
+                },
+                _ => out.push_str(&rest[..length]),
+            }
+            index += length;
+            line_start = false;
+            continue;
+        }
+
+        let next: char = rest.chars().next().unwrap();
+        out.push(next);
+        line_start = next == '\n' && block;
+        index += next.len_utf8();
+    }
+}
+
+fn push_text(nodes: &mut Vec<Node>, next: char) {
+    // consecutive characters all end up in the same text node
+    if let Some(Node::Text(text)) = nodes.last_mut() {
+        text.push(next);
+        return;
+    }
+    nodes.push(Node::Text(next.to_string()));
+}
+
+fn parse_tag(text: &str, block: bool) -> Option<(Node, usize)> {
+    if let Some(inner) = text.strip_prefix(CODE_OPEN) {
+        let end: usize = inner.find(CODE_CLOSE)?;
+        // a newline straight after the opening tag is just there to start the block
+        let code: &str = inner[..end].strip_prefix('\n').unwrap_or(&inner[..end]);
+        return Some((
+            Node::Code(code.to_string()),
+            CODE_OPEN.len() + end + CODE_CLOSE.len(),
+        ));
+    }
+
+    if let Some(inner) = text.strip_prefix(SPOILER_OPEN) {
+        let end: usize = find_spoiler_close(inner)?;
+        return Some((
+            Node::Spoiler(parse_nodes(&inner[..end], block)),
+            SPOILER_OPEN.len() + end + SPOILER_CLOSE.len(),
+        ));
+    }
+
+    return None;
+}
+
+fn find_spoiler_close(text: &str) -> Option<usize> {
+    // spoilers nest, so this finds the close tag matching the open tag just before the text
+    let mut depth: usize = 0;
+    let mut index: usize = 0;
+
+    while index < text.len() {
+        let rest: &str = &text[index..];
+        if rest.starts_with(SPOILER_OPEN) {
+            depth += 1;
+            index += SPOILER_OPEN.len();
+        } else if rest.starts_with(SPOILER_CLOSE) {
+            if depth == 0 {
+                return Some(index);
+            }
+            depth -= 1;
+            index += SPOILER_CLOSE.len();
+        } else {
+            index += rest.chars().next().unwrap().len_utf8();
+        }
+    }
+
+    return None;
+}
+
+fn parse_link(text: &str) -> Option<(Node, usize)> {
+    if let Some(rest) = text.strip_prefix(BOARD_LINK) {
+        let board_length: usize = rest
+            .find(|c: char| !c.is_alphanumeric())
+            .unwrap_or(rest.len());
+        if board_length == 0 || !rest[board_length..].starts_with('/') {
+            return None;
+        }
+
+        let board: String = rest[..board_length].to_string();
+        let after: &str = &rest[board_length + 1..];
+        let (post, post_length): (Option<u64>, usize) = match parse_number(after) {
+            Some((post, length)) => (Some(post), length),
+            None => (None, 0),
+        };
+
+        return Some((
+            Node::BoardLink { board, post },
+            BOARD_LINK.len() + board_length + 1 + post_length,
+        ));
+    }
+
+    let rest: &str = text.strip_prefix(POST_LINK)?;
+    let (post, length): (u64, usize) = parse_number(rest)?;
+    return Some((Node::PostLink(post), POST_LINK.len() + length));
+}
+
+fn parse_number(text: &str) -> Option<(u64, usize)> {
+    let length: usize = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let number: u64 = text[..length].parse().ok()?;
+    return Some((number, length));
+}
+
+fn parse_url(text: &str) -> Option<(Node, usize)> {
+    let scheme: &str = URL_SCHEMES
+        .iter()
+        .find(|scheme| text.starts_with(**scheme))?;
+
+    let mut length: usize = text
+        .find(|c: char| c.is_whitespace() || c == '<' || c == '>' || c == '[' || c == ']')
+        .unwrap_or(text.len());
+    // punctuation at the end is almost always the end of a sentence, not part of the URL
+    length = text[..length].trim_end_matches(URL_TRAILING).len();
+    if length <= scheme.len() {
+        return None;
+    }
+
+    return Some((Node::Url(text[..length].to_string()), length));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> Node {
+        return Node::Text(value.to_string());
+    }
+
+    #[test]
+    fn test_parse_plain() {
+        assert_eq!(parse(""), vec![]);
+        assert_eq!(
+            parse("hello <b>world</b>"),
+            vec![text("hello <b>world</b>")]
+        );
+        assert_eq!(
+            parse("one\r\ntwo"),
+            vec![text("one"), Node::LineBreak, text("two")]
+        );
+    }
+
+    #[test]
+    fn test_parse_greentext() {
+        assert_eq!(
+            parse(">be me\nnot green > either"),
+            vec![
+                Node::Greentext(vec![text(">be me")]),
+                Node::LineBreak,
+                text("not green > either")
+            ]
+        );
+        assert_eq!(
+            parse(">implying >>12"),
+            vec![Node::Greentext(vec![
+                text(">implying "),
+                Node::PostLink(12)
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_parse_post_link() {
+        assert_eq!(parse(">>123 yes"), vec![Node::PostLink(123), text(" yes")]);
+        assert_eq!(
+            parse("see>>4>>5"),
+            vec![text("see"), Node::PostLink(4), Node::PostLink(5)]
+        );
+        assert_eq!(parse(">>abc"), vec![Node::Greentext(vec![text(">>abc")])]);
+        // too big for a post ID
+        assert_eq!(
+            parse("x >>99999999999999999999999"),
+            vec![text("x >>99999999999999999999999")]
+        );
+    }
+
+    #[test]
+    fn test_parse_board_link() {
+        assert_eq!(
+            parse(">>>/\u{3B1}/42 and >>>/test/"),
+            vec![
+                Node::BoardLink {
+                    board: "\u{3B1}".to_string(),
+                    post: Some(42)
+                },
+                text(" and "),
+                Node::BoardLink {
+                    board: "test".to_string(),
+                    post: None
+                }
+            ]
+        );
+        assert_eq!(parse("x >>>/../1"), vec![text("x >>>/../1")]);
+    }
+
+    #[test]
+    fn test_parse_spoiler() {
+        assert_eq!(
+            parse("a [spoiler]b [spoiler]c[/spoiler][/spoiler] d"),
+            vec![
+                text("a "),
+                Node::Spoiler(vec![text("b "), Node::Spoiler(vec![text("c")])]),
+                text(" d")
+            ]
+        );
+        assert_eq!(parse("[spoiler]open"), vec![text("[spoiler]open")]);
+        assert_eq!(
+            parse("[spoiler]one\n>two[/spoiler]"),
+            vec![Node::Spoiler(vec![
+                text("one"),
+                Node::LineBreak,
+                Node::Greentext(vec![text(">two")])
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_parse_spoiler_in_greentext() {
+        assert_eq!(
+            parse(">[spoiler]a[/spoiler]\n>[spoiler]b\n[/spoiler]"),
+            vec![
+                Node::Greentext(vec![text(">"), Node::Spoiler(vec![text("a")])]),
+                Node::LineBreak,
+                Node::Greentext(vec![text(">[spoiler]b")]),
+                Node::LineBreak,
+                text("[/spoiler]")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_code() {
+        assert_eq!(
+            parse("[code]\nfn main() {\n    >>1 [spoiler]\n}[/code]after"),
+            vec![
+                Node::Code("fn main() {\n    >>1 [spoiler]\n}".to_string()),
+                text("after")
+            ]
+        );
+        assert_eq!(parse("[code]no end"), vec![text("[code]no end")]);
+    }
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            parse("go to https://example.com/a?b=c&d, now"),
+            vec![
+                text("go to "),
+                Node::Url("https://example.com/a?b=c&d".to_string()),
+                text(", now")
+            ]
+        );
+        assert_eq!(parse("http:// nothing"), vec![text("http:// nothing")]);
+        assert_eq!(
+            parse("javascript:alert(1)"),
+            vec![text("javascript:alert(1)")]
+        );
+    }
+
+    #[test]
+    fn test_rewrite_post_links() {
+        let rewrite = |post: u64| match post {
+            1 => Some(">>10".to_string()),
+            2 => Some(">>>/a/20".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            rewrite_post_links(
+                ">>1 >>3 >>>/b/1\r\n>green >>1\r\n[spoiler]>>2 [code]>>1[/code][/spoiler] http://x/>>1",
+                &rewrite
+            ),
+            ">>10 >>3 >>>/b/1\r\n>green >>10\r\n[spoiler]>>>/a/20 [code]>>1[/code][/spoiler] http://x/>>10"
+        );
+        assert_eq!(
+            rewrite_post_links("[spoiler]>>1", &rewrite),
+            "[spoiler]>>10"
+        );
+        assert_eq!(rewrite_post_links("", &rewrite), "");
+    }
+}
@@ -0,0 +1,281 @@
+// HTML rendering of parsed post markup. Everything that came from the poster is escaped, the only
+// markup in the output is what the renderer itself writes.
+// Post links need to know where a post lives, so they are looked up through a LinkResolver.
+// Links to posts that can't be found (never existed, deleted, wrong board) render as dead links.
+use super::parser::{self, Node};
+use std::string::String;
+use std::vec::Vec;
+
+pub(crate) trait LinkResolver {
+    // the thread a live post is in, or None if there is no such post on the board
+    fn find_thread(&self, board: &str, post: u64) -> Option<u64>;
+    fn board_exists(&self, board: &str) -> bool;
+}
+
+pub(crate) fn render_text(
+    text: &str,
+    board: &str,
+    thread: u64,
+    links: &dyn LinkResolver,
+) -> String {
+    return render(&parser::parse(text), board, thread, links);
+}
+
+pub(crate) fn render(nodes: &[Node], board: &str, thread: u64, links: &dyn LinkResolver) -> String {
+    // board and thread are where the post being rendered lives, so links can stay in-thread
+    let mut html: String = String::new();
+
+    for node in nodes {
+        match node {
+            Node::Text(text) => html.push_str(&escape(text)),
+            Node::LineBreak => html.push_str("<br>"),
+            Node::Greentext(children) => {
+                html.push_str("<span class=\"quote\">");
+                html.push_str(&render(children, board, thread, links));
+                html.push_str("</span>");
+            }
+            Node::Spoiler(children) => {
+                html.push_str("<span class=\"spoiler\">");
+                html.push_str(&render(children, board, thread, links));
+                html.push_str("</span>");
+            }
+            Node::Code(code) => {
+                html.push_str("<pre class=\"code\">");
+                html.push_str(&escape(code));
+                html.push_str("</pre>");
+            }
+            Node::Url(url) => {
+                let url: String = escape(url);
+                html.push_str(&format!(
+                    "<a href=\"{}\" rel=\"nofollow noopener noreferrer\" target=\"_blank\">{}</a>",
+                    url, url
+                ));
+            }
+            Node::PostLink(post) => {
+                let text: String = format!("&gt;&gt;{}", post);
+                html.push_str(&post_link(board, *post, thread, false, &text, links));
+            }
+            Node::BoardLink {
+                board: other,
+                post: Some(post),
+            } => {
+                let text: String = format!("&gt;&gt;&gt;/{}/{}", escape(other), post);
+                html.push_str(&post_link(
+                    other,
+                    *post,
+                    thread,
+                    other != board,
+                    &text,
+                    links,
+                ));
+            }
+            Node::BoardLink {
+                board: other,
+                post: None,
+            } => {
+                let text: String = format!("&gt;&gt;&gt;/{}/", escape(other));
+                match links.board_exists(other) {
+                    true => html.push_str(&format!(
+                        "<a href=\"/{}/\" class=\"quotelink\">{}</a>",
+                        escape(other),
+                        text
+                    )),
+                    false => html.push_str(&dead_link(&text)),
+                }
+            }
+        }
+    }
+
+    return html;
+}
+
+pub(crate) fn render_backlinks(
+    backlinks: &[(String, u64)],
+    board: &str,
+    thread: u64,
+    links: &dyn LinkResolver,
+) -> String {
+    // the posts quoting a post, written the same way they would be quoted from this post
+    let mut rendered: Vec<String> = Vec::new();
+
+    for (other, post) in backlinks {
+        let cross_board: bool = other != board;
+        let text: String = match cross_board {
+            true => format!("&gt;&gt;&gt;/{}/{}", escape(other), post),
+            false => format!("&gt;&gt;{}", post),
+        };
+        rendered.push(post_link(other, *post, thread, cross_board, &text, links));
+    }
+
+    return rendered.join(" ");
+}
+
+fn post_link(
+    board: &str,
+    post: u64,
+    thread: u64,
+    cross_board: bool,
+    text: &str,
+    links: &dyn LinkResolver,
+) -> String {
+    let target: u64 = match links.find_thread(board, post) {
+        Some(target) => target,
+        None => return dead_link(text),
+    };
+
+    // links within the same thread only need the anchor
+    if target == thread && !cross_board {
+        return format!("<a href=\"#p{}\" class=\"quotelink\">{}</a>", post, text);
+    }
+
+    return format!(
+        "<a href=\"/{}/thread/{}#p{}\" class=\"quotelink\">{}</a>",
+        escape(board),
+        target,
+        post,
+        text
+    );
+}
+
+fn dead_link(text: &str) -> String {
+    return format!("<span class=\"deadlink\">{}</span>", text);
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped: String = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    return escaped;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOARD: &str = "test";
+    const THREAD: u64 = 10;
+
+    // posts 10 and 11 are in thread 10, 20 is in thread 20, and /α/ has post 5 in thread 1
+    struct TestLinks;
+
+    impl LinkResolver for TestLinks {
+        fn find_thread(&self, board: &str, post: u64) -> Option<u64> {
+            return match (board, post) {
+                (BOARD, 10) | (BOARD, 11) => Some(10),
+                (BOARD, 20) => Some(20),
+                ("\u{3B1}", 5) => Some(1),
+                _ => None,
+            };
+        }
+
+        fn board_exists(&self, board: &str) -> bool {
+            return board == BOARD || board == "\u{3B1}";
+        }
+    }
+
+    fn render_test(text: &str) -> String {
+        return render_text(text, BOARD, THREAD, &TestLinks);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("<script>alert('x' & \"y\")</script>"),
+            "&lt;script&gt;alert(&#39;x&#39; &amp; &quot;y&quot;)&lt;/script&gt;"
+        );
+    }
+
+    #[test]
+    fn test_render_text() {
+        assert_eq!(
+            render_test("<b>hi</b>\n>green <i>"),
+            "&lt;b&gt;hi&lt;/b&gt;<br><span class=\"quote\">&gt;green &lt;i&gt;</span>"
+        );
+    }
+
+    #[test]
+    fn test_render_post_links() {
+        assert_eq!(
+            render_test(">>11"),
+            "<a href=\"#p11\" class=\"quotelink\">&gt;&gt;11</a>"
+        );
+        assert_eq!(
+            render_test(">>20"),
+            "<a href=\"/test/thread/20#p20\" class=\"quotelink\">&gt;&gt;20</a>"
+        );
+        assert_eq!(
+            render_test(">>99"),
+            "<span class=\"deadlink\">&gt;&gt;99</span>"
+        );
+    }
+
+    #[test]
+    fn test_render_board_links() {
+        assert_eq!(
+            render_test(">>>/\u{3B1}/5"),
+            "<a href=\"/\u{3B1}/thread/1#p5\" class=\"quotelink\">&gt;&gt;&gt;/\u{3B1}/5</a>"
+        );
+        assert_eq!(
+            render_test(">>>/test/11"),
+            "<a href=\"#p11\" class=\"quotelink\">&gt;&gt;&gt;/test/11</a>"
+        );
+        assert_eq!(
+            render_test(">>>/\u{3B1}/6"),
+            "<span class=\"deadlink\">&gt;&gt;&gt;/\u{3B1}/6</span>"
+        );
+        assert_eq!(
+            render_test(">>>/test/"),
+            "<a href=\"/test/\" class=\"quotelink\">&gt;&gt;&gt;/test/</a>"
+        );
+        assert_eq!(
+            render_test(">>>/nope/"),
+            "<span class=\"deadlink\">&gt;&gt;&gt;/nope/</span>"
+        );
+    }
+
+    #[test]
+    fn test_render_backlinks() {
+        let backlinks = vec![
+            (BOARD.to_string(), 11),
+            (BOARD.to_string(), 20),
+            ("\u{3B1}".to_string(), 5),
+            (BOARD.to_string(), 99),
+        ];
+
+        assert_eq!(
+            render_backlinks(&backlinks, BOARD, THREAD, &TestLinks),
+            "<a href=\"#p11\" class=\"quotelink\">&gt;&gt;11</a> \
+             <a href=\"/test/thread/20#p20\" class=\"quotelink\">&gt;&gt;20</a> \
+             <a href=\"/\u{3B1}/thread/1#p5\" class=\"quotelink\">&gt;&gt;&gt;/\u{3B1}/5</a> \
+             <span class=\"deadlink\">&gt;&gt;99</span>"
+        );
+        assert_eq!(render_backlinks(&[], BOARD, THREAD, &TestLinks), "");
+    }
+
+    #[test]
+    fn test_render_spoiler_and_code() {
+        assert_eq!(
+            render_test("[spoiler]<b>[/spoiler][code]\n<a>\n >>11[/code]"),
+            "<span class=\"spoiler\">&lt;b&gt;</span><pre class=\"code\">&lt;a&gt;\n &gt;&gt;11</pre>"
+        );
+    }
+
+    #[test]
+    fn test_render_url() {
+        assert_eq!(
+            render_test("https://example.com/?a=1&b=\"2\""),
+            "<a href=\"https://example.com/?a=1&amp;b=&quot;2\" rel=\"nofollow noopener noreferrer\" \
+             target=\"_blank\">https://example.com/?a=1&amp;b=&quot;2</a>&quot;"
+        );
+    }
+}
@@ -0,0 +1,238 @@
+// Detection of what an uploaded file *actually* is, going by its contents rather than its name.
+use image::error::ImageFormatHint;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageError, ImageFormat, ImageReader};
+use std::fmt;
+use std::io::Cursor;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum FileType {
+    Png,
+    Jpeg,
+    Gif,
+    WebP,
+    WebM,
+    Mp4,
+    Mp3,
+    Pdf,
+}
+
+const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+const JPEG_MAGIC: &[u8] = b"\xFF\xD8\xFF";
+const GIF87_MAGIC: &[u8] = b"GIF87a";
+const GIF89_MAGIC: &[u8] = b"GIF89a";
+const RIFF_MAGIC: &[u8] = b"RIFF";
+const WEBP_MAGIC: &[u8] = b"WEBP"; // at offset 8, after the RIFF size
+const EBML_MAGIC: &[u8] = b"\x1A\x45\xDF\xA3";
+const WEBM_DOCTYPE: &[u8] = b"webm"; // somewhere in the EBML header
+const EBML_HEADER_SCAN: usize = 64;
+const FTYP_MAGIC: &[u8] = b"ftyp"; // at offset 4, after the box size
+const ID3_MAGIC: &[u8] = b"ID3";
+const PDF_MAGIC: &[u8] = b"%PDF-";
+
+impl FileType {
+    pub fn detect(data: &[u8]) -> Option<FileType> {
+        if data.starts_with(PNG_MAGIC) {
+            return Some(FileType::Png);
+        }
+        if data.starts_with(JPEG_MAGIC) {
+            return Some(FileType::Jpeg);
+        }
+        if data.starts_with(GIF87_MAGIC) || data.starts_with(GIF89_MAGIC) {
+            return Some(FileType::Gif);
+        }
+        if data.starts_with(RIFF_MAGIC) && data.get(8..12) == Some(WEBP_MAGIC) {
+            return Some(FileType::WebP);
+        }
+        if data.starts_with(EBML_MAGIC) {
+            // matroska shares the EBML magic, so the doctype is what tells webm apart
+            let header: &[u8] = &data[..data.len().min(EBML_HEADER_SCAN)];
+            if header
+                .windows(WEBM_DOCTYPE.len())
+                .any(|window| window == WEBM_DOCTYPE)
+            {
+                return Some(FileType::WebM);
+            }
+            return None;
+        }
+        if data.get(4..8) == Some(FTYP_MAGIC) {
+            return Some(FileType::Mp4);
+        }
+        if data.starts_with(ID3_MAGIC) || FileType::is_mpeg_audio_frame(data) {
+            return Some(FileType::Mp3);
+        }
+        if data.starts_with(PDF_MAGIC) {
+            return Some(FileType::Pdf);
+        }
+        return None;
+    }
+
+    fn is_mpeg_audio_frame(data: &[u8]) -> bool {
+        // an 11 bit frame sync, followed by a layer III marker
+        return match data {
+            [0xFF, second, ..] => second & 0xE0 == 0xE0 && second & 0x06 == 0x02,
+            _ => false,
+        };
+    }
+
+    pub fn get_mime(&self) -> &'static str {
+        return match self {
+            FileType::Png => "image/png",
+            FileType::Jpeg => "image/jpeg",
+            FileType::Gif => "image/gif",
+            FileType::WebP => "image/webp",
+            FileType::WebM => "video/webm",
+            FileType::Mp4 => "video/mp4",
+            FileType::Mp3 => "audio/mpeg",
+            FileType::Pdf => "application/pdf",
+        };
+    }
+
+    pub fn get_extensions(&self) -> &'static [&'static str] {
+        // the first extension is the one files are stored under
+        return match self {
+            FileType::Png => &["png"],
+            FileType::Jpeg => &["jpg", "jpeg", "jpe", "jfif"],
+            FileType::Gif => &["gif"],
+            FileType::WebP => &["webp"],
+            FileType::WebM => &["webm"],
+            FileType::Mp4 => &["mp4", "m4v"],
+            FileType::Mp3 => &["mp3"],
+            FileType::Pdf => &["pdf"],
+        };
+    }
+
+    pub fn get_extension(&self) -> &'static str {
+        return self.get_extensions()[0];
+    }
+
+    pub fn is_image(&self) -> bool {
+        return matches!(
+            self,
+            FileType::Png | FileType::Jpeg | FileType::Gif | FileType::WebP
+        );
+    }
+
+    pub fn decode(&self, data: &[u8]) -> Result<DynamicImage, ImageError> {
+        // decoding a GIF as a single image only gives the first frame
+        let format: ImageFormat = match ImageFormat::from_mime_type(self.get_mime()) {
+            Some(format) if self.is_image() => format,
+            _ => {
+                let hint: ImageFormatHint = ImageFormatHint::Name(self.to_string());
+                return Err(ImageError::Unsupported(hint.into()));
+            }
+        };
+
+        // cameras store photos sideways along with an EXIF orientation, which is applied here so
+        // hashes and thumbnails go by the image the way it's shown
+        let mut decoder = ImageReader::with_format(Cursor::new(data), format).into_decoder()?;
+        let orientation: Orientation = decoder.orientation()?;
+        let mut image: DynamicImage = DynamicImage::from_decoder(decoder)?;
+        image.apply_orientation(orientation);
+
+        return Ok(image);
+    }
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name: &str = match self {
+            FileType::Png => "PNG",
+            FileType::Jpeg => "JPEG",
+            FileType::Gif => "GIF",
+            FileType::WebP => "WebP",
+            FileType::WebM => "WebM",
+            FileType::Mp4 => "MP4",
+            FileType::Mp3 => "MP3",
+            FileType::Pdf => "PDF",
+        };
+        return write!(f, "{}", name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_TYPES: [FileType; 8] = [
+        FileType::Png,
+        FileType::Jpeg,
+        FileType::Gif,
+        FileType::WebP,
+        FileType::WebM,
+        FileType::Mp4,
+        FileType::Mp3,
+        FileType::Pdf,
+    ];
+
+    #[test]
+    fn test_detect_images() {
+        assert_eq!(
+            FileType::detect(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+            Some(FileType::Png)
+        );
+        assert_eq!(
+            FileType::detect(b"\xFF\xD8\xFF\xE0\0\x10JFIF"),
+            Some(FileType::Jpeg)
+        );
+        assert_eq!(FileType::detect(b"GIF87a\x01\0\x01\0"), Some(FileType::Gif));
+        assert_eq!(FileType::detect(b"GIF89a\x01\0\x01\0"), Some(FileType::Gif));
+        assert_eq!(
+            FileType::detect(b"RIFF\x24\0\0\0WEBPVP8 "),
+            Some(FileType::WebP)
+        );
+    }
+
+    #[test]
+    fn test_detect_media() {
+        let webm: &[u8] = b"\x1A\x45\xDF\xA3\x9F\x42\x86\x81\x01\x42\x82\x84webm";
+        let matroska: &[u8] = b"\x1A\x45\xDF\xA3\xA3\x42\x86\x81\x01\x42\x82\x88matroska";
+
+        assert_eq!(FileType::detect(webm), Some(FileType::WebM));
+        assert_eq!(FileType::detect(matroska), None);
+        assert_eq!(FileType::detect(b"\0\0\0\x20ftypisom"), Some(FileType::Mp4));
+        assert_eq!(FileType::detect(b"ID3\x04\0\0"), Some(FileType::Mp3));
+        assert_eq!(FileType::detect(b"\xFF\xFB\x90\x64"), Some(FileType::Mp3));
+        assert_eq!(FileType::detect(b"%PDF-1.7\n"), Some(FileType::Pdf));
+    }
+
+    #[test]
+    fn test_detect_unknown() {
+        assert_eq!(FileType::detect(b""), None);
+        assert_eq!(FileType::detect(b"just some text"), None);
+        assert_eq!(FileType::detect(b"RIFF\x24\0\0\0WAVEfmt "), None);
+        assert_eq!(FileType::detect(b"MZ\x90\0"), None);
+    }
+
+    #[test]
+    fn test_get_extension() {
+        assert_eq!(FileType::Jpeg.get_extension(), "jpg");
+        for file_type in ALL_TYPES {
+            assert!(file_type
+                .get_extensions()
+                .contains(&file_type.get_extension()));
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut data: Vec<u8> = Vec::new();
+        DynamicImage::new_rgb8(3, 2)
+            .write_to(&mut std::io::Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+
+        let decoded = FileType::Png.decode(&data).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (3, 2));
+        assert!(FileType::Jpeg.decode(&data).is_err());
+        assert!(FileType::Pdf.decode(b"%PDF-1.7\n").is_err());
+        assert!(FileType::Png.decode(b"\x89PNG\r\n\x1a\ngarbage").is_err());
+    }
+
+    #[test]
+    fn test_is_image() {
+        assert!(FileType::Png.is_image());
+        assert!(FileType::WebP.is_image());
+        assert!(!FileType::WebM.is_image());
+        assert!(!FileType::Pdf.is_image());
+    }
+}
@@ -0,0 +1,7 @@
+// This module handles the contents of uploaded files, as opposed to the attachment metadata
+// kept on a post.
+pub mod file_type;
+pub mod perceptual;
+pub mod sanitize;
+pub mod thumbnail;
+pub mod validation;
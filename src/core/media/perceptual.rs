@@ -0,0 +1,104 @@
+// Perceptual hashing, for catching images that have been re-encoded, resized or otherwise fiddled
+// with enough to dodge an exact hash comparison.
+// This is a difference hash (dHash): the image is shrunk to 9x8 greyscale pixels, and each bit
+// records whether a pixel is brighter than its right hand neighbour. Similar images end up with
+// hashes that differ in only a handful of bits.
+use image::imageops::FilterType;
+use image::{DynamicImage, GrayImage};
+
+const HASH_WIDTH: u32 = 9;
+const HASH_HEIGHT: u32 = 8;
+
+pub(crate) fn dhash(image: &DynamicImage) -> u64 {
+    let small: GrayImage = image
+        .resize_exact(HASH_WIDTH, HASH_HEIGHT, FilterType::Triangle)
+        .into_luma8();
+    let mut hash: u64 = 0;
+
+    for y in 0..HASH_HEIGHT {
+        for x in 0..HASH_WIDTH - 1 {
+            let brighter: bool = small[(x, y)][0] > small[(x + 1, y)][0];
+            hash = (hash << 1) | brighter as u64;
+        }
+    }
+
+    return hash;
+}
+
+pub(crate) fn distance(a: u64, b: u64) -> u32 {
+    // the hamming distance, ie how many bits differ
+    return (a ^ b).count_ones();
+}
+
+pub(crate) fn to_hex(hash: u64) -> String {
+    return format!("{:016x}", hash);
+}
+
+pub(crate) fn from_hex(hex: &str) -> Option<u64> {
+    if hex.len() != 16 {
+        return None;
+    }
+    return u64::from_str_radix(hex, 16).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use std::io::Cursor;
+
+    fn gradient(width: u32, height: u32, flipped: bool) -> DynamicImage {
+        let image = RgbImage::from_fn(width, height, |x, y| {
+            let level = ((x * 255 / width + y * 64 / height) % 256) as u8;
+            match flipped {
+                true => Rgb([255 - level, 255 - level, 255 - level]),
+                false => Rgb([level, level / 2, 255 - level]),
+            }
+        });
+        return DynamicImage::ImageRgb8(image);
+    }
+
+    #[test]
+    fn test_dhash_stable() {
+        let image = gradient(64, 64, false);
+        assert_eq!(dhash(&image), dhash(&image.clone()));
+    }
+
+    #[test]
+    fn test_dhash_similar() {
+        // resized and recompressed copies should land close to the original
+        let original = gradient(256, 256, false);
+        let resized = original.resize_exact(100, 100, FilterType::Nearest);
+        let mut jpeg: Vec<u8> = Vec::new();
+        original
+            .write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+            .unwrap();
+        let recompressed = image::load_from_memory(&jpeg).unwrap();
+
+        assert!(distance(dhash(&original), dhash(&resized)) <= 4);
+        assert!(distance(dhash(&original), dhash(&recompressed)) <= 4);
+    }
+
+    #[test]
+    fn test_dhash_different() {
+        let original = gradient(256, 256, false);
+        let flipped = gradient(256, 256, true);
+
+        assert!(distance(dhash(&original), dhash(&flipped)) > 16);
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance(0, 0), 0);
+        assert_eq!(distance(0, u64::MAX), 64);
+        assert_eq!(distance(0b1010, 0b0110), 2);
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(to_hex(0xff), "00000000000000ff");
+        assert_eq!(from_hex(&to_hex(0xdeadbeef)), Some(0xdeadbeef));
+        assert_eq!(from_hex("ff"), None);
+        assert_eq!(from_hex("zzzzzzzzzzzzzzzz"), None);
+    }
+}
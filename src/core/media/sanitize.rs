@@ -0,0 +1,692 @@
+// Metadata stripping for uploaded images, so posters don't leak GPS coordinates, camera serials
+// and the like. This works on the container format directly rather than re-encoding, so the pixel
+// data is never touched. Anything after the end of a JPEG or PNG is dropped as well, since that's
+// where other files get tacked on.
+// EXIF orientation is the one piece of metadata that changes how an image is displayed, so when an
+// image has one, it's kept in a freshly written EXIF block holding nothing else.
+// GIFs don't carry EXIF, and are passed through as they are.
+use super::file_type::FileType;
+use std::fmt;
+use std::vec::Vec;
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const ORIENTATION_TAG: u16 = 0x0112;
+const SHORT_TYPE: u16 = 3;
+const NORMAL_ORIENTATION: u16 = 1;
+
+const JPEG_SOI: &[u8] = b"\xFF\xD8";
+const JPEG_APP0: u8 = 0xE0;
+const JPEG_APP1: u8 = 0xE1;
+const JPEG_APP2: u8 = 0xE2;
+const JPEG_APP14: u8 = 0xEE;
+const JPEG_APP15: u8 = 0xEF;
+const JPEG_COM: u8 = 0xFE;
+const JPEG_SOS: u8 = 0xDA;
+const JPEG_EOI: u8 = 0xD9;
+const JPEG_ICC_PROFILE: &[u8] = b"ICC_PROFILE\0";
+
+const PNG_SIGNATURE_LENGTH: usize = 8;
+const PNG_IHDR: &[u8] = b"IHDR";
+const PNG_EXIF: &[u8] = b"eXIf";
+const PNG_IEND: &[u8] = b"IEND";
+const PNG_METADATA_CHUNKS: [&[u8]; 5] = [b"tEXt", b"zTXt", b"iTXt", b"tIME", PNG_EXIF];
+
+const WEBP_HEADER_LENGTH: usize = 12;
+const WEBP_VP8X: &[u8] = b"VP8X";
+const WEBP_EXIF: &[u8] = b"EXIF";
+const WEBP_XMP: &[u8] = b"XMP ";
+const WEBP_EXIF_FLAG: u8 = 0x08;
+const WEBP_XMP_FLAG: u8 = 0x04;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct MalformedImage;
+
+impl fmt::Display for MalformedImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "image structure is malformed");
+    }
+}
+
+impl std::error::Error for MalformedImage {}
+
+pub(crate) fn strip_metadata(data: &[u8], file_type: FileType) -> Result<Vec<u8>, MalformedImage> {
+    return match file_type {
+        FileType::Jpeg => strip_jpeg(data),
+        FileType::Png => strip_png(data),
+        FileType::WebP => strip_webp(data),
+        _ => Ok(data.to_vec()),
+    };
+}
+
+fn strip_jpeg(data: &[u8]) -> Result<Vec<u8>, MalformedImage> {
+    // segments are copied across one at a time, dropping every APPn segment except for
+    // JFIF (APP0), ICC profiles (APP2) and Adobe colour info (APP14), and all comments
+    let mut kept: Vec<&[u8]> = Vec::new();
+    let mut orientation: u16 = NORMAL_ORIENTATION;
+    let mut position: usize = JPEG_SOI.len();
+
+    if !data.starts_with(JPEG_SOI) {
+        return Err(MalformedImage);
+    }
+
+    while position < data.len() {
+        if data[position] != 0xFF {
+            return Err(MalformedImage);
+        }
+        let marker: u8 = *data.get(position + 1).ok_or(MalformedImage)?;
+
+        if marker == 0xFF {
+            position += 1; // fill byte
+            continue;
+        }
+        if marker == JPEG_EOI {
+            // anything after the end of the image goes, which is where MPF's extra images and
+            // files tacked on to the end live
+            kept.push(&data[position..position + 2]);
+            break;
+        }
+        if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            kept.push(&data[position..position + 2]);
+            position += 2;
+            continue;
+        }
+
+        let length: usize = read_u16(data, position + 2, false)? as usize;
+        if length < 2 {
+            return Err(MalformedImage);
+        }
+        let end: usize = position + 2 + length;
+        let segment: &[u8] = data.get(position..end).ok_or(MalformedImage)?;
+        let payload: &[u8] = &segment[4..];
+
+        if marker == JPEG_SOS {
+            // the scan header, then image data up to the next marker. progressive images have
+            // more tables and scans after it, so the segments carry on from there
+            let scan_end: usize = find_scan_end(data, end);
+            kept.push(&data[position..scan_end]);
+            position = scan_end;
+            continue;
+        }
+
+        let keep: bool = match marker {
+            JPEG_APP0 | JPEG_APP14 => true,
+            JPEG_APP2 => payload.starts_with(JPEG_ICC_PROFILE),
+            JPEG_APP0..=JPEG_APP15 | JPEG_COM => false,
+            _ => true,
+        };
+        if marker == JPEG_APP1 && payload.starts_with(EXIF_HEADER) {
+            orientation = read_orientation(&payload[EXIF_HEADER.len()..]).unwrap_or(orientation);
+        }
+        if keep {
+            kept.push(segment);
+        }
+        position = end;
+    }
+
+    let mut stripped: Vec<u8> = JPEG_SOI.to_vec();
+    let mut segments = kept.into_iter().peekable();
+
+    // EXIF should come after the JFIF header, if there is one
+    if let Some(segment) = segments.next_if(|segment| segment[1] == JPEG_APP0) {
+        stripped.extend_from_slice(segment);
+    }
+    if orientation != NORMAL_ORIENTATION {
+        let exif: Vec<u8> = [EXIF_HEADER, &orientation_tiff(orientation)].concat();
+        stripped.extend_from_slice(&[0xFF, JPEG_APP1]);
+        stripped.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+        stripped.extend_from_slice(&exif);
+    }
+    for segment in segments {
+        stripped.extend_from_slice(segment);
+    }
+
+    return Ok(stripped);
+}
+
+fn find_scan_end(data: &[u8], start: usize) -> usize {
+    // 0xFF in image data is always followed by a zero byte, and restart markers can sit in the
+    // middle of it, so the first other marker ends it. a truncated image runs to the end
+    let mut position: usize = start;
+
+    while position + 1 < data.len() {
+        if data[position] != 0xFF {
+            position += 1;
+            continue;
+        }
+        match data[position + 1] {
+            0x00 | 0xD0..=0xD7 => position += 2,
+            0xFF => position += 1, // fill byte before a marker
+            _ => return position,
+        }
+    }
+
+    return data.len();
+}
+
+fn strip_png(data: &[u8]) -> Result<Vec<u8>, MalformedImage> {
+    let mut stripped: Vec<u8> = data
+        .get(..PNG_SIGNATURE_LENGTH)
+        .ok_or(MalformedImage)?
+        .to_vec();
+    let mut orientation: u16 = NORMAL_ORIENTATION;
+    let mut kept: Vec<&[u8]> = Vec::new();
+    let mut position: usize = PNG_SIGNATURE_LENGTH;
+
+    // chunks are a length, a type, the data, and then a CRC
+    while position < data.len() {
+        let length: usize = read_u32(data, position, false)? as usize;
+        let end: usize = position + 12 + length;
+        let chunk: &[u8] = data.get(position..end).ok_or(MalformedImage)?;
+        let chunk_type: &[u8] = &chunk[4..8];
+
+        if chunk_type == PNG_EXIF {
+            orientation = read_orientation(&chunk[8..8 + length]).unwrap_or(orientation);
+        }
+        if !PNG_METADATA_CHUNKS.contains(&chunk_type) {
+            kept.push(chunk);
+        }
+        if chunk_type == PNG_IEND {
+            // the same as a JPEG's end of image, whatever's tacked on after it goes
+            break;
+        }
+        position = end;
+    }
+
+    for chunk in kept {
+        stripped.extend_from_slice(chunk);
+        // eXIf has to come before the image data, so it goes right after the header
+        if &chunk[4..8] == PNG_IHDR && orientation != NORMAL_ORIENTATION {
+            let exif: Vec<u8> = orientation_tiff(orientation);
+            let typed: Vec<u8> = [PNG_EXIF, &exif].concat();
+            stripped.extend_from_slice(&(exif.len() as u32).to_be_bytes());
+            stripped.extend_from_slice(&typed);
+            stripped.extend_from_slice(&crc32(&typed).to_be_bytes());
+        }
+    }
+
+    return Ok(stripped);
+}
+
+fn strip_webp(data: &[u8]) -> Result<Vec<u8>, MalformedImage> {
+    // RIFF chunks are a type, a little endian length, then the data padded to an even length.
+    // the VP8X chunk has flags advertising EXIF and XMP, so those need to agree with what's left
+    let mut orientation: u16 = NORMAL_ORIENTATION;
+    let mut kept: Vec<Vec<u8>> = Vec::new();
+    let mut position: usize = WEBP_HEADER_LENGTH;
+
+    if data.len() < WEBP_HEADER_LENGTH {
+        return Err(MalformedImage);
+    }
+
+    while position < data.len() {
+        let length: usize = read_u32(data, position + 4, true)? as usize;
+        let end: usize = (position + 8 + length + (length & 1)).min(data.len());
+        let chunk: &[u8] = data.get(position..end).ok_or(MalformedImage)?;
+        let chunk_type: &[u8] = &chunk[..4];
+        let chunk_data: &[u8] = chunk.get(8..8 + length).ok_or(MalformedImage)?;
+
+        if chunk_type == WEBP_EXIF {
+            let tiff: &[u8] = chunk_data.strip_prefix(EXIF_HEADER).unwrap_or(chunk_data);
+            orientation = read_orientation(tiff).unwrap_or(orientation);
+        }
+        if chunk_type != WEBP_EXIF && chunk_type != WEBP_XMP {
+            kept.push(chunk.to_vec());
+        }
+        position = end;
+    }
+
+    let has_vp8x: bool = kept.first().is_some_and(|chunk| &chunk[..4] == WEBP_VP8X);
+    if has_vp8x {
+        let flags: &mut u8 = kept[0].get_mut(8).ok_or(MalformedImage)?;
+        *flags &= !(WEBP_EXIF_FLAG | WEBP_XMP_FLAG);
+        if orientation != NORMAL_ORIENTATION {
+            *flags |= WEBP_EXIF_FLAG;
+            let exif: Vec<u8> = orientation_tiff(orientation);
+            kept.push([WEBP_EXIF, &(exif.len() as u32).to_le_bytes(), &exif].concat());
+        }
+    }
+
+    let body: Vec<u8> = kept.concat();
+    let mut stripped: Vec<u8> = Vec::with_capacity(WEBP_HEADER_LENGTH + body.len());
+    stripped.extend_from_slice(&data[..4]);
+    stripped.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+    stripped.extend_from_slice(&data[8..WEBP_HEADER_LENGTH]);
+    stripped.extend_from_slice(&body);
+
+    return Ok(stripped);
+}
+
+fn read_orientation(tiff: &[u8]) -> Option<u16> {
+    // finds the orientation tag in the first IFD of a TIFF structure
+    let little_endian: bool = match tiff.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let ifd: usize = read_u32(tiff, 4, little_endian).ok()? as usize;
+    let count: usize = read_u16(tiff, ifd, little_endian).ok()? as usize;
+
+    for index in 0..count {
+        let entry: usize = ifd + 2 + index * 12;
+        if read_u16(tiff, entry, little_endian).ok()? != ORIENTATION_TAG {
+            continue;
+        }
+        if read_u16(tiff, entry + 2, little_endian).ok()? != SHORT_TYPE {
+            return None;
+        }
+        let orientation: u16 = read_u16(tiff, entry + 8, little_endian).ok()?;
+        return match orientation {
+            1..=8 => Some(orientation),
+            _ => None,
+        };
+    }
+
+    return None;
+}
+
+fn orientation_tiff(orientation: u16) -> Vec<u8> {
+    // a big endian TIFF header, and a single IFD with a single entry
+    let mut tiff: Vec<u8> = b"MM\0\x2A\0\0\0\x08\0\x01".to_vec();
+    tiff.extend_from_slice(&ORIENTATION_TAG.to_be_bytes());
+    tiff.extend_from_slice(&SHORT_TYPE.to_be_bytes());
+    tiff.extend_from_slice(&1u32.to_be_bytes());
+    tiff.extend_from_slice(&orientation.to_be_bytes());
+    tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]); // value padding, then no next IFD
+
+    return tiff;
+}
+
+fn read_u16(data: &[u8], position: usize, little_endian: bool) -> Result<u16, MalformedImage> {
+    let bytes: [u8; 2] = data
+        .get(position..position + 2)
+        .ok_or(MalformedImage)?
+        .try_into()
+        .unwrap();
+
+    return match little_endian {
+        true => Ok(u16::from_le_bytes(bytes)),
+        false => Ok(u16::from_be_bytes(bytes)),
+    };
+}
+
+fn read_u32(data: &[u8], position: usize, little_endian: bool) -> Result<u32, MalformedImage> {
+    let bytes: [u8; 4] = data
+        .get(position..position + 4)
+        .ok_or(MalformedImage)?
+        .try_into()
+        .unwrap();
+
+    return match little_endian {
+        true => Ok(u32::from_le_bytes(bytes)),
+        false => Ok(u32::from_be_bytes(bytes)),
+    };
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    // the standard CRC-32 PNG uses, done bitwise since it's only ever run over one small chunk
+    let mut crc: u32 = 0xFFFFFFFF;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB88320,
+                _ => crc >> 1,
+            };
+        }
+    }
+
+    return !crc;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    const GPS: &[u8] = b"GPS 51.5007 N 0.1246 W";
+
+    fn test_image() -> DynamicImage {
+        let image = RgbImage::from_fn(8, 8, |x, y| image::Rgb([x as u8 * 30, y as u8 * 30, 128]));
+        return DynamicImage::ImageRgb8(image);
+    }
+
+    fn encode(format: ImageFormat) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        test_image()
+            .write_to(&mut Cursor::new(&mut data), format)
+            .unwrap();
+        return data;
+    }
+
+    fn decode(data: &[u8], format: ImageFormat) -> Vec<u8> {
+        return image::load_from_memory_with_format(data, format)
+            .unwrap()
+            .into_rgb8()
+            .into_raw();
+    }
+
+    fn contains(data: &[u8], needle: &[u8]) -> bool {
+        return data.windows(needle.len()).any(|window| window == needle);
+    }
+
+    fn exif_tiff(orientation: u16) -> Vec<u8> {
+        // little endian, an orientation entry followed by a string entry that stands in for GPS data
+        let mut tiff: Vec<u8> = b"II\x2A\0\x08\0\0\0\x02\0".to_vec();
+        tiff.extend_from_slice(&[0x12, 0x01, 0x03, 0x00, 0x01, 0, 0, 0]);
+        tiff.extend_from_slice(&orientation.to_le_bytes());
+        tiff.extend_from_slice(&[0, 0]);
+        tiff.extend_from_slice(&[0x0E, 0x01, 0x02, 0x00]);
+        tiff.extend_from_slice(&(GPS.len() as u32).to_le_bytes());
+        tiff.extend_from_slice(&38u32.to_le_bytes());
+        tiff.extend_from_slice(&[0, 0, 0, 0]);
+        tiff.extend_from_slice(GPS);
+        return tiff;
+    }
+
+    fn jpeg_segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        return [
+            &[0xFF, marker],
+            &(payload.len() as u16 + 2).to_be_bytes()[..],
+            payload,
+        ]
+        .concat();
+    }
+
+    fn jpeg_with_metadata(orientation: u16) -> Vec<u8> {
+        let clean = encode(ImageFormat::Jpeg);
+        let exif = jpeg_segment(JPEG_APP1, &[EXIF_HEADER, &exif_tiff(orientation)].concat());
+        let xmp = jpeg_segment(
+            JPEG_APP1,
+            b"http://ns.adobe.com/xap/1.0/\0<x:xmpmeta>secret</x:xmpmeta>",
+        );
+        let iptc = jpeg_segment(0xED, b"Photoshop 3.0\0secret");
+        let comment = jpeg_segment(JPEG_COM, b"secret comment");
+        let icc = jpeg_segment(JPEG_APP2, b"ICC_PROFILE\0\x01\x01profile");
+
+        return [&clean[..2], &exif, &xmp, &iptc, &comment, &icc, &clean[2..]].concat();
+    }
+
+    fn png_chunk(chunk_type: &[u8], chunk_data: &[u8]) -> Vec<u8> {
+        let typed = [chunk_type, chunk_data].concat();
+        return [
+            &(chunk_data.len() as u32).to_be_bytes()[..],
+            &typed,
+            &crc32(&typed).to_be_bytes(),
+        ]
+        .concat();
+    }
+
+    fn png_with_metadata(orientation: u16) -> Vec<u8> {
+        let clean = encode(ImageFormat::Png);
+        let ihdr_end = PNG_SIGNATURE_LENGTH + 25;
+        let text = png_chunk(b"tEXt", b"Comment\0secret");
+        let itxt = png_chunk(b"iTXt", b"XML:com.adobe.xmp\0\0\0\0\0secret");
+        let exif = png_chunk(PNG_EXIF, &exif_tiff(orientation));
+
+        return [&clean[..ihdr_end], &exif, &text, &itxt, &clean[ihdr_end..]].concat();
+    }
+
+    fn webp_chunk(chunk_type: &[u8], chunk_data: &[u8]) -> Vec<u8> {
+        let mut chunk = [
+            chunk_type,
+            &(chunk_data.len() as u32).to_le_bytes(),
+            chunk_data,
+        ]
+        .concat();
+        if chunk_data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        return chunk;
+    }
+
+    fn webp_with_metadata(orientation: u16) -> Vec<u8> {
+        // the encoder only writes simple files, so the extended header is put together by hand
+        let clean = encode(ImageFormat::WebP);
+        let vp8x = webp_chunk(
+            WEBP_VP8X,
+            &[WEBP_EXIF_FLAG | WEBP_XMP_FLAG, 0, 0, 0, 7, 0, 0, 7, 0, 0],
+        );
+        let exif = webp_chunk(WEBP_EXIF, &[EXIF_HEADER, &exif_tiff(orientation)].concat());
+        let xmp = webp_chunk(WEBP_XMP, b"<x:xmpmeta>secret</x:xmpmeta>");
+
+        let body = [&vp8x, &clean[WEBP_HEADER_LENGTH..], &exif, &xmp].concat();
+        return [
+            b"RIFF",
+            &(body.len() as u32 + 4).to_le_bytes()[..],
+            b"WEBP",
+            &body,
+        ]
+        .concat();
+    }
+
+    #[test]
+    fn test_strip_jpeg() {
+        let data = jpeg_with_metadata(NORMAL_ORIENTATION);
+        let stripped = strip_metadata(&data, FileType::Jpeg).unwrap();
+
+        assert!(contains(&data, GPS));
+        assert!(!contains(&stripped, GPS));
+        assert!(!contains(&stripped, EXIF_HEADER));
+        assert!(!contains(&stripped, b"secret"));
+        assert!(contains(&stripped, JPEG_ICC_PROFILE));
+        assert_eq!(
+            decode(&stripped, ImageFormat::Jpeg),
+            decode(&data, ImageFormat::Jpeg)
+        );
+    }
+
+    #[test]
+    fn test_strip_jpeg_keeps_orientation() {
+        let data = jpeg_with_metadata(6);
+        let stripped = strip_metadata(&data, FileType::Jpeg).unwrap();
+        let exif_start = stripped
+            .windows(EXIF_HEADER.len())
+            .position(|window| window == EXIF_HEADER)
+            .unwrap();
+
+        assert!(!contains(&stripped, GPS));
+        assert_eq!(
+            read_orientation(&stripped[exif_start + EXIF_HEADER.len()..]),
+            Some(6)
+        );
+        assert_eq!(
+            decode(&stripped, ImageFormat::Jpeg),
+            decode(&data, ImageFormat::Jpeg)
+        );
+    }
+
+    #[test]
+    fn test_strip_jpeg_clean() {
+        let data = encode(ImageFormat::Jpeg);
+        assert_eq!(strip_metadata(&data, FileType::Jpeg).unwrap(), data);
+    }
+
+    #[test]
+    fn test_strip_jpeg_after_end() {
+        // an appended file, and a second image the way MPF stores them, both after the EOI
+        let clean = encode(ImageFormat::Jpeg);
+        let data = [&clean[..], b"PK\x03\x04secret.zip", &clean].concat();
+        let stripped = strip_metadata(&data, FileType::Jpeg).unwrap();
+
+        assert_eq!(stripped, clean);
+    }
+
+    #[test]
+    fn test_strip_jpeg_progressive() {
+        // restart markers and stuffed bytes inside a scan, and a second scan after more tables
+        let clean = encode(ImageFormat::Jpeg);
+        let sos = clean
+            .windows(2)
+            .position(|window| window == [0xFF, JPEG_SOS])
+            .unwrap();
+        let scan = jpeg_segment(JPEG_SOS, &[0x01, 0x01, 0x00, 0x00, 0x3F, 0x00]);
+        let scan_data = [0x12, 0xFF, 0x00, 0x34, 0xFF, 0xD0, 0x56];
+        let dht = jpeg_segment(0xC4, &[0x00, 0xFF, JPEG_EOI]);
+        let comment = jpeg_segment(JPEG_COM, b"secret");
+        let eoi = [0xFF, JPEG_EOI];
+        let data = [
+            &clean[..sos],
+            &scan,
+            &scan_data,
+            &comment,
+            &dht,
+            &scan,
+            &scan_data,
+            &eoi,
+            b"appended",
+        ]
+        .concat();
+
+        assert_eq!(
+            strip_metadata(&data, FileType::Jpeg).unwrap(),
+            [
+                &clean[..sos],
+                &scan,
+                &scan_data,
+                &dht,
+                &scan,
+                &scan_data,
+                &eoi
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_strip_jpeg_malformed() {
+        assert_eq!(
+            strip_metadata(b"\xFF\xD8\xFF\xE1\xFF", FileType::Jpeg),
+            Err(MalformedImage)
+        );
+        assert_eq!(
+            strip_metadata(b"\xFF\xD8\xFF\xE1\0\0", FileType::Jpeg),
+            Err(MalformedImage)
+        );
+        assert_eq!(
+            strip_metadata(b"\xFF\xD8garbage", FileType::Jpeg),
+            Err(MalformedImage)
+        );
+    }
+
+    #[test]
+    fn test_strip_png() {
+        let data = png_with_metadata(NORMAL_ORIENTATION);
+        let stripped = strip_metadata(&data, FileType::Png).unwrap();
+
+        assert!(contains(&data, b"secret"));
+        assert!(!contains(&stripped, b"secret"));
+        assert!(!contains(&stripped, GPS));
+        assert!(!contains(&stripped, PNG_EXIF));
+        assert_eq!(stripped, encode(ImageFormat::Png));
+    }
+
+    #[test]
+    fn test_strip_png_keeps_orientation() {
+        let data = png_with_metadata(8);
+        let stripped = strip_metadata(&data, FileType::Png).unwrap();
+        let exif_start = stripped
+            .windows(PNG_EXIF.len())
+            .position(|window| window == PNG_EXIF)
+            .unwrap();
+
+        assert!(!contains(&stripped, GPS));
+        assert_eq!(
+            read_orientation(&stripped[exif_start + PNG_EXIF.len()..]),
+            Some(8)
+        );
+        assert_eq!(
+            decode(&stripped, ImageFormat::Png),
+            decode(&data, ImageFormat::Png)
+        );
+    }
+
+    #[test]
+    fn test_strip_png_after_end() {
+        // a chunk after IEND, and an appended file that isn't in chunks at all
+        let clean = encode(ImageFormat::Png);
+        let text = png_chunk(b"tEXt", b"Comment\0secret");
+        let chunk_after = [&clean[..], &text].concat();
+        let appended = [&clean[..], b"PK\x03\x04secret.zip"].concat();
+
+        assert_eq!(strip_metadata(&chunk_after, FileType::Png).unwrap(), clean);
+        assert_eq!(strip_metadata(&appended, FileType::Png).unwrap(), clean);
+    }
+
+    #[test]
+    fn test_strip_png_malformed() {
+        let data = encode(ImageFormat::Png);
+        assert_eq!(
+            strip_metadata(&data[..20], FileType::Png),
+            Err(MalformedImage)
+        );
+    }
+
+    #[test]
+    fn test_strip_webp() {
+        let data = webp_with_metadata(NORMAL_ORIENTATION);
+        let stripped = strip_metadata(&data, FileType::WebP).unwrap();
+
+        assert!(!contains(&stripped, b"secret"));
+        assert!(!contains(&stripped, GPS));
+        assert_eq!(
+            stripped[WEBP_HEADER_LENGTH + 8] & (WEBP_EXIF_FLAG | WEBP_XMP_FLAG),
+            0
+        );
+        assert_eq!(
+            read_u32(&stripped, 4, true).unwrap() as usize,
+            stripped.len() - 8
+        );
+        assert_eq!(
+            decode(&stripped, ImageFormat::WebP),
+            decode(&data, ImageFormat::WebP)
+        );
+    }
+
+    #[test]
+    fn test_strip_webp_keeps_orientation() {
+        let data = webp_with_metadata(3);
+        let stripped = strip_metadata(&data, FileType::WebP).unwrap();
+        let exif_start = stripped
+            .windows(WEBP_EXIF.len())
+            .position(|window| window == WEBP_EXIF)
+            .unwrap();
+
+        assert!(!contains(&stripped, GPS));
+        assert_eq!(
+            stripped[WEBP_HEADER_LENGTH + 8] & WEBP_EXIF_FLAG,
+            WEBP_EXIF_FLAG
+        );
+        assert_eq!(read_orientation(&stripped[exif_start + 8..]), Some(3));
+        assert_eq!(
+            decode(&stripped, ImageFormat::WebP),
+            decode(&data, ImageFormat::WebP)
+        );
+    }
+
+    #[test]
+    fn test_strip_other_types() {
+        let data = encode(ImageFormat::Gif);
+        assert_eq!(strip_metadata(&data, FileType::Gif).unwrap(), data);
+        assert_eq!(
+            strip_metadata(b"%PDF-1.7\n", FileType::Pdf).unwrap(),
+            b"%PDF-1.7\n"
+        );
+    }
+
+    #[test]
+    fn test_read_orientation() {
+        assert_eq!(read_orientation(&exif_tiff(6)), Some(6));
+        assert_eq!(read_orientation(&orientation_tiff(5)), Some(5));
+        assert_eq!(read_orientation(&exif_tiff(9)), None);
+        assert_eq!(read_orientation(b"MM\0\x2A\0\0\0\x08"), None);
+        assert_eq!(read_orientation(b"garbage"), None);
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"IEND"), 0xAE426082);
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+}
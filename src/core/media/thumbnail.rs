@@ -0,0 +1,183 @@
+// Thumbnail generation for image attachments.
+// Every image gets two thumbnails, one bounded for display on an OP and one for display on a reply.
+// Thumbnails are always JPEG, so transparent images are flattened onto a white background first.
+// Animated GIFs only ever decode their first frame, so that's what their thumbnail shows.
+use crate::core::structs::board_config::BoardConfig;
+use image::{DynamicImage, ImageError, ImageFormat, Rgb, RgbImage};
+use std::fmt;
+use std::io::Cursor;
+use std::vec::Vec;
+
+pub(crate) const THUMBNAIL_EXTENSION: &str = "jpg";
+const BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum ThumbnailSize {
+    Op,
+    Reply,
+}
+
+pub(crate) const ALL_SIZES: [ThumbnailSize; 2] = [ThumbnailSize::Op, ThumbnailSize::Reply];
+
+impl ThumbnailSize {
+    pub fn get_bound(&self, config: &BoardConfig) -> u32 {
+        return match self {
+            ThumbnailSize::Op => config.get_op_thumbnail_bound(),
+            ThumbnailSize::Reply => config.get_reply_thumbnail_bound(),
+        };
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        return match self {
+            ThumbnailSize::Op => "op",
+            ThumbnailSize::Reply => "reply",
+        };
+    }
+}
+
+impl fmt::Display for ThumbnailSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.get_name());
+    }
+}
+
+pub(crate) fn generate(image: &DynamicImage, bound: u32) -> Result<Vec<u8>, ImageError> {
+    // never scale up, small images just get re-encoded
+    let resized: DynamicImage = match image.width() > bound || image.height() > bound {
+        true => image.thumbnail(bound, bound),
+        false => image.clone(),
+    };
+
+    let mut encoded: Vec<u8> = Vec::new();
+    flatten(resized).write_to(&mut Cursor::new(&mut encoded), ImageFormat::Jpeg)?;
+
+    return Ok(encoded);
+}
+
+pub(crate) fn get_dimensions(width: u32, height: u32, bound: u32) -> (u32, u32) {
+    // what generate makes of an image this size, without decoding it. same rounding as image's
+    // own resizing, and never smaller than a pixel
+    if width <= bound && height <= bound {
+        return (width, height);
+    }
+
+    let ratio: f64 = f64::min(bound as f64 / width as f64, bound as f64 / height as f64);
+    let scale = |side: u32| -> u32 { ((side as f64 * ratio).round() as u32).max(1) };
+    return (scale(width), scale(height));
+}
+
+pub(crate) fn generate_all(
+    image: &DynamicImage,
+    config: &BoardConfig,
+) -> Result<Vec<(ThumbnailSize, Vec<u8>)>, ImageError> {
+    let mut thumbnails: Vec<(ThumbnailSize, Vec<u8>)> = Vec::new();
+
+    for size in ALL_SIZES {
+        thumbnails.push((size, generate(image, size.get_bound(config))?));
+    }
+
+    return Ok(thumbnails);
+}
+
+fn flatten(image: DynamicImage) -> RgbImage {
+    if !image.color().has_alpha() {
+        return image.into_rgb8();
+    }
+
+    let rgba = image.into_rgba8();
+    let mut flattened: RgbImage = RgbImage::from_pixel(rgba.width(), rgba.height(), BACKGROUND);
+
+    for (x, y, pixel) in rgba.enumerate_pixels() {
+        let alpha: u32 = pixel[3] as u32;
+        let blended: [u8; 3] = [0, 1, 2].map(|channel| {
+            let over: u32 = pixel[channel] as u32 * alpha;
+            let under: u32 = BACKGROUND[channel] as u32 * (255 - alpha);
+            return ((over + under) / 255) as u8;
+        });
+        flattened.put_pixel(x, y, Rgb(blended));
+    }
+
+    return flattened;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::media::file_type::FileType;
+    use image::{Frame, Rgba, RgbaImage};
+
+    fn dimensions(data: &[u8]) -> (u32, u32) {
+        let image = image::load_from_memory_with_format(data, ImageFormat::Jpeg).unwrap();
+        return (image.width(), image.height());
+    }
+
+    #[test]
+    fn test_generate_scales_down() {
+        let thumbnail = generate(&DynamicImage::new_rgb8(400, 200), 100).unwrap();
+
+        assert_eq!(FileType::detect(&thumbnail), Some(FileType::Jpeg));
+        assert_eq!(dimensions(&thumbnail), (100, 50));
+    }
+
+    #[test]
+    fn test_generate_never_scales_up() {
+        let thumbnail = generate(&DynamicImage::new_rgb8(20, 10), 100).unwrap();
+
+        assert_eq!(dimensions(&thumbnail), (20, 10));
+    }
+
+    #[test]
+    fn test_get_dimensions() {
+        for (width, height) in [(400, 200), (20, 10), (1000, 3), (333, 777), (101, 100)] {
+            let thumbnail = generate(&DynamicImage::new_rgb8(width, height), 100).unwrap();
+            assert_eq!(get_dimensions(width, height, 100), dimensions(&thumbnail));
+        }
+    }
+
+    #[test]
+    fn test_generate_gif_first_frame() {
+        let red = RgbaImage::from_pixel(8, 8, Rgba([255, 0, 0, 255]));
+        let blue = RgbaImage::from_pixel(8, 8, Rgba([0, 0, 255, 255]));
+        let mut data: Vec<u8> = Vec::new();
+        {
+            let mut encoder = image::codecs::gif::GifEncoder::new(&mut data);
+            encoder
+                .encode_frames(vec![Frame::new(red), Frame::new(blue)])
+                .unwrap();
+        }
+
+        let thumbnail = generate(&FileType::Gif.decode(&data).unwrap(), 100).unwrap();
+        let pixel = image::load_from_memory(&thumbnail).unwrap().into_rgb8()[(4, 4)];
+        assert!(pixel[0] > 200 && pixel[2] < 50);
+    }
+
+    #[test]
+    fn test_generate_flattens_alpha() {
+        let clear = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 0]));
+        let thumbnail = generate(&DynamicImage::ImageRgba8(clear), 100).unwrap();
+        let pixel = image::load_from_memory(&thumbnail).unwrap().into_rgb8()[(2, 2)];
+        assert!(pixel.0.iter().all(|channel| *channel > 245));
+    }
+
+    #[test]
+    fn test_generate_all() {
+        let config = BoardConfig::new();
+        let thumbnails = generate_all(&DynamicImage::new_rgb8(1000, 1000), &config).unwrap();
+
+        assert_eq!(thumbnails.len(), ALL_SIZES.len());
+        for (size, thumbnail) in thumbnails {
+            let bound = size.get_bound(&config);
+            assert_eq!(dimensions(&thumbnail), (bound, bound));
+        }
+    }
+
+    #[test]
+    fn test_get_bound() {
+        let mut config = BoardConfig::new();
+        config.modify_op_thumbnail_bound(300);
+        config.modify_reply_thumbnail_bound(150);
+
+        assert_eq!(ThumbnailSize::Op.get_bound(&config), 300);
+        assert_eq!(ThumbnailSize::Reply.get_bound(&config), 150);
+    }
+}
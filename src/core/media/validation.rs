@@ -0,0 +1,401 @@
+// Upload validation. Everything here is checked against what the file contents say it is, the
+// filename is only ever trusted to agree with the contents.
+// The error messages are meant to be shown to the poster as-is.
+use super::file_type::FileType;
+use crate::core::structs::attachment::Attachment;
+use crate::core::structs::board_config::BoardConfig;
+use image::metadata::Orientation;
+use image::{ImageDecoder, ImageReader};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::{Cursor, ErrorKind};
+use std::string::String;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum UploadError {
+    Empty,
+    UnknownType,
+    MissingExtension,
+    ExtensionMismatch {
+        extension: String,
+        detected: FileType,
+    },
+    TypeNotAllowed(FileType),
+    TooLarge {
+        size: u64,
+        limit: u64,
+    },
+    DimensionsTooLarge {
+        width: u32,
+        height: u32,
+        max_width: u32,
+        max_height: u32,
+    },
+    Unreadable(FileType),
+    TooManyFiles {
+        limit: u8,
+    },
+    Banned,
+    Storage(ErrorKind),
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            UploadError::Empty => write!(f, "The uploaded file is empty."),
+            UploadError::UnknownType => {
+                write!(f, "The uploaded file is not a supported file type.")
+            }
+            UploadError::MissingExtension => write!(f, "The uploaded file has no file extension."),
+            UploadError::ExtensionMismatch {
+                extension,
+                detected,
+            } => write!(
+                f,
+                "The file extension \".{}\" does not match the file contents, which are {}.",
+                extension, detected
+            ),
+            UploadError::TypeNotAllowed(file_type) => {
+                write!(f, "{} files are not allowed on this board.", file_type)
+            }
+            UploadError::TooLarge { size, limit } => write!(
+                f,
+                "The uploaded file is {} bytes, the limit on this board is {} bytes.",
+                size, limit
+            ),
+            UploadError::DimensionsTooLarge {
+                width,
+                height,
+                max_width,
+                max_height,
+            } => write!(
+                f,
+                "The image is {}x{}, the limit on this board is {}x{}.",
+                width, height, max_width, max_height
+            ),
+            UploadError::Unreadable(file_type) => {
+                write!(
+                    f,
+                    "The uploaded file claims to be {}, but could not be read.",
+                    file_type
+                )
+            }
+            UploadError::TooManyFiles { limit } => {
+                write!(f, "Posts on this board can have at most {} files.", limit)
+            }
+            UploadError::Banned => write!(f, "This image has been banned."),
+            UploadError::Storage(_) => {
+                write!(f, "The uploaded file could not be saved, please try again.")
+            }
+        };
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+// the result of a successful validation, enough to describe the file without looking at it again
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ValidUpload {
+    file_type: FileType,
+    size: u64,
+    dimensions: Option<(u32, u32)>,
+    hash: String,
+}
+
+impl ValidUpload {
+    pub fn get_file_type(&self) -> FileType {
+        return self.file_type;
+    }
+
+    pub fn into_attachment(
+        self,
+        original_name: String,
+        stored_name: String,
+        spoiler: bool,
+    ) -> Attachment {
+        let attachment: Attachment = Attachment::new(
+            original_name,
+            stored_name,
+            self.file_type.get_mime().to_string(),
+            self.size,
+            self.dimensions,
+            self.hash,
+            spoiler,
+        );
+
+        return attachment;
+    }
+}
+
+pub(crate) fn validate(
+    original_name: &str,
+    data: &[u8],
+    config: &BoardConfig,
+) -> Result<ValidUpload, UploadError> {
+    // cheap checks go first, so oversized junk is never decoded
+    if data.is_empty() {
+        return Err(UploadError::Empty);
+    }
+
+    let size: u64 = data.len() as u64;
+    if size > config.get_max_file_size() {
+        return Err(UploadError::TooLarge {
+            size,
+            limit: config.get_max_file_size(),
+        });
+    }
+
+    let file_type: FileType = FileType::detect(data).ok_or(UploadError::UnknownType)?;
+    check_extension(original_name, file_type)?;
+
+    if !config
+        .get_allowed_types()
+        .iter()
+        .any(|mime| mime == file_type.get_mime())
+    {
+        return Err(UploadError::TypeNotAllowed(file_type));
+    }
+
+    let dimensions: Option<(u32, u32)> = match file_type.is_image() {
+        true => Some(read_dimensions(data, file_type)?),
+        false => None,
+    };
+
+    if let Some((width, height)) = dimensions {
+        let max_width: u32 = config.get_max_image_width();
+        let max_height: u32 = config.get_max_image_height();
+        if width > max_width || height > max_height {
+            return Err(UploadError::DimensionsTooLarge {
+                width,
+                height,
+                max_width,
+                max_height,
+            });
+        }
+    }
+
+    let upload: ValidUpload = ValidUpload {
+        file_type,
+        size,
+        dimensions,
+        hash: hash(data),
+    };
+
+    return Ok(upload);
+}
+
+fn check_extension(original_name: &str, file_type: FileType) -> Result<(), UploadError> {
+    let extension: String = match original_name.rsplit_once('.') {
+        Some((_, extension)) if !extension.is_empty() => extension.to_lowercase(),
+        _ => return Err(UploadError::MissingExtension),
+    };
+
+    if !file_type.get_extensions().contains(&extension.as_str()) {
+        return Err(UploadError::ExtensionMismatch {
+            extension,
+            detected: file_type,
+        });
+    }
+
+    return Ok(());
+}
+
+fn read_dimensions(data: &[u8], file_type: FileType) -> Result<(u32, u32), UploadError> {
+    // only the headers are read here, the image isn't decoded. the dimensions are the ones it's
+    // shown at, so a quarter turn from its EXIF orientation swaps them, the same as decoding does
+    let format: image::ImageFormat = match image::ImageFormat::from_mime_type(file_type.get_mime())
+    {
+        Some(format) => format,
+        None => return Err(UploadError::Unreadable(file_type)),
+    };
+
+    let mut decoder = ImageReader::with_format(Cursor::new(data), format)
+        .into_decoder()
+        .map_err(|_| UploadError::Unreadable(file_type))?;
+    let (width, height): (u32, u32) = decoder.dimensions();
+    let orientation: Orientation = decoder
+        .orientation()
+        .map_err(|_| UploadError::Unreadable(file_type))?;
+
+    return match orientation {
+        Orientation::Rotate90
+        | Orientation::Rotate270
+        | Orientation::Rotate90FlipH
+        | Orientation::Rotate270FlipH => Ok((height, width)),
+        _ => Ok((width, height)),
+    };
+}
+
+pub(crate) fn hash(data: &[u8]) -> String {
+    return hex::encode(Sha256::digest(data));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = ImageBuffer::from_pixel(width, height, Rgb([255u8, 0, 0]));
+        let mut data: Vec<u8> = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png)
+            .unwrap();
+        return data;
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = BoardConfig::new();
+        let data = png(4, 3);
+        let upload = validate("cat.PNG", &data, &config).unwrap();
+
+        assert_eq!(upload.get_file_type(), FileType::Png);
+        assert_eq!(upload.size, data.len() as u64);
+        assert_eq!(upload.dimensions, Some((4, 3)));
+        assert_eq!(upload.hash, hash(&data));
+    }
+
+    #[test]
+    fn test_validate_non_image() {
+        let config = BoardConfig::new();
+        let upload = validate("paper.pdf", b"%PDF-1.7\n", &config).unwrap();
+
+        assert_eq!(upload.get_file_type(), FileType::Pdf);
+        assert_eq!(upload.dimensions, None);
+    }
+
+    #[test]
+    fn test_validate_empty() {
+        let config = BoardConfig::new();
+        assert_eq!(validate("cat.png", b"", &config), Err(UploadError::Empty));
+    }
+
+    #[test]
+    fn test_validate_unknown_type() {
+        let config = BoardConfig::new();
+        assert_eq!(
+            validate("cat.png", b"<html>", &config),
+            Err(UploadError::UnknownType)
+        );
+    }
+
+    #[test]
+    fn test_validate_extension() {
+        let config = BoardConfig::new();
+        let data = png(1, 1);
+
+        assert_eq!(
+            validate("cat", &data, &config),
+            Err(UploadError::MissingExtension)
+        );
+        assert_eq!(
+            validate("cat.", &data, &config),
+            Err(UploadError::MissingExtension)
+        );
+        assert_eq!(
+            validate("cat.jpg", &data, &config),
+            Err(UploadError::ExtensionMismatch {
+                extension: "jpg".to_string(),
+                detected: FileType::Png
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_type_not_allowed() {
+        let mut config = BoardConfig::new();
+        config.modify_allowed_types(vec![FileType::Png.get_mime().to_string()]);
+
+        assert!(validate("cat.png", &png(1, 1), &config).is_ok());
+        assert_eq!(
+            validate("paper.pdf", b"%PDF-1.7\n", &config),
+            Err(UploadError::TypeNotAllowed(FileType::Pdf))
+        );
+    }
+
+    #[test]
+    fn test_validate_too_large() {
+        let mut config = BoardConfig::new();
+        let data = png(1, 1);
+        config.modify_max_file_size(data.len() as u64 - 1);
+
+        assert_eq!(
+            validate("cat.png", &data, &config),
+            Err(UploadError::TooLarge {
+                size: data.len() as u64,
+                limit: data.len() as u64 - 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_dimensions() {
+        let mut config = BoardConfig::new();
+        config.modify_max_image_width(8);
+        config.modify_max_image_height(8);
+
+        assert!(validate("cat.png", &png(8, 8), &config).is_ok());
+        assert_eq!(
+            validate("cat.png", &png(9, 1), &config),
+            Err(UploadError::DimensionsTooLarge {
+                width: 9,
+                height: 1,
+                max_width: 8,
+                max_height: 8
+            })
+        );
+        assert_eq!(
+            validate("cat.png", &png(1, 9), &config),
+            Err(UploadError::DimensionsTooLarge {
+                width: 1,
+                height: 9,
+                max_width: 8,
+                max_height: 8
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_unreadable() {
+        let config = BoardConfig::new();
+        let data: &[u8] = b"\x89PNG\r\n\x1a\ngarbage";
+
+        assert_eq!(
+            validate("cat.png", data, &config),
+            Err(UploadError::Unreadable(FileType::Png))
+        );
+    }
+
+    #[test]
+    fn test_into_attachment() {
+        let config = BoardConfig::new();
+        let data = png(2, 2);
+        let upload = validate("cat.png", &data, &config).unwrap();
+        let attachment = upload.into_attachment("cat.png".to_string(), "1.png".to_string(), true);
+
+        assert_eq!(attachment.get_original_name(), "cat.png");
+        assert_eq!(attachment.get_stored_name(), "1.png");
+        assert_eq!(attachment.get_mime(), "image/png");
+        assert_eq!(attachment.get_size(), data.len() as u64);
+        assert_eq!(attachment.get_dimensions(), Some((2, 2)));
+        assert_eq!(*attachment.get_hash(), hash(&data));
+        assert!(attachment.get_spoiler());
+    }
+
+    #[test]
+    fn test_error_messages() {
+        let error = UploadError::ExtensionMismatch {
+            extension: "jpg".to_string(),
+            detected: FileType::Png,
+        };
+        assert_eq!(
+            error.to_string(),
+            "The file extension \".jpg\" does not match the file contents, which are PNG."
+        );
+        assert_eq!(
+            UploadError::TypeNotAllowed(FileType::Pdf).to_string(),
+            "PDF files are not allowed on this board."
+        );
+    }
+}
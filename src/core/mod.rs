@@ -1,2 +1,21 @@
+pub mod api_tokens;
+pub mod attachments;
+pub mod backlinks;
+pub mod bans;
+pub mod catalog;
+pub mod creation;
+pub mod deletion;
+pub mod index;
+pub mod markup;
+pub mod media;
+pub mod moving;
+pub mod own_posts;
+pub mod poster_id;
+pub mod posting;
+pub mod replies;
+pub mod revisions;
+pub mod secrets;
+pub mod serialization;
 pub mod storage;
 pub mod structs;
+pub mod tripcode;
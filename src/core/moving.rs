@@ -0,0 +1,340 @@
+// Moving threads between boards. A moved thread gets a fresh set of post IDs from the destination
+// board's counter, and everything pointing at the old IDs is pointed at the new ones: links inside
+// the thread, backlinks, attachment names and both boards' thread lists. Links in the thread to
+// other posts on the old board are turned into cross-board links, so they keep working.
+// The old board keeps a redirect for every moved post, so links from elsewhere still lead somewhere.
+// None of this is atomic, a failure part way through can leave the thread on both boards.
+use crate::core::backlinks;
+use crate::core::markup::parser;
+use crate::core::storage::attachment_storage;
+use crate::core::storage::board_storage;
+use crate::core::storage::config::Config;
+use crate::core::storage::post_storage;
+use crate::core::storage::thread_storage;
+use crate::core::structs::attachment::Attachment;
+use crate::core::structs::board::Board;
+use crate::core::structs::post::Post;
+use crate::core::structs::thread::Thread;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::string::String;
+use std::vec::Vec;
+
+pub(crate) fn move_thread(
+    config: &Config,
+    from: &mut Board,
+    thread: u64,
+    to: &mut Board,
+) -> Result<u64, Error> {
+    // returns the thread's new ID on the destination board
+    if from.get_name() == to.get_name() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "a thread can only be moved to a different board",
+        ));
+    }
+    let from_name: String = from.get_name().clone();
+    let to_name: String = to.get_name().clone();
+
+    let old_thread: Thread = thread_storage::read_thread(config, &from_name, thread)?;
+    let children: Vec<u64> = old_thread
+        .get_children()
+        .iter()
+        .copied()
+        .filter(|child| post_storage::post_exists(config, &from_name, *child))
+        .collect();
+    // a thread whose OP is gone has nothing to move under, and nothing has changed yet
+    if children.first() != Some(&thread) {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("/{}/{} has no opening post", from_name, thread),
+        ));
+    }
+
+    // backlinks the thread left anywhere go first, they're all linked again from the new IDs
+    for child in &children {
+        let post: Post = post_storage::read_post(config, &from_name, *child)?;
+        if !post.get_deleted() {
+            backlinks::unlink_post(config, &from_name, *child, post.get_text())?;
+        }
+    }
+
+    let new_ids: HashMap<u64, u64> = children
+        .iter()
+        .map(|child| (*child, to.increment_count()))
+        .collect();
+    let new_thread: u64 = *new_ids.get(&thread).ok_or(Error::new(
+        ErrorKind::NotFound,
+        "the thread has no opening post",
+    ))?;
+
+    let mut moved: Vec<(u64, String, u64)> = Vec::new();
+    for child in &children {
+        let new_id: u64 = new_ids[child];
+        let mut post: Post = post_storage::read_post(config, &from_name, *child)?;
+        move_post(config, (&from_name, &to_name), &new_ids, new_id, &mut post)?;
+        post.modify_parent(new_thread);
+        post_storage::write_post(config, &to_name, new_id, &post)?;
+        moved.push((*child, to_name.clone(), new_id));
+    }
+    for (_, _, new_id) in &moved {
+        let post: Post = post_storage::read_post(config, &to_name, *new_id)?;
+        backlinks::link_post(config, &to_name, *new_id, &post)?;
+    }
+
+    let mut moved_thread: Thread = Thread::new(old_thread.get_name().clone(), new_thread);
+    for (_, _, new_id) in moved.iter().skip(1) {
+        moved_thread
+            .add_child(*new_id, u16::MAX)
+            .map_err(|error| Error::new(ErrorKind::InvalidData, error.to_string()))?;
+    }
+    if old_thread.get_locked() {
+        moved_thread.lock();
+    }
+    if old_thread.get_deleted() {
+        moved_thread.delete();
+    }
+    moved_thread.modify_cyclic(old_thread.get_cyclic());
+    if let Some(bumped) = old_thread.get_bumped() {
+        moved_thread.modify_bumped(*bumped);
+    }
+    thread_storage::write_thread(config, &to_name, &moved_thread)?;
+
+    // the thread keeps its place as active or archived, but stickies stay behind
+    if from.remove_active(thread) {
+        to.add_active(new_thread);
+    } else if from.remove_archive(thread) {
+        to.add_archive(new_thread);
+    }
+    from.remove_sticky(thread);
+
+    thread_storage::add_redirects(config, &from_name, &moved)?;
+    for child in &children {
+        post_storage::remove_post(config, &from_name, *child)?;
+    }
+    thread_storage::remove_thread(config, &from_name, thread)?;
+
+    board_storage::write_board(config, to)?;
+    board_storage::write_board(config, from)?;
+
+    return Ok(new_thread);
+}
+
+fn move_post(
+    config: &Config,
+    boards: (&str, &str),
+    new_ids: &HashMap<u64, u64>,
+    new_id: u64,
+    post: &mut Post,
+) -> Result<(), Error> {
+    // links to posts in the thread follow them, links to anything else stay on the old board
+    let (from_board, to_board): (&str, &str) = boards;
+    let rewrite = |linked: u64| -> Option<String> {
+        return match new_ids.get(&linked) {
+            Some(new) => Some(format!(">>{}", new)),
+            None => Some(format!(">>>/{}/{}", from_board, linked)),
+        };
+    };
+    post.modify_links(parser::rewrite_post_links(post.get_text(), &rewrite));
+
+    // stored names start with the post ID, so files are renamed to match the new one
+    let attachments: Vec<Attachment> = post.remove_attachments();
+    for (index, mut attachment) in attachments.into_iter().enumerate() {
+        let old_name: String = attachment.get_stored_name().clone();
+        let new_name: String = match old_name.rsplit_once('.') {
+            Some((_, extension)) => format!("{}-{}.{}", new_id, index, extension),
+            None => format!("{}-{}", new_id, index),
+        };
+
+        attachment_storage::move_attachment(
+            config,
+            (from_board, &old_name),
+            (to_board, &new_name),
+        )?;
+        attachment.modify_stored_name(new_name);
+        post.add_attachment(attachment, u8::MAX);
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::fixtures::TestRoot;
+
+    const FROM: &str = "test";
+    const TO: &str = "other";
+
+    fn test_config(name: &str) -> TestRoot {
+        return TestRoot::with_boards("moving", name, &[FROM, TO]);
+    }
+
+    fn create(config: &Config, id: u64, text: &str, parent: u64, file: bool) {
+        let mut post = Post::new(
+            "me".to_string(),
+            "Anonymous".to_string(),
+            None,
+            text.to_string(),
+            parent,
+        );
+        if file {
+            let stored_name: String = format!("{}-0.pdf", id);
+            post.add_attachment(
+                Attachment::new(
+                    "a.pdf".to_string(),
+                    stored_name.clone(),
+                    "application/pdf".to_string(),
+                    9,
+                    None,
+                    "hash".to_string(),
+                    false,
+                ),
+                4,
+            );
+            attachment_storage::write_attachment(config, FROM, &stored_name, b"%PDF-1.7\n")
+                .unwrap();
+        }
+        post_storage::write_post(config, FROM, id, &post).unwrap();
+        backlinks::link_post(config, FROM, id, &post).unwrap();
+
+        let mut thread: Thread = match id == parent {
+            true => Thread::new("thread".to_string(), id),
+            false => thread_storage::read_thread(config, FROM, parent).unwrap(),
+        };
+        if id != parent {
+            thread.add_child(id, 100).unwrap();
+        }
+        thread_storage::write_thread(config, FROM, &thread).unwrap();
+    }
+
+    fn setup(config: &Config) -> (Board, Board) {
+        // thread 1 with replies 2 and 3 gets moved, thread 5 stays
+        create(config, 5, "other thread", 5, false);
+        create(config, 1, "op", 1, false);
+        create(config, 2, ">>1 >>5", 1, false);
+        create(config, 3, ">>2 [code]>>2[/code]", 1, true);
+
+        let mut from = board_storage::read_board(config, FROM).unwrap();
+        from.restore_count(5);
+        from.add_active(5);
+        from.add_active(1);
+        from.add_sticky(1, 0, None);
+        board_storage::write_board(config, &from).unwrap();
+
+        let mut to = board_storage::read_board(config, TO).unwrap();
+        to.restore_count(7);
+        board_storage::write_board(config, &to).unwrap();
+
+        return (from, to);
+    }
+
+    fn read(config: &Config, board: &str, id: u64) -> Post {
+        return post_storage::read_post(config, board, id).unwrap();
+    }
+
+    #[test]
+    fn test_move_thread() {
+        let config = test_config("move");
+        let (mut from, mut to) = setup(&config);
+
+        assert_eq!(move_thread(&config, &mut from, 1, &mut to).unwrap(), 8);
+
+        // new IDs and rewritten links
+        let thread = thread_storage::read_thread(&config, TO, 8).unwrap();
+        assert_eq!(*thread.get_children(), vec![8, 9, 10]);
+        assert_eq!(read(&config, TO, 9).get_text(), ">>8 >>>/test/5");
+        assert_eq!(read(&config, TO, 10).get_text(), ">>9 [code]>>2[/code]");
+        assert_eq!(read(&config, TO, 10).get_parent(), 8);
+
+        // backlinks follow the posts
+        assert_eq!(
+            *read(&config, TO, 8).get_backlinks(),
+            vec![(TO.to_string(), 9)]
+        );
+        assert_eq!(
+            *read(&config, TO, 9).get_backlinks(),
+            vec![(TO.to_string(), 10)]
+        );
+        assert_eq!(
+            *read(&config, FROM, 5).get_backlinks(),
+            vec![(TO.to_string(), 9)]
+        );
+
+        // files are renamed
+        let post = read(&config, TO, 10);
+        assert_eq!(post.get_attachments()[0].get_stored_name(), "10-0.pdf");
+        assert!(attachment_storage::read_attachment(&config, TO, "10-0.pdf").is_ok());
+        assert!(attachment_storage::read_attachment(&config, FROM, "3-0.pdf").is_err());
+
+        // nothing is left behind but redirects
+        for id in [1, 2, 3] {
+            assert!(!post_storage::post_exists(&config, FROM, id));
+        }
+        assert!(!thread_storage::thread_exists(&config, FROM, 1));
+        let redirects = thread_storage::read_redirects(&config, FROM).unwrap();
+        assert_eq!(redirects.get(&1), Some(&(TO.to_string(), 8)));
+        assert_eq!(redirects.get(&3), Some(&(TO.to_string(), 10)));
+
+        // and both boards are saved
+        let from = board_storage::read_board(&config, FROM).unwrap();
+        let to = board_storage::read_board(&config, TO).unwrap();
+        assert_eq!(*from.get_active(), vec![5]);
+        assert!(from.get_sticky().is_empty());
+        assert_eq!(*to.get_active(), vec![8]);
+        assert_eq!(to.get_count(), 10);
+    }
+
+    #[test]
+    fn test_move_thread_same_board() {
+        let config = test_config("same_board");
+        let (mut from, _) = setup(&config);
+        let mut again = board_storage::read_board(&config, FROM).unwrap();
+
+        assert_eq!(
+            move_thread(&config, &mut from, 1, &mut again)
+                .err()
+                .unwrap()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+        assert!(post_storage::post_exists(&config, FROM, 1));
+    }
+
+    #[test]
+    fn test_move_thread_missing() {
+        let config = test_config("missing");
+        let (mut from, mut to) = setup(&config);
+
+        assert_eq!(
+            move_thread(&config, &mut from, 4, &mut to)
+                .err()
+                .unwrap()
+                .kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(to.get_count(), 7);
+    }
+
+    #[test]
+    fn test_move_thread_missing_op() {
+        // the thread is still there but its OP isn't, so nothing moves
+        let config = test_config("missing_op");
+        let (mut from, mut to) = setup(&config);
+        post_storage::remove_post(&config, FROM, 1).unwrap();
+
+        assert_eq!(
+            move_thread(&config, &mut from, 1, &mut to)
+                .err()
+                .unwrap()
+                .kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(to.get_count(), 7);
+        assert!(post_storage::post_exists(&config, FROM, 2));
+        assert_eq!(
+            *read(&config, FROM, 5).get_backlinks(),
+            vec![(FROM.to_string(), 2)]
+        );
+    }
+}
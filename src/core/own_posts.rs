@@ -0,0 +1,497 @@
+// Posters managing their own posts, without accounts. A post can be claimed either with the
+// deletion password given when it was made, or by the owner cookie it was made with.
+// Either way it only works for a short window after posting, set per board, and never once the
+// thread has been locked. Edits go through the same posting rules as new posts, and banned posters
+// can't edit.
+use crate::core::backlinks;
+use crate::core::bans;
+use crate::core::creation;
+use crate::core::deletion;
+use crate::core::posting::{self, PostingError, Submission};
+use crate::core::storage::attachment_storage;
+use crate::core::storage::config::Config;
+use crate::core::storage::post_storage;
+use crate::core::storage::site_storage;
+use crate::core::storage::thread_storage;
+use crate::core::structs::attachment::Attachment;
+use crate::core::structs::ban::Ban;
+use crate::core::structs::board::Board;
+use crate::core::structs::post::Post;
+use crate::core::structs::revision::Editor;
+use pwhash::sha512_crypt;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::time::{Duration, SystemTime};
+
+pub(crate) enum Credential<'a> {
+    Password(&'a str),
+    Owner(&'a str), // the owner cookie, the part of the owner after the IP
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum OwnPostError {
+    NotFound,
+    NotAllowed,
+    WindowClosed { window: u32 },
+    ThreadLocked,
+    Posting(PostingError),
+    Banned(Ban),
+    Storage(ErrorKind),
+}
+
+impl fmt::Display for OwnPostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            OwnPostError::NotFound => write!(f, "That post doesn't exist."),
+            OwnPostError::NotAllowed => write!(f, "Wrong password, or that isn't your post."),
+            OwnPostError::WindowClosed { window } => write!(
+                f,
+                "Posts on this board can only be changed for {} seconds after posting.",
+                window
+            ),
+            OwnPostError::ThreadLocked => write!(f, "This thread is locked."),
+            OwnPostError::Posting(error) => write!(f, "{}", error),
+            OwnPostError::Banned(ban) => {
+                write!(
+                    f,
+                    "You're banned from posting. Reason: {}",
+                    ban.get_reason()
+                )
+            }
+            OwnPostError::Storage(_) => {
+                write!(f, "The post could not be saved, please try again.")
+            }
+        };
+    }
+}
+
+impl std::error::Error for OwnPostError {}
+
+impl From<Error> for OwnPostError {
+    fn from(error: Error) -> OwnPostError {
+        return OwnPostError::Storage(error.kind());
+    }
+}
+
+pub(crate) fn hash_password(password: &str) -> Option<String> {
+    // an empty password means the poster didn't want one
+    if password.is_empty() {
+        return None;
+    }
+    return sha512_crypt::hash(password).ok();
+}
+
+fn check_credential(post: &Post, credential: &Credential) -> bool {
+    return match credential {
+        Credential::Password(password) => match post.get_deletion_password() {
+            Some(hash) => sha512_crypt::verify(password, hash),
+            None => false,
+        },
+        Credential::Owner(owner) => {
+            !owner.is_empty() && creation::owner_cookie(post.get_owner()) == *owner
+        }
+    };
+}
+
+fn claim(
+    config: &Config,
+    board: &Board,
+    id: u64,
+    credential: &Credential,
+    now: SystemTime,
+) -> Result<Post, OwnPostError> {
+    // everything that has to hold before a poster can touch their post
+    if !post_storage::post_exists(config, board.get_name(), id) {
+        return Err(OwnPostError::NotFound);
+    }
+    let post: Post = post_storage::read_post(config, board.get_name(), id)?;
+    if post.get_deleted() {
+        return Err(OwnPostError::NotFound);
+    }
+
+    if !check_credential(&post, credential) {
+        return Err(OwnPostError::NotAllowed);
+    }
+
+    let window: u32 = board.get_config().get_edit_window();
+    let age: Duration = now.duration_since(*post.get_created()).unwrap_or_default();
+    if age > Duration::from_secs(window as u64) {
+        return Err(OwnPostError::WindowClosed { window });
+    }
+
+    if thread_storage::read_thread(config, board.get_name(), post.get_parent())?.get_locked() {
+        return Err(OwnPostError::ThreadLocked);
+    }
+
+    return Ok(post);
+}
+
+pub(crate) fn delete_own_post(
+    config: &Config,
+    board: &Board,
+    id: u64,
+    credential: &Credential,
+    files_only: bool,
+    now: SystemTime,
+) -> Result<(), OwnPostError> {
+    let mut post: Post = claim(config, board, id, credential, now)?;
+
+    // a deleted post stays restorable by moderators, files removed on their own are gone for good
+    if !files_only {
+        deletion::delete_post(config, board.get_name(), id, &mut post)?;
+        return Ok(());
+    }
+
+    let removed: Vec<Attachment> = post.remove_attachments();
+    for attachment in &removed {
+        attachment_storage::remove_attachment(
+            config,
+            board.get_name(),
+            attachment.get_stored_name(),
+        )?;
+    }
+    post_storage::write_post(config, board.get_name(), id, &post)?;
+
+    return Ok(());
+}
+
+pub(crate) fn edit_own_post(
+    config: &Config,
+    board: &Board,
+    id: u64,
+    credential: &Credential,
+    new_text: String,
+    ip: &str,
+    now: SystemTime,
+) -> Result<(), OwnPostError> {
+    let mut post: Post = claim(config, board, id, credential, now)?;
+    if let Some(ban) = bans::find_ban(&site_storage::read_bans(config)?, ip, now) {
+        return Err(OwnPostError::Banned(ban.clone()));
+    }
+
+    // only the text can change, so that's all that's checked. the files already on the post
+    // count towards it not being empty
+    let submission: Submission = Submission {
+        poster: ip,
+        name: "",
+        subject: "",
+        text: &new_text,
+        files: &[],
+        new_thread: false,
+    };
+    match posting::check_submission(board.get_config(), &submission) {
+        Err(PostingError::Empty) if !post.get_attachments().is_empty() => (),
+        Err(error) => return Err(OwnPostError::Posting(error)),
+        Ok(()) => (),
+    }
+    let old_text: String = post.get_text().clone();
+
+    post.modify_text(
+        new_text,
+        Editor::Poster,
+        board.get_config().get_revision_limit(),
+    );
+    post_storage::write_post(config, board.get_name(), id, &post)?;
+    backlinks::relink_post(config, board.get_name(), id, &old_text, &post)?;
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::board_storage;
+    use crate::core::storage::fixtures::TestRoot;
+    use crate::core::structs::thread::Thread;
+
+    const BOARD: &str = "test";
+    const OWNER: &str = "cookie";
+    const PASSWORD: &str = "hunter2";
+    const IP: &str = "192.0.2.1";
+
+    fn test_config(name: &str) -> TestRoot {
+        return TestRoot::with_boards("own_posts", name, &[BOARD]);
+    }
+
+    fn setup(config: &Config) -> Board {
+        // thread 1 with the OP, and a reply 2 with a password and a file
+        let mut thread = Thread::new("thread".to_string(), 1);
+        thread.add_child(2, 10).unwrap();
+        thread_storage::write_thread(config, BOARD, &thread).unwrap();
+
+        let op = Post::new(
+            "someone".to_string(),
+            "Anonymous".to_string(),
+            None,
+            "op".to_string(),
+            1,
+        );
+        post_storage::write_post(config, BOARD, 1, &op).unwrap();
+
+        let mut reply = Post::new(
+            OWNER.to_string(),
+            "Anonymous".to_string(),
+            None,
+            ">>1 mine".to_string(),
+            1,
+        );
+        reply.modify_deletion_password(hash_password(PASSWORD));
+        reply.add_attachment(
+            Attachment::new(
+                "a.pdf".to_string(),
+                "2-0.pdf".to_string(),
+                "application/pdf".to_string(),
+                9,
+                None,
+                "hash".to_string(),
+                false,
+            ),
+            4,
+        );
+        attachment_storage::write_attachment(config, BOARD, "2-0.pdf", b"%PDF-1.7\n").unwrap();
+        post_storage::write_post(config, BOARD, 2, &reply).unwrap();
+        backlinks::link_post(config, BOARD, 2, &reply).unwrap();
+
+        return board_storage::read_board(config, BOARD).unwrap();
+    }
+
+    fn read(config: &Config, id: u64) -> Post {
+        return post_storage::read_post(config, BOARD, id).unwrap();
+    }
+
+    #[test]
+    fn test_hash_password() {
+        let hash = hash_password(PASSWORD).unwrap();
+        assert_ne!(hash, PASSWORD);
+        assert!(sha512_crypt::verify(PASSWORD, &hash));
+        assert_eq!(hash_password(""), None);
+    }
+
+    #[test]
+    fn test_delete_own_post() {
+        let config = test_config("delete");
+        let board = setup(&config);
+
+        delete_own_post(
+            &config,
+            &board,
+            2,
+            &Credential::Password(PASSWORD),
+            false,
+            SystemTime::now(),
+        )
+        .unwrap();
+        assert!(read(&config, 2).get_deleted());
+        assert!(read(&config, 1).get_backlinks().is_empty());
+        assert_eq!(
+            delete_own_post(
+                &config,
+                &board,
+                2,
+                &Credential::Password(PASSWORD),
+                false,
+                SystemTime::now()
+            ),
+            Err(OwnPostError::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_delete_own_files() {
+        let config = test_config("delete_files");
+        let board = setup(&config);
+
+        delete_own_post(
+            &config,
+            &board,
+            2,
+            &Credential::Owner(OWNER),
+            true,
+            SystemTime::now(),
+        )
+        .unwrap();
+        let post = read(&config, 2);
+        assert!(!post.get_deleted());
+        assert!(post.get_attachments().is_empty());
+        assert!(attachment_storage::read_attachment(&config, BOARD, "2-0.pdf").is_err());
+    }
+
+    #[test]
+    fn test_edit_own_post() {
+        let config = test_config("edit");
+        let board = setup(&config);
+
+        edit_own_post(
+            &config,
+            &board,
+            2,
+            &Credential::Owner(OWNER),
+            "fixed typo".to_string(),
+            IP,
+            SystemTime::now(),
+        )
+        .unwrap();
+        let post = read(&config, 2);
+        assert_eq!(post.get_text(), "fixed typo");
+        assert_eq!(post.get_revisions()[0].get_text(), ">>1 mine");
+        assert_eq!(*post.get_revisions()[0].get_editor(), Editor::Poster);
+        assert!(read(&config, 1).get_backlinks().is_empty());
+    }
+
+    #[test]
+    fn test_not_allowed() {
+        let config = test_config("not_allowed");
+        let board = setup(&config);
+
+        for credential in [
+            Credential::Password("wrong"),
+            Credential::Owner("someone else"),
+            Credential::Owner(""),
+        ] {
+            assert_eq!(
+                delete_own_post(&config, &board, 2, &credential, false, SystemTime::now()),
+                Err(OwnPostError::NotAllowed)
+            );
+        }
+        // the OP never set a password
+        assert_eq!(
+            edit_own_post(
+                &config,
+                &board,
+                1,
+                &Credential::Password(""),
+                "mine now".to_string(),
+                IP,
+                SystemTime::now()
+            ),
+            Err(OwnPostError::NotAllowed)
+        );
+        assert_eq!(
+            delete_own_post(
+                &config,
+                &board,
+                3,
+                &Credential::Owner(OWNER),
+                false,
+                SystemTime::now()
+            ),
+            Err(OwnPostError::NotFound)
+        );
+        assert!(!read(&config, 2).get_deleted());
+    }
+
+    #[test]
+    fn test_window_closed() {
+        let config = test_config("window");
+        let mut board = setup(&config);
+        let mut board_config = board.get_config().clone();
+        board_config.modify_edit_window(60);
+        board.modify_config(board_config);
+        let created = *read(&config, 2).get_created();
+
+        assert_eq!(
+            edit_own_post(
+                &config,
+                &board,
+                2,
+                &Credential::Owner(OWNER),
+                "too late".to_string(),
+                IP,
+                created + Duration::from_secs(61)
+            ),
+            Err(OwnPostError::WindowClosed { window: 60 })
+        );
+        // a clock that went backwards doesn't close it
+        assert_eq!(
+            delete_own_post(
+                &config,
+                &board,
+                2,
+                &Credential::Owner(OWNER),
+                true,
+                created - Duration::from_secs(1)
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_thread_locked() {
+        let config = test_config("locked");
+        let board = setup(&config);
+        let mut thread = thread_storage::read_thread(&config, BOARD, 1).unwrap();
+        thread.lock();
+        thread_storage::write_thread(&config, BOARD, &thread).unwrap();
+
+        assert_eq!(
+            delete_own_post(
+                &config,
+                &board,
+                2,
+                &Credential::Owner(OWNER),
+                true,
+                SystemTime::now()
+            ),
+            Err(OwnPostError::ThreadLocked)
+        );
+        assert_eq!(read(&config, 2).get_attachments().len(), 1);
+    }
+
+    fn edit(config: &Config, board: &Board, text: &str) -> Result<(), OwnPostError> {
+        return edit_own_post(
+            config,
+            board,
+            2,
+            &Credential::Owner(OWNER),
+            text.to_string(),
+            IP,
+            SystemTime::now(),
+        );
+    }
+
+    #[test]
+    fn test_edit_posting_rules() {
+        let config = test_config("rules");
+        let mut board = setup(&config);
+        let mut board_config = board.get_config().clone();
+        board_config.modify_max_comment_length(5);
+        board.modify_config(board_config);
+
+        assert_eq!(
+            edit(&config, &board, "too long"),
+            Err(OwnPostError::Posting(PostingError::TextTooLong {
+                length: 8,
+                limit: 5
+            }))
+        );
+        // the reply has a file, so it can do without text
+        assert_eq!(edit(&config, &board, " "), Ok(()));
+        delete_own_post(
+            &config,
+            &board,
+            2,
+            &Credential::Owner(OWNER),
+            true,
+            SystemTime::now(),
+        )
+        .unwrap();
+        assert_eq!(
+            edit(&config, &board, ""),
+            Err(OwnPostError::Posting(PostingError::Empty))
+        );
+    }
+
+    #[test]
+    fn test_edit_banned() {
+        let config = test_config("banned");
+        let board = setup(&config);
+        let ban = Ban::new(IP.to_string(), "spam".to_string(), None);
+        site_storage::write_bans(&config, std::slice::from_ref(&ban)).unwrap();
+
+        assert_eq!(
+            edit(&config, &board, "edited"),
+            Err(OwnPostError::Banned(ban))
+        );
+        assert_eq!(read(&config, 2).get_text(), ">>1 mine");
+    }
+}
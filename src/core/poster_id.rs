@@ -0,0 +1,112 @@
+// Poster IDs. On boards that have them, every post carries a short code that's the same for one
+// poster throughout a thread, but different in every other thread, so posters can be told apart
+// without being tracked across the site.
+// The code is a keyed hash of the poster's IP, the board and the thread, so it can't be reversed or
+// precomputed without the server secret. The secret is replaced every week, which means a poster's
+// ID in a long-running thread changes once it does. IDs are stored with the post when it's made,
+// so rotating never changes the ID of an existing post.
+use crate::core::secrets;
+use crate::core::storage::config::Config;
+use crate::core::structs::board_config::BoardConfig;
+use base64::alphabet;
+use base64::engine::general_purpose::{GeneralPurpose, NO_PAD};
+use base64::Engine;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+use std::io::Error;
+use std::string::String;
+use std::time::{Duration, SystemTime};
+use std::vec::Vec;
+
+pub(crate) const ID_LENGTH: usize = 8;
+const ROTATION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+// the same alphabet as tripcodes
+const CRYPT_BASE64: GeneralPurpose = GeneralPurpose::new(&alphabet::CRYPT, NO_PAD);
+
+pub(crate) fn derive(secret: &[u8], ip: &str, board: &str, thread: u64) -> String {
+    // the fields are separated by a zero byte, which can't show up in any of them
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap(); // any key length works
+    mac.update(ip.as_bytes());
+    mac.update(b"\0");
+    mac.update(board.as_bytes());
+    mac.update(b"\0");
+    mac.update(thread.to_string().as_bytes());
+
+    let encoded: String = CRYPT_BASE64.encode(mac.finalize().into_bytes());
+    return encoded[..ID_LENGTH].to_string();
+}
+
+pub(crate) fn assign(
+    board_config: &BoardConfig,
+    secret: &[u8],
+    ip: &str,
+    board: &str,
+    thread: u64,
+) -> Option<String> {
+    // the ID a new post should be stored with, if its board shows them
+    if !board_config.get_poster_ids() {
+        return None;
+    }
+    return Some(derive(secret, ip, board, thread));
+}
+
+pub(crate) fn current_secret(config: &Config, now: SystemTime) -> Result<Vec<u8>, Error> {
+    return secrets::current(config, secrets::POSTER_ID, Some(ROTATION), now);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::fixtures::TestRoot;
+
+    const SECRET: &[u8] = b"server secret";
+    const IP: &str = "192.0.2.1";
+
+    fn test_config(name: &str) -> TestRoot {
+        let config = TestRoot::new("poster_id", name);
+        config.connect().unwrap();
+        return config;
+    }
+
+    #[test]
+    fn test_derive() {
+        let id = derive(SECRET, IP, "test", 1);
+        assert_eq!(id.len(), ID_LENGTH);
+        assert_eq!(id, derive(SECRET, IP, "test", 1));
+
+        // anything changing gives a different ID
+        assert_ne!(id, derive(SECRET, IP, "test", 2));
+        assert_ne!(id, derive(SECRET, IP, "other", 1));
+        assert_ne!(id, derive(SECRET, "192.0.2.2", "test", 1));
+        assert_ne!(id, derive(b"another secret", IP, "test", 1));
+    }
+
+    #[test]
+    fn test_derive_separated() {
+        // fields can't run into each other
+        assert_ne!(derive(SECRET, "1", "21", 3), derive(SECRET, "12", "1", 3));
+    }
+
+    #[test]
+    fn test_assign() {
+        let mut board_config = BoardConfig::new();
+        assert_eq!(assign(&board_config, SECRET, IP, "test", 1), None);
+
+        board_config.modify_poster_ids(true);
+        assert_eq!(
+            assign(&board_config, SECRET, IP, "test", 1),
+            Some(derive(SECRET, IP, "test", 1))
+        );
+    }
+
+    #[test]
+    fn test_current_secret() {
+        let config = test_config("current_secret");
+        let now = SystemTime::now();
+
+        let secret = current_secret(&config, now).unwrap();
+        assert_eq!(current_secret(&config, now + ROTATION / 2).unwrap(), secret);
+        assert_ne!(current_secret(&config, now + ROTATION).unwrap(), secret);
+    }
+}
@@ -0,0 +1,458 @@
+// Posting rules. Everything a board's config says about what a post may look like is checked here,
+// before anything is written. Files are only counted and sniffed, they're validated one by one
+// when they're attached.
+// The error messages are meant to be shown to the poster as-is.
+use crate::core::attachments::UploadedFile;
+use crate::core::media::file_type::FileType;
+use crate::core::structs::board_config::BoardConfig;
+use crate::core::tripcode;
+use std::collections::HashMap;
+use std::fmt;
+use std::string::String;
+use std::time::{Duration, SystemTime};
+
+// the same on every board, these are only there so names and subjects stay a sensible size
+pub(crate) const MAX_NAME_LENGTH: usize = 100; // characters, tripcode password and all
+pub(crate) const MAX_SUBJECT_LENGTH: usize = 100; // characters
+
+// a post as it was sent, before anything is done with it
+pub(crate) struct Submission<'a> {
+    pub poster: &'a str, // whatever identifies the poster for cooldowns, like their IP
+    pub name: &'a str,   // the raw name field, possibly with a tripcode password
+    pub subject: &'a str,
+    pub text: &'a str,
+    pub files: &'a [UploadedFile<'a>],
+    pub new_thread: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum PostingError {
+    Empty,
+    TextTooLong { length: usize, limit: u32 },
+    NameTooLong { length: usize, limit: usize },
+    SubjectTooLong { length: usize, limit: usize },
+    TooManyLines { lines: usize, limit: u16 },
+    TooManyFiles { count: usize, limit: u8 },
+    ImageRequired,
+    SubjectRequired,
+    Cooldown { remaining: u64 }, // seconds, rounded up
+}
+
+impl fmt::Display for PostingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            PostingError::Empty => write!(f, "A post needs either a comment or a file."),
+            PostingError::TextTooLong { length, limit } => write!(
+                f,
+                "The comment is {} characters long, the limit on this board is {}.",
+                length, limit
+            ),
+            PostingError::NameTooLong { length, limit } => write!(
+                f,
+                "The name is {} characters long, the limit is {}.",
+                length, limit
+            ),
+            PostingError::SubjectTooLong { length, limit } => write!(
+                f,
+                "The subject is {} characters long, the limit is {}.",
+                length, limit
+            ),
+            PostingError::TooManyLines { lines, limit } => write!(
+                f,
+                "The comment is {} lines long, the limit on this board is {}.",
+                lines, limit
+            ),
+            PostingError::TooManyFiles { count, limit } => write!(
+                f,
+                "{} files were attached, the limit on this board is {}.",
+                count, limit
+            ),
+            PostingError::ImageRequired => {
+                write!(f, "New threads on this board need an image.")
+            }
+            PostingError::SubjectRequired => {
+                write!(f, "New threads on this board need a subject.")
+            }
+            PostingError::Cooldown { remaining } => write!(
+                f,
+                "You're posting too fast, please wait {} more seconds.",
+                remaining
+            ),
+        };
+    }
+}
+
+impl std::error::Error for PostingError {}
+
+pub(crate) fn check_submission(
+    config: &BoardConfig,
+    submission: &Submission,
+) -> Result<(), PostingError> {
+    // only the rules about the post itself, cooldowns are checked separately
+    let text: &str = submission.text;
+    if text.trim().is_empty() && submission.files.is_empty() {
+        return Err(PostingError::Empty);
+    }
+
+    let length: usize = text.chars().count();
+    if length > config.get_max_comment_length() as usize {
+        return Err(PostingError::TextTooLong {
+            length,
+            limit: config.get_max_comment_length(),
+        });
+    }
+
+    let length: usize = submission.name.chars().count();
+    if length > MAX_NAME_LENGTH {
+        return Err(PostingError::NameTooLong {
+            length,
+            limit: MAX_NAME_LENGTH,
+        });
+    }
+
+    let length: usize = submission.subject.chars().count();
+    if length > MAX_SUBJECT_LENGTH {
+        return Err(PostingError::SubjectTooLong {
+            length,
+            limit: MAX_SUBJECT_LENGTH,
+        });
+    }
+
+    let lines: usize = text.lines().count();
+    if lines > config.get_max_lines() as usize {
+        return Err(PostingError::TooManyLines {
+            lines,
+            limit: config.get_max_lines(),
+        });
+    }
+
+    let count: usize = submission.files.len();
+    if count > config.get_max_files() as usize {
+        return Err(PostingError::TooManyFiles {
+            count,
+            limit: config.get_max_files(),
+        });
+    }
+
+    if !submission.new_thread {
+        return Ok(());
+    }
+
+    // what the contents say, a renamed text file doesn't count as an image
+    let has_image: bool = submission
+        .files
+        .iter()
+        .any(|file| FileType::detect(file.data).is_some_and(|file_type| file_type.is_image()));
+    if config.get_op_requires_image() && !has_image {
+        return Err(PostingError::ImageRequired);
+    }
+    if config.get_subject_required() && submission.subject.trim().is_empty() {
+        return Err(PostingError::SubjectRequired);
+    }
+
+    return Ok(());
+}
+
+pub(crate) fn poster_name(
+    config: &BoardConfig,
+    field: &str,
+    secure_salt: &[u8],
+) -> (String, Option<String>) {
+    // the name and tripcode a post goes up under. with forced anonymity the name field is
+    // thrown away entirely, tripcode password and all
+    if config.get_forced_anonymity() {
+        return (config.get_default_name().clone(), None);
+    }
+
+    let (name, tripcode): (String, Option<String>) =
+        tripcode::parse_name(field, secure_salt, config.get_tripcodes());
+    return match name.trim().is_empty() {
+        true => (config.get_default_name().clone(), tripcode),
+        false => (name, tripcode),
+    };
+}
+
+// when each poster last posted on a board. only kept in memory, a restart forgives everyone
+pub(crate) struct Cooldowns {
+    last_post: HashMap<String, SystemTime>,
+    last_thread: HashMap<String, SystemTime>,
+}
+
+impl Cooldowns {
+    pub fn new() -> Cooldowns {
+        let cooldowns: Cooldowns = Cooldowns {
+            last_post: HashMap::new(),
+            last_thread: HashMap::new(),
+        };
+
+        return cooldowns;
+    }
+
+    pub fn check(
+        &self,
+        config: &BoardConfig,
+        poster: &str,
+        new_thread: bool,
+        now: SystemTime,
+    ) -> Result<(), PostingError> {
+        // every post counts towards the reply cooldown, new threads have their own on top
+        let mut remaining: Duration =
+            Cooldowns::remaining(self.last_post.get(poster), config.get_reply_cooldown(), now);
+        if new_thread {
+            remaining = remaining.max(Cooldowns::remaining(
+                self.last_thread.get(poster),
+                config.get_thread_cooldown(),
+                now,
+            ));
+        }
+
+        if remaining.is_zero() {
+            return Ok(());
+        }
+        return Err(PostingError::Cooldown {
+            remaining: remaining.as_secs() + (remaining.subsec_nanos() > 0) as u64,
+        });
+    }
+
+    pub fn record(&mut self, poster: &str, new_thread: bool, now: SystemTime) {
+        self.last_post.insert(poster.to_string(), now);
+        if new_thread {
+            self.last_thread.insert(poster.to_string(), now);
+        }
+    }
+
+    pub fn prune(&mut self, before: SystemTime) {
+        // forgets posts from before the given time, which should be older than any cooldown
+        self.last_post.retain(|_, last| *last >= before);
+        self.last_thread.retain(|_, last| *last >= before);
+    }
+
+    fn remaining(last: Option<&SystemTime>, cooldown: u32, now: SystemTime) -> Duration {
+        let last: &SystemTime = match last {
+            Some(last) => last,
+            None => return Duration::ZERO,
+        };
+        let elapsed: Duration = now.duration_since(*last).unwrap_or_default();
+
+        return Duration::from_secs(cooldown as u64).saturating_sub(elapsed);
+    }
+}
+
+pub(crate) fn check(
+    config: &BoardConfig,
+    cooldowns: &Cooldowns,
+    submission: &Submission,
+    now: SystemTime,
+) -> Result<(), PostingError> {
+    // the post itself goes first, so a poster fixing it isn't also told to wait
+    check_submission(config, submission)?;
+    return cooldowns.check(config, submission.poster, submission.new_thread, now);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    const PDF: &[u8] = b"%PDF-1.7\n";
+    const SALT: &[u8] = b"salt";
+
+    fn file(data: &[u8]) -> UploadedFile<'_> {
+        return UploadedFile {
+            original_name: "file",
+            data,
+            spoiler: false,
+        };
+    }
+
+    fn submission<'a>(text: &'a str, files: &'a [UploadedFile<'a>]) -> Submission<'a> {
+        return Submission {
+            poster: "me",
+            name: "",
+            subject: "",
+            text,
+            files,
+            new_thread: false,
+        };
+    }
+
+    #[test]
+    fn test_check_empty() {
+        let config = BoardConfig::new();
+        assert_eq!(
+            check_submission(&config, &submission(" \n ", &[])),
+            Err(PostingError::Empty)
+        );
+        assert!(check_submission(&config, &submission("", &[file(PDF)])).is_ok());
+    }
+
+    #[test]
+    fn test_check_text_limits() {
+        let mut config = BoardConfig::new();
+        config.modify_max_comment_length(5);
+        config.modify_max_lines(2);
+
+        // characters, not bytes
+        assert!(check_submission(
+            &config,
+            &submission("\u{3B1}\u{3B2}\u{3B3}\u{3B4}\u{3B5}", &[])
+        )
+        .is_ok());
+        assert_eq!(
+            check_submission(&config, &submission("abcdef", &[])),
+            Err(PostingError::TextTooLong {
+                length: 6,
+                limit: 5
+            })
+        );
+        assert_eq!(
+            check_submission(&config, &submission("a\nb\nc", &[])),
+            Err(PostingError::TooManyLines { lines: 3, limit: 2 })
+        );
+    }
+
+    #[test]
+    fn test_check_name_and_subject() {
+        let config = BoardConfig::new();
+        let long: String = "\u{3B1}".repeat(MAX_NAME_LENGTH + 1);
+        let fits: String = "\u{3B1}".repeat(MAX_NAME_LENGTH);
+
+        let mut named = submission("text", &[]);
+        named.name = &fits;
+        named.subject = &fits;
+        assert!(check_submission(&config, &named).is_ok());
+
+        named.name = &long;
+        assert_eq!(
+            check_submission(&config, &named),
+            Err(PostingError::NameTooLong {
+                length: MAX_NAME_LENGTH + 1,
+                limit: MAX_NAME_LENGTH
+            })
+        );
+
+        named.name = "";
+        named.subject = &long;
+        assert_eq!(
+            check_submission(&config, &named),
+            Err(PostingError::SubjectTooLong {
+                length: MAX_SUBJECT_LENGTH + 1,
+                limit: MAX_SUBJECT_LENGTH
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_too_many_files() {
+        let mut config = BoardConfig::new();
+        config.modify_max_files(1);
+        let files = [file(PDF), file(PDF)];
+
+        assert_eq!(
+            check_submission(&config, &submission("text", &files)),
+            Err(PostingError::TooManyFiles { count: 2, limit: 1 })
+        );
+    }
+
+    #[test]
+    fn test_check_new_thread() {
+        let mut config = BoardConfig::new();
+        config.modify_subject_required(true);
+        let image = [file(PNG)];
+        let pdf = [file(PDF)];
+
+        let mut thread = submission("op", &pdf);
+        thread.new_thread = true;
+        assert_eq!(
+            check_submission(&config, &thread),
+            Err(PostingError::ImageRequired)
+        );
+
+        thread.files = &image;
+        assert_eq!(
+            check_submission(&config, &thread),
+            Err(PostingError::SubjectRequired)
+        );
+
+        thread.subject = "subject";
+        assert!(check_submission(&config, &thread).is_ok());
+
+        // replies don't need either
+        assert!(check_submission(&config, &submission("reply", &[])).is_ok());
+
+        config.modify_op_requires_image(false);
+        thread.files = &[];
+        assert!(check_submission(&config, &thread).is_ok());
+    }
+
+    #[test]
+    fn test_poster_name() {
+        let mut config = BoardConfig::new();
+        let (name, tripcode) = poster_name(&config, "", SALT);
+        assert_eq!(name, "Anonymous");
+        assert_eq!(tripcode, None);
+
+        let (name, tripcode) = poster_name(&config, "#password", SALT);
+        assert_eq!(name, "Anonymous");
+        assert_eq!(tripcode, Some(tripcode::classic("password")));
+
+        config.modify_default_name("Nameless".to_string());
+        config.modify_forced_anonymity(true);
+        let (name, tripcode) = poster_name(&config, "me#password", SALT);
+        assert_eq!(name, "Nameless");
+        assert_eq!(tripcode, None);
+    }
+
+    #[test]
+    fn test_cooldowns() {
+        let config = BoardConfig::new();
+        let mut cooldowns = Cooldowns::new();
+        let now = SystemTime::now();
+
+        assert!(cooldowns.check(&config, "me", true, now).is_ok());
+        cooldowns.record("me", true, now);
+
+        let later = now + Duration::from_millis(10500);
+        assert_eq!(
+            cooldowns.check(&config, "me", false, later),
+            Err(PostingError::Cooldown { remaining: 5 })
+        );
+        assert_eq!(
+            cooldowns.check(&config, "me", true, later),
+            Err(PostingError::Cooldown { remaining: 290 })
+        );
+        assert!(cooldowns.check(&config, "you", true, later).is_ok());
+
+        let later = now + Duration::from_secs(15);
+        assert!(cooldowns.check(&config, "me", false, later).is_ok());
+        assert!(cooldowns.check(&config, "me", true, later).is_err());
+    }
+
+    #[test]
+    fn test_cooldowns_prune() {
+        let config = BoardConfig::new();
+        let mut cooldowns = Cooldowns::new();
+        let now = SystemTime::now();
+        cooldowns.record("me", true, now);
+
+        cooldowns.prune(now + Duration::from_secs(1));
+        assert!(cooldowns.check(&config, "me", true, now).is_ok());
+    }
+
+    #[test]
+    fn test_check() {
+        let config = BoardConfig::new();
+        let mut cooldowns = Cooldowns::new();
+        let now = SystemTime::now();
+        cooldowns.record("me", false, now);
+
+        assert_eq!(
+            check(&config, &cooldowns, &submission("", &[]), now),
+            Err(PostingError::Empty)
+        );
+        assert_eq!(
+            check(&config, &cooldowns, &submission("text", &[]), now),
+            Err(PostingError::Cooldown { remaining: 15 })
+        );
+    }
+}
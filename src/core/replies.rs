@@ -0,0 +1,199 @@
+// Adding replies to threads. The reply has to be written already, this only puts it in its thread.
+// Cyclic threads never hit the reply limit, instead every reply pushes the oldest ones out once
+// the board's cyclic limit is passed. Pruned replies are soft-deleted like any other deleted post,
+// so they're purged after the retention window, but restoring one doesn't put it back in the thread.
+// Whether a reply bumps is up to the caller, the thread just keeps the time of its last bump.
+use crate::core::deletion;
+use crate::core::storage::config::Config;
+use crate::core::storage::post_storage;
+use crate::core::storage::thread_storage;
+use crate::core::structs::board::Board;
+use crate::core::structs::post::Post;
+use crate::core::structs::thread::{Thread, ThreadError};
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::time::SystemTime;
+use std::vec::Vec;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ReplyError {
+    Thread(ThreadError),
+    Storage(ErrorKind),
+}
+
+impl fmt::Display for ReplyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            ReplyError::Thread(error) => error.fmt(f),
+            ReplyError::Storage(_) => {
+                write!(f, "The post could not be saved, please try again.")
+            }
+        };
+    }
+}
+
+impl std::error::Error for ReplyError {}
+
+impl From<Error> for ReplyError {
+    fn from(error: Error) -> ReplyError {
+        return ReplyError::Storage(error.kind());
+    }
+}
+
+pub(crate) fn add_reply(
+    config: &Config,
+    board: &Board,
+    thread: u64,
+    reply: u64,
+    bumped: Option<SystemTime>,
+) -> Result<Vec<u64>, ReplyError> {
+    // returns the replies pruned to make room, which is always empty unless the thread is cyclic.
+    // bumped is the reply's time if it bumps the thread
+    let board_name: &str = board.get_name();
+    let mut read_thread: Thread = thread_storage::read_thread(config, board_name, thread)?;
+
+    if let Err(error) = read_thread.add_child(reply, board.get_config().get_max_replies()) {
+        // hitting the limit locks the thread, which has to stick
+        if read_thread.get_locked() {
+            thread_storage::write_thread(config, board_name, &read_thread)?;
+        }
+        return Err(ReplyError::Thread(error));
+    }
+
+    let pruned: Vec<u64> = match read_thread.get_cyclic() {
+        true => read_thread.prune_replies(board.get_config().get_cyclic_limit()),
+        false => Vec::new(),
+    };
+    if let Some(bumped) = bumped {
+        read_thread.modify_bumped(bumped);
+    }
+    thread_storage::write_thread(config, board_name, &read_thread)?;
+
+    for id in &pruned {
+        if !post_storage::post_exists(config, board_name, *id) {
+            continue;
+        }
+        let mut post: Post = post_storage::read_post(config, board_name, *id)?;
+        if !post.get_deleted() {
+            deletion::delete_post(config, board_name, *id, &mut post)?;
+        }
+    }
+
+    return Ok(pruned);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::backlinks;
+    use crate::core::storage::board_storage;
+    use crate::core::storage::fixtures::TestRoot;
+
+    const BOARD: &str = "test";
+
+    fn test_config(name: &str) -> TestRoot {
+        return TestRoot::with_boards("replies", name, &[BOARD]);
+    }
+
+    fn write(config: &Config, id: u64, text: &str) {
+        let post = Post::new(
+            "me".to_string(),
+            "Anonymous".to_string(),
+            None,
+            text.to_string(),
+            1,
+        );
+        post_storage::write_post(config, BOARD, id, &post).unwrap();
+        backlinks::link_post(config, BOARD, id, &post).unwrap();
+    }
+
+    fn setup(config: &Config, cyclic: bool) -> Board {
+        // thread 1, on a board that keeps 2 replies in cyclic threads and locks at 3
+        write(config, 1, "op");
+        let mut thread = Thread::new("thread".to_string(), 1);
+        thread.modify_cyclic(cyclic);
+        thread_storage::write_thread(config, BOARD, &thread).unwrap();
+
+        let mut board = board_storage::read_board(config, BOARD).unwrap();
+        let mut board_config = board.get_config().clone();
+        board_config.modify_cyclic_limit(2);
+        board_config.modify_max_replies(3);
+        board.modify_config(board_config);
+        return board;
+    }
+
+    fn children(config: &Config) -> Vec<u64> {
+        return thread_storage::read_thread(config, BOARD, 1)
+            .unwrap()
+            .get_children()
+            .clone();
+    }
+
+    #[test]
+    fn test_add_reply() {
+        let config = test_config("add");
+        let board = setup(&config, false);
+        write(&config, 2, "reply");
+
+        assert!(add_reply(&config, &board, 1, 2, None).unwrap().is_empty());
+        assert_eq!(children(&config), vec![1, 2]);
+        let thread = thread_storage::read_thread(&config, BOARD, 1).unwrap();
+        assert_eq!(*thread.get_bumped(), None);
+
+        // a bumping reply leaves its time on the thread
+        write(&config, 3, "reply");
+        add_reply(&config, &board, 1, 3, Some(SystemTime::UNIX_EPOCH)).unwrap();
+        let thread = thread_storage::read_thread(&config, BOARD, 1).unwrap();
+        assert_eq!(*thread.get_bumped(), Some(SystemTime::UNIX_EPOCH));
+    }
+
+    #[test]
+    fn test_add_reply_limit() {
+        let config = test_config("limit");
+        let board = setup(&config, false);
+        for id in 2..=4 {
+            write(&config, id, "reply");
+            add_reply(&config, &board, 1, id, None).unwrap();
+        }
+        write(&config, 5, "reply");
+
+        assert_eq!(
+            add_reply(&config, &board, 1, 5, None),
+            Err(ReplyError::Thread(ThreadError::Locked))
+        );
+        assert!(thread_storage::read_thread(&config, BOARD, 1)
+            .unwrap()
+            .get_locked());
+    }
+
+    #[test]
+    fn test_add_reply_cyclic() {
+        let config = test_config("cyclic");
+        let board = setup(&config, true);
+        write(&config, 2, ">>1");
+        write(&config, 3, "reply");
+        add_reply(&config, &board, 1, 2, None).unwrap();
+        add_reply(&config, &board, 1, 3, None).unwrap();
+
+        for id in 4..=5 {
+            write(&config, id, "reply");
+            assert_eq!(
+                add_reply(&config, &board, 1, id, None).unwrap(),
+                vec![id - 2]
+            );
+        }
+
+        // past the reply limit, but never locked
+        let thread = thread_storage::read_thread(&config, BOARD, 1).unwrap();
+        assert_eq!(*thread.get_children(), vec![1, 4, 5]);
+        assert!(!thread.get_locked());
+
+        // pruned replies are soft-deleted, and take their backlinks with them
+        let pruned = post_storage::read_post(&config, BOARD, 2).unwrap();
+        assert!(pruned.get_deleted());
+        assert!(post_storage::read_post(&config, BOARD, 1)
+            .unwrap()
+            .get_backlinks()
+            .is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+// Post edit history, for moderators. Every kept revision is diffed against whatever replaced it,
+// line by line, so it's easy to see what an edit actually changed.
+// None of this is ever shown to regular users, revisions can hold text a moderator edited out.
+use crate::core::structs::post::Post;
+use crate::core::structs::revision::Revision;
+use std::string::String;
+use std::time::UNIX_EPOCH;
+use std::vec::Vec;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum DiffLine {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+pub(crate) fn diff(old: &str, new: &str) -> Vec<DiffLine> {
+    // a longest common subsequence over lines, walked from the front to build the diff
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut common: Vec<Vec<u32>> = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+
+    let mut lines: Vec<DiffLine> = Vec::new();
+    let (mut i, mut j): (usize, usize) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(DiffLine::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            // removals go before additions, the way most diffs show a changed line
+            lines.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+
+    return lines;
+}
+
+pub(crate) fn history(post: &Post) -> Vec<(&Revision, Vec<DiffLine>)> {
+    // every revision along with the diff to the text that replaced it. the last revision was
+    // replaced by the post as it is now
+    let revisions: &Vec<Revision> = post.get_revisions();
+    let mut history: Vec<(&Revision, Vec<DiffLine>)> = Vec::new();
+
+    for (index, revision) in revisions.iter().enumerate() {
+        let next_text: &String = match revisions.get(index + 1) {
+            Some(next) => next.get_text(),
+            None => post.get_text(),
+        };
+        history.push((revision, diff(revision.get_text(), next_text)));
+    }
+
+    return history;
+}
+
+pub(crate) fn format_history(post: &Post) -> String {
+    let mut formatted: String = String::new();
+
+    for (revision, lines) in history(post) {
+        let edited: u128 = revision
+            .get_edited()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        formatted.push_str(&format!(
+            "edited at {} by {}\n",
+            edited,
+            revision.get_editor()
+        ));
+
+        for line in lines {
+            match line {
+                DiffLine::Same(text) => formatted.push_str(&format!("  {}\n", text)),
+                DiffLine::Added(text) => formatted.push_str(&format!("+ {}\n", text)),
+                DiffLine::Removed(text) => formatted.push_str(&format!("- {}\n", text)),
+            }
+        }
+    }
+
+    return formatted;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::structs::revision::Editor;
+
+    fn same(text: &str) -> DiffLine {
+        return DiffLine::Same(text.to_string());
+    }
+
+    fn added(text: &str) -> DiffLine {
+        return DiffLine::Added(text.to_string());
+    }
+
+    fn removed(text: &str) -> DiffLine {
+        return DiffLine::Removed(text.to_string());
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(
+            diff("a\nb\nc", "a\nx\nc\nd"),
+            vec![same("a"), removed("b"), added("x"), same("c"), added("d")]
+        );
+        assert_eq!(diff("", "a"), vec![added("a")]);
+        assert_eq!(diff("a", ""), vec![removed("a")]);
+        assert_eq!(diff("a\r\nb", "a\nb"), vec![same("a"), same("b")]);
+    }
+
+    #[test]
+    fn test_history() {
+        let mut post = Post::new(
+            "me".to_string(),
+            "Anonymous".to_string(),
+            None,
+            "first".to_string(),
+            1,
+        );
+        post.modify_text("second".to_string(), Editor::Poster, 10);
+        post.modify_text(
+            "third".to_string(),
+            Editor::Moderator("jan".to_string()),
+            10,
+        );
+
+        let history = history(&post);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].0.get_text(), "first");
+        assert_eq!(history[0].1, vec![removed("first"), added("second")]);
+        assert_eq!(history[1].0.get_text(), "second");
+        assert_eq!(history[1].1, vec![removed("second"), added("third")]);
+    }
+
+    #[test]
+    fn test_format_history() {
+        let mut post = Post::new(
+            "me".to_string(),
+            "Anonymous".to_string(),
+            None,
+            "kept\nremoved".to_string(),
+            1,
+        );
+        post.modify_text("kept".to_string(), Editor::Poster, 10);
+        post.modify_text(
+            "kept\nadded".to_string(),
+            Editor::Moderator("jan".to_string()),
+            10,
+        );
+
+        let formatted = format_history(&post);
+        let lines: Vec<&str> = formatted.lines().collect();
+        assert!(lines[0].ends_with("by poster"));
+        assert_eq!(&lines[1..3], ["  kept", "- removed"]);
+        assert!(lines[3].ends_with("by mod:jan"));
+        assert_eq!(&lines[4..], ["  kept", "+ added"]);
+    }
+}
@@ -0,0 +1,81 @@
+// Server secrets, for anything that has to be keyed so it can't be worked out offline: secure
+// tripcodes and poster IDs. Each one is made the first time it's needed and kept in site storage.
+// Some are replaced once they get too old, the rest live as long as the site does.
+use crate::core::storage::config::Config;
+use crate::core::storage::site_storage;
+use std::io::Error;
+use std::time::{Duration, SystemTime};
+use std::vec::Vec;
+
+pub(crate) const TRIPCODE: &str = "tripcode";
+pub(crate) const POSTER_ID: &str = "poster_id";
+const SECRET_LENGTH: usize = 32;
+
+pub(crate) fn current(
+    config: &Config,
+    name: &str,
+    rotation: Option<Duration>,
+    now: SystemTime,
+) -> Result<Vec<u8>, Error> {
+    // makes a new secret when there isn't one yet, or the current one is due to be replaced
+    if let Some((secret, created)) = site_storage::read_secret(config, name)? {
+        let age: Duration = now.duration_since(created).unwrap_or_default();
+        if rotation.is_none_or(|rotation| age < rotation) {
+            return Ok(secret);
+        }
+    }
+
+    let mut secret: Vec<u8> = vec![0; SECRET_LENGTH];
+    getrandom::fill(&mut secret).map_err(Error::other)?;
+    site_storage::write_secret(config, name, &secret, &now)?;
+
+    return Ok(secret);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::fixtures::TestRoot;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn test_config(name: &str) -> TestRoot {
+        let config = TestRoot::new("secrets", name);
+        config.connect().unwrap();
+        return config;
+    }
+
+    #[test]
+    fn test_current() {
+        let config = test_config("current");
+        let now = SystemTime::now();
+
+        let secret = current(&config, POSTER_ID, Some(DAY), now).unwrap();
+        assert_eq!(secret.len(), SECRET_LENGTH);
+        assert_eq!(
+            current(&config, POSTER_ID, Some(DAY), now + DAY / 2).unwrap(),
+            secret
+        );
+        // each name has its own
+        assert_ne!(current(&config, TRIPCODE, None, now).unwrap(), secret);
+
+        let rotated = current(&config, POSTER_ID, Some(DAY), now + DAY).unwrap();
+        assert_ne!(rotated, secret);
+        assert_eq!(
+            current(&config, POSTER_ID, Some(DAY), now + DAY + DAY / 2).unwrap(),
+            rotated
+        );
+    }
+
+    #[test]
+    fn test_current_never_rotated() {
+        let config = test_config("never_rotated");
+        let now = SystemTime::now();
+
+        let secret = current(&config, TRIPCODE, None, now).unwrap();
+        assert_eq!(
+            current(&config, TRIPCODE, None, now + DAY * 10000).unwrap(),
+            secret
+        );
+    }
+}
@@ -0,0 +1,513 @@
+// Serialization. Boards, threads and posts each have one JSON form, shared by anything that hands
+// them to the outside world, like the command line exports. Every document carries the version it
+// was written with, which goes up whenever a field changes meaning or goes away. Adding a field
+// doesn't need a new version, older documents just leave it out.
+// Posts come in two forms. The public form is safe to show to anyone, the admin form adds what only
+// moderators should see: the owner's IP and cookie, deletion details and the edit history. A deleted
+// post in the public form is only a marker, with its ID, thread and times, and nothing of what was
+// in it.
+// A thread exports as one document, the thread with every post in it. A board exports on its own,
+// with its threads listed by ID.
+// Times are unix milliseconds, the same as in storage.
+use crate::core::media::perceptual;
+use crate::core::structs::attachment::Attachment;
+use crate::core::structs::board::Board;
+use crate::core::structs::board_config::BoardConfig;
+use crate::core::structs::post::Post;
+use crate::core::structs::sticky::Sticky;
+use crate::core::structs::thread::Thread;
+use serde::Serialize;
+use serde_json::Value;
+use std::string::String;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+
+pub(crate) const VERSION: u64 = 1;
+const VERSION_KEY: &str = "version";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Form {
+    Public,
+    Admin,
+}
+
+impl Form {
+    pub fn parse(name: &str) -> Option<Form> {
+        return match name {
+            "public" => Some(Form::Public),
+            "admin" => Some(Form::Admin),
+            _ => None,
+        };
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct AttachmentJson {
+    pub original_name: String,
+    pub stored_name: String,
+    pub mime: String,
+    pub size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    pub hash: String,
+    pub spoiler: bool,
+    // admin only, knowing it makes it easier to dodge image bans
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub perceptual_hash: Option<String>,
+}
+
+impl AttachmentJson {
+    pub fn new(attachment: &Attachment, form: Form) -> AttachmentJson {
+        let dimensions: Option<(u32, u32)> = attachment.get_dimensions();
+        let perceptual_hash: Option<String> = match form {
+            Form::Public => None,
+            Form::Admin => attachment.get_perceptual_hash().map(perceptual::to_hex),
+        };
+
+        let new_json: AttachmentJson = AttachmentJson {
+            original_name: attachment.get_original_name().clone(),
+            stored_name: attachment.get_stored_name().clone(),
+            mime: attachment.get_mime().clone(),
+            size: attachment.get_size(),
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+            hash: attachment.get_hash().clone(),
+            spoiler: attachment.get_spoiler(),
+            perceptual_hash,
+        };
+
+        return new_json;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct BacklinkJson {
+    pub board: String,
+    pub post: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct RevisionJson {
+    pub edited: u64,
+    pub editor: String, // poster, or mod:ID
+    pub owner: String,
+    pub text: String,
+}
+
+// everything about a post that only moderators get to see
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct PostAdminJson {
+    pub owner: String, // IP and cookie
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deletion_password: Option<String>, // the hash
+    pub revisions: Vec<RevisionJson>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct PostJson {
+    pub id: u64,
+    pub parent: u64,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tripcode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poster_id: Option<String>,
+    pub text: String,
+    pub attachments: Vec<AttachmentJson>,
+    pub created: u64,
+    pub modified: u64,
+    pub deleted: bool,
+    pub backlinks: Vec<BacklinkJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin: Option<PostAdminJson>, // only in the admin form
+}
+
+impl PostJson {
+    pub fn new(id: u64, post: &Post, form: Form) -> PostJson {
+        let admin: Option<PostAdminJson> = match form {
+            Form::Public => None,
+            Form::Admin => Some(PostAdminJson {
+                owner: post.get_owner().clone(),
+                deleted_at: post.get_deleted_at().as_ref().map(to_millis),
+                deletion_password: post.get_deletion_password().clone(),
+                revisions: post
+                    .get_revisions()
+                    .iter()
+                    .map(|revision| RevisionJson {
+                        edited: to_millis(revision.get_edited()),
+                        editor: revision.get_editor().to_string(),
+                        owner: revision.get_owner().clone(),
+                        text: revision.get_text().clone(),
+                    })
+                    .collect(),
+            }),
+        };
+
+        let mut new_json: PostJson = PostJson {
+            id,
+            parent: post.get_parent(),
+            name: post.get_name().clone(),
+            tripcode: post.get_tripcode().clone(),
+            poster_id: post.get_poster_id().clone(),
+            text: post.get_text().clone(),
+            attachments: post
+                .get_attachments()
+                .iter()
+                .map(|attachment| AttachmentJson::new(attachment, form))
+                .collect(),
+            created: to_millis(post.get_created()),
+            modified: to_millis(post.get_modified()),
+            deleted: post.get_deleted(),
+            backlinks: post
+                .get_backlinks()
+                .iter()
+                .map(|(board, post)| BacklinkJson {
+                    board: board.clone(),
+                    post: *post,
+                })
+                .collect(),
+            admin,
+        };
+        if form == Form::Public && post.get_deleted() {
+            // deleted posts keep everything until they're purged, none of it is public
+            new_json.name = String::new();
+            new_json.tripcode = None;
+            new_json.poster_id = None;
+            new_json.text = String::new();
+            new_json.attachments.clear();
+            new_json.backlinks.clear();
+        }
+
+        return new_json;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ThreadJson {
+    pub name: String,
+    pub parent: u64,
+    pub replies: Vec<u64>, // oldest first
+    pub locked: bool,
+    pub deleted: bool,
+    pub cyclic: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bumped: Option<u64>,
+}
+
+impl ThreadJson {
+    pub fn new(thread: &Thread) -> ThreadJson {
+        // threads don't have anything private, so there's only the one form
+        let new_json: ThreadJson = ThreadJson {
+            name: thread.get_name().clone(),
+            parent: thread.get_parent(),
+            replies: thread.get_children()[1..].to_vec(),
+            locked: thread.get_locked(),
+            deleted: thread.get_deleted(),
+            cyclic: thread.get_cyclic(),
+            bumped: thread.get_bumped().as_ref().map(to_millis),
+        };
+
+        return new_json;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ThreadExportJson {
+    pub thread: ThreadJson,
+    pub posts: Vec<PostJson>, // the OP first
+}
+
+impl ThreadExportJson {
+    pub fn new(thread: &Thread, posts: &[(u64, Post)], form: Form) -> ThreadExportJson {
+        let new_json: ThreadExportJson = ThreadExportJson {
+            thread: ThreadJson::new(thread),
+            posts: posts
+                .iter()
+                .map(|(id, post)| PostJson::new(*id, post, form))
+                .collect(),
+        };
+
+        return new_json;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct StickyJson {
+    pub parent: u64,
+    pub priority: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct BoardJson {
+    pub name: String,
+    pub description: String,
+    pub active: Vec<u64>,
+    pub archive: Vec<u64>,
+    pub sticky: Vec<StickyJson>, // highest priority first
+    pub count: u64,
+    pub config: BoardConfig,
+}
+
+impl BoardJson {
+    pub fn new(board: &Board) -> BoardJson {
+        // boards don't have anything private either
+        let new_json: BoardJson = BoardJson {
+            name: board.get_name().clone(),
+            description: board.get_description().clone(),
+            active: board.get_active().clone(),
+            archive: board.get_archive().clone(),
+            sticky: board
+                .get_sticky()
+                .iter()
+                .map(|sticky: &Sticky| StickyJson {
+                    parent: sticky.get_parent(),
+                    priority: sticky.get_priority(),
+                    expires: sticky.get_expires().as_ref().map(to_millis),
+                })
+                .collect(),
+            count: board.get_count(),
+            config: board.get_config().clone(),
+        };
+
+        return new_json;
+    }
+}
+
+pub(crate) fn to_json<T: Serialize>(data: &T) -> String {
+    // every document is an object, with the version alongside everything else.
+    // none of them have maps with non-string keys, which is the only way serializing can fail
+    let mut value: Value = serde_json::to_value(data).unwrap();
+    if let Some(object) = value.as_object_mut() {
+        object.insert(VERSION_KEY.to_string(), Value::from(VERSION));
+    }
+    return value.to_string();
+}
+
+pub(crate) fn thread_export_to_json(thread: &Thread, posts: &[(u64, Post)], form: Form) -> String {
+    return to_json(&ThreadExportJson::new(thread, posts, form));
+}
+
+pub(crate) fn board_to_json(board: &Board) -> String {
+    return to_json(&BoardJson::new(board));
+}
+
+fn to_millis(time: &SystemTime) -> u64 {
+    // the same as storage, anything before the epoch is the epoch
+    return time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::structs::revision::Editor;
+    use std::time::Duration;
+
+    const OWNER: &str = "192.0.2.1;cookie";
+
+    fn post_init() -> Post {
+        let mut a_post = Post::new(
+            OWNER.to_string(),
+            "name".to_string(),
+            Some("!tripcode".to_string()),
+            ">>12\n>green \"text\"".to_string(),
+            12,
+        );
+        let mut attachment = Attachment::new(
+            "cat.png".to_string(),
+            "13-0.png".to_string(),
+            "image/png".to_string(),
+            2048,
+            Some((640, 480)),
+            "abcd".to_string(),
+            true,
+        );
+        attachment.modify_perceptual_hash(Some(0x0123_4567_89AB_CDEF));
+        a_post.add_attachment(attachment, 4);
+        a_post.add_backlink("\u{3B1}".to_string(), 40);
+        a_post.modify_deletion_password(Some("$6$salt$hash".to_string()));
+        a_post.modify_poster_id(Some("Ab3/xY.9".to_string()));
+        a_post.modify_text(
+            "edited".to_string(),
+            Editor::Moderator("jan".to_string()),
+            4,
+        );
+        a_post.delete();
+        return a_post;
+    }
+
+    fn object(json: &str) -> serde_json::Map<String, Value> {
+        let value: Value = serde_json::from_str(json).unwrap();
+        return value.as_object().unwrap().clone();
+    }
+
+    #[test]
+    fn test_form_names() {
+        assert_eq!(Form::parse("public"), Some(Form::Public));
+        assert_eq!(Form::parse("admin"), Some(Form::Admin));
+        assert_eq!(Form::parse("Admin"), None);
+    }
+
+    #[test]
+    fn test_post_public() {
+        let mut post = post_init();
+        post.undelete();
+        let json = to_json(&PostJson::new(13, &post, Form::Public));
+        let fields = object(&json);
+
+        assert_eq!(fields["version"], VERSION);
+        assert_eq!(fields["id"], 13);
+        assert_eq!(fields["poster_id"], "Ab3/xY.9");
+        assert_eq!(fields["attachments"][0]["width"], 640);
+        assert!(!fields.contains_key("admin"));
+        assert!(!fields["attachments"][0]
+            .as_object()
+            .unwrap()
+            .contains_key("perceptual_hash"));
+        // nothing private anywhere in it
+        assert!(!json.contains("192.0.2.1"));
+        assert!(!json.contains("$6$"));
+        assert!(!json.contains("jan"));
+    }
+
+    #[test]
+    fn test_post_public_deleted() {
+        // only the marker is left, the text and files it still has in storage stay out
+        let json = to_json(&PostJson::new(13, &post_init(), Form::Public));
+        let fields = object(&json);
+
+        assert_eq!(fields["id"], 13);
+        assert_eq!(fields["parent"], 12);
+        assert_eq!(fields["deleted"], true);
+        assert_eq!(fields["name"], "");
+        assert_eq!(fields["text"], "");
+        assert_eq!(fields["attachments"], serde_json::json!([]));
+        assert_eq!(fields["backlinks"], serde_json::json!([]));
+        assert!(!fields.contains_key("tripcode"));
+        assert!(!fields.contains_key("poster_id"));
+        assert!(!json.contains("edited"));
+        assert!(!json.contains("13-0.png"));
+
+        // the admin form still has all of it
+        let admin = object(&to_json(&PostJson::new(13, &post_init(), Form::Admin)));
+        assert_eq!(admin["text"], "edited");
+        assert_eq!(admin["attachments"][0]["stored_name"], "13-0.png");
+    }
+
+    #[test]
+    fn test_post_admin() {
+        let post = post_init();
+        let fields = object(&to_json(&PostJson::new(13, &post, Form::Admin)));
+
+        assert_eq!(fields["id"], 13);
+        assert_eq!(fields["tripcode"], "!tripcode");
+        assert_eq!(fields["backlinks"][0]["board"], "\u{3B1}");
+        assert_eq!(fields["backlinks"][0]["post"], 40);
+        assert_eq!(
+            fields["attachments"][0]["perceptual_hash"],
+            perceptual::to_hex(0x0123_4567_89AB_CDEF)
+        );
+        assert_eq!(fields["admin"]["owner"], OWNER);
+        assert_eq!(fields["admin"]["deletion_password"], "$6$salt$hash");
+        assert_eq!(
+            fields["admin"]["deleted_at"],
+            to_millis(post.get_deleted_at().as_ref().unwrap())
+        );
+        assert_eq!(fields["admin"]["revisions"][0]["editor"], "mod:jan");
+        assert_eq!(
+            fields["admin"]["revisions"][0]["text"],
+            ">>12\n>green \"text\""
+        );
+    }
+
+    #[test]
+    fn test_thread() {
+        let mut thread = Thread::new("general".to_string(), 40);
+        thread.add_child(41, 10).unwrap();
+        thread.add_child(45, 10).unwrap();
+        thread.lock();
+        thread.modify_cyclic(true);
+        thread.modify_bumped(UNIX_EPOCH + Duration::from_millis(1_700_000_000_000));
+
+        let fields = object(&to_json(&ThreadJson::new(&thread)));
+        assert_eq!(fields["name"], "general");
+        assert_eq!(fields["parent"], 40);
+        assert_eq!(fields["replies"], serde_json::json!([41, 45]));
+        assert_eq!(fields["locked"], true);
+        assert_eq!(fields["deleted"], false);
+        assert_eq!(fields["cyclic"], true);
+        assert_eq!(fields["bumped"], 1_700_000_000_000u64);
+
+        let unbumped = Thread::new("name".to_string(), 40);
+        assert!(!object(&to_json(&ThreadJson::new(&unbumped))).contains_key("bumped"));
+    }
+
+    #[test]
+    fn test_thread_export() {
+        let mut thread = Thread::new("general".to_string(), 12);
+        thread.add_child(13, 10).unwrap();
+        let op = Post::new(
+            OWNER.to_string(),
+            "name".to_string(),
+            None,
+            "op".to_string(),
+            12,
+        );
+        let posts = vec![(12, op), (13, post_init())];
+
+        let json = thread_export_to_json(&thread, &posts, Form::Public);
+        let fields = object(&json);
+        assert_eq!(fields["version"], VERSION);
+        assert_eq!(fields["thread"]["replies"], serde_json::json!([13]));
+        assert_eq!(fields["posts"][0]["text"], "op");
+        assert_eq!(fields["posts"][1]["text"], "");
+        assert!(!json.contains("192.0.2.1"));
+
+        let admin = object(&thread_export_to_json(&thread, &posts, Form::Admin));
+        assert_eq!(admin["posts"][1]["admin"]["owner"], OWNER);
+    }
+
+    #[test]
+    fn test_board() {
+        let mut board = Board::new("test".to_string(), "a board".to_string());
+        let mut board_config = BoardConfig::new();
+        board_config.modify_poster_ids(true);
+        board_config.modify_default_name("Nameless".to_string());
+        board.modify_config(board_config);
+        board.restore_count(12);
+        board.add_active(12);
+        board.add_active(3);
+        board.add_archive(1);
+        board.add_sticky(3, 1, None);
+        board.add_sticky(
+            12,
+            1,
+            Some(UNIX_EPOCH + Duration::from_millis(1_700_000_000_000)),
+        );
+
+        let fields = object(&board_to_json(&board));
+        assert_eq!(fields["version"], VERSION);
+        assert_eq!(fields["name"], "test");
+        assert_eq!(fields["description"], "a board");
+        assert_eq!(fields["count"], 12);
+        assert_eq!(fields["active"], serde_json::json!([12, 3]));
+        assert_eq!(fields["archive"], serde_json::json!([1]));
+        assert_eq!(
+            fields["sticky"],
+            serde_json::json!([
+                {"parent": 3, "priority": 1},
+                {"parent": 12, "priority": 1, "expires": 1_700_000_000_000u64}
+            ])
+        );
+        assert_eq!(fields["config"]["poster_ids"], true);
+        assert_eq!(fields["config"]["default_name"], "Nameless");
+    }
+}
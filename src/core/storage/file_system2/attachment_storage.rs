@@ -0,0 +1,364 @@
+// Attachment file data, kept apart from the post data so posts stay small enough to hold in memory.
+// Originals are stored under their stored name, with their thumbnails right next to them:
+// |-attachments
+// |-|-12-0.png
+// |-|-12-0.op.jpg
+// |-|-12-0.reply.jpg
+// Images also have their perceptual hash indexed, in a single file next to the attachment folder,
+// so similar images can be looked up without decoding everything again.
+use super::common;
+use super::config::Config;
+use crate::core::media::perceptual;
+use crate::core::media::thumbnail::{ThumbnailSize, ALL_SIZES, THUMBNAIL_EXTENSION};
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read, read_dir, remove_file, rename, File};
+use std::io::{Error, ErrorKind, Write};
+use std::path::PathBuf;
+use std::vec::Vec;
+
+const ATTACHMENT_DIR: &str = "attachments";
+const PERCEPTUAL_HASH_FILE: &str = "perceptual_hashes.txt";
+
+pub(crate) fn initialize(config: &Config, board: &str) -> Result<(), Error> {
+    let attachment_path: PathBuf = get_path(config, board);
+
+    if !attachment_path.exists() {
+        create_dir_all(attachment_path)?;
+    }
+
+    return Ok(());
+}
+
+fn get_path(config: &Config, board: &str) -> PathBuf {
+    return config.get_board_path(board).join(ATTACHMENT_DIR);
+}
+
+fn get_file_path(config: &Config, board: &str, name: &str) -> Result<PathBuf, Error> {
+    if !common::is_safe_name(name) {
+        return Err(common::unsafe_name(name));
+    }
+
+    return Ok(get_path(config, board).join(name));
+}
+
+pub(crate) fn get_thumbnail_name(stored_name: &str, size: ThumbnailSize) -> String {
+    let stem: &str = match stored_name.split_once('.') {
+        Some((stem, _)) => stem,
+        None => stored_name,
+    };
+
+    return format!("{}.{}.{}", stem, size.get_name(), THUMBNAIL_EXTENSION);
+}
+
+fn is_original(name: &str) -> bool {
+    // originals are `stem.extension`, while thumbnails have the size wedged in between
+    return common::is_safe_name(name) && name.matches('.').count() == 1;
+}
+
+pub(crate) fn write_attachment(
+    config: &Config,
+    board: &str,
+    stored_name: &str,
+    data: &[u8],
+) -> Result<(), Error> {
+    let mut attachment_file: File = File::create(get_file_path(config, board, stored_name)?)?;
+
+    attachment_file.write_all(data)?;
+    attachment_file.sync_all()?;
+
+    return Ok(());
+}
+
+pub(crate) fn read_attachment(
+    config: &Config,
+    board: &str,
+    stored_name: &str,
+) -> Result<Vec<u8>, Error> {
+    return read(get_file_path(config, board, stored_name)?);
+}
+
+pub(crate) fn write_thumbnail(
+    config: &Config,
+    board: &str,
+    stored_name: &str,
+    size: ThumbnailSize,
+    data: &[u8],
+) -> Result<(), Error> {
+    return write_attachment(config, board, &get_thumbnail_name(stored_name, size), data);
+}
+
+pub(crate) fn read_thumbnail(
+    config: &Config,
+    board: &str,
+    stored_name: &str,
+    size: ThumbnailSize,
+) -> Result<Vec<u8>, Error> {
+    return read_attachment(config, board, &get_thumbnail_name(stored_name, size));
+}
+
+pub(crate) fn remove_attachment(
+    config: &Config,
+    board: &str,
+    stored_name: &str,
+) -> Result<(), Error> {
+    // thumbnails go along with the original, though not every attachment has them
+    remove_file(get_file_path(config, board, stored_name)?)?;
+
+    for size in ALL_SIZES {
+        match remove_file(get_file_path(
+            config,
+            board,
+            &get_thumbnail_name(stored_name, size),
+        )?) {
+            Err(error) if error.kind() != ErrorKind::NotFound => return Err(error),
+            _ => (),
+        }
+    }
+
+    let mut hashes: HashMap<String, u64> = read_perceptual_hashes(config, board)?;
+    if hashes.remove(stored_name).is_some() {
+        write_perceptual_hashes(config, board, &hashes)?;
+    }
+
+    return Ok(());
+}
+
+pub(crate) fn move_attachment(
+    config: &Config,
+    from: (&str, &str),
+    to: (&str, &str),
+) -> Result<(), Error> {
+    // moves an original, its thumbnails and its perceptual hash, as board and stored name.
+    // used for moving threads between boards, where posts and so their files get new names
+    let (from_board, from_name): (&str, &str) = from;
+    let (to_board, to_name): (&str, &str) = to;
+
+    rename(
+        get_file_path(config, from_board, from_name)?,
+        get_file_path(config, to_board, to_name)?,
+    )?;
+    for size in ALL_SIZES {
+        match rename(
+            get_file_path(config, from_board, &get_thumbnail_name(from_name, size))?,
+            get_file_path(config, to_board, &get_thumbnail_name(to_name, size))?,
+        ) {
+            Err(error) if error.kind() != ErrorKind::NotFound => return Err(error),
+            _ => (),
+        }
+    }
+
+    let mut hashes: HashMap<String, u64> = read_perceptual_hashes(config, from_board)?;
+    if let Some(hash) = hashes.remove(from_name) {
+        write_perceptual_hashes(config, from_board, &hashes)?;
+        write_perceptual_hash(config, to_board, to_name, hash)?;
+    }
+
+    return Ok(());
+}
+
+pub(crate) fn read_perceptual_hashes(
+    config: &Config,
+    board: &str,
+) -> Result<HashMap<String, u64>, Error> {
+    let index_path: PathBuf = config.get_board_path(board).join(PERCEPTUAL_HASH_FILE);
+    let mut hashes: HashMap<String, u64> = HashMap::new();
+
+    if !index_path.exists() {
+        return Ok(hashes);
+    }
+
+    for (stored_name, hash) in common::read_config(&index_path)? {
+        let hash: u64 = perceptual::from_hex(&hash).ok_or(Error::new(
+            ErrorKind::InvalidData,
+            format!("bad perceptual hash for {}", stored_name),
+        ))?;
+        hashes.insert(stored_name, hash);
+    }
+
+    return Ok(hashes);
+}
+
+fn write_perceptual_hashes(
+    config: &Config,
+    board: &str,
+    hashes: &HashMap<String, u64>,
+) -> Result<(), Error> {
+    let mut entries: Vec<(&str, String)> = hashes
+        .iter()
+        .map(|(stored_name, hash)| (stored_name.as_str(), perceptual::to_hex(*hash)))
+        .collect();
+    entries.sort();
+
+    return common::write_config(
+        &config.get_board_path(board).join(PERCEPTUAL_HASH_FILE),
+        &entries,
+    );
+}
+
+pub(crate) fn write_perceptual_hash(
+    config: &Config,
+    board: &str,
+    stored_name: &str,
+    hash: u64,
+) -> Result<(), Error> {
+    let mut hashes: HashMap<String, u64> = read_perceptual_hashes(config, board)?;
+    hashes.insert(stored_name.to_string(), hash);
+
+    return write_perceptual_hashes(config, board, &hashes);
+}
+
+pub(crate) fn list_attachments(config: &Config, board: &str) -> Result<Vec<String>, Error> {
+    // lists originals only, sorted so bulk jobs run in a predictable order
+    let mut stored_names: Vec<String> = Vec::new();
+
+    for entry in read_dir(get_path(config, board))? {
+        let name: String = entry?.file_name().to_string_lossy().to_string();
+        if is_original(&name) {
+            stored_names.push(name);
+        }
+    }
+    stored_names.sort();
+
+    return Ok(stored_names);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::fixtures::TestRoot;
+
+    const BOARD: &str = "test";
+    const STORED_NAME: &str = "12-0.png";
+    const DATA: &[u8] = b"not really a png";
+
+    fn test_config(name: &str) -> TestRoot {
+        let config = TestRoot::new("attachment", name);
+        initialize(&config, BOARD).unwrap();
+        return config;
+    }
+
+    #[test]
+    fn test_initialize() {
+        let config = test_config("initialize");
+
+        assert!(get_path(&config, BOARD).is_dir());
+        initialize(&config, BOARD).unwrap();
+    }
+
+    #[test]
+    fn test_get_thumbnail_name() {
+        assert_eq!(
+            get_thumbnail_name(STORED_NAME, ThumbnailSize::Op),
+            "12-0.op.jpg"
+        );
+        assert_eq!(
+            get_thumbnail_name(STORED_NAME, ThumbnailSize::Reply),
+            "12-0.reply.jpg"
+        );
+    }
+
+    #[test]
+    fn test_write_read_attachment() {
+        let config = test_config("write_read");
+
+        write_attachment(&config, BOARD, STORED_NAME, DATA).unwrap();
+        assert_eq!(read_attachment(&config, BOARD, STORED_NAME).unwrap(), DATA);
+
+        write_thumbnail(&config, BOARD, STORED_NAME, ThumbnailSize::Op, b"thumb").unwrap();
+        assert_eq!(
+            read_thumbnail(&config, BOARD, STORED_NAME, ThumbnailSize::Op).unwrap(),
+            b"thumb"
+        );
+    }
+
+    #[test]
+    fn test_unsafe_names() {
+        let config = test_config("unsafe_names");
+
+        assert_eq!(
+            write_attachment(&config, BOARD, "../../config.txt", DATA)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            read_attachment(&config, BOARD, "../config.txt")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_remove_attachment() {
+        let config = test_config("remove");
+        write_attachment(&config, BOARD, STORED_NAME, DATA).unwrap();
+        write_thumbnail(&config, BOARD, STORED_NAME, ThumbnailSize::Op, b"thumb").unwrap();
+        write_attachment(&config, BOARD, "12-1.pdf", DATA).unwrap();
+
+        remove_attachment(&config, BOARD, STORED_NAME).unwrap();
+        remove_attachment(&config, BOARD, "12-1.pdf").unwrap();
+        assert!(read_attachment(&config, BOARD, STORED_NAME).is_err());
+        assert!(read_thumbnail(&config, BOARD, STORED_NAME, ThumbnailSize::Op).is_err());
+        assert!(remove_attachment(&config, BOARD, STORED_NAME).is_err());
+    }
+
+    #[test]
+    fn test_move_attachment() {
+        let config = test_config("move");
+        initialize(&config, "other").unwrap();
+        write_attachment(&config, BOARD, STORED_NAME, DATA).unwrap();
+        write_thumbnail(&config, BOARD, STORED_NAME, ThumbnailSize::Reply, b"thumb").unwrap();
+        write_perceptual_hash(&config, BOARD, STORED_NAME, 42).unwrap();
+
+        move_attachment(&config, (BOARD, STORED_NAME), ("other", "3-0.png")).unwrap();
+        assert!(read_attachment(&config, BOARD, STORED_NAME).is_err());
+        assert_eq!(read_attachment(&config, "other", "3-0.png").unwrap(), DATA);
+        assert_eq!(
+            read_thumbnail(&config, "other", "3-0.png", ThumbnailSize::Reply).unwrap(),
+            b"thumb"
+        );
+        assert!(read_perceptual_hashes(&config, BOARD).unwrap().is_empty());
+        assert_eq!(
+            read_perceptual_hashes(&config, "other")
+                .unwrap()
+                .get("3-0.png"),
+            Some(&42)
+        );
+    }
+
+    #[test]
+    fn test_perceptual_hashes() {
+        let config = test_config("perceptual_hashes");
+        assert!(read_perceptual_hashes(&config, BOARD).unwrap().is_empty());
+
+        write_attachment(&config, BOARD, STORED_NAME, DATA).unwrap();
+        write_perceptual_hash(&config, BOARD, STORED_NAME, 42).unwrap();
+        write_perceptual_hash(&config, BOARD, "13-0.gif", u64::MAX).unwrap();
+
+        let hashes = read_perceptual_hashes(&config, BOARD).unwrap();
+        assert_eq!(hashes.get(STORED_NAME), Some(&42));
+        assert_eq!(hashes.get("13-0.gif"), Some(&u64::MAX));
+        assert_eq!(
+            list_attachments(&config, BOARD).unwrap(),
+            vec![STORED_NAME.to_string()]
+        );
+
+        remove_attachment(&config, BOARD, STORED_NAME).unwrap();
+        assert_eq!(read_perceptual_hashes(&config, BOARD).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_list_attachments() {
+        let config = test_config("list");
+        write_attachment(&config, BOARD, "13-0.gif", DATA).unwrap();
+        write_attachment(&config, BOARD, STORED_NAME, DATA).unwrap();
+        write_thumbnail(&config, BOARD, STORED_NAME, ThumbnailSize::Op, b"thumb").unwrap();
+        write_thumbnail(&config, BOARD, STORED_NAME, ThumbnailSize::Reply, b"thumb").unwrap();
+
+        assert_eq!(
+            list_attachments(&config, BOARD).unwrap(),
+            vec![STORED_NAME.to_string(), "13-0.gif".to_string()]
+        );
+    }
+}
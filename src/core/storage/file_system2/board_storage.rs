@@ -0,0 +1,474 @@
+// Per-board storage. Each board is a folder named after the board, holding its config file,
+// the board's threads, posts and attachments.
+// The config file is meant to be edited by hand, so the board's running state (the post counter
+// and thread lists) is kept apart from it in a state file, with stickies in a records file of
+// their own: parent;priority;expires; with expires in unix millis, or empty for permanent ones.
+// Board state is written as read, change, write, so two writers at once can roll the counter back
+// and hand out a post ID twice. Everything that writes, the server and the command line alike,
+// holds the advisory lock from lock_boards() for the whole of it. It covers the whole data root,
+// since moving a thread writes two boards and bans are shared by every board.
+use super::attachment_storage;
+use super::common;
+use super::config::Config;
+use super::post_storage;
+use super::structs::board::Board;
+use super::structs::board_config::BoardConfig;
+use super::structs::sticky::Sticky;
+use super::thread_storage;
+use std::collections::HashMap;
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+const CONFIG_FILE: &str = "config.txt";
+const STATE_FILE: &str = "state.txt";
+const STICKY_FILE: &str = "stickies.txt";
+const LOCK_FILE: &str = "boards.lock";
+const DEFAULT_DESCRIPTION: &str = "This is the default board description.";
+
+pub(crate) fn initialize(config: &Config, board: &str) -> Result<(), Error> {
+    // using the board list from site_storage::initialize()
+    // 1. check for directory
+    // 2. create it if it doesn't exist
+    // 3. check for config file
+    // 4. create if doesn't exist
+    // 5. read and validate config file
+    // 6. initialize the thread, post and attachment stores
+    let board_path: PathBuf = config.get_board_path(board);
+
+    if !board_path.exists() {
+        create_dir_all(&board_path)?;
+    }
+    if !board_path.join(CONFIG_FILE).exists() {
+        write_board(
+            config,
+            &Board::new(board.to_string(), DEFAULT_DESCRIPTION.to_string()),
+        )?;
+    }
+    read_board(config, board)?;
+    thread_storage::initialize(config, board)?;
+    post_storage::initialize(config, board)?;
+    attachment_storage::initialize(config, board)?;
+
+    return Ok(());
+}
+
+pub(crate) fn lock_boards(config: &Config) -> Result<File, Error> {
+    // waits for whoever has it now. the lock goes when the file is dropped, or the process exits
+    let file: File = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(config.get_root().join(LOCK_FILE))?;
+    file.lock()?;
+
+    return Ok(file);
+}
+
+pub(crate) fn read_board(config: &Config, board: &str) -> Result<Board, Error> {
+    // the board name is handled by the folder name
+    let data: HashMap<String, String> =
+        common::read_config(&config.get_board_path(board).join(CONFIG_FILE))?;
+    let description: String = data.get("description").cloned().unwrap_or_default();
+
+    let mut read_board: Board = Board::new(board.to_string(), description);
+    read_board.modify_config(parse_config(&data)?);
+
+    // boards that have never been written since state was kept start out empty
+    let state_path: PathBuf = config.get_board_path(board).join(STATE_FILE);
+    if state_path.exists() {
+        restore_state(&mut read_board, &common::read_config(&state_path)?)?;
+    }
+    let sticky_path: PathBuf = config.get_board_path(board).join(STICKY_FILE);
+    if sticky_path.exists() {
+        restore_stickies(&mut read_board, &common::read_records(&sticky_path)?)?;
+    }
+
+    return Ok(read_board);
+}
+
+fn restore_state(board: &mut Board, data: &HashMap<String, String>) -> Result<(), Error> {
+    board.restore_count(parse_value(data, "count")?.unwrap_or_default());
+    for parent in parse_list(data, "active")? {
+        board.add_active(parent);
+    }
+    for parent in parse_list(data, "archive")? {
+        board.add_archive(parent);
+    }
+
+    return Ok(());
+}
+
+fn restore_stickies(board: &mut Board, records: &[Vec<String>]) -> Result<(), Error> {
+    // stickies are stored in order, and adding them back in order keeps ties as they were
+    for record in records {
+        let [parent, priority, expires] = record.as_slice() else {
+            return Err(malformed_sticky(&record.join(";")));
+        };
+        let expires: Option<SystemTime> = match expires.as_str() {
+            "" => None,
+            millis => Some(common::from_millis(
+                millis.parse().map_err(|_| malformed_sticky(millis))?,
+            )),
+        };
+
+        board.add_sticky(
+            parent.parse().map_err(|_| malformed_sticky(parent))?,
+            priority.parse().map_err(|_| malformed_sticky(priority))?,
+            expires,
+        );
+    }
+
+    return Ok(());
+}
+
+fn malformed_sticky(value: &str) -> Error {
+    return Error::new(
+        ErrorKind::InvalidData,
+        format!("bad sticky in board state: {}", value),
+    );
+}
+
+fn parse_list(data: &HashMap<String, String>, key: &str) -> Result<Vec<u64>, Error> {
+    let value: &str = data.get(key).map(String::as_str).unwrap_or_default();
+
+    return value
+        .split(',')
+        .map(str::trim)
+        .filter(|parent| !parent.is_empty())
+        .map(|parent| {
+            parent.parse().map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("bad value for {} in board state: {}", key, parent),
+                )
+            })
+        })
+        .collect();
+}
+
+fn join_list<'a>(parents: impl Iterator<Item = &'a u64>) -> String {
+    return parents
+        .map(u64::to_string)
+        .collect::<Vec<String>>()
+        .join(", ");
+}
+
+pub(crate) fn write_board(config: &Config, board: &Board) -> Result<(), Error> {
+    let board_config: &BoardConfig = board.get_config();
+    let entries: Vec<(&str, String)> = vec![
+        ("description", board.get_description().clone()),
+        ("active_count", board_config.get_active_count().to_string()),
+        (
+            "archive_count",
+            board_config.get_archive_count().to_string(),
+        ),
+        ("tripcodes", board_config.get_tripcodes().to_string()),
+        ("max_files", board_config.get_max_files().to_string()),
+        ("allowed_types", board_config.get_allowed_types().join(", ")),
+        (
+            "max_file_size",
+            board_config.get_max_file_size().to_string(),
+        ),
+        (
+            "max_image_width",
+            board_config.get_max_image_width().to_string(),
+        ),
+        (
+            "max_image_height",
+            board_config.get_max_image_height().to_string(),
+        ),
+        (
+            "op_thumbnail_bound",
+            board_config.get_op_thumbnail_bound().to_string(),
+        ),
+        (
+            "reply_thumbnail_bound",
+            board_config.get_reply_thumbnail_bound().to_string(),
+        ),
+        (
+            "strip_metadata",
+            board_config.get_strip_metadata().to_string(),
+        ),
+        ("max_replies", board_config.get_max_replies().to_string()),
+        (
+            "revision_limit",
+            board_config.get_revision_limit().to_string(),
+        ),
+        ("edit_window", board_config.get_edit_window().to_string()),
+        (
+            "retention_days",
+            board_config.get_retention_days().to_string(),
+        ),
+        (
+            "max_comment_length",
+            board_config.get_max_comment_length().to_string(),
+        ),
+        ("max_lines", board_config.get_max_lines().to_string()),
+        (
+            "op_requires_image",
+            board_config.get_op_requires_image().to_string(),
+        ),
+        (
+            "subject_required",
+            board_config.get_subject_required().to_string(),
+        ),
+        (
+            "forced_anonymity",
+            board_config.get_forced_anonymity().to_string(),
+        ),
+        (
+            "reply_cooldown",
+            board_config.get_reply_cooldown().to_string(),
+        ),
+        (
+            "thread_cooldown",
+            board_config.get_thread_cooldown().to_string(),
+        ),
+        ("default_name", board_config.get_default_name().clone()),
+        ("cyclic_limit", board_config.get_cyclic_limit().to_string()),
+        ("poster_ids", board_config.get_poster_ids().to_string()),
+    ];
+
+    common::write_config(
+        &config.get_board_path(board.get_name()).join(CONFIG_FILE),
+        &entries,
+    )?;
+
+    let state: Vec<(&str, String)> = vec![
+        ("count", board.get_count().to_string()),
+        ("active", join_list(board.get_active().iter())),
+        ("archive", join_list(board.get_archive().iter())),
+    ];
+    common::write_config(
+        &config.get_board_path(board.get_name()).join(STATE_FILE),
+        &state,
+    )?;
+
+    let stickies: Vec<Vec<String>> = board
+        .get_sticky()
+        .iter()
+        .map(|sticky: &Sticky| {
+            vec![
+                sticky.get_parent().to_string(),
+                sticky.get_priority().to_string(),
+                sticky
+                    .get_expires()
+                    .as_ref()
+                    .map(|expires| common::to_millis(expires).to_string())
+                    .unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    return common::write_records(
+        &config.get_board_path(board.get_name()).join(STICKY_FILE),
+        &stickies,
+    );
+}
+
+fn parse_config(data: &HashMap<String, String>) -> Result<BoardConfig, Error> {
+    // any missing keys keep their defaults, so older config files keep working as settings are added
+    let mut board_config: BoardConfig = BoardConfig::new();
+
+    if let Some(value) = parse_value(data, "active_count")? {
+        board_config.modify_active_count(value);
+    }
+    if let Some(value) = parse_value(data, "archive_count")? {
+        board_config.modify_archive_count(value);
+    }
+    if let Some(value) = parse_value(data, "tripcodes")? {
+        board_config.modify_tripcodes(value);
+    }
+    if let Some(value) = parse_value(data, "max_files")? {
+        board_config.modify_max_files(value);
+    }
+    if let Some(value) = data.get("allowed_types") {
+        let types: Vec<String> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|mime| !mime.is_empty())
+            .map(str::to_string)
+            .collect();
+        board_config.modify_allowed_types(types);
+    }
+    if let Some(value) = parse_value(data, "max_file_size")? {
+        board_config.modify_max_file_size(value);
+    }
+    if let Some(value) = parse_value(data, "max_image_width")? {
+        board_config.modify_max_image_width(value);
+    }
+    if let Some(value) = parse_value(data, "max_image_height")? {
+        board_config.modify_max_image_height(value);
+    }
+    if let Some(value) = parse_value(data, "op_thumbnail_bound")? {
+        board_config.modify_op_thumbnail_bound(value);
+    }
+    if let Some(value) = parse_value(data, "reply_thumbnail_bound")? {
+        board_config.modify_reply_thumbnail_bound(value);
+    }
+    if let Some(value) = parse_value(data, "strip_metadata")? {
+        board_config.modify_strip_metadata(value);
+    }
+    if let Some(value) = parse_value(data, "max_replies")? {
+        board_config.modify_max_replies(value);
+    }
+    if let Some(value) = parse_value(data, "revision_limit")? {
+        board_config.modify_revision_limit(value);
+    }
+    if let Some(value) = parse_value(data, "edit_window")? {
+        board_config.modify_edit_window(value);
+    }
+    if let Some(value) = parse_value(data, "retention_days")? {
+        board_config.modify_retention_days(value);
+    }
+    if let Some(value) = parse_value(data, "max_comment_length")? {
+        board_config.modify_max_comment_length(value);
+    }
+    if let Some(value) = parse_value(data, "max_lines")? {
+        board_config.modify_max_lines(value);
+    }
+    if let Some(value) = parse_value(data, "op_requires_image")? {
+        board_config.modify_op_requires_image(value);
+    }
+    if let Some(value) = parse_value(data, "subject_required")? {
+        board_config.modify_subject_required(value);
+    }
+    if let Some(value) = parse_value(data, "forced_anonymity")? {
+        board_config.modify_forced_anonymity(value);
+    }
+    if let Some(value) = parse_value(data, "reply_cooldown")? {
+        board_config.modify_reply_cooldown(value);
+    }
+    if let Some(value) = parse_value(data, "thread_cooldown")? {
+        board_config.modify_thread_cooldown(value);
+    }
+    if let Some(value) = data.get("default_name").filter(|name| !name.is_empty()) {
+        board_config.modify_default_name(value.clone());
+    }
+    if let Some(value) = parse_value(data, "cyclic_limit")? {
+        board_config.modify_cyclic_limit(value);
+    }
+    if let Some(value) = parse_value(data, "poster_ids")? {
+        board_config.modify_poster_ids(value);
+    }
+
+    return Ok(board_config);
+}
+
+fn parse_value<T: FromStr>(data: &HashMap<String, String>, key: &str) -> Result<Option<T>, Error> {
+    let value: &String = match data.get(key) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+
+    return match value.parse::<T>() {
+        Ok(parsed) => Ok(Some(parsed)),
+        Err(_) => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("bad value for {} in board config: {}", key, value),
+        )),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::fixtures::TestRoot;
+    use std::fs::write;
+
+    const BOARD: &str = "test";
+
+    fn test_config(name: &str) -> TestRoot {
+        return TestRoot::new("board", name);
+    }
+
+    #[test]
+    fn test_initialize() {
+        let config = test_config("initialize");
+        initialize(&config, BOARD).unwrap();
+
+        let board = read_board(&config, BOARD).unwrap();
+        assert_eq!(board.get_name(), BOARD);
+        assert_eq!(board.get_description(), DEFAULT_DESCRIPTION);
+        assert_eq!(*board.get_config(), BoardConfig::new());
+        initialize(&config, BOARD).unwrap();
+    }
+
+    #[test]
+    fn test_lock_boards() {
+        let config = test_config("lock");
+        initialize(&config, BOARD).unwrap();
+
+        let locked = lock_boards(&config).unwrap();
+        let other = File::open(config.get_root().join(LOCK_FILE)).unwrap();
+        assert!(other.try_lock_shared().is_err());
+        drop(locked);
+        assert!(other.try_lock_shared().is_ok());
+    }
+
+    #[test]
+    fn test_write_read_board() {
+        let config = test_config("write_read");
+        initialize(&config, BOARD).unwrap();
+
+        let mut board = Board::new(BOARD.to_string(), "Another description".to_string());
+        let mut board_config = BoardConfig::new();
+        board_config.modify_tripcodes(false);
+        board_config.modify_allowed_types(vec!["image/png".to_string(), "image/gif".to_string()]);
+        board_config.modify_op_thumbnail_bound(300);
+        board_config.modify_strip_metadata(false);
+        board_config.modify_default_name("Nameless".to_string());
+        board.modify_config(board_config.clone());
+        board.restore_count(12);
+        board.add_active(12);
+        board.add_active(3);
+        board.add_archive(1);
+        board.add_sticky(3, 1, None);
+        board.add_sticky(12, 1, Some(common::from_millis(1_700_000_000_000)));
+        board.add_sticky(1, 7, None);
+
+        write_board(&config, &board).unwrap();
+        let read = read_board(&config, BOARD).unwrap();
+        assert_eq!(read.get_description(), "Another description");
+        assert_eq!(*read.get_config(), board_config);
+        assert_eq!(read.get_count(), 12);
+        assert_eq!(*read.get_active(), vec![12, 3]);
+        assert_eq!(*read.get_archive(), vec![1]);
+        assert_eq!(*read.get_sticky(), *board.get_sticky());
+    }
+
+    #[test]
+    fn test_read_board_partial_config() {
+        let config = test_config("partial");
+        create_dir_all(config.get_board_path(BOARD)).unwrap();
+        write(
+            config.get_board_path(BOARD).join(CONFIG_FILE),
+            "description: old\r\nactive_count: 4\r\n",
+        )
+        .unwrap();
+
+        let board = read_board(&config, BOARD).unwrap();
+        assert_eq!(board.get_config().get_active_count(), 4);
+        assert_eq!(
+            board.get_config().get_max_files(),
+            BoardConfig::new().get_max_files()
+        );
+    }
+
+    #[test]
+    fn test_read_board_bad_value() {
+        let config = test_config("bad_value");
+        create_dir_all(config.get_board_path(BOARD)).unwrap();
+        write(
+            config.get_board_path(BOARD).join(CONFIG_FILE),
+            "active_count: lots\r\n",
+        )
+        .unwrap();
+
+        let error = read_board(&config, BOARD).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
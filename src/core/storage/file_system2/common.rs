@@ -0,0 +1,259 @@
+// This module contains commonly used functions across the file_system storage scheme
+use std::collections::HashMap;
+use std::fs::{read_to_string, File};
+use std::io::{Error, ErrorKind, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const LINE_ENDING: &str = "\r\n";
+const SEPARATOR: char = ':';
+const FIELD_SEPARATOR: char = ';';
+const BASE36_DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+pub(crate) fn read_config(file_path: &Path) -> Result<HashMap<String, String>, Error> {
+    // config files are `key: value` pairs, one per line
+    let contents: String = read_to_string(file_path)?;
+    let mut keyed_contents: HashMap<String, String> = HashMap::new();
+
+    for line in contents
+        .split(LINE_ENDING)
+        .filter(|line| !line.trim().is_empty())
+    {
+        let (key, value) = match line.split_once(SEPARATOR) {
+            Some(split) => split,
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("malformed line in {}: {}", file_path.display(), line),
+                ))
+            }
+        };
+        keyed_contents.insert(key.trim().to_string(), value.trim().to_string());
+    }
+
+    return Ok(keyed_contents);
+}
+
+pub(crate) fn write_config(file_path: &Path, entries: &[(&str, String)]) -> Result<(), Error> {
+    let mut config_file: File = File::create(file_path)?;
+
+    for (key, value) in entries {
+        config_file
+            .write_all(format!("{}{} {}{}", key, SEPARATOR, value, LINE_ENDING).as_bytes())?;
+    }
+    config_file.sync_all()?;
+
+    return Ok(());
+}
+
+pub(crate) fn read_records(file_path: &Path) -> Result<Vec<Vec<String>>, Error> {
+    // record files are one record per line, with every field terminated by a `;`
+    let contents: String = read_to_string(file_path)?;
+    let mut records: Vec<Vec<String>> = Vec::new();
+
+    for line in contents.split(LINE_ENDING).filter(|line| !line.is_empty()) {
+        let fields: &str = line.strip_suffix(FIELD_SEPARATOR).ok_or(Error::new(
+            ErrorKind::InvalidData,
+            format!("malformed record in {}: {}", file_path.display(), line),
+        ))?;
+        records.push(fields.split(FIELD_SEPARATOR).map(unescape_field).collect());
+    }
+
+    return Ok(records);
+}
+
+pub(crate) fn write_records(file_path: &Path, records: &[Vec<String>]) -> Result<(), Error> {
+    let mut record_file: File = File::create(file_path)?;
+
+    for record in records {
+        let mut line: String = String::new();
+        for field in record {
+            line.push_str(&escape_field(field));
+            line.push(FIELD_SEPARATOR);
+        }
+        line.push_str(LINE_ENDING);
+        record_file.write_all(line.as_bytes())?;
+    }
+    record_file.sync_all()?;
+
+    return Ok(());
+}
+
+pub(crate) fn escape_field(field: &str) -> String {
+    // anything user supplied can contain separators or line breaks, so those are percent encoded
+    let mut escaped: String = String::with_capacity(field.len());
+
+    for c in field.chars() {
+        match c {
+            '%' => escaped.push_str("%25"),
+            ';' => escaped.push_str("%3B"),
+            '\r' => escaped.push_str("%0D"),
+            '\n' => escaped.push_str("%0A"),
+            _ => escaped.push(c),
+        }
+    }
+
+    return escaped;
+}
+
+pub(crate) fn unescape_field(field: &str) -> String {
+    return field
+        .replace("%0A", "\n")
+        .replace("%0D", "\r")
+        .replace("%3B", ";")
+        .replace("%25", "%");
+}
+
+pub(crate) fn to_base36(mut number: u64) -> String {
+    let mut digits: Vec<u8> = Vec::new();
+
+    loop {
+        digits.push(BASE36_DIGITS[(number % 36) as usize]);
+        number /= 36;
+        if number == 0 {
+            break;
+        }
+    }
+    digits.reverse();
+
+    return String::from_utf8(digits).unwrap();
+}
+
+pub(crate) fn from_base36(digits: &str) -> Option<u64> {
+    // u64::from_str_radix takes uppercase too, but only lowercase names are ever written
+    if digits.is_empty() || digits.chars().any(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    return u64::from_str_radix(digits, 36).ok();
+}
+
+pub(crate) fn to_millis(time: &SystemTime) -> u64 {
+    // anything before the epoch would be a broken clock, and is stored as the epoch itself
+    return time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+}
+
+pub(crate) fn from_millis(millis: u64) -> SystemTime {
+    return UNIX_EPOCH + Duration::from_millis(millis);
+}
+
+pub(crate) fn is_safe_name(name: &str) -> bool {
+    // names that end up as paths should never be able to leave their directory
+    return !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\']);
+}
+
+pub(crate) fn unsafe_name(name: &str) -> Error {
+    return Error::new(
+        ErrorKind::InvalidInput,
+        format!("unsafe file name: {}", name),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs::{remove_file, write};
+    use std::path::PathBuf;
+
+    fn test_path(name: &str) -> PathBuf {
+        return temp_dir().join(format!(
+            "image_board_common_{}_{}.txt",
+            name,
+            std::process::id()
+        ));
+    }
+
+    #[test]
+    fn test_read_write_config() {
+        let path = test_path("round_trip");
+        let entries = [
+            ("description", "a board: for testing".to_string()),
+            ("count", "16".to_string()),
+        ];
+
+        write_config(&path, &entries).unwrap();
+        let data = read_config(&path).unwrap();
+
+        assert_eq!(data.len(), 2);
+        assert_eq!(data.get("description").unwrap(), "a board: for testing");
+        assert_eq!(data.get("count").unwrap(), "16");
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_config_malformed() {
+        let path = test_path("malformed");
+        write(&path, "description: fine\r\nnot a pair\r\n").unwrap();
+
+        assert_eq!(
+            read_config(&path).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_write_records() {
+        let path = test_path("records");
+        let records = vec![
+            vec!["a;b".to_string(), "100%".to_string(), "".to_string()],
+            vec!["two\r\nlines".to_string()],
+        ];
+
+        write_records(&path, &records).unwrap();
+        assert_eq!(read_records(&path).unwrap(), records);
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_records_malformed() {
+        let path = test_path("records_malformed");
+        write(&path, "fine;\r\nnot terminated\r\n").unwrap();
+
+        assert_eq!(
+            read_records(&path).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_escape_field() {
+        assert_eq!(escape_field("a;b%c\nd"), "a%3Bb%25c%0Ad");
+        assert_eq!(unescape_field("a%3Bb%25c%0Ad"), "a;b%c\nd");
+        assert_eq!(unescape_field(&escape_field("%3B")), "%3B");
+    }
+
+    #[test]
+    fn test_base36() {
+        assert_eq!(to_base36(0), "0");
+        assert_eq!(to_base36(35), "z");
+        assert_eq!(to_base36(36), "10");
+        assert_eq!(from_base36(&to_base36(u64::MAX)), Some(u64::MAX));
+        assert_eq!(from_base36("10"), Some(36));
+        assert_eq!(from_base36("Z"), None);
+        assert_eq!(from_base36(""), None);
+        assert_eq!(from_base36("../"), None);
+    }
+
+    #[test]
+    fn test_millis() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        assert_eq!(to_millis(&time), 1_700_000_000_123);
+        assert_eq!(from_millis(to_millis(&time)), time);
+        assert_eq!(to_millis(&(UNIX_EPOCH - Duration::from_secs(1))), 0);
+    }
+
+    #[test]
+    fn test_is_safe_name() {
+        assert!(is_safe_name("1-0.png"));
+        assert!(!is_safe_name(""));
+        assert!(!is_safe_name(".hidden"));
+        assert!(!is_safe_name("../config.txt"));
+        assert!(!is_safe_name("a/b.png"));
+        assert!(!is_safe_name("a\\b.png"));
+    }
+}
@@ -1,19 +1,86 @@
 // Config should contain auth / connection details.
 // ie, it should be the configuration required to make a connection to the storage system.
 // this can also act as a state manager; eg in the case of an active db connection
-use std::path::Path;
+use std::fs::create_dir_all;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+const BOARD_DIR: &str = "boards";
 
 pub(crate) struct Config {
-    root: &'static Path,
+    root: PathBuf,
 }
 
 impl Config {
-    pub fn new(root: String) {
-        let root_path = Path::new(&root);
-        let config = Config { root: &root_path };
+    pub fn new(root: String) -> Config {
+        let config = Config {
+            root: PathBuf::from(root),
+        };
+
+        return config;
+    }
+
+    pub fn connect(&self) -> Result<(), Error> {
+        // for a file system, "connecting" is just making sure there is a root directory to work in
+        create_dir_all(&self.root)?;
+        if !self.root.is_dir() {
+            return Err(Error::new(
+                ErrorKind::NotADirectory,
+                format!("storage root {} is not a directory", self.root.display()),
+            ));
+        }
+
+        return Ok(());
+    }
+
+    pub fn get_root(&self) -> &Path {
+        return &self.root;
+    }
+
+    pub fn get_board_path(&self, board: &str) -> PathBuf {
+        return self.root.join(BOARD_DIR).join(board);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::fixtures::TestRoot;
+    use std::env::temp_dir;
+    use std::fs::File;
+
+    fn test_root(name: &str) -> String {
+        // for a root that's a file, which the fixture wouldn't remove
+        let root = temp_dir().join(format!(
+            "image_board_config_{}_{}",
+            name,
+            std::process::id()
+        ));
+        return root.to_string_lossy().to_string();
+    }
+
+    #[test]
+    fn test_connect() {
+        let a = TestRoot::new("config", "connect");
+
+        a.connect().unwrap();
+        assert!(a.get_root().is_dir());
+        a.connect().unwrap();
+    }
+
+    #[test]
+    fn test_connect_not_a_directory() {
+        let root = test_root("not_a_directory");
+        File::create(&root).unwrap();
+
+        assert!(Config::new(root.clone()).connect().is_err());
+
+        std::fs::remove_file(&root).unwrap();
     }
 
-    pub fn connect(&self) -> Result<(), std::io::Error> {
-        self.root;
+    #[test]
+    fn test_get_board_path() {
+        let a = Config::new("data".to_string());
+        assert_eq!(a.get_board_path("test"), Path::new("data/boards/test"));
     }
 }
@@ -0,0 +1,93 @@
+// Shared test fixtures. A TestRoot is a data root of its own under the system temp folder, which is
+// removed when it's dropped, so a failing test doesn't leave it behind. It derefs to the Config, so
+// it goes anywhere a &Config does.
+// The posts written here double their ID as their creation time in seconds.
+use super::board_storage;
+use super::config::Config;
+use super::post_storage;
+use super::structs::board::Board;
+use super::structs::post::Post;
+use super::structs::thread::Thread;
+use super::thread_storage;
+use std::env::temp_dir;
+use std::fs::remove_dir_all;
+use std::ops::Deref;
+use std::time::{Duration, UNIX_EPOCH};
+
+pub(crate) struct TestRoot {
+    config: Config,
+}
+
+impl TestRoot {
+    pub fn new(area: &str, name: &str) -> TestRoot {
+        // nothing is made yet. the area keeps tests in different modules apart, the process ID
+        // keeps test runs apart
+        let root = temp_dir().join(format!(
+            "image_board_{}_{}_{}",
+            area,
+            name,
+            std::process::id()
+        ));
+        let test_root: TestRoot = TestRoot {
+            config: Config::new(root.to_string_lossy().to_string()),
+        };
+
+        return test_root;
+    }
+
+    pub fn with_boards(area: &str, name: &str, boards: &[&str]) -> TestRoot {
+        let test_root: TestRoot = TestRoot::new(area, name);
+        for board in boards {
+            board_storage::initialize(&test_root, board).unwrap();
+        }
+
+        return test_root;
+    }
+}
+
+impl Deref for TestRoot {
+    type Target = Config;
+
+    fn deref(&self) -> &Config {
+        return &self.config;
+    }
+}
+
+impl Drop for TestRoot {
+    fn drop(&mut self) {
+        // some tests never make anything
+        let _ = remove_dir_all(self.config.get_root());
+    }
+}
+
+pub(crate) fn write_post(config: &Config, board: &str, id: u64, parent: u64, text: &str) {
+    let mut post: Post = Post::new(
+        "me".to_string(),
+        "Anonymous".to_string(),
+        None,
+        text.to_string(),
+        parent,
+    );
+    let created = UNIX_EPOCH + Duration::from_secs(id);
+    post.restore_timestamps(created, created);
+    post_storage::write_post(config, board, id, &post).unwrap();
+}
+
+pub(crate) fn write_thread(config: &Config, board: &mut Board, subject: &str, ids: &[u64]) {
+    // the OP first, and bumped to the top of the board. the board itself isn't written
+    let board_name: String = board.get_name().to_string();
+    let mut thread: Thread = Thread::new(subject.to_string(), ids[0]);
+    write_post(
+        config,
+        &board_name,
+        ids[0],
+        ids[0],
+        &format!("op {}", ids[0]),
+    );
+    for id in &ids[1..] {
+        write_post(config, &board_name, *id, ids[0], "reply");
+        thread.add_child(*id, u16::MAX).unwrap();
+    }
+    thread_storage::write_thread(config, &board_name, &thread).unwrap();
+    board.bump(ids[0]);
+}
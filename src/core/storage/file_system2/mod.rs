@@ -1 +1,11 @@
+// file system backed storage, the successor to the original file_system module.
+use super::structs;
+pub mod attachment_storage;
+pub mod board_storage;
+mod common;
 pub mod config;
+#[cfg(test)]
+pub mod fixtures;
+pub mod post_storage;
+pub mod site_storage;
+pub mod thread_storage;
@@ -0,0 +1,425 @@
+// Post storage. Each post is a folder named after its ID in base 36, inside the board's posts folder.
+// |
+// |-boards
+// |-|-board_a
+// |-|-|-posts
+// |-|-|-|-c
+// |-|-|-|-|-meta.txt         key: value pairs, everything but the text and the lists
+// |-|-|-|-|-text.txt         the post text, exactly as posted
+// |-|-|-|-|-attachments.txt  one record per attached file
+// |-|-|-|-|-backlinks.txt    one record per post quoting this one
+// |-|-|-|-|-revisions.txt    one record per kept revision, oldest first
+use super::common;
+use super::config::Config;
+use super::structs::attachment::Attachment;
+use super::structs::post::Post;
+use super::structs::revision::{Editor, Revision};
+use crate::core::media::perceptual;
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read_dir, read_to_string, remove_dir_all, write};
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+const POST_DIR: &str = "posts";
+const META_FILE: &str = "meta.txt";
+const TEXT_FILE: &str = "text.txt";
+const ATTACHMENTS_FILE: &str = "attachments.txt";
+const BACKLINKS_FILE: &str = "backlinks.txt";
+const REVISIONS_FILE: &str = "revisions.txt";
+
+fn get_posts_path(config: &Config, board: &str) -> PathBuf {
+    return config.get_board_path(board).join(POST_DIR);
+}
+
+fn get_post_path(config: &Config, board: &str, post: u64) -> PathBuf {
+    return get_posts_path(config, board).join(common::to_base36(post));
+}
+
+pub(crate) fn initialize(config: &Config, board: &str) -> Result<(), Error> {
+    return create_dir_all(get_posts_path(config, board));
+}
+
+pub(crate) fn post_exists(config: &Config, board: &str, post: u64) -> bool {
+    return get_post_path(config, board, post).join(META_FILE).exists();
+}
+
+pub(crate) fn write_post(config: &Config, board: &str, id: u64, post: &Post) -> Result<(), Error> {
+    let post_path: PathBuf = get_post_path(config, board, id);
+    create_dir_all(&post_path)?;
+
+    let mut entries: Vec<(&str, String)> = vec![
+        ("owner", common::escape_field(post.get_owner())),
+        ("name", common::escape_field(post.get_name())),
+        ("created", common::to_millis(post.get_created()).to_string()),
+        (
+            "modified",
+            common::to_millis(post.get_modified()).to_string(),
+        ),
+        ("deleted", post.get_deleted().to_string()),
+        ("parent", post.get_parent().to_string()),
+    ];
+    if let Some(tripcode) = post.get_tripcode() {
+        entries.push(("tripcode", common::escape_field(tripcode)));
+    }
+    if let Some(deletion_password) = post.get_deletion_password() {
+        entries.push(("deletion_password", deletion_password.clone()));
+    }
+    if let Some(poster_id) = post.get_poster_id() {
+        entries.push(("poster_id", poster_id.clone()));
+    }
+    if let Some(deleted_at) = post.get_deleted_at() {
+        entries.push(("deleted_at", common::to_millis(deleted_at).to_string()));
+    }
+    common::write_config(&post_path.join(META_FILE), &entries)?;
+
+    write(post_path.join(TEXT_FILE), post.get_text())?;
+
+    let attachments: Vec<Vec<String>> = post
+        .get_attachments()
+        .iter()
+        .map(attachment_record)
+        .collect();
+    common::write_records(&post_path.join(ATTACHMENTS_FILE), &attachments)?;
+
+    let backlinks: Vec<Vec<String>> = post
+        .get_backlinks()
+        .iter()
+        .map(|(board, post)| vec![board.clone(), post.to_string()])
+        .collect();
+    common::write_records(&post_path.join(BACKLINKS_FILE), &backlinks)?;
+
+    // edited;editor;owner;text;
+    let revisions: Vec<Vec<String>> = post
+        .get_revisions()
+        .iter()
+        .map(|revision| {
+            vec![
+                common::to_millis(revision.get_edited()).to_string(),
+                revision.get_editor().to_string(),
+                revision.get_owner().clone(),
+                revision.get_text().clone(),
+            ]
+        })
+        .collect();
+    common::write_records(&post_path.join(REVISIONS_FILE), &revisions)?;
+
+    return Ok(());
+}
+
+pub(crate) fn read_post(config: &Config, board: &str, id: u64) -> Result<Post, Error> {
+    let post_path: PathBuf = get_post_path(config, board, id);
+    let meta: HashMap<String, String> = common::read_config(&post_path.join(META_FILE))?;
+    let text: String = read_to_string(post_path.join(TEXT_FILE))?;
+
+    let mut read_post: Post = Post::new(
+        common::unescape_field(get_value(&meta, "owner")?),
+        common::unescape_field(get_value(&meta, "name")?),
+        meta.get("tripcode")
+            .map(|tripcode| common::unescape_field(tripcode)),
+        text,
+        parse_value(&meta, "parent")?,
+    );
+
+    read_post.modify_deletion_password(meta.get("deletion_password").cloned());
+    read_post.modify_poster_id(meta.get("poster_id").cloned());
+
+    // limits are enforced when posting, a stored post keeps everything it was allowed to have
+    for record in common::read_records(&post_path.join(ATTACHMENTS_FILE))? {
+        read_post.add_attachment(parse_attachment(&record)?, u8::MAX);
+    }
+    for record in common::read_records(&post_path.join(BACKLINKS_FILE))? {
+        match record.as_slice() {
+            [board, post] => read_post.add_backlink(board.clone(), parse_field(post)?),
+            _ => return Err(malformed("backlink")),
+        };
+    }
+
+    let mut revisions: Vec<Revision> = Vec::new();
+    for record in common::read_records(&post_path.join(REVISIONS_FILE))? {
+        let [edited, editor, owner, text] = record.as_slice() else {
+            return Err(malformed("revision"));
+        };
+        revisions.push(Revision::new(
+            common::from_millis(parse_field(edited)?),
+            Editor::parse(editor).ok_or(malformed("revision editor"))?,
+            owner.clone(),
+            text.clone(),
+        ));
+    }
+    read_post.restore_revisions(revisions);
+
+    if parse_value(&meta, "deleted")? {
+        // posts deleted before deletion times were kept start their retention window now
+        match meta.contains_key("deleted_at") {
+            true => read_post
+                .restore_deleted(Some(common::from_millis(parse_value(&meta, "deleted_at")?))),
+            false => read_post.delete(),
+        }
+    }
+    read_post.restore_timestamps(
+        common::from_millis(parse_value(&meta, "created")?),
+        common::from_millis(parse_value(&meta, "modified")?),
+    );
+
+    return Ok(read_post);
+}
+
+pub(crate) fn remove_post(config: &Config, board: &str, id: u64) -> Result<(), Error> {
+    // removes the post for good. its attachments are stored separately and aren't touched
+    return remove_dir_all(get_post_path(config, board, id));
+}
+
+pub(crate) fn list_posts(config: &Config, board: &str) -> Result<Vec<u64>, Error> {
+    // anything in the posts folder that isn't named like a post is ignored
+    let mut posts: Vec<u64> = Vec::new();
+
+    for entry in read_dir(get_posts_path(config, board))? {
+        let entry = entry?;
+        if let Some(post) = entry.file_name().to_str().and_then(common::from_base36) {
+            posts.push(post);
+        }
+    }
+    posts.sort();
+
+    return Ok(posts);
+}
+
+fn attachment_record(attachment: &Attachment) -> Vec<String> {
+    // filename;stored_name;mime;size;width;height;hash;perceptual_hash;spoiler;
+    let (width, height): (String, String) = match attachment.get_dimensions() {
+        Some((width, height)) => (width.to_string(), height.to_string()),
+        None => (String::new(), String::new()),
+    };
+    let perceptual_hash: String = attachment
+        .get_perceptual_hash()
+        .map(perceptual::to_hex)
+        .unwrap_or_default();
+
+    return vec![
+        attachment.get_original_name().clone(),
+        attachment.get_stored_name().clone(),
+        attachment.get_mime().clone(),
+        attachment.get_size().to_string(),
+        width,
+        height,
+        attachment.get_hash().clone(),
+        perceptual_hash,
+        attachment.get_spoiler().to_string(),
+    ];
+}
+
+fn parse_attachment(record: &[String]) -> Result<Attachment, Error> {
+    let [original_name, stored_name, mime, size, width, height, hash, perceptual_hash, spoiler] =
+        record
+    else {
+        return Err(malformed("attachment"));
+    };
+
+    let dimensions: Option<(u32, u32)> = match (width.as_str(), height.as_str()) {
+        ("", "") => None,
+        _ => Some((parse_field(width)?, parse_field(height)?)),
+    };
+
+    let mut attachment: Attachment = Attachment::new(
+        original_name.clone(),
+        stored_name.clone(),
+        mime.clone(),
+        parse_field(size)?,
+        dimensions,
+        hash.clone(),
+        parse_field(spoiler)?,
+    );
+    if !perceptual_hash.is_empty() {
+        let hash: u64 =
+            perceptual::from_hex(perceptual_hash).ok_or(malformed("perceptual hash"))?;
+        attachment.modify_perceptual_hash(Some(hash));
+    }
+
+    return Ok(attachment);
+}
+
+fn get_value<'a>(data: &'a HashMap<String, String>, key: &str) -> Result<&'a String, Error> {
+    return data.get(key).ok_or(Error::new(
+        ErrorKind::InvalidData,
+        format!("missing {} in post", key),
+    ));
+}
+
+fn parse_value<T: std::str::FromStr>(
+    data: &HashMap<String, String>,
+    key: &str,
+) -> Result<T, Error> {
+    return parse_field(get_value(data, key)?);
+}
+
+fn parse_field<T: std::str::FromStr>(value: &str) -> Result<T, Error> {
+    return value.parse::<T>().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("bad value in post: {}", value),
+        )
+    });
+}
+
+fn malformed(what: &str) -> Error {
+    return Error::new(
+        ErrorKind::InvalidData,
+        format!("malformed {} in post", what),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::fixtures::TestRoot;
+
+    const BOARD: &str = "test";
+
+    fn test_config(name: &str) -> TestRoot {
+        let config = TestRoot::new("post", name);
+        initialize(&config, BOARD).unwrap();
+        return config;
+    }
+
+    fn post_init() -> Post {
+        let mut a_post = Post::new(
+            "me".to_string(),
+            "Anon;ymous".to_string(),
+            Some("!ZnBI2EKkq.".to_string()),
+            ">be me\r\n>>12\n[code]a;b[/code]".to_string(),
+            12,
+        );
+        let mut attachment = Attachment::new(
+            "a;b%.png".to_string(),
+            "13-0.png".to_string(),
+            "image/png".to_string(),
+            42,
+            Some((4, 3)),
+            "abcd".to_string(),
+            true,
+        );
+        attachment.modify_perceptual_hash(Some(0xF0F0));
+        a_post.add_attachment(attachment, 4);
+        a_post.add_attachment(
+            Attachment::new(
+                "paper.pdf".to_string(),
+                "13-1.pdf".to_string(),
+                "application/pdf".to_string(),
+                9,
+                None,
+                "ef01".to_string(),
+                false,
+            ),
+            4,
+        );
+        a_post.add_backlink("\u{3B1}".to_string(), 40);
+        a_post.modify_deletion_password(Some("$6$salt$hash".to_string()));
+        a_post.modify_poster_id(Some("Ab3/xY.9".to_string()));
+        a_post.modify_text("edited;\r\ntext".to_string(), Editor::Poster, 4);
+        a_post.modify_text(
+            "edited again".to_string(),
+            Editor::Moderator("jan".to_string()),
+            4,
+        );
+        return a_post;
+    }
+
+    fn assert_same(a: &Post, b: &Post) {
+        assert_eq!(a.get_owner(), b.get_owner());
+        assert_eq!(a.get_name(), b.get_name());
+        assert_eq!(a.get_tripcode(), b.get_tripcode());
+        assert_eq!(a.get_text(), b.get_text());
+        assert_eq!(a.get_attachments(), b.get_attachments());
+        assert_eq!(
+            common::to_millis(a.get_created()),
+            common::to_millis(b.get_created())
+        );
+        assert_eq!(
+            common::to_millis(a.get_modified()),
+            common::to_millis(b.get_modified())
+        );
+        assert_eq!(a.get_deleted(), b.get_deleted());
+        assert_eq!(
+            a.get_deleted_at().as_ref().map(common::to_millis),
+            b.get_deleted_at().as_ref().map(common::to_millis)
+        );
+        assert_eq!(a.get_parent(), b.get_parent());
+        assert_eq!(a.get_backlinks(), b.get_backlinks());
+        assert_eq!(a.get_deletion_password(), b.get_deletion_password());
+        assert_eq!(a.get_poster_id(), b.get_poster_id());
+
+        assert_eq!(a.get_revisions().len(), b.get_revisions().len());
+        for (a, b) in a.get_revisions().iter().zip(b.get_revisions()) {
+            assert_eq!(
+                common::to_millis(a.get_edited()),
+                common::to_millis(b.get_edited())
+            );
+            assert_eq!(a.get_editor(), b.get_editor());
+            assert_eq!(a.get_owner(), b.get_owner());
+            assert_eq!(a.get_text(), b.get_text());
+        }
+    }
+
+    #[test]
+    fn test_write_read_post() {
+        let config = test_config("write_read");
+        let post = post_init();
+
+        assert!(!post_exists(&config, BOARD, 13));
+        write_post(&config, BOARD, 13, &post).unwrap();
+        assert!(post_exists(&config, BOARD, 13));
+        assert!(get_posts_path(&config, BOARD).join("d").is_dir());
+
+        assert_same(&read_post(&config, BOARD, 13).unwrap(), &post);
+    }
+
+    #[test]
+    fn test_write_read_deleted_post() {
+        let config = test_config("deleted");
+        let mut post = Post::new(
+            "me".to_string(),
+            "Anonymous".to_string(),
+            None,
+            String::new(),
+            1,
+        );
+        post.delete();
+
+        write_post(&config, BOARD, 1, &post).unwrap();
+        assert_same(&read_post(&config, BOARD, 1).unwrap(), &post);
+    }
+
+    #[test]
+    fn test_remove_post() {
+        let config = test_config("remove");
+        write_post(&config, BOARD, 1, &post_init()).unwrap();
+        write_post(&config, BOARD, 2, &post_init()).unwrap();
+
+        remove_post(&config, BOARD, 1).unwrap();
+        assert!(!post_exists(&config, BOARD, 1));
+        assert!(post_exists(&config, BOARD, 2));
+        assert_eq!(
+            remove_post(&config, BOARD, 1).err().unwrap().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_read_post_missing() {
+        let config = test_config("missing");
+        assert_eq!(
+            read_post(&config, BOARD, 1).err().unwrap().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_list_posts() {
+        let config = test_config("list");
+        write_post(&config, BOARD, 40, &post_init()).unwrap();
+        write_post(&config, BOARD, 3, &post_init()).unwrap();
+        create_dir_all(get_posts_path(&config, BOARD).join("NOT_A_POST")).unwrap();
+
+        assert_eq!(list_posts(&config, BOARD).unwrap(), vec![3, 40]);
+    }
+}
@@ -0,0 +1,440 @@
+// This file should contain the ``site'' data. This should be more or less the root of the website's
+// data. The main goal here should be taking the storage root, and then validating RW access +
+// determining if the basic file structure exists.
+// |
+// |-config.txt
+// |-banned_images.txt
+// |-bans.txt             banned IPs, as records: ip;reason;expires; with expires in unix millis or empty
+// |-api_tokens.txt       write API clients, as records: name;hash;scopes;rate_limit; scopes comma separated
+// |-secrets.txt          server secrets, hex encoded, and when each was made. never served
+// |-templates            optional replacements for the server's page templates, as name.html
+// |-boards
+// |-|-board_a
+// |-|-|-config.txt
+// |-|-|-attachments
+use super::board_storage;
+use super::common;
+use super::config::Config;
+use super::structs::api_token::{ApiToken, Scope};
+use super::structs::ban::Ban;
+use super::structs::banned_images::{BannedImages, DEFAULT_THRESHOLD};
+use crate::core::media::perceptual;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+const CONFIG_FILE: &str = "config.txt";
+const BANNED_IMAGES_FILE: &str = "banned_images.txt";
+const BANS_FILE: &str = "bans.txt";
+const API_TOKENS_FILE: &str = "api_tokens.txt";
+const SECRETS_FILE: &str = "secrets.txt";
+const TEMPLATE_DIR: &str = "templates";
+const TEMPLATE_EXTENSION: &str = "html";
+const THRESHOLD_KEY: &str = "threshold";
+const DEFAULT_BOARDS: &str = "\u{3B1}, test";
+
+pub(crate) fn initialize(config: &Config) -> Result<Vec<String>, Error> {
+    // 1. make sure the storage root exists
+    // 2. create a basic configuration file if none exists
+    // 3. read configuration file
+    // 4. initialize each board listed in it, filling in anything missing
+    let config_path: PathBuf = config.get_root().join(CONFIG_FILE);
+
+    config.connect()?;
+    if !config_path.exists() {
+        common::write_config(&config_path, &[("boards", DEFAULT_BOARDS.to_string())])?;
+    }
+
+    let boards: Vec<String> = read_boards(config)?;
+    for board in &boards {
+        board_storage::initialize(config, board)?;
+    }
+
+    return Ok(boards);
+}
+
+pub(crate) fn read_boards(config: &Config) -> Result<Vec<String>, Error> {
+    let data: HashMap<String, String> = common::read_config(&config.get_root().join(CONFIG_FILE))?;
+    let boards: &String = data.get("boards").ok_or(Error::new(
+        ErrorKind::InvalidData,
+        "site config has no board list",
+    ))?;
+
+    let mut board_list: Vec<String> = Vec::new();
+    for board in boards
+        .split(',')
+        .map(str::trim)
+        .filter(|board| !board.is_empty())
+    {
+        if !common::is_safe_name(board) {
+            return Err(common::unsafe_name(board));
+        }
+        board_list.push(board.to_string());
+    }
+
+    return Ok(board_list);
+}
+
+pub(crate) fn read_banned_images(config: &Config) -> Result<BannedImages, Error> {
+    // one line for the threshold, then one line per banned hash with the reason it was banned
+    let banned_path: PathBuf = config.get_root().join(BANNED_IMAGES_FILE);
+    if !banned_path.exists() {
+        return Ok(BannedImages::new(DEFAULT_THRESHOLD));
+    }
+
+    let data: HashMap<String, String> = common::read_config(&banned_path)?;
+    let threshold: u32 = match data.get(THRESHOLD_KEY) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "bad banned image threshold"))?,
+        None => DEFAULT_THRESHOLD,
+    };
+
+    let mut banned_images: BannedImages = BannedImages::new(threshold);
+    let mut entries: Vec<(&String, &String)> = data
+        .iter()
+        .filter(|(key, _)| *key != THRESHOLD_KEY)
+        .collect();
+    entries.sort();
+    for (hash, reason) in entries {
+        let hash: u64 = perceptual::from_hex(hash).ok_or(Error::new(
+            ErrorKind::InvalidData,
+            format!("bad banned image hash: {}", hash),
+        ))?;
+        banned_images.add_hash(hash, reason.clone());
+    }
+
+    return Ok(banned_images);
+}
+
+pub(crate) fn write_banned_images(
+    config: &Config,
+    banned_images: &BannedImages,
+) -> Result<(), Error> {
+    let mut entries: Vec<(&str, String)> = Vec::new();
+    let hashes: Vec<String> = banned_images
+        .get_hashes()
+        .iter()
+        .map(|(hash, _)| perceptual::to_hex(*hash))
+        .collect();
+
+    entries.push((THRESHOLD_KEY, banned_images.get_threshold().to_string()));
+    for (hash, (_, reason)) in hashes.iter().zip(banned_images.get_hashes()) {
+        entries.push((hash, reason.clone()));
+    }
+
+    return common::write_config(&config.get_root().join(BANNED_IMAGES_FILE), &entries);
+}
+
+pub(crate) fn read_bans(config: &Config) -> Result<Vec<Ban>, Error> {
+    let bans_path: PathBuf = config.get_root().join(BANS_FILE);
+    if !bans_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut bans: Vec<Ban> = Vec::new();
+    for record in common::read_records(&bans_path)? {
+        let [ip, reason, expires] = record.as_slice() else {
+            return Err(malformed("ban", &record.join(";")));
+        };
+        let expires: Option<SystemTime> = match expires.as_str() {
+            "" => None,
+            millis => Some(common::from_millis(
+                millis.parse().map_err(|_| malformed("ban", millis))?,
+            )),
+        };
+        bans.push(Ban::new(ip.clone(), reason.clone(), expires));
+    }
+
+    return Ok(bans);
+}
+
+pub(crate) fn write_bans(config: &Config, bans: &[Ban]) -> Result<(), Error> {
+    let records: Vec<Vec<String>> = bans
+        .iter()
+        .map(|ban| {
+            vec![
+                ban.get_ip().clone(),
+                ban.get_reason().clone(),
+                ban.get_expires()
+                    .as_ref()
+                    .map(|expires| common::to_millis(expires).to_string())
+                    .unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    return common::write_records(&config.get_root().join(BANS_FILE), &records);
+}
+
+pub(crate) fn read_api_tokens(config: &Config) -> Result<Vec<ApiToken>, Error> {
+    let tokens_path: PathBuf = config.get_root().join(API_TOKENS_FILE);
+    if !tokens_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut tokens: Vec<ApiToken> = Vec::new();
+    for record in common::read_records(&tokens_path)? {
+        let [name, hash, scopes, rate_limit] = record.as_slice() else {
+            return Err(malformed("API token", &record.join(";")));
+        };
+        let scopes: Vec<Scope> = scopes
+            .split(',')
+            .filter(|scope| !scope.is_empty())
+            .map(|scope| Scope::parse(scope).ok_or(malformed("API token scope", scope)))
+            .collect::<Result<Vec<Scope>, Error>>()?;
+        let rate_limit: u32 = rate_limit
+            .parse()
+            .map_err(|_| malformed("API token rate limit", rate_limit))?;
+        tokens.push(ApiToken::new(
+            name.clone(),
+            hash.clone(),
+            scopes,
+            rate_limit,
+        ));
+    }
+
+    return Ok(tokens);
+}
+
+pub(crate) fn write_api_tokens(config: &Config, tokens: &[ApiToken]) -> Result<(), Error> {
+    let records: Vec<Vec<String>> = tokens
+        .iter()
+        .map(|token| {
+            vec![
+                token.get_name().clone(),
+                token.get_hash().clone(),
+                token
+                    .get_scopes()
+                    .iter()
+                    .map(Scope::get_name)
+                    .collect::<Vec<&str>>()
+                    .join(","),
+                token.get_rate_limit().to_string(),
+            ]
+        })
+        .collect();
+
+    return common::write_records(&config.get_root().join(API_TOKENS_FILE), &records);
+}
+
+fn malformed(what: &str, value: &str) -> Error {
+    return Error::new(
+        ErrorKind::InvalidData,
+        format!("bad {} in site storage: {}", what, value),
+    );
+}
+
+pub(crate) fn read_secret(
+    config: &Config,
+    name: &str,
+) -> Result<Option<(Vec<u8>, SystemTime)>, Error> {
+    // a named secret and when it was made, None until it's first made
+    let data: HashMap<String, String> = read_secrets(config)?;
+    let malformed = || Error::new(ErrorKind::InvalidData, format!("bad {} secret", name));
+
+    let secret: &String = match data.get(&format!("{}_secret", name)) {
+        Some(secret) => secret,
+        None => return Ok(None),
+    };
+    let secret: Vec<u8> = hex::decode(secret).map_err(|_| malformed())?;
+    let created: u64 = data
+        .get(&format!("{}_created", name))
+        .ok_or_else(malformed)?
+        .parse()
+        .map_err(|_| malformed())?;
+
+    return Ok(Some((secret, common::from_millis(created))));
+}
+
+pub(crate) fn write_secret(
+    config: &Config,
+    name: &str,
+    secret: &[u8],
+    created: &SystemTime,
+) -> Result<(), Error> {
+    // every other secret is kept as it was
+    let mut data: HashMap<String, String> = read_secrets(config)?;
+    data.insert(format!("{}_secret", name), hex::encode(secret));
+    data.insert(
+        format!("{}_created", name),
+        common::to_millis(created).to_string(),
+    );
+
+    let mut entries: Vec<(&str, String)> = data
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.clone()))
+        .collect();
+    entries.sort();
+
+    return common::write_config(&config.get_root().join(SECRETS_FILE), &entries);
+}
+
+pub(crate) fn read_template(config: &Config, name: &str) -> Result<Option<String>, Error> {
+    // an operator's replacement for a template, None to use the built in one
+    if !common::is_safe_name(name) {
+        return Err(common::unsafe_name(name));
+    }
+
+    let template_path: PathBuf = config
+        .get_root()
+        .join(TEMPLATE_DIR)
+        .join(format!("{}.{}", name, TEMPLATE_EXTENSION));
+    return match read_to_string(template_path) {
+        Ok(template) => Ok(Some(template)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    };
+}
+
+fn read_secrets(config: &Config) -> Result<HashMap<String, String>, Error> {
+    let secrets_path: PathBuf = config.get_root().join(SECRETS_FILE);
+    if !secrets_path.exists() {
+        return Ok(HashMap::new());
+    }
+    return common::read_config(&secrets_path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::fixtures::TestRoot;
+    use std::fs::{create_dir_all, write};
+
+    fn test_config(name: &str) -> TestRoot {
+        return TestRoot::new("site", name);
+    }
+
+    #[test]
+    fn test_initialize() {
+        let config = test_config("initialize");
+        let boards = initialize(&config).unwrap();
+
+        assert_eq!(boards, vec!["\u{3B1}".to_string(), "test".to_string()]);
+        for board in &boards {
+            assert!(config.get_board_path(board).is_dir());
+        }
+        assert_eq!(initialize(&config).unwrap(), boards);
+    }
+
+    #[test]
+    fn test_read_write_banned_images() {
+        let config = test_config("banned_images");
+        config.connect().unwrap();
+        assert_eq!(
+            read_banned_images(&config).unwrap(),
+            BannedImages::new(DEFAULT_THRESHOLD)
+        );
+
+        let mut banned_images = BannedImages::new(4);
+        banned_images.add_hash(0x0123_4567_89AB_CDEF, "spam".to_string());
+        banned_images.add_hash(0xFF, "illegal: do not repost".to_string());
+        write_banned_images(&config, &banned_images).unwrap();
+
+        let read = read_banned_images(&config).unwrap();
+        assert_eq!(read.get_threshold(), 4);
+        assert_eq!(read.get_hashes().len(), 2);
+        assert_eq!(read.find_match(0xFF).unwrap().1, "illegal: do not repost");
+        assert_eq!(read.find_match(0x0123_4567_89AB_CDEF).unwrap().1, "spam");
+    }
+
+    #[test]
+    fn test_read_write_bans() {
+        let config = test_config("bans");
+        config.connect().unwrap();
+        assert!(read_bans(&config).unwrap().is_empty());
+
+        let bans = vec![
+            Ban::new("192.0.2.1".to_string(), "spam; again".to_string(), None),
+            Ban::new(
+                "2001:db8::1".to_string(),
+                "rule 3\nand 4".to_string(),
+                Some(common::from_millis(1_700_000_000_000)),
+            ),
+        ];
+        write_bans(&config, &bans).unwrap();
+        assert_eq!(read_bans(&config).unwrap(), bans);
+    }
+
+    #[test]
+    fn test_read_write_api_tokens() {
+        let config = test_config("api_tokens");
+        config.connect().unwrap();
+        assert!(read_api_tokens(&config).unwrap().is_empty());
+
+        let tokens = vec![
+            ApiToken::new("bot".to_string(), "ab".repeat(32), vec![Scope::Post], 30),
+            ApiToken::new(
+                "mods".to_string(),
+                "cd".repeat(32),
+                vec![Scope::Lock, Scope::Delete, Scope::Ban],
+                0,
+            ),
+            ApiToken::new("none".to_string(), "ef".repeat(32), Vec::new(), 1),
+        ];
+        write_api_tokens(&config, &tokens).unwrap();
+        assert_eq!(read_api_tokens(&config).unwrap(), tokens);
+
+        write(
+            config.get_root().join(API_TOKENS_FILE),
+            "bot;ab;post,fly;1;\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            read_api_tokens(&config).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_read_boards_unsafe() {
+        let config = test_config("unsafe");
+        config.connect().unwrap();
+        write(config.get_root().join(CONFIG_FILE), "boards: a, ../b\r\n").unwrap();
+
+        assert_eq!(
+            read_boards(&config).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_read_write_secret() {
+        let config = test_config("secret");
+        config.connect().unwrap();
+        assert_eq!(read_secret(&config, "poster_id").unwrap(), None);
+
+        let created = common::from_millis(1_700_000_000_000);
+        write_secret(&config, "poster_id", b"\x00secret\xff", &created).unwrap();
+        write_secret(&config, "tripcode", b"other", &created).unwrap();
+        assert_eq!(
+            read_secret(&config, "poster_id").unwrap(),
+            Some((b"\x00secret\xff".to_vec(), created))
+        );
+        assert_eq!(
+            read_secret(&config, "tripcode").unwrap(),
+            Some((b"other".to_vec(), created))
+        );
+    }
+
+    #[test]
+    fn test_read_template() {
+        let config = test_config("template");
+        config.connect().unwrap();
+        assert_eq!(read_template(&config, "layout").unwrap(), None);
+
+        create_dir_all(config.get_root().join(TEMPLATE_DIR)).unwrap();
+        write(
+            config.get_root().join(TEMPLATE_DIR).join("layout.html"),
+            "<p>{{title}}</p>",
+        )
+        .unwrap();
+        assert_eq!(
+            read_template(&config, "layout").unwrap(),
+            Some("<p>{{title}}</p>".to_string())
+        );
+        assert!(read_template(&config, "../layout").is_err());
+    }
+}
@@ -0,0 +1,283 @@
+// Thread storage. Each thread is a folder named after its parent post's ID in base 36, inside the
+// board's threads folder.
+// |
+// |-boards
+// |-|-board_a
+// |-|-|-threads
+// |-|-|-|-c
+// |-|-|-|-|-meta.txt      key: value pairs for the name, status and last bump
+// |-|-|-|-|-children.txt  one record per post in the thread, the parent first
+// |-|-|-redirects.txt       one record per post moved off the board: old post;board;new post;
+use super::common;
+use super::config::Config;
+use super::structs::thread::Thread;
+use std::collections::HashMap;
+use std::fs::{create_dir_all, remove_dir_all};
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+const THREAD_DIR: &str = "threads";
+const META_FILE: &str = "meta.txt";
+const CHILDREN_FILE: &str = "children.txt";
+const REDIRECT_FILE: &str = "redirects.txt";
+
+fn get_thread_path(config: &Config, board: &str, thread: u64) -> PathBuf {
+    return config
+        .get_board_path(board)
+        .join(THREAD_DIR)
+        .join(common::to_base36(thread));
+}
+
+pub(crate) fn initialize(config: &Config, board: &str) -> Result<(), Error> {
+    return create_dir_all(config.get_board_path(board).join(THREAD_DIR));
+}
+
+pub(crate) fn thread_exists(config: &Config, board: &str, thread: u64) -> bool {
+    return get_thread_path(config, board, thread)
+        .join(META_FILE)
+        .exists();
+}
+
+pub(crate) fn write_thread(config: &Config, board: &str, thread: &Thread) -> Result<(), Error> {
+    let thread_path: PathBuf = get_thread_path(config, board, thread.get_parent());
+    create_dir_all(&thread_path)?;
+
+    let entries: Vec<(&str, String)> = vec![
+        ("name", common::escape_field(thread.get_name())),
+        ("locked", thread.get_locked().to_string()),
+        ("deleted", thread.get_deleted().to_string()),
+        ("cyclic", thread.get_cyclic().to_string()),
+        (
+            "bumped",
+            thread
+                .get_bumped()
+                .as_ref()
+                .map(|bumped| common::to_millis(bumped).to_string())
+                .unwrap_or_default(),
+        ),
+    ];
+    common::write_config(&thread_path.join(META_FILE), &entries)?;
+
+    let children: Vec<Vec<String>> = thread
+        .get_children()
+        .iter()
+        .map(|child| vec![child.to_string()])
+        .collect();
+    common::write_records(&thread_path.join(CHILDREN_FILE), &children)?;
+
+    return Ok(());
+}
+
+pub(crate) fn read_thread(config: &Config, board: &str, thread: u64) -> Result<Thread, Error> {
+    let thread_path: PathBuf = get_thread_path(config, board, thread);
+    let meta: HashMap<String, String> = common::read_config(&thread_path.join(META_FILE))?;
+    let name: String = common::unescape_field(meta.get("name").ok_or(malformed("name"))?);
+
+    let mut read_thread: Thread = Thread::new(name, thread);
+    let children: Vec<Vec<String>> = common::read_records(&thread_path.join(CHILDREN_FILE))?;
+    if children.first().map(Vec::as_slice) != Some(&[thread.to_string()]) {
+        return Err(malformed("parent"));
+    }
+
+    // children go back in before the thread is locked, and were already checked when posted
+    for record in &children[1..] {
+        let child: u64 = match record.as_slice() {
+            [child] => child.parse().map_err(|_| malformed("child"))?,
+            _ => return Err(malformed("child")),
+        };
+        read_thread
+            .add_child(child, u16::MAX)
+            .map_err(|error| Error::new(ErrorKind::InvalidData, error.to_string()))?;
+    }
+
+    if parse_flag(&meta, "locked")? {
+        read_thread.lock();
+    }
+    if parse_flag(&meta, "deleted")? {
+        read_thread.delete();
+    }
+    // threads written before cyclic threads existed aren't cyclic
+    if meta.contains_key("cyclic") {
+        read_thread.modify_cyclic(parse_flag(&meta, "cyclic")?);
+    }
+    // and threads from before bump times were kept have none
+    match meta.get("bumped").map(String::as_str) {
+        None | Some("") => (),
+        Some(bumped) => {
+            let bumped: u64 = bumped.parse().map_err(|_| malformed("bumped"))?;
+            read_thread.modify_bumped(common::from_millis(bumped));
+        }
+    }
+
+    return Ok(read_thread);
+}
+
+pub(crate) fn remove_thread(config: &Config, board: &str, thread: u64) -> Result<(), Error> {
+    // only the thread itself, its posts are removed separately
+    return remove_dir_all(get_thread_path(config, board, thread));
+}
+
+pub(crate) fn read_redirects(
+    config: &Config,
+    board: &str,
+) -> Result<HashMap<u64, (String, u64)>, Error> {
+    // where every post that was moved off the board went, as board and post ID
+    let redirect_path: PathBuf = config.get_board_path(board).join(REDIRECT_FILE);
+    let mut redirects: HashMap<u64, (String, u64)> = HashMap::new();
+
+    if !redirect_path.exists() {
+        return Ok(redirects);
+    }
+
+    for record in common::read_records(&redirect_path)? {
+        let [old, to_board, new] = record.as_slice() else {
+            return Err(malformed("redirect"));
+        };
+        let old: u64 = old.parse().map_err(|_| malformed("redirect"))?;
+        let new: u64 = new.parse().map_err(|_| malformed("redirect"))?;
+        redirects.insert(old, (to_board.clone(), new));
+    }
+
+    return Ok(redirects);
+}
+
+pub(crate) fn add_redirects(
+    config: &Config,
+    board: &str,
+    moved: &[(u64, String, u64)],
+) -> Result<(), Error> {
+    let mut redirects: HashMap<u64, (String, u64)> = read_redirects(config, board)?;
+    for (old, to_board, new) in moved {
+        redirects.insert(*old, (to_board.clone(), *new));
+    }
+
+    let mut records: Vec<Vec<String>> = redirects
+        .into_iter()
+        .map(|(old, (to_board, new))| vec![old.to_string(), to_board, new.to_string()])
+        .collect();
+    records.sort_by_key(|record| record[0].parse::<u64>().unwrap_or_default());
+
+    return common::write_records(&config.get_board_path(board).join(REDIRECT_FILE), &records);
+}
+
+fn parse_flag(meta: &HashMap<String, String>, key: &str) -> Result<bool, Error> {
+    return meta
+        .get(key)
+        .ok_or(malformed(key))?
+        .parse()
+        .map_err(|_| malformed(key));
+}
+
+fn malformed(what: &str) -> Error {
+    return Error::new(
+        ErrorKind::InvalidData,
+        format!("malformed {} in thread", what),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::fixtures::TestRoot;
+    use std::fs::{read_to_string, write};
+
+    const BOARD: &str = "test";
+
+    fn test_config(name: &str) -> TestRoot {
+        let config = TestRoot::new("thread", name);
+        initialize(&config, BOARD).unwrap();
+        return config;
+    }
+
+    #[test]
+    fn test_write_read_thread() {
+        let config = test_config("write_read");
+        let mut thread = Thread::new("a;thread\nname".to_string(), 40);
+        thread.add_child(41, 10).unwrap();
+        thread.add_child(45, 10).unwrap();
+        thread.lock();
+        thread.modify_cyclic(true);
+        thread.modify_bumped(common::from_millis(1234));
+
+        assert!(!thread_exists(&config, BOARD, 40));
+        write_thread(&config, BOARD, &thread).unwrap();
+        assert!(thread_exists(&config, BOARD, 40));
+
+        let read = read_thread(&config, BOARD, 40).unwrap();
+        assert_eq!(read.get_name(), thread.get_name());
+        assert_eq!(read.get_children(), thread.get_children());
+        assert!(read.get_locked());
+        assert!(!read.get_deleted());
+        assert!(read.get_cyclic());
+        assert_eq!(*read.get_bumped(), Some(common::from_millis(1234)));
+    }
+
+    #[test]
+    fn test_read_old_thread() {
+        // from before cyclic threads and bump times
+        let config = test_config("old");
+        let mut thread = Thread::new("name".to_string(), 40);
+        thread.modify_cyclic(true);
+        thread.modify_bumped(common::from_millis(1234));
+        write_thread(&config, BOARD, &thread).unwrap();
+
+        let meta_path = get_thread_path(&config, BOARD, 40).join(META_FILE);
+        let meta: String = read_to_string(&meta_path).unwrap();
+        let meta: Vec<&str> = meta
+            .lines()
+            .filter(|line| !line.starts_with("cyclic") && !line.starts_with("bumped"))
+            .collect();
+        write(&meta_path, meta.join("\r\n")).unwrap();
+
+        let read = read_thread(&config, BOARD, 40).unwrap();
+        assert!(!read.get_cyclic());
+        assert_eq!(*read.get_bumped(), None);
+    }
+
+    #[test]
+    fn test_read_thread_out_of_order() {
+        let config = test_config("out_of_order");
+        write_thread(&config, BOARD, &Thread::new("name".to_string(), 40)).unwrap();
+        write(
+            get_thread_path(&config, BOARD, 40).join(CHILDREN_FILE),
+            "40;\r\n42;\r\n41;\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_thread(&config, BOARD, 40).err().unwrap().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_remove_thread() {
+        let config = test_config("remove");
+        write_thread(&config, BOARD, &Thread::new("name".to_string(), 40)).unwrap();
+
+        remove_thread(&config, BOARD, 40).unwrap();
+        assert!(!thread_exists(&config, BOARD, 40));
+    }
+
+    #[test]
+    fn test_redirects() {
+        let config = test_config("redirects");
+        assert!(read_redirects(&config, BOARD).unwrap().is_empty());
+
+        add_redirects(&config, BOARD, &[(40, "other".to_string(), 3)]).unwrap();
+        add_redirects(&config, BOARD, &[(41, "other".to_string(), 4)]).unwrap();
+        let redirects = read_redirects(&config, BOARD).unwrap();
+        assert_eq!(redirects.len(), 2);
+        assert_eq!(redirects.get(&40), Some(&("other".to_string(), 3)));
+        assert_eq!(redirects.get(&41), Some(&("other".to_string(), 4)));
+    }
+
+    #[test]
+    fn test_read_thread_missing() {
+        let config = test_config("missing");
+        assert_eq!(
+            read_thread(&config, BOARD, 1).err().unwrap().kind(),
+            ErrorKind::NotFound
+        );
+    }
+}
@@ -4,3 +4,4 @@
 
 mod file_system2;
 use super::structs;
+pub use file_system2::*;
@@ -0,0 +1,152 @@
+use std::fmt;
+use std::string::String;
+use std::vec::Vec;
+
+// what an API token is allowed to do. Each scope is one of the API's actions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Scope {
+    Post,
+    Lock,
+    Sticky,
+    Delete,
+    Ban,
+}
+
+pub(crate) const ALL_SCOPES: [Scope; 5] = [
+    Scope::Post,
+    Scope::Lock,
+    Scope::Sticky,
+    Scope::Delete,
+    Scope::Ban,
+];
+
+impl Scope {
+    pub fn parse(name: &str) -> Option<Scope> {
+        return ALL_SCOPES
+            .iter()
+            .find(|scope| scope.get_name() == name)
+            .copied();
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        return match self {
+            Scope::Post => "post",
+            Scope::Lock => "lock",
+            Scope::Sticky => "sticky",
+            Scope::Delete => "delete",
+            Scope::Ban => "ban",
+        };
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.get_name());
+    }
+}
+
+// a client of the write API. only the token's hash is kept, the token itself is shown once when
+// it's made
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ApiToken {
+    name: String,
+    hash: String, // SHA-256 of the token, hex encoded
+    scopes: Vec<Scope>,
+    rate_limit: u32, // requests a minute, 0 for no limit
+}
+
+impl ApiToken {
+    pub fn new(name: String, hash: String, scopes: Vec<Scope>, rate_limit: u32) -> ApiToken {
+        let new_token: ApiToken = ApiToken {
+            name,
+            hash,
+            scopes,
+            rate_limit,
+        };
+
+        return new_token;
+    }
+
+    pub fn get_name(&self) -> &String {
+        return &self.name;
+    }
+
+    pub fn get_hash(&self) -> &String {
+        return &self.hash;
+    }
+
+    pub fn get_scopes(&self) -> &Vec<Scope> {
+        return &self.scopes;
+    }
+
+    pub fn get_rate_limit(&self) -> u32 {
+        return self.rate_limit;
+    }
+
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        return self.scopes.contains(&scope);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAME: &str = "archiver";
+    const HASH: &str = "abcdef";
+    const RATE_LIMIT: u32 = 60;
+
+    fn struct_init() -> ApiToken {
+        let a_token = ApiToken::new(
+            NAME.to_string(),
+            HASH.to_string(),
+            vec![Scope::Post, Scope::Lock],
+            RATE_LIMIT,
+        );
+        return a_token;
+    }
+
+    #[test]
+    fn test_init() {
+        struct_init();
+    }
+
+    #[test]
+    fn test_attributes_direct() {
+        let a = struct_init();
+
+        assert_eq!(a.name, NAME);
+        assert_eq!(a.hash, HASH);
+        assert_eq!(a.scopes, vec![Scope::Post, Scope::Lock]);
+        assert_eq!(a.rate_limit, RATE_LIMIT);
+    }
+
+    #[test]
+    fn test_getters() {
+        let a = struct_init();
+
+        assert_eq!(*a.get_name(), a.name);
+        assert_eq!(*a.get_hash(), a.hash);
+        assert_eq!(*a.get_scopes(), a.scopes);
+        assert_eq!(a.get_rate_limit(), a.rate_limit);
+    }
+
+    #[test]
+    fn test_has_scope() {
+        let a = struct_init();
+
+        assert!(a.has_scope(Scope::Post));
+        assert!(a.has_scope(Scope::Lock));
+        assert!(!a.has_scope(Scope::Ban));
+    }
+
+    #[test]
+    fn test_scope_names() {
+        for scope in ALL_SCOPES {
+            assert_eq!(Scope::parse(scope.get_name()), Some(scope));
+            assert_eq!(scope.to_string(), scope.get_name());
+        }
+        assert_eq!(Scope::parse("Post"), None);
+        assert_eq!(Scope::parse(""), None);
+    }
+}
@@ -0,0 +1,187 @@
+use std::string::String;
+
+// metadata for a single uploaded file. The file data itself lives in storage under `stored_name`,
+// which keeps posts small enough to hold in memory.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Attachment {
+    original_name: String, // filename as uploaded by the poster
+    stored_name: String,   // filename in storage, unique per board
+    mime: String,
+    size: u64,                      // bytes
+    dimensions: Option<(u32, u32)>, // width, height; only present for images
+    hash: String,                   // sha256 of the file data, hex encoded
+    perceptual_hash: Option<u64>,   // only present for images
+    spoiler: bool,
+}
+
+impl Attachment {
+    pub fn new(
+        original_name: String,
+        stored_name: String,
+        mime: String,
+        size: u64,
+        dimensions: Option<(u32, u32)>,
+        hash: String,
+        spoiler: bool,
+    ) -> Attachment {
+        let new_attachment: Attachment = Attachment {
+            original_name,
+            stored_name,
+            mime,
+            size,
+            dimensions,
+            hash,
+            perceptual_hash: None,
+            spoiler,
+        };
+
+        return new_attachment;
+    }
+
+    pub fn get_original_name(&self) -> &String {
+        return &self.original_name;
+    }
+
+    pub fn get_stored_name(&self) -> &String {
+        return &self.stored_name;
+    }
+
+    pub fn get_mime(&self) -> &String {
+        return &self.mime;
+    }
+
+    pub fn get_size(&self) -> u64 {
+        return self.size;
+    }
+
+    pub fn get_dimensions(&self) -> Option<(u32, u32)> {
+        return self.dimensions;
+    }
+
+    pub fn get_hash(&self) -> &String {
+        return &self.hash;
+    }
+
+    pub fn get_perceptual_hash(&self) -> Option<u64> {
+        return self.perceptual_hash;
+    }
+
+    pub fn get_spoiler(&self) -> bool {
+        return self.spoiler;
+    }
+
+    pub fn modify_perceptual_hash(&mut self, new_perceptual_hash: Option<u64>) {
+        self.perceptual_hash = new_perceptual_hash;
+    }
+
+    pub fn modify_stored_name(&mut self, new_stored_name: String) {
+        // only when the file itself is moved in storage
+        self.stored_name = new_stored_name;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL_NAME: &str = "cat.png";
+    const STORED_NAME: &str = "1700000000000.png";
+    const MIME: &str = "image/png";
+    const SIZE: u64 = 1024;
+    const DIMENSIONS: Option<(u32, u32)> = Some((640, 480));
+    const HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    fn struct_init() -> Attachment {
+        let an_attachment = Attachment::new(
+            ORIGINAL_NAME.to_string(),
+            STORED_NAME.to_string(),
+            MIME.to_string(),
+            SIZE,
+            DIMENSIONS,
+            HASH.to_string(),
+            false,
+        );
+        return an_attachment;
+    }
+
+    #[test]
+    fn test_init() {
+        struct_init();
+    }
+
+    #[test]
+    fn test_attributes_direct() {
+        let a = struct_init();
+
+        assert_eq!(a.original_name, ORIGINAL_NAME);
+        assert_eq!(a.stored_name, STORED_NAME);
+        assert_eq!(a.mime, MIME);
+        assert_eq!(a.size, SIZE);
+        assert_eq!(a.dimensions, DIMENSIONS);
+        assert_eq!(a.hash, HASH);
+        assert_eq!(a.perceptual_hash, None);
+        assert!(!a.spoiler);
+    }
+
+    #[test]
+    fn test_get_original_name() {
+        let a = struct_init();
+        assert_eq!(a.get_original_name(), ORIGINAL_NAME);
+    }
+
+    #[test]
+    fn test_get_stored_name() {
+        let a = struct_init();
+        assert_eq!(a.get_stored_name(), STORED_NAME);
+    }
+
+    #[test]
+    fn test_get_mime() {
+        let a = struct_init();
+        assert_eq!(a.get_mime(), MIME);
+    }
+
+    #[test]
+    fn test_get_size() {
+        let a = struct_init();
+        assert_eq!(a.get_size(), SIZE);
+    }
+
+    #[test]
+    fn test_get_dimensions() {
+        let a = struct_init();
+        assert_eq!(a.get_dimensions(), DIMENSIONS);
+    }
+
+    #[test]
+    fn test_get_hash() {
+        let a = struct_init();
+        assert_eq!(a.get_hash(), HASH);
+    }
+
+    #[test]
+    fn test_get_perceptual_hash() {
+        let a = struct_init();
+        assert_eq!(a.get_perceptual_hash(), None);
+    }
+
+    #[test]
+    fn test_get_spoiler() {
+        let a = struct_init();
+        assert!(!a.get_spoiler());
+    }
+
+    #[test]
+    fn test_modify_perceptual_hash() {
+        let mut a = struct_init();
+        a.modify_perceptual_hash(Some(42));
+        assert_eq!(a.get_perceptual_hash(), Some(42));
+    }
+
+    #[test]
+    fn test_modify_stored_name() {
+        let mut a = struct_init();
+        a.modify_stored_name("13-0.png".to_string());
+        assert_eq!(a.get_stored_name(), "13-0.png");
+    }
+}
@@ -0,0 +1,93 @@
+use std::string::String;
+use std::time::SystemTime;
+
+// a banned IP. Bans are site wide, and stop the IP from posting anywhere until they expire.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Ban {
+    ip: String,
+    reason: String,              // shown to the banned poster
+    expires: Option<SystemTime>, // None for a permanent ban
+}
+
+impl Ban {
+    pub fn new(ip: String, reason: String, expires: Option<SystemTime>) -> Ban {
+        let new_ban: Ban = Ban {
+            ip,
+            reason,
+            expires,
+        };
+
+        return new_ban;
+    }
+
+    pub fn get_ip(&self) -> &String {
+        return &self.ip;
+    }
+
+    pub fn get_reason(&self) -> &String {
+        return &self.reason;
+    }
+
+    pub fn get_expires(&self) -> &Option<SystemTime> {
+        return &self.expires;
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        return match self.expires {
+            Some(expires) => expires <= now,
+            None => false,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const IP: &str = "192.0.2.1";
+    const REASON: &str = "spam";
+
+    fn struct_init() -> Ban {
+        let a_ban = Ban::new(
+            IP.to_string(),
+            REASON.to_string(),
+            Some(SystemTime::UNIX_EPOCH),
+        );
+        return a_ban;
+    }
+
+    #[test]
+    fn test_init() {
+        struct_init();
+    }
+
+    #[test]
+    fn test_attributes_direct() {
+        let a = struct_init();
+
+        assert_eq!(a.ip, IP);
+        assert_eq!(a.reason, REASON);
+        assert_eq!(a.expires, Some(SystemTime::UNIX_EPOCH));
+    }
+
+    #[test]
+    fn test_getters() {
+        let a = struct_init();
+
+        assert_eq!(*a.get_ip(), a.ip);
+        assert_eq!(*a.get_reason(), a.reason);
+        assert_eq!(*a.get_expires(), a.expires);
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let a = struct_init();
+        let permanent = Ban::new(IP.to_string(), REASON.to_string(), None);
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+
+        assert!(a.is_expired(SystemTime::UNIX_EPOCH));
+        assert!(a.is_expired(now));
+        assert!(!permanent.is_expired(now));
+    }
+}
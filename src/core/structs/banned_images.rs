@@ -0,0 +1,126 @@
+use std::string::String;
+use std::vec::Vec;
+
+// the site wide list of banned images, kept as perceptual hashes so re-encoded copies are caught too.
+// an upload matches a banned image when their hashes differ by at most `threshold` bits.
+pub(crate) const DEFAULT_THRESHOLD: u32 = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct BannedImages {
+    threshold: u32,
+    hashes: Vec<(u64, String)>, // perceptual hash, and the reason it was banned
+}
+
+impl BannedImages {
+    pub fn new(threshold: u32) -> BannedImages {
+        let banned_images = BannedImages {
+            threshold,
+            hashes: Vec::new(),
+        };
+
+        return banned_images;
+    }
+
+    pub fn get_threshold(&self) -> u32 {
+        return self.threshold;
+    }
+
+    pub fn get_hashes(&self) -> &Vec<(u64, String)> {
+        return &self.hashes;
+    }
+
+    pub fn add_hash(&mut self, hash: u64, reason: String) -> bool {
+        if self.hashes.iter().any(|(banned, _)| *banned == hash) {
+            return false;
+        }
+        self.hashes.push((hash, reason));
+        return true;
+    }
+
+    pub fn remove_hash(&mut self, hash: u64) -> bool {
+        let length: usize = self.hashes.len();
+        self.hashes.retain(|(banned, _)| *banned != hash);
+        return self.hashes.len() != length;
+    }
+
+    pub fn find_match(&self, hash: u64) -> Option<&(u64, String)> {
+        // the closest banned hash within the threshold, if there is one
+        return self
+            .hashes
+            .iter()
+            .map(|entry| ((entry.0 ^ hash).count_ones(), entry))
+            .filter(|(distance, _)| *distance <= self.threshold)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, entry)| entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: u64 = 0xF0F0_F0F0_F0F0_F0F0;
+    const REASON: &str = "spam";
+
+    fn struct_init() -> BannedImages {
+        let banned_images = BannedImages::new(DEFAULT_THRESHOLD);
+        return banned_images;
+    }
+
+    #[test]
+    fn test_init() {
+        struct_init();
+    }
+
+    #[test]
+    fn test_attributes_direct() {
+        let a = struct_init();
+
+        assert_eq!(a.threshold, DEFAULT_THRESHOLD);
+        assert!(a.hashes.is_empty());
+    }
+
+    #[test]
+    fn test_get_threshold() {
+        let a = struct_init();
+        assert_eq!(a.get_threshold(), a.threshold);
+    }
+
+    #[test]
+    fn test_get_hashes() {
+        let mut a = struct_init();
+        a.hashes.push((HASH, REASON.to_string()));
+        assert_eq!(*a.get_hashes(), a.hashes);
+    }
+
+    #[test]
+    fn test_add_hash() {
+        let mut a = struct_init();
+
+        assert!(a.add_hash(HASH, REASON.to_string()));
+        assert!(!a.add_hash(HASH, "again".to_string()));
+        assert_eq!(*a.get_hashes(), vec![(HASH, REASON.to_string())]);
+    }
+
+    #[test]
+    fn test_remove_hash() {
+        let mut a = struct_init();
+        a.add_hash(HASH, REASON.to_string());
+
+        assert!(a.remove_hash(HASH));
+        assert!(!a.remove_hash(HASH));
+        assert!(a.get_hashes().is_empty());
+    }
+
+    #[test]
+    fn test_find_match() {
+        let mut a = struct_init();
+        a.add_hash(HASH, REASON.to_string());
+        a.add_hash(HASH ^ 0b11, "closer".to_string());
+
+        assert_eq!(a.find_match(HASH ^ 0b1).unwrap().0, HASH);
+        assert_eq!(a.find_match(HASH ^ 0b111).unwrap().1, "closer");
+        assert!(a.find_match(HASH ^ 0xFFF00).is_none());
+        assert!(a.find_match(!HASH).is_none());
+    }
+}
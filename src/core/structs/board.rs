@@ -1,13 +1,16 @@
-use std::collections::HashSet;
+use super::board_config::BoardConfig;
+use super::sticky::Sticky;
 use std::string::String;
+use std::time::SystemTime;
 
 pub(crate) struct Board {
     name: String,
     description: String,
-    active: Vec<u64>,     // active threads, as parent post IDs
-    archive: Vec<u64>,    // archive threads, as parent post IDs
-    sticky: HashSet<u64>, // should contain a subset of threads
-    count: u64,           // running total of all posts
+    active: Vec<u64>,    // active threads, as parent post IDs
+    archive: Vec<u64>,   // archive threads, as parent post IDs
+    sticky: Vec<Sticky>, // highest priority first, ties in the order they were stickied
+    count: u64,          // running total of all posts
+    config: BoardConfig,
 }
 
 impl Board {
@@ -17,8 +20,9 @@ impl Board {
             description,
             active: Vec::new(),
             archive: Vec::new(),
-            sticky: HashSet::new(),
+            sticky: Vec::new(),
             count: 0,
+            config: BoardConfig::new(),
         };
 
         return board;
@@ -40,20 +44,38 @@ impl Board {
         return &self.archive;
     }
 
-    pub fn get_sticky(&self) -> &HashSet<u64> {
+    pub fn get_sticky(&self) -> &Vec<Sticky> {
         return &self.sticky;
     }
 
+    pub fn get_thread_order(&self, now: SystemTime) -> Vec<u64> {
+        // active threads in the order they're shown: live stickies first, then everything else
+        let mut order: Vec<u64> = self
+            .sticky
+            .iter()
+            .filter(|sticky| !sticky.is_expired(now) && self.active.contains(&sticky.get_parent()))
+            .map(Sticky::get_parent)
+            .collect();
+        let stickied: Vec<u64> = order.clone();
+        order.extend(
+            self.active
+                .iter()
+                .filter(|parent| !stickied.contains(parent)),
+        );
+
+        return order;
+    }
+
     pub fn get_count(&self) -> u64 {
         return self.count;
     }
 
-    pub fn modify_name(&mut self, new_name: String) {
-        self.name = new_name;
+    pub fn get_config(&self) -> &BoardConfig {
+        return &self.config;
     }
 
-    pub fn modify_description(&mut self, new_description: String) {
-        self.description = new_description;
+    pub fn modify_config(&mut self, new_config: BoardConfig) {
+        self.config = new_config;
     }
 
     pub fn add_active(&mut self, parent: u64) {
@@ -64,8 +86,66 @@ impl Board {
         self.archive.push(parent);
     }
 
-    pub fn add_sticky(&mut self, parent: u64) -> bool {
-        return self.sticky.insert(parent);
+    pub fn add_sticky(&mut self, parent: u64, priority: u16, expires: Option<SystemTime>) -> bool {
+        // goes after every sticky with the same priority or higher
+        if self
+            .sticky
+            .iter()
+            .any(|sticky| sticky.get_parent() == parent)
+        {
+            return false;
+        }
+
+        let index: usize = self
+            .sticky
+            .iter()
+            .position(|sticky| sticky.get_priority() < priority)
+            .unwrap_or(self.sticky.len());
+        self.sticky
+            .insert(index, Sticky::new(parent, priority, expires));
+        return true;
+    }
+
+    pub fn modify_sticky(
+        &mut self,
+        parent: u64,
+        priority: u16,
+        expires: Option<SystemTime>,
+    ) -> bool {
+        // changing a priority moves the sticky to the end of its new priority
+        if !self.remove_sticky(parent) {
+            return false;
+        }
+        return self.add_sticky(parent, priority, expires);
+    }
+
+    pub fn reorder_stickies(&mut self, order: &[u64]) -> bool {
+        // puts the stickies in exactly the given order, which has to list every one of them.
+        // priorities are renumbered to match, the first gets the highest
+        let mut given: Vec<u64> = order.to_vec();
+        let mut current: Vec<u64> = self.sticky.iter().map(Sticky::get_parent).collect();
+        given.sort();
+        given.dedup();
+        current.sort();
+        if given != current || order.len() != current.len() || order.len() > u16::MAX as usize {
+            return false;
+        }
+
+        let old: Vec<Sticky> = std::mem::take(&mut self.sticky);
+        for (index, parent) in order.iter().enumerate() {
+            let expires: Option<SystemTime> = old
+                .iter()
+                .find(|sticky| sticky.get_parent() == *parent)
+                .and_then(|sticky| *sticky.get_expires());
+            self.sticky
+                .push(Sticky::new(*parent, (order.len() - index) as u16, expires));
+        }
+        return true;
+    }
+
+    pub fn restore_count(&mut self, count: u64) {
+        // only for loading a board back out of storage
+        self.count = count;
     }
 
     pub fn increment_count(&mut self) -> u64 {
@@ -92,11 +172,57 @@ impl Board {
     }
 
     pub fn remove_sticky(&mut self, parent: u64) -> bool {
-        return self.sticky.remove(&parent);
-    }
+        let count: usize = self.sticky.len();
+        self.sticky.retain(|sticky| sticky.get_parent() != parent);
+        return self.sticky.len() != count;
+    }
+
+    pub fn remove_expired_stickies(&mut self, now: SystemTime) -> Vec<u64> {
+        let expired: Vec<u64> = self
+            .sticky
+            .iter()
+            .filter(|sticky| sticky.is_expired(now))
+            .map(Sticky::get_parent)
+            .collect();
+        self.sticky.retain(|sticky| !sticky.is_expired(now));
+
+        return expired;
+    }
+
+    pub fn bump(&mut self, parent: u64) {
+        // puts a thread at the top of the board, whether it was already active or is new
+        Board::remove_element(&mut self.active, parent);
+        self.active.insert(0, parent);
+    }
+
+    pub fn archive_overflow(&mut self, now: SystemTime) -> (Vec<u64>, Vec<u64>) {
+        // enforces the board's thread counts, returning the threads that were archived and the
+        // ones that fell off the archive. the lowest thread that isn't sticky goes first, live
+        // stickies only ever leave the board by hand and expired ones go like any other thread
+        let mut archived: Vec<u64> = Vec::new();
+        while self.active.len() > self.config.get_active_count() as usize {
+            let lowest: Option<usize> = self.active.iter().rposition(|parent| {
+                !self
+                    .sticky
+                    .iter()
+                    .any(|s| s.get_parent() == *parent && !s.is_expired(now))
+            });
+            match lowest {
+                Some(index) => {
+                    let parent: u64 = self.active.remove(index);
+                    self.archive.insert(0, parent);
+                    archived.push(parent);
+                }
+                None => break,
+            }
+        }
+
+        let mut dropped: Vec<u64> = Vec::new();
+        while self.archive.len() > self.config.get_archive_count() as usize {
+            dropped.extend(self.pop_archive());
+        }
 
-    pub fn pop_active(&mut self) -> Option<u64> {
-        return self.active.pop();
+        return (archived, dropped);
     }
 
     pub fn pop_archive(&mut self) -> Option<u64> {
@@ -107,6 +233,7 @@ impl Board {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Duration;
 
     const NAME: &str = "Test Board";
     const DESC: &str = "This is a test board.";
@@ -131,6 +258,7 @@ mod tests {
         assert_eq!(a.archive.len(), 0);
         assert_eq!(a.sticky.len(), 0);
         assert_eq!(a.count, 0);
+        assert_eq!(a.config, BoardConfig::new());
     }
 
     #[test]
@@ -149,21 +277,21 @@ mod tests {
     fn test_get_active() {
         let a = struct_init();
         assert_eq!(a.get_active().len(), 0);
-        assert_eq!(a.get_active().is_empty(), true);
+        assert!(a.get_active().is_empty());
     }
 
     #[test]
     fn test_get_archive() {
         let a = struct_init();
         assert_eq!(a.get_archive().len(), 0);
-        assert_eq!(a.get_archive().is_empty(), true);
+        assert!(a.get_archive().is_empty());
     }
 
     #[test]
     fn test_get_sticky() {
         let a = struct_init();
         assert_eq!(a.get_sticky().len(), 0);
-        assert_eq!(a.get_sticky().is_empty(), true);
+        assert!(a.get_sticky().is_empty());
     }
 
     #[test]
@@ -173,21 +301,27 @@ mod tests {
     }
 
     #[test]
-    fn test_modify_name() {
+    fn test_restore_count() {
         let mut a = struct_init();
-        let new_name = &"Not a test board anymore";
+        a.restore_count(41);
+        assert_eq!(a.get_count(), 41);
+        assert_eq!(a.increment_count(), 42);
+    }
 
-        a.modify_name(new_name.to_string());
-        assert_eq!(a.get_name(), new_name)
+    #[test]
+    fn test_get_config() {
+        let a = struct_init();
+        assert_eq!(*a.get_config(), a.config);
     }
 
     #[test]
-    fn test_modify_description() {
+    fn test_modify_config() {
         let mut a = struct_init();
-        let new_desc = &"Cooked brainrot central on god fr fr skibidi gyatt ohio rizz";
+        let mut new_config = BoardConfig::new();
+        new_config.modify_tripcodes(!a.get_config().get_tripcodes());
 
-        a.modify_description(new_desc.to_string());
-        assert_eq!(a.get_description(), new_desc);
+        a.modify_config(new_config.clone());
+        assert_eq!(*a.get_config(), new_config);
     }
 
     #[test]
@@ -220,19 +354,90 @@ mod tests {
         }
     }
 
+    fn parents(a: &Board) -> Vec<u64> {
+        return a.get_sticky().iter().map(Sticky::get_parent).collect();
+    }
+
     #[test]
     fn test_add_sticky() {
         let mut a = struct_init();
-        let size: usize = 127;
 
-        for i in 0..size {
-            a.add_sticky(i as u64);
-        }
+        assert!(a.add_sticky(1, 0, None));
+        assert!(a.add_sticky(2, 5, None));
+        assert!(a.add_sticky(3, 5, Some(SystemTime::UNIX_EPOCH)));
+        assert!(a.add_sticky(4, 1, None));
+        assert!(!a.add_sticky(4, 9, None));
 
-        assert_eq!(a.get_sticky().len(), size);
-        for i in 0..size {
-            assert!(a.get_sticky().contains(&(i as u64)));
+        assert_eq!(parents(&a), vec![2, 3, 4, 1]);
+        assert_eq!(
+            *a.get_sticky()[1].get_expires(),
+            Some(SystemTime::UNIX_EPOCH)
+        );
+    }
+
+    #[test]
+    fn test_modify_sticky() {
+        let mut a = struct_init();
+        a.add_sticky(1, 5, None);
+        a.add_sticky(2, 5, None);
+        a.add_sticky(3, 1, None);
+
+        assert!(a.modify_sticky(1, 5, None));
+        assert_eq!(parents(&a), vec![2, 1, 3]);
+        assert!(a.modify_sticky(3, 9, Some(SystemTime::UNIX_EPOCH)));
+        assert_eq!(parents(&a), vec![3, 2, 1]);
+        assert_eq!(a.get_sticky()[0].get_priority(), 9);
+        assert!(!a.modify_sticky(4, 1, None));
+    }
+
+    #[test]
+    fn test_reorder_stickies() {
+        let mut a = struct_init();
+        a.add_sticky(1, 5, None);
+        a.add_sticky(2, 5, Some(SystemTime::UNIX_EPOCH));
+        a.add_sticky(3, 1, None);
+
+        assert!(a.reorder_stickies(&[3, 1, 2]));
+        assert_eq!(parents(&a), vec![3, 1, 2]);
+        let priorities: Vec<u16> = a.get_sticky().iter().map(Sticky::get_priority).collect();
+        assert_eq!(priorities, vec![3, 2, 1]);
+        assert_eq!(
+            *a.get_sticky()[2].get_expires(),
+            Some(SystemTime::UNIX_EPOCH)
+        );
+
+        // every sticky, exactly once
+        assert!(!a.reorder_stickies(&[3, 1]));
+        assert!(!a.reorder_stickies(&[3, 1, 1]));
+        assert!(!a.reorder_stickies(&[3, 1, 2, 2]));
+        assert!(!a.reorder_stickies(&[3, 1, 4]));
+        assert_eq!(parents(&a), vec![3, 1, 2]);
+    }
+
+    #[test]
+    fn test_remove_expired_stickies() {
+        let mut a = struct_init();
+        let now = SystemTime::now();
+        a.add_sticky(1, 0, Some(SystemTime::UNIX_EPOCH));
+        a.add_sticky(2, 0, None);
+        a.add_sticky(3, 0, Some(now + std::time::Duration::from_secs(60)));
+
+        assert_eq!(a.remove_expired_stickies(now), vec![1]);
+        assert_eq!(parents(&a), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_get_thread_order() {
+        let mut a = struct_init();
+        for parent in [1, 2, 3, 4, 5] {
+            a.add_active(parent);
         }
+        a.add_sticky(4, 1, None);
+        a.add_sticky(2, 9, None);
+        a.add_sticky(5, 9, Some(SystemTime::UNIX_EPOCH));
+        a.add_sticky(7, 9, None); // not an active thread
+
+        assert_eq!(a.get_thread_order(SystemTime::now()), vec![2, 4, 1, 3, 5]);
     }
 
     #[test]
@@ -279,10 +484,10 @@ mod tests {
         let size: usize = 127;
 
         for i in 0..size {
-            a.add_sticky(i as u64);
+            a.add_sticky(i as u64, 0, None);
         }
         assert!(a.remove_sticky((size - 1) as u64));
-        a.add_sticky((size - 1) as u64);
+        a.add_sticky((size - 1) as u64, 0, None);
 
         assert!(!a.remove_sticky(size as u64));
         for i in 0..size {
@@ -293,21 +498,60 @@ mod tests {
     }
 
     #[test]
-    fn test_pop_active() {
+    fn test_bump() {
         let mut a = struct_init();
-        let size: usize = 127;
+        a.add_active(1);
+        a.add_active(2);
 
-        for i in 0..size {
-            a.add_active(i as u64);
+        a.bump(2);
+        assert_eq!(*a.get_active(), vec![2, 1]);
+        a.bump(3);
+        assert_eq!(*a.get_active(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_archive_overflow() {
+        let mut a = struct_init();
+        let mut config = BoardConfig::new();
+        config.modify_active_count(2);
+        config.modify_archive_count(2);
+        a.modify_config(config);
+
+        for i in 1..=4 {
+            a.bump(i);
         }
+        a.add_sticky(1, 0, None);
+        let now = SystemTime::now();
 
-        for i in 0..size {
-            let thread = a.pop_active();
-            assert!(thread.is_some());
-            assert_eq!(thread.unwrap(), (size - i - 1) as u64);
-            assert_eq!(a.get_active().len(), size - i - 1);
-            assert!(!a.remove_active((size - i - 1) as u64));
+        // the sticky stays active even though it's at the bottom
+        assert_eq!(a.archive_overflow(now), (vec![2, 3], vec![]));
+        assert_eq!(*a.get_active(), vec![4, 1]);
+        assert_eq!(*a.get_archive(), vec![3, 2]);
+
+        // the oldest archived thread falls off
+        a.bump(5);
+        assert_eq!(a.archive_overflow(now), (vec![4], vec![2]));
+        assert_eq!(*a.get_archive(), vec![4, 3]);
+        assert_eq!(a.archive_overflow(now), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_archive_overflow_expired_sticky() {
+        // an expired sticky is archived like any other thread, even though it's still listed
+        let mut a = struct_init();
+        let mut config = BoardConfig::new();
+        config.modify_active_count(2);
+        a.modify_config(config);
+
+        for i in 1..=3 {
+            a.bump(i);
         }
+        let now = SystemTime::now();
+        a.add_sticky(1, 0, Some(now - Duration::from_secs(1)));
+
+        assert_eq!(a.archive_overflow(now), (vec![1], vec![]));
+        assert_eq!(*a.get_active(), vec![3, 2]);
+        assert_eq!(*a.get_archive(), vec![1]);
     }
 
     #[test]
@@ -0,0 +1,685 @@
+use serde::Serialize;
+use std::string::String;
+use std::vec::Vec;
+
+// per-board settings, loaded from each board's config file.
+// the board description lives in the same file, but is kept on the board struct itself.
+pub(crate) const DEFAULT_ACTIVE_COUNT: u8 = 16;
+pub(crate) const DEFAULT_ARCHIVE_COUNT: u8 = 8;
+pub(crate) const DEFAULT_TRIPCODES: bool = true;
+pub(crate) const DEFAULT_MAX_FILES: u8 = 4;
+pub(crate) const DEFAULT_ALLOWED_TYPES: [&str; 8] = [
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "video/webm",
+    "video/mp4",
+    "audio/mpeg",
+    "application/pdf",
+];
+pub(crate) const DEFAULT_MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+pub(crate) const DEFAULT_MAX_IMAGE_WIDTH: u32 = 10000;
+pub(crate) const DEFAULT_MAX_IMAGE_HEIGHT: u32 = 10000;
+pub(crate) const DEFAULT_OP_THUMBNAIL_BOUND: u32 = 250;
+pub(crate) const DEFAULT_REPLY_THUMBNAIL_BOUND: u32 = 125;
+pub(crate) const DEFAULT_STRIP_METADATA: bool = true;
+pub(crate) const DEFAULT_MAX_REPLIES: u16 = 500;
+pub(crate) const DEFAULT_REVISION_LIMIT: u8 = 10;
+pub(crate) const DEFAULT_EDIT_WINDOW: u32 = 300;
+pub(crate) const DEFAULT_RETENTION_DAYS: u16 = 7;
+pub(crate) const DEFAULT_MAX_COMMENT_LENGTH: u32 = 2000;
+pub(crate) const DEFAULT_MAX_LINES: u16 = 100;
+pub(crate) const DEFAULT_OP_REQUIRES_IMAGE: bool = true;
+pub(crate) const DEFAULT_SUBJECT_REQUIRED: bool = false;
+pub(crate) const DEFAULT_FORCED_ANONYMITY: bool = false;
+pub(crate) const DEFAULT_REPLY_COOLDOWN: u32 = 15;
+pub(crate) const DEFAULT_THREAD_COOLDOWN: u32 = 300;
+pub(crate) const DEFAULT_NAME: &str = "Anonymous";
+pub(crate) const DEFAULT_CYCLIC_LIMIT: u16 = 250;
+pub(crate) const DEFAULT_POSTER_IDS: bool = false;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct BoardConfig {
+    active_count: u8,           // total active threads (sticky + bumped)
+    archive_count: u8,          // total locked threads kept in storage
+    tripcodes: bool,            // whether `name#password` is turned into a tripcode
+    max_files: u8,              // attachments allowed per post
+    allowed_types: Vec<String>, // MIME types of files that may be uploaded
+    max_file_size: u64,         // bytes, per file
+    max_image_width: u32,
+    max_image_height: u32,
+    op_thumbnail_bound: u32,    // longest side of a thumbnail shown on an OP
+    reply_thumbnail_bound: u32, // longest side of a thumbnail shown on a reply
+    strip_metadata: bool,       // whether EXIF and the like are removed from uploaded images
+    max_replies: u16,
+    revision_limit: u8,
+    edit_window: u32,
+    retention_days: u16,
+    max_comment_length: u32, // characters
+    max_lines: u16,
+    op_requires_image: bool,
+    subject_required: bool, // only ever for new threads, replies don't have subjects
+    forced_anonymity: bool, // every post goes under default_name, without a tripcode
+    reply_cooldown: u32,    // seconds between posts from the same poster
+    thread_cooldown: u32,   // seconds between new threads from the same poster
+    default_name: String,   // used when a poster leaves the name empty
+    cyclic_limit: u16,
+    poster_ids: bool,
+}
+
+impl BoardConfig {
+    pub fn new() -> BoardConfig {
+        let config = BoardConfig {
+            active_count: DEFAULT_ACTIVE_COUNT,
+            archive_count: DEFAULT_ARCHIVE_COUNT,
+            tripcodes: DEFAULT_TRIPCODES,
+            max_files: DEFAULT_MAX_FILES,
+            allowed_types: DEFAULT_ALLOWED_TYPES
+                .iter()
+                .map(|mime| mime.to_string())
+                .collect(),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            max_image_width: DEFAULT_MAX_IMAGE_WIDTH,
+            max_image_height: DEFAULT_MAX_IMAGE_HEIGHT,
+            op_thumbnail_bound: DEFAULT_OP_THUMBNAIL_BOUND,
+            reply_thumbnail_bound: DEFAULT_REPLY_THUMBNAIL_BOUND,
+            strip_metadata: DEFAULT_STRIP_METADATA,
+            max_replies: DEFAULT_MAX_REPLIES,
+            revision_limit: DEFAULT_REVISION_LIMIT,
+            edit_window: DEFAULT_EDIT_WINDOW,
+            retention_days: DEFAULT_RETENTION_DAYS,
+            max_comment_length: DEFAULT_MAX_COMMENT_LENGTH,
+            max_lines: DEFAULT_MAX_LINES,
+            op_requires_image: DEFAULT_OP_REQUIRES_IMAGE,
+            subject_required: DEFAULT_SUBJECT_REQUIRED,
+            forced_anonymity: DEFAULT_FORCED_ANONYMITY,
+            reply_cooldown: DEFAULT_REPLY_COOLDOWN,
+            thread_cooldown: DEFAULT_THREAD_COOLDOWN,
+            default_name: DEFAULT_NAME.to_string(),
+            cyclic_limit: DEFAULT_CYCLIC_LIMIT,
+            poster_ids: DEFAULT_POSTER_IDS,
+        };
+
+        return config;
+    }
+
+    pub fn get_active_count(&self) -> u8 {
+        return self.active_count;
+    }
+
+    pub fn get_archive_count(&self) -> u8 {
+        return self.archive_count;
+    }
+
+    pub fn get_tripcodes(&self) -> bool {
+        return self.tripcodes;
+    }
+
+    pub fn get_max_files(&self) -> u8 {
+        return self.max_files;
+    }
+
+    pub fn get_allowed_types(&self) -> &Vec<String> {
+        return &self.allowed_types;
+    }
+
+    pub fn get_max_file_size(&self) -> u64 {
+        return self.max_file_size;
+    }
+
+    pub fn get_max_image_width(&self) -> u32 {
+        return self.max_image_width;
+    }
+
+    pub fn get_max_image_height(&self) -> u32 {
+        return self.max_image_height;
+    }
+
+    pub fn get_op_thumbnail_bound(&self) -> u32 {
+        return self.op_thumbnail_bound;
+    }
+
+    pub fn get_reply_thumbnail_bound(&self) -> u32 {
+        return self.reply_thumbnail_bound;
+    }
+
+    pub fn get_strip_metadata(&self) -> bool {
+        return self.strip_metadata;
+    }
+
+    pub fn get_max_replies(&self) -> u16 {
+        return self.max_replies;
+    }
+
+    pub fn get_revision_limit(&self) -> u8 {
+        return self.revision_limit;
+    }
+
+    pub fn get_edit_window(&self) -> u32 {
+        return self.edit_window;
+    }
+
+    pub fn get_retention_days(&self) -> u16 {
+        return self.retention_days;
+    }
+
+    pub fn get_max_comment_length(&self) -> u32 {
+        return self.max_comment_length;
+    }
+
+    pub fn get_max_lines(&self) -> u16 {
+        return self.max_lines;
+    }
+
+    pub fn get_op_requires_image(&self) -> bool {
+        return self.op_requires_image;
+    }
+
+    pub fn get_subject_required(&self) -> bool {
+        return self.subject_required;
+    }
+
+    pub fn get_forced_anonymity(&self) -> bool {
+        return self.forced_anonymity;
+    }
+
+    pub fn get_reply_cooldown(&self) -> u32 {
+        return self.reply_cooldown;
+    }
+
+    pub fn get_thread_cooldown(&self) -> u32 {
+        return self.thread_cooldown;
+    }
+
+    pub fn get_default_name(&self) -> &String {
+        return &self.default_name;
+    }
+
+    pub fn get_cyclic_limit(&self) -> u16 {
+        return self.cyclic_limit;
+    }
+
+    pub fn get_poster_ids(&self) -> bool {
+        return self.poster_ids;
+    }
+
+    pub fn modify_active_count(&mut self, new_active_count: u8) {
+        self.active_count = new_active_count;
+    }
+
+    pub fn modify_archive_count(&mut self, new_archive_count: u8) {
+        self.archive_count = new_archive_count;
+    }
+
+    pub fn modify_tripcodes(&mut self, new_tripcodes: bool) {
+        self.tripcodes = new_tripcodes;
+    }
+
+    pub fn modify_max_files(&mut self, new_max_files: u8) {
+        self.max_files = new_max_files;
+    }
+
+    pub fn modify_allowed_types(&mut self, new_allowed_types: Vec<String>) {
+        self.allowed_types = new_allowed_types;
+    }
+
+    pub fn modify_max_file_size(&mut self, new_max_file_size: u64) {
+        self.max_file_size = new_max_file_size;
+    }
+
+    pub fn modify_max_image_width(&mut self, new_max_image_width: u32) {
+        self.max_image_width = new_max_image_width;
+    }
+
+    pub fn modify_max_image_height(&mut self, new_max_image_height: u32) {
+        self.max_image_height = new_max_image_height;
+    }
+
+    pub fn modify_op_thumbnail_bound(&mut self, new_op_thumbnail_bound: u32) {
+        self.op_thumbnail_bound = new_op_thumbnail_bound;
+    }
+
+    pub fn modify_reply_thumbnail_bound(&mut self, new_reply_thumbnail_bound: u32) {
+        self.reply_thumbnail_bound = new_reply_thumbnail_bound;
+    }
+
+    pub fn modify_strip_metadata(&mut self, new_strip_metadata: bool) {
+        self.strip_metadata = new_strip_metadata;
+    }
+
+    pub fn modify_max_replies(&mut self, new_max_replies: u16) {
+        self.max_replies = new_max_replies;
+    }
+
+    pub fn modify_revision_limit(&mut self, new_revision_limit: u8) {
+        self.revision_limit = new_revision_limit;
+    }
+
+    pub fn modify_edit_window(&mut self, new_edit_window: u32) {
+        self.edit_window = new_edit_window;
+    }
+
+    pub fn modify_retention_days(&mut self, new_retention_days: u16) {
+        self.retention_days = new_retention_days;
+    }
+
+    pub fn modify_max_comment_length(&mut self, new_max_comment_length: u32) {
+        self.max_comment_length = new_max_comment_length;
+    }
+
+    pub fn modify_max_lines(&mut self, new_max_lines: u16) {
+        self.max_lines = new_max_lines;
+    }
+
+    pub fn modify_op_requires_image(&mut self, new_op_requires_image: bool) {
+        self.op_requires_image = new_op_requires_image;
+    }
+
+    pub fn modify_subject_required(&mut self, new_subject_required: bool) {
+        self.subject_required = new_subject_required;
+    }
+
+    pub fn modify_forced_anonymity(&mut self, new_forced_anonymity: bool) {
+        self.forced_anonymity = new_forced_anonymity;
+    }
+
+    pub fn modify_reply_cooldown(&mut self, new_reply_cooldown: u32) {
+        self.reply_cooldown = new_reply_cooldown;
+    }
+
+    pub fn modify_thread_cooldown(&mut self, new_thread_cooldown: u32) {
+        self.thread_cooldown = new_thread_cooldown;
+    }
+
+    pub fn modify_default_name(&mut self, new_default_name: String) {
+        self.default_name = new_default_name;
+    }
+
+    pub fn modify_cyclic_limit(&mut self, new_cyclic_limit: u16) {
+        self.cyclic_limit = new_cyclic_limit;
+    }
+
+    pub fn modify_poster_ids(&mut self, new_poster_ids: bool) {
+        self.poster_ids = new_poster_ids;
+    }
+}
+
+impl Default for BoardConfig {
+    fn default() -> BoardConfig {
+        return BoardConfig::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn struct_init() -> BoardConfig {
+        let a_config = BoardConfig::new();
+        return a_config;
+    }
+
+    #[test]
+    fn test_init() {
+        struct_init();
+    }
+
+    #[test]
+    fn test_attributes_direct() {
+        let a = struct_init();
+
+        assert_eq!(a.active_count, DEFAULT_ACTIVE_COUNT);
+        assert_eq!(a.archive_count, DEFAULT_ARCHIVE_COUNT);
+        assert_eq!(a.tripcodes, DEFAULT_TRIPCODES);
+        assert_eq!(a.max_files, DEFAULT_MAX_FILES);
+        assert_eq!(a.allowed_types, DEFAULT_ALLOWED_TYPES);
+        assert_eq!(a.max_file_size, DEFAULT_MAX_FILE_SIZE);
+        assert_eq!(a.max_image_width, DEFAULT_MAX_IMAGE_WIDTH);
+        assert_eq!(a.max_image_height, DEFAULT_MAX_IMAGE_HEIGHT);
+        assert_eq!(a.op_thumbnail_bound, DEFAULT_OP_THUMBNAIL_BOUND);
+        assert_eq!(a.reply_thumbnail_bound, DEFAULT_REPLY_THUMBNAIL_BOUND);
+        assert_eq!(a.strip_metadata, DEFAULT_STRIP_METADATA);
+        assert_eq!(a.max_replies, DEFAULT_MAX_REPLIES);
+        assert_eq!(a.revision_limit, DEFAULT_REVISION_LIMIT);
+        assert_eq!(a.edit_window, DEFAULT_EDIT_WINDOW);
+        assert_eq!(a.retention_days, DEFAULT_RETENTION_DAYS);
+        assert_eq!(a.max_comment_length, DEFAULT_MAX_COMMENT_LENGTH);
+        assert_eq!(a.max_lines, DEFAULT_MAX_LINES);
+        assert_eq!(a.op_requires_image, DEFAULT_OP_REQUIRES_IMAGE);
+        assert_eq!(a.subject_required, DEFAULT_SUBJECT_REQUIRED);
+        assert_eq!(a.forced_anonymity, DEFAULT_FORCED_ANONYMITY);
+        assert_eq!(a.reply_cooldown, DEFAULT_REPLY_COOLDOWN);
+        assert_eq!(a.thread_cooldown, DEFAULT_THREAD_COOLDOWN);
+        assert_eq!(a.default_name, DEFAULT_NAME);
+        assert_eq!(a.cyclic_limit, DEFAULT_CYCLIC_LIMIT);
+        assert_eq!(a.poster_ids, DEFAULT_POSTER_IDS);
+    }
+
+    #[test]
+    fn test_get_active_count() {
+        let a = struct_init();
+        assert_eq!(a.get_active_count(), a.active_count);
+    }
+
+    #[test]
+    fn test_get_archive_count() {
+        let a = struct_init();
+        assert_eq!(a.get_archive_count(), a.archive_count);
+    }
+
+    #[test]
+    fn test_get_tripcodes() {
+        let a = struct_init();
+        assert_eq!(a.get_tripcodes(), a.tripcodes);
+    }
+
+    #[test]
+    fn test_get_max_files() {
+        let a = struct_init();
+        assert_eq!(a.get_max_files(), a.max_files);
+    }
+
+    #[test]
+    fn test_get_allowed_types() {
+        let a = struct_init();
+        assert_eq!(*a.get_allowed_types(), a.allowed_types);
+    }
+
+    #[test]
+    fn test_get_max_file_size() {
+        let a = struct_init();
+        assert_eq!(a.get_max_file_size(), a.max_file_size);
+    }
+
+    #[test]
+    fn test_get_max_image_width() {
+        let a = struct_init();
+        assert_eq!(a.get_max_image_width(), a.max_image_width);
+    }
+
+    #[test]
+    fn test_get_max_image_height() {
+        let a = struct_init();
+        assert_eq!(a.get_max_image_height(), a.max_image_height);
+    }
+
+    #[test]
+    fn test_get_op_thumbnail_bound() {
+        let a = struct_init();
+        assert_eq!(a.get_op_thumbnail_bound(), a.op_thumbnail_bound);
+    }
+
+    #[test]
+    fn test_get_reply_thumbnail_bound() {
+        let a = struct_init();
+        assert_eq!(a.get_reply_thumbnail_bound(), a.reply_thumbnail_bound);
+    }
+
+    #[test]
+    fn test_get_strip_metadata() {
+        let a = struct_init();
+        assert_eq!(a.get_strip_metadata(), a.strip_metadata);
+    }
+
+    #[test]
+    fn test_get_max_replies() {
+        let a = struct_init();
+        assert_eq!(a.get_max_replies(), a.max_replies);
+    }
+
+    #[test]
+    fn test_get_revision_limit() {
+        let a = struct_init();
+        assert_eq!(a.get_revision_limit(), a.revision_limit);
+    }
+
+    #[test]
+    fn test_get_edit_window() {
+        let a = struct_init();
+        assert_eq!(a.get_edit_window(), a.edit_window);
+    }
+
+    #[test]
+    fn test_get_retention_days() {
+        let a = struct_init();
+        assert_eq!(a.get_retention_days(), a.retention_days);
+    }
+
+    #[test]
+    fn test_get_max_comment_length() {
+        let a = struct_init();
+        assert_eq!(a.get_max_comment_length(), a.max_comment_length);
+    }
+
+    #[test]
+    fn test_get_max_lines() {
+        let a = struct_init();
+        assert_eq!(a.get_max_lines(), a.max_lines);
+    }
+
+    #[test]
+    fn test_get_op_requires_image() {
+        let a = struct_init();
+        assert_eq!(a.get_op_requires_image(), a.op_requires_image);
+    }
+
+    #[test]
+    fn test_get_subject_required() {
+        let a = struct_init();
+        assert_eq!(a.get_subject_required(), a.subject_required);
+    }
+
+    #[test]
+    fn test_get_forced_anonymity() {
+        let a = struct_init();
+        assert_eq!(a.get_forced_anonymity(), a.forced_anonymity);
+    }
+
+    #[test]
+    fn test_get_reply_cooldown() {
+        let a = struct_init();
+        assert_eq!(a.get_reply_cooldown(), a.reply_cooldown);
+    }
+
+    #[test]
+    fn test_get_thread_cooldown() {
+        let a = struct_init();
+        assert_eq!(a.get_thread_cooldown(), a.thread_cooldown);
+    }
+
+    #[test]
+    fn test_get_default_name() {
+        let a = struct_init();
+        assert_eq!(*a.get_default_name(), a.default_name);
+    }
+
+    #[test]
+    fn test_get_cyclic_limit() {
+        let a = struct_init();
+        assert_eq!(a.get_cyclic_limit(), a.cyclic_limit);
+    }
+
+    #[test]
+    fn test_get_poster_ids() {
+        let a = struct_init();
+        assert_eq!(a.get_poster_ids(), a.poster_ids);
+    }
+
+    #[test]
+    fn test_modify_active_count() {
+        let mut a = struct_init();
+        a.modify_active_count(42);
+        assert_eq!(a.get_active_count(), 42);
+    }
+
+    #[test]
+    fn test_modify_archive_count() {
+        let mut a = struct_init();
+        a.modify_archive_count(42);
+        assert_eq!(a.get_archive_count(), 42);
+    }
+
+    #[test]
+    fn test_modify_tripcodes() {
+        let mut a = struct_init();
+        a.modify_tripcodes(!DEFAULT_TRIPCODES);
+        assert_eq!(a.get_tripcodes(), !DEFAULT_TRIPCODES);
+    }
+
+    #[test]
+    fn test_modify_max_files() {
+        let mut a = struct_init();
+        a.modify_max_files(1);
+        assert_eq!(a.get_max_files(), 1);
+    }
+
+    #[test]
+    fn test_modify_allowed_types() {
+        let mut a = struct_init();
+        let new_types = vec!["image/png".to_string()];
+
+        a.modify_allowed_types(new_types.clone());
+        assert_eq!(*a.get_allowed_types(), new_types);
+    }
+
+    #[test]
+    fn test_modify_max_file_size() {
+        let mut a = struct_init();
+        a.modify_max_file_size(42);
+        assert_eq!(a.get_max_file_size(), 42);
+    }
+
+    #[test]
+    fn test_modify_max_image_width() {
+        let mut a = struct_init();
+        a.modify_max_image_width(42);
+        assert_eq!(a.get_max_image_width(), 42);
+    }
+
+    #[test]
+    fn test_modify_max_image_height() {
+        let mut a = struct_init();
+        a.modify_max_image_height(42);
+        assert_eq!(a.get_max_image_height(), 42);
+    }
+
+    #[test]
+    fn test_modify_op_thumbnail_bound() {
+        let mut a = struct_init();
+        a.modify_op_thumbnail_bound(42);
+        assert_eq!(a.get_op_thumbnail_bound(), 42);
+    }
+
+    #[test]
+    fn test_modify_reply_thumbnail_bound() {
+        let mut a = struct_init();
+        a.modify_reply_thumbnail_bound(42);
+        assert_eq!(a.get_reply_thumbnail_bound(), 42);
+    }
+
+    #[test]
+    fn test_modify_strip_metadata() {
+        let mut a = struct_init();
+        a.modify_strip_metadata(!DEFAULT_STRIP_METADATA);
+        assert_eq!(a.get_strip_metadata(), !DEFAULT_STRIP_METADATA);
+    }
+
+    #[test]
+    fn test_modify_max_replies() {
+        let mut a = struct_init();
+        a.modify_max_replies(42);
+        assert_eq!(a.get_max_replies(), 42);
+    }
+
+    #[test]
+    fn test_modify_revision_limit() {
+        let mut a = struct_init();
+        a.modify_revision_limit(2);
+        assert_eq!(a.get_revision_limit(), 2);
+    }
+
+    #[test]
+    fn test_modify_edit_window() {
+        let mut a = struct_init();
+        a.modify_edit_window(42);
+        assert_eq!(a.get_edit_window(), 42);
+    }
+
+    #[test]
+    fn test_modify_retention_days() {
+        let mut a = struct_init();
+        a.modify_retention_days(30);
+        assert_eq!(a.get_retention_days(), 30);
+    }
+
+    #[test]
+    fn test_modify_max_comment_length() {
+        let mut a = struct_init();
+        a.modify_max_comment_length(4000);
+        assert_eq!(a.get_max_comment_length(), 4000);
+    }
+
+    #[test]
+    fn test_modify_max_lines() {
+        let mut a = struct_init();
+        a.modify_max_lines(50);
+        assert_eq!(a.get_max_lines(), 50);
+    }
+
+    #[test]
+    fn test_modify_op_requires_image() {
+        let mut a = struct_init();
+        a.modify_op_requires_image(!DEFAULT_OP_REQUIRES_IMAGE);
+        assert_eq!(a.get_op_requires_image(), !DEFAULT_OP_REQUIRES_IMAGE);
+    }
+
+    #[test]
+    fn test_modify_subject_required() {
+        let mut a = struct_init();
+        a.modify_subject_required(!DEFAULT_SUBJECT_REQUIRED);
+        assert_eq!(a.get_subject_required(), !DEFAULT_SUBJECT_REQUIRED);
+    }
+
+    #[test]
+    fn test_modify_forced_anonymity() {
+        let mut a = struct_init();
+        a.modify_forced_anonymity(!DEFAULT_FORCED_ANONYMITY);
+        assert_eq!(a.get_forced_anonymity(), !DEFAULT_FORCED_ANONYMITY);
+    }
+
+    #[test]
+    fn test_modify_reply_cooldown() {
+        let mut a = struct_init();
+        a.modify_reply_cooldown(30);
+        assert_eq!(a.get_reply_cooldown(), 30);
+    }
+
+    #[test]
+    fn test_modify_thread_cooldown() {
+        let mut a = struct_init();
+        a.modify_thread_cooldown(600);
+        assert_eq!(a.get_thread_cooldown(), 600);
+    }
+
+    #[test]
+    fn test_modify_default_name() {
+        let mut a = struct_init();
+        a.modify_default_name("Nameless".to_string());
+        assert_eq!(a.get_default_name(), "Nameless");
+    }
+
+    #[test]
+    fn test_modify_cyclic_limit() {
+        let mut a = struct_init();
+        a.modify_cyclic_limit(42);
+        assert_eq!(a.get_cyclic_limit(), 42);
+    }
+
+    #[test]
+    fn test_modify_poster_ids() {
+        let mut a = struct_init();
+        a.modify_poster_ids(!DEFAULT_POSTER_IDS);
+        assert_eq!(a.get_poster_ids(), !DEFAULT_POSTER_IDS);
+    }
+}
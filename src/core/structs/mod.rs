@@ -3,9 +3,15 @@
 // if you're using this *way* beyond the original scope, you could replace the u64 with u128
 // beyond that you'll need to either a big number library, a hypothetical u256 data type, or some other funny solution
 
-
 // the structs in this module are intended to define the *basic* access controls.
 // Any higher level interactions are handle in the core module
+pub mod api_token;
+pub mod attachment;
+pub mod ban;
+pub mod banned_images;
 pub mod board;
+pub mod board_config;
 pub mod post;
+pub mod revision;
+pub mod sticky;
 pub mod thread;
@@ -1,29 +1,50 @@
+use super::attachment::Attachment;
+use super::revision::{Editor, Revision};
 use std::string::String;
-use std::time::Instant;
+use std::time::SystemTime;
+use std::vec::Vec;
 
 pub(crate) struct Post {
     owner: String,
+    name: String,
+    tripcode: Option<String>, // only ever the derived code, never the password
     text: String,
-    attachment: &[u8],
-    created: Instant,  // unix milli
-    modified: Instant, // unix milli
-    deleted: bool,
-    parent: u64, // thread ID #
+    attachments: Vec<Attachment>,
+    created: SystemTime,               // unix milli
+    modified: SystemTime,              // unix milli
+    deleted: Option<SystemTime>,       // when it was deleted, kept until it's purged
+    parent: u64,                       // thread ID #
+    backlinks: Vec<(String, u64)>,     // board and post ID of every post quoting this one
+    revisions: Vec<Revision>,          // oldest first, only appended to except for retention
+    deletion_password: Option<String>, // only ever the hash, never the password
+    poster_id: Option<String>,         // only on boards with poster IDs turned on
 }
 
 impl Post {
-    pub fn new(owner: String, text: String, attachment: &[u8], parent: u64) -> Post {
-        let created: Instant = Instant::now();
-        let modified: Instant = created;
+    pub fn new(
+        owner: String,
+        name: String,
+        tripcode: Option<String>,
+        text: String,
+        parent: u64,
+    ) -> Post {
+        let created: SystemTime = SystemTime::now();
+        let modified: SystemTime = created;
 
         let new_post: Post = Post {
             owner,
+            name,
+            tripcode,
             text,
-            attachment,
+            attachments: Vec::new(),
             created,
             modified,
-            deleted: false,
+            deleted: None,
             parent,
+            backlinks: Vec::new(),
+            revisions: Vec::new(),
+            deletion_password: None,
+            poster_id: None,
         };
 
         return new_post;
@@ -33,58 +54,171 @@ impl Post {
         return &self.owner;
     }
 
+    pub fn get_name(&self) -> &String {
+        return &self.name;
+    }
+
+    pub fn get_tripcode(&self) -> &Option<String> {
+        return &self.tripcode;
+    }
+
     pub fn get_text(&self) -> &String {
         return &self.text;
     }
 
-    pub fn get_attachment(&self) -> &'static [u8] {
-        return &self.attachment;
+    pub fn get_attachments(&self) -> &Vec<Attachment> {
+        return &self.attachments;
     }
 
-    pub fn get_created(&self) -> &Instant {
+    pub fn get_created(&self) -> &SystemTime {
         return &self.created;
     }
 
-    pub fn get_modified(&self) -> &Instant {
+    pub fn get_modified(&self) -> &SystemTime {
         return &self.modified;
     }
 
     pub fn get_deleted(&self) -> bool {
-        return self.deleted;
+        return self.deleted.is_some();
+    }
+
+    pub fn get_deleted_at(&self) -> &Option<SystemTime> {
+        return &self.deleted;
     }
 
     pub fn get_parent(&self) -> u64 {
         return self.parent;
     }
 
-    pub fn modify_owner(&mut self, new_owner: String) {
-        // Does this functionality make sense?
-        self.modification();
-        self.owner = new_owner;
+    pub fn get_backlinks(&self) -> &Vec<(String, u64)> {
+        return &self.backlinks;
     }
 
-    pub fn modify_text(&mut self, new_text: String) {
-        self.modification();
+    pub fn get_revisions(&self) -> &Vec<Revision> {
+        return &self.revisions;
+    }
+
+    pub fn get_deletion_password(&self) -> &Option<String> {
+        return &self.deletion_password;
+    }
+
+    pub fn get_poster_id(&self) -> &Option<String> {
+        return &self.poster_id;
+    }
+
+    pub fn modify_text(&mut self, new_text: String, editor: Editor, limit: u8) {
+        self.revision(editor, limit);
         self.text = new_text;
     }
 
-    pub fn remove_attachment(&mut self) {
+    pub fn modify_deletion_password(&mut self, new_deletion_password: Option<String>) {
+        // set when the post is made, so this isn't counted as a modification
+        self.deletion_password = new_deletion_password;
+    }
+
+    pub fn modify_poster_id(&mut self, new_poster_id: Option<String>) {
+        // set when the post is made, so this isn't counted as a modification either
+        self.poster_id = new_poster_id;
+    }
+
+    pub fn modify_parent(&mut self, new_parent: u64) {
+        // only for moving a post along with its thread, which isn't an edit
+        self.parent = new_parent;
+    }
+
+    pub fn modify_links(&mut self, new_text: String) {
+        // only for rewriting links to posts that moved. the post still says the same thing,
+        // so this isn't an edit either
+        self.text = new_text;
+    }
+
+    pub fn add_attachment(&mut self, attachment: Attachment, limit: u8) -> bool {
+        // files are attached while the post is being made, so this isn't counted as a modification
+        if self.attachments.len() >= limit as usize {
+            return false;
+        }
+        self.attachments.push(attachment);
+        return true;
+    }
+
+    pub fn remove_attachment(&mut self, stored_name: &str) -> Option<Attachment> {
+        let index: usize = self
+            .attachments
+            .iter()
+            .position(|attachment| attachment.get_stored_name() == stored_name)?;
+
+        self.modification();
+        return Some(self.attachments.remove(index));
+    }
+
+    pub fn remove_attachments(&mut self) -> Vec<Attachment> {
         self.modification();
-        self.attachment = b"";
+        return std::mem::take(&mut self.attachments);
+    }
+
+    pub fn add_backlink(&mut self, board: String, post: u64) -> bool {
+        // another post quoting this one doesn't change this post, so it isn't a modification
+        if self
+            .backlinks
+            .iter()
+            .any(|(b, p)| *b == board && *p == post)
+        {
+            return false;
+        }
+        self.backlinks.push((board, post));
+        return true;
+    }
+
+    pub fn remove_backlink(&mut self, board: &str, post: u64) -> bool {
+        let length: usize = self.backlinks.len();
+        self.backlinks.retain(|(b, p)| !(b == board && *p == post));
+        return self.backlinks.len() != length;
+    }
+
+    pub fn restore_revisions(&mut self, revisions: Vec<Revision>) {
+        // only for loading a post back out of storage
+        self.revisions = revisions;
+    }
+
+    pub fn restore_timestamps(&mut self, created: SystemTime, modified: SystemTime) {
+        // only for loading a post back out of storage
+        self.created = created;
+        self.modified = modified;
+    }
+
+    pub fn restore_deleted(&mut self, deleted: Option<SystemTime>) {
+        // only for loading a post back out of storage
+        self.deleted = deleted;
     }
 
     pub fn delete(&mut self) {
+        // deleting again doesn't restart the retention window
         self.modification();
-        self.deleted = true;
+        self.deleted.get_or_insert(self.modified);
     }
 
     pub fn undelete(&mut self) {
         self.modification();
-        self.deleted = false;
+        self.deleted = None;
+    }
+
+    fn revision(&mut self, editor: Editor, limit: u8) {
+        // keeps what the post looked like before the edit, dropping the oldest revisions
+        // once there are more than the board keeps
+        self.modification();
+        self.revisions.push(Revision::new(
+            self.modified,
+            editor,
+            self.owner.clone(),
+            self.text.clone(),
+        ));
+
+        let excess: usize = self.revisions.len().saturating_sub(limit as usize);
+        self.revisions.drain(..excess);
     }
 
     fn modification(&mut self) {
-        let modified: Instant = Instant::now();
+        let modified: SystemTime = SystemTime::now();
         self.modified = modified;
     }
 }
@@ -94,12 +228,20 @@ mod tests {
     use super::*;
 
     const OWNER: &str = "me";
+    const NAME: &str = "Anonymous";
+    const TRIPCODE: &str = "!ZnBI2EKkq.";
     const TEXT: &str = "Yeeting on em";
-    const FILE: &'static [u8] = b"file.png";
     const ID: u64 = 0;
+    const LIMIT: u8 = 4;
 
     fn struct_init() -> Post {
-        let a_post = Post::new(OWNER.to_string(), TEXT.to_string(), FILE, ID);
+        let a_post = Post::new(
+            OWNER.to_string(),
+            NAME.to_string(),
+            Some(TRIPCODE.to_string()),
+            TEXT.to_string(),
+            ID,
+        );
         return a_post;
     }
 
@@ -111,18 +253,22 @@ mod tests {
     #[test]
     fn test_attributes_direct() {
         let a = struct_init();
-        let start = Instant::now();
+        let start = SystemTime::now();
 
         assert_eq!(a.owner, OWNER);
+        assert_eq!(a.name, NAME);
+        assert_eq!(a.tripcode.as_deref(), Some(TRIPCODE));
         assert_eq!(a.text, TEXT);
-        assert_eq!(a.attachment, FILE);
-        let delta = Instant::now()
-            .duration_since(start)
-            .abs_diff(a.created.elapsed()); // I shouldn't need abs here???
+        assert!(a.attachments.is_empty());
+        let delta = a.created.duration_since(start).unwrap_or_default();
         assert!(delta.as_micros() < 1000);
         assert_eq!(a.created, a.modified);
-        assert!(!a.deleted);
+        assert_eq!(a.deleted, None);
         assert_eq!(a.parent, ID);
+        assert!(a.backlinks.is_empty());
+        assert!(a.revisions.is_empty());
+        assert_eq!(a.deletion_password, None);
+        assert_eq!(a.poster_id, None);
     }
 
     #[test]
@@ -138,16 +284,42 @@ mod tests {
         assert_eq!(a.owner, *a.get_owner());
     }
 
+    #[test]
+    fn test_get_name() {
+        let a = &struct_init();
+        assert_eq!(a.name, *a.get_name());
+    }
+
+    #[test]
+    fn test_get_tripcode() {
+        let a = &struct_init();
+        assert_eq!(a.tripcode, *a.get_tripcode());
+    }
+
     #[test]
     fn test_get_text() {
         let a = &struct_init();
         assert_eq!(a.text, *a.get_text());
     }
 
+    fn attachment_init(stored_name: &str) -> Attachment {
+        let an_attachment = Attachment::new(
+            "file.png".to_string(),
+            stored_name.to_string(),
+            "image/png".to_string(),
+            8,
+            Some((1, 1)),
+            "hash".to_string(),
+            false,
+        );
+        return an_attachment;
+    }
+
     #[test]
-    fn test_get_attachment() {
-        let a = &struct_init();
-        assert_eq!(a.attachment, a.get_attachment());
+    fn test_get_attachments() {
+        let a = &mut struct_init();
+        a.attachments.push(attachment_init("0.png"));
+        assert_eq!(a.attachments, *a.get_attachments());
     }
 
     #[test]
@@ -165,23 +337,30 @@ mod tests {
     #[test]
     fn test_get_deleted() {
         let a = &struct_init();
-        assert_eq!(a.deleted, a.get_deleted());
+        assert_eq!(a.deleted.is_some(), a.get_deleted());
     }
 
     #[test]
-    fn test_get_parent() {
-        let a = &struct_init();
-        assert_eq!(a.parent, a.get_parent());
+    fn test_get_deleted_at() {
+        let a = &mut struct_init();
+        a.deleted = Some(SystemTime::UNIX_EPOCH);
+        assert_eq!(*a.get_deleted_at(), a.deleted);
     }
 
     #[test]
-    fn test_modify_owner() {
+    fn test_restore_deleted() {
         let a = &mut struct_init();
-        let new_owner = &"you";
+        let modified: SystemTime = a.modified;
 
-        a.modify_owner(new_owner.to_string());
-        assert_eq!(a.get_owner(), new_owner);
-        assert!(a.get_created().lt(a.get_modified()))
+        a.restore_deleted(Some(SystemTime::UNIX_EPOCH));
+        assert_eq!(a.deleted, Some(SystemTime::UNIX_EPOCH));
+        assert_eq!(a.modified, modified);
+    }
+
+    #[test]
+    fn test_get_parent() {
+        let a = &struct_init();
+        assert_eq!(a.parent, a.get_parent());
     }
 
     #[test]
@@ -189,17 +368,74 @@ mod tests {
         let a = &mut struct_init();
         let new_text = &"yooted below us";
 
-        a.modify_text(new_text.to_string());
+        a.modify_text(new_text.to_string(), Editor::Poster, LIMIT);
         assert_eq!(a.get_text(), new_text);
-        assert!(a.get_created().lt(a.get_modified()))
+        assert!(a.get_created().lt(a.get_modified()));
+
+        let revision = &a.get_revisions()[0];
+        assert_eq!(revision.get_text(), TEXT);
+        assert_eq!(*revision.get_editor(), Editor::Poster);
+        assert_eq!(revision.get_edited(), a.get_modified());
+    }
+
+    #[test]
+    fn test_modify_parent() {
+        let a = &mut struct_init();
+        let modified: SystemTime = a.modified;
+
+        a.modify_parent(ID + 40);
+        assert_eq!(a.get_parent(), ID + 40);
+        assert_eq!(a.modified, modified);
+    }
+
+    #[test]
+    fn test_modify_links() {
+        let a = &mut struct_init();
+        let modified: SystemTime = a.modified;
+
+        a.modify_links(">>40".to_string());
+        assert_eq!(a.get_text(), ">>40");
+        assert_eq!(a.modified, modified);
+        assert!(a.get_revisions().is_empty());
+    }
+
+    #[test]
+    fn test_add_attachment() {
+        let a = &mut struct_init();
+
+        for i in 0..LIMIT {
+            assert!(a.add_attachment(attachment_init(&format!("{}.png", i)), LIMIT));
+            assert_eq!(a.get_attachments().len(), (i + 1) as usize);
+        }
+        assert!(!a.add_attachment(attachment_init("extra.png"), LIMIT));
+        assert_eq!(a.get_attachments().len(), LIMIT as usize);
+        assert_eq!(a.get_created(), a.get_modified());
     }
 
     #[test]
     fn test_remove_attachment() {
         let a = &mut struct_init();
+        a.add_attachment(attachment_init("0.png"), LIMIT);
+        a.add_attachment(attachment_init("1.png"), LIMIT);
+
+        assert!(a.remove_attachment("2.png").is_none());
+        assert_eq!(a.get_created(), a.get_modified());
+
+        let removed = a.remove_attachment("0.png").unwrap();
+        assert_eq!(removed.get_stored_name(), "0.png");
+        assert_eq!(a.get_attachments().len(), 1);
+        assert_eq!(a.get_attachments()[0].get_stored_name(), "1.png");
+        assert!(a.get_created().lt(a.get_modified()));
+    }
+
+    #[test]
+    fn test_remove_attachments() {
+        let a = &mut struct_init();
+        a.add_attachment(attachment_init("0.png"), LIMIT);
+        a.add_attachment(attachment_init("1.png"), LIMIT);
 
-        a.remove_attachment();
-        assert_eq!(a.get_attachment(), b"");
+        assert_eq!(a.remove_attachments().len(), 2);
+        assert!(a.get_attachments().is_empty());
         assert!(a.get_created().lt(a.get_modified()));
     }
 
@@ -210,15 +446,134 @@ mod tests {
         a.delete();
         assert!(a.get_deleted());
         assert!(a.get_created().lt(a.get_modified()));
+        assert_eq!(*a.get_deleted_at(), Some(*a.get_modified()));
+
+        let deleted: Option<SystemTime> = a.deleted;
+        a.delete();
+        assert_eq!(a.deleted, deleted);
     }
 
     #[test]
     fn test_undelete() {
         let a = &mut struct_init();
-        a.deleted = true;
+        a.deleted = Some(SystemTime::now());
 
         a.undelete();
         assert!(!a.get_deleted());
         assert!(a.get_created().lt(a.get_modified()));
     }
+
+    #[test]
+    fn test_get_backlinks() {
+        let a = &mut struct_init();
+        a.backlinks.push(("test".to_string(), 12));
+        assert_eq!(a.backlinks, *a.get_backlinks());
+    }
+
+    #[test]
+    fn test_add_backlink() {
+        let a = &mut struct_init();
+
+        assert!(a.add_backlink("test".to_string(), 12));
+        assert!(a.add_backlink("other".to_string(), 12));
+        assert!(!a.add_backlink("test".to_string(), 12));
+        assert_eq!(a.get_backlinks().len(), 2);
+        assert_eq!(a.get_created(), a.get_modified());
+    }
+
+    #[test]
+    fn test_remove_backlink() {
+        let a = &mut struct_init();
+        a.add_backlink("test".to_string(), 12);
+        a.add_backlink("other".to_string(), 12);
+
+        assert!(a.remove_backlink("test", 12));
+        assert!(!a.remove_backlink("test", 12));
+        assert_eq!(*a.get_backlinks(), vec![("other".to_string(), 12)]);
+        assert_eq!(a.get_created(), a.get_modified());
+    }
+
+    #[test]
+    fn test_restore_timestamps() {
+        let a = &mut struct_init();
+        let created = SystemTime::UNIX_EPOCH;
+        let modified = created + std::time::Duration::from_millis(42);
+
+        a.restore_timestamps(created, modified);
+        assert_eq!(*a.get_created(), created);
+        assert_eq!(*a.get_modified(), modified);
+    }
+
+    #[test]
+    fn test_get_revisions() {
+        let a = &mut struct_init();
+        a.modify_text("edited".to_string(), Editor::Poster, LIMIT);
+        assert_eq!(a.revisions, *a.get_revisions());
+    }
+
+    #[test]
+    fn test_revision_limit() {
+        let a = &mut struct_init();
+        let moderator = Editor::Moderator("jan".to_string());
+
+        for i in 0..LIMIT + 2 {
+            a.modify_text(format!("edit {}", i), moderator.clone(), LIMIT);
+        }
+
+        // the original text and the first edit have been dropped
+        assert_eq!(a.get_revisions().len(), LIMIT as usize);
+        assert_eq!(a.get_revisions()[0].get_text(), "edit 1");
+        assert_eq!(*a.get_revisions()[0].get_editor(), moderator);
+        assert_eq!(a.get_text(), &format!("edit {}", LIMIT + 1));
+
+        a.modify_text("no history".to_string(), Editor::Poster, 0);
+        assert!(a.get_revisions().is_empty());
+    }
+
+    #[test]
+    fn test_restore_revisions() {
+        let a = &mut struct_init();
+        let revision = Revision::new(
+            SystemTime::UNIX_EPOCH,
+            Editor::Poster,
+            OWNER.to_string(),
+            "old".to_string(),
+        );
+
+        a.restore_revisions(vec![revision.clone()]);
+        assert_eq!(*a.get_revisions(), vec![revision]);
+        assert_eq!(a.get_created(), a.get_modified());
+    }
+
+    #[test]
+    fn test_get_deletion_password() {
+        let a = &mut struct_init();
+        a.deletion_password = Some("hash".to_string());
+        assert_eq!(a.deletion_password, *a.get_deletion_password());
+    }
+
+    #[test]
+    fn test_modify_deletion_password() {
+        let a = &mut struct_init();
+
+        a.modify_deletion_password(Some("hash".to_string()));
+        assert_eq!(a.get_deletion_password().as_deref(), Some("hash"));
+        assert_eq!(a.get_created(), a.get_modified());
+    }
+
+    #[test]
+    fn test_get_poster_id() {
+        let a = &mut struct_init();
+        a.poster_id = Some("Ab3/xY.9".to_string());
+        assert_eq!(a.poster_id, *a.get_poster_id());
+    }
+
+    #[test]
+    fn test_modify_poster_id() {
+        let a = &mut struct_init();
+
+        a.modify_poster_id(Some("Ab3/xY.9".to_string()));
+        assert_eq!(a.get_poster_id().as_deref(), Some("Ab3/xY.9"));
+        assert_eq!(a.get_created(), a.get_modified());
+    }
 }
@@ -0,0 +1,139 @@
+use std::fmt;
+use std::string::String;
+use std::time::SystemTime;
+
+// who made an edit. Posters don't have IDs of their own, the post's owner is what identifies them
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Editor {
+    Poster,
+    Moderator(String), // moderator ID
+}
+
+const POSTER: &str = "poster";
+const MODERATOR_PREFIX: &str = "mod:";
+
+impl Editor {
+    pub fn parse(editor: &str) -> Option<Editor> {
+        if editor == POSTER {
+            return Some(Editor::Poster);
+        }
+        return editor
+            .strip_prefix(MODERATOR_PREFIX)
+            .filter(|id| !id.is_empty())
+            .map(|id| Editor::Moderator(id.to_string()));
+    }
+}
+
+impl fmt::Display for Editor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Editor::Poster => write!(f, "{}", POSTER),
+            Editor::Moderator(id) => write!(f, "{}{}", MODERATOR_PREFIX, id),
+        };
+    }
+}
+
+// what a post looked like before an edit, along with when the edit happened and who made it
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Revision {
+    edited: SystemTime,
+    editor: Editor,
+    owner: String, // the owner and text from before the edit
+    text: String,
+}
+
+impl Revision {
+    pub fn new(edited: SystemTime, editor: Editor, owner: String, text: String) -> Revision {
+        let new_revision: Revision = Revision {
+            edited,
+            editor,
+            owner,
+            text,
+        };
+
+        return new_revision;
+    }
+
+    pub fn get_edited(&self) -> &SystemTime {
+        return &self.edited;
+    }
+
+    pub fn get_editor(&self) -> &Editor {
+        return &self.editor;
+    }
+
+    pub fn get_owner(&self) -> &String {
+        return &self.owner;
+    }
+
+    pub fn get_text(&self) -> &String {
+        return &self.text;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OWNER: &str = "me";
+    const TEXT: &str = "before the edit";
+
+    fn struct_init() -> Revision {
+        let a_revision = Revision::new(
+            SystemTime::UNIX_EPOCH,
+            Editor::Moderator("jan".to_string()),
+            OWNER.to_string(),
+            TEXT.to_string(),
+        );
+        return a_revision;
+    }
+
+    #[test]
+    fn test_init() {
+        struct_init();
+    }
+
+    #[test]
+    fn test_attributes_direct() {
+        let a = struct_init();
+
+        assert_eq!(a.edited, SystemTime::UNIX_EPOCH);
+        assert_eq!(a.editor, Editor::Moderator("jan".to_string()));
+        assert_eq!(a.owner, OWNER);
+        assert_eq!(a.text, TEXT);
+    }
+
+    #[test]
+    fn test_get_edited() {
+        let a = struct_init();
+        assert_eq!(*a.get_edited(), a.edited);
+    }
+
+    #[test]
+    fn test_get_editor() {
+        let a = struct_init();
+        assert_eq!(*a.get_editor(), a.editor);
+    }
+
+    #[test]
+    fn test_get_owner() {
+        let a = struct_init();
+        assert_eq!(*a.get_owner(), a.owner);
+    }
+
+    #[test]
+    fn test_get_text() {
+        let a = struct_init();
+        assert_eq!(*a.get_text(), a.text);
+    }
+
+    #[test]
+    fn test_editor_round_trip() {
+        for editor in [Editor::Poster, Editor::Moderator("jan".to_string())] {
+            assert_eq!(Editor::parse(&editor.to_string()), Some(editor));
+        }
+        assert_eq!(Editor::Moderator("jan".to_string()).to_string(), "mod:jan");
+        assert_eq!(Editor::parse("mod:"), None);
+        assert_eq!(Editor::parse("admin"), None);
+    }
+}
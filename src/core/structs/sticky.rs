@@ -0,0 +1,101 @@
+use std::time::SystemTime;
+
+// a stickied thread. Stickies are shown before every other thread, highest priority first, and
+// temporary ones stop being sticky once they expire.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Sticky {
+    parent: u64,                 // thread ID
+    priority: u16,               // higher goes first
+    expires: Option<SystemTime>, // None for a permanent sticky
+}
+
+impl Sticky {
+    pub fn new(parent: u64, priority: u16, expires: Option<SystemTime>) -> Sticky {
+        let new_sticky: Sticky = Sticky {
+            parent,
+            priority,
+            expires,
+        };
+
+        return new_sticky;
+    }
+
+    pub fn get_parent(&self) -> u64 {
+        return self.parent;
+    }
+
+    pub fn get_priority(&self) -> u16 {
+        return self.priority;
+    }
+
+    pub fn get_expires(&self) -> &Option<SystemTime> {
+        return &self.expires;
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        return match self.expires {
+            Some(expires) => expires <= now,
+            None => false,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const PARENT: u64 = 12;
+    const PRIORITY: u16 = 5;
+
+    fn struct_init() -> Sticky {
+        let a_sticky = Sticky::new(PARENT, PRIORITY, Some(SystemTime::UNIX_EPOCH));
+        return a_sticky;
+    }
+
+    #[test]
+    fn test_init() {
+        struct_init();
+    }
+
+    #[test]
+    fn test_attributes_direct() {
+        let a = struct_init();
+
+        assert_eq!(a.parent, PARENT);
+        assert_eq!(a.priority, PRIORITY);
+        assert_eq!(a.expires, Some(SystemTime::UNIX_EPOCH));
+    }
+
+    #[test]
+    fn test_get_parent() {
+        let a = struct_init();
+        assert_eq!(a.get_parent(), a.parent);
+    }
+
+    #[test]
+    fn test_get_priority() {
+        let a = struct_init();
+        assert_eq!(a.get_priority(), a.priority);
+    }
+
+    #[test]
+    fn test_get_expires() {
+        let a = struct_init();
+        assert_eq!(*a.get_expires(), a.expires);
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let a = struct_init();
+        let now = SystemTime::now();
+        assert!(a.is_expired(now));
+        assert!(a.is_expired(SystemTime::UNIX_EPOCH));
+
+        let a = Sticky::new(PARENT, PRIORITY, Some(now + Duration::from_secs(60)));
+        assert!(!a.is_expired(now));
+
+        let a = Sticky::new(PARENT, PRIORITY, None);
+        assert!(!a.is_expired(now));
+    }
+}
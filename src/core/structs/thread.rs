@@ -1,11 +1,42 @@
+use std::fmt;
 use std::string::String;
+use std::time::SystemTime;
 use std::vec::Vec;
 
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ThreadError {
+    Locked,
+    NotNewer { child: u64, last: u64 },
+    ReplyLimit { limit: u16 },
+}
+
+impl fmt::Display for ThreadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            ThreadError::Locked => write!(f, "This thread is locked."),
+            ThreadError::NotNewer { child, last } => write!(
+                f,
+                "Post {} is not newer than the last post in the thread, {}.",
+                child, last
+            ),
+            ThreadError::ReplyLimit { limit } => write!(
+                f,
+                "This thread has reached its limit of {} replies and is now locked.",
+                limit
+            ),
+        };
+    }
+}
+
+impl std::error::Error for ThreadError {}
+
 pub(crate) struct Thread {
     name: String,
     children: Vec<u64>, // thread IDs
     locked: bool,
-    deleted: bool, // could be considered redundant with post's deleted, but this will simplify things
+    deleted: bool, // follows the parent post, deleting the OP deletes the whole thread
+    cyclic: bool,  // drops its oldest replies instead of hitting the reply limit
+    bumped: Option<SystemTime>, // None for threads from before bump times were kept
 }
 
 impl Thread {
@@ -15,6 +46,8 @@ impl Thread {
             children: Vec::from([parent]),
             locked: false,
             deleted: false,
+            cyclic: false,
+            bumped: None,
         };
 
         return new_thread;
@@ -25,7 +58,7 @@ impl Thread {
     }
 
     pub fn get_parent(&self) -> u64 {
-        return *self.children.get(0).unwrap();
+        return *self.children.first().unwrap();
     }
 
     pub fn get_children(&self) -> &Vec<u64> {
@@ -40,19 +73,65 @@ impl Thread {
         return self.deleted;
     }
 
-    pub fn modify_name(&mut self, new_name: String) {
-        self.name = new_name;
+    pub fn get_cyclic(&self) -> bool {
+        return self.cyclic;
+    }
+
+    pub fn get_bumped(&self) -> &Option<SystemTime> {
+        return &self.bumped;
+    }
+
+    pub fn get_reply_count(&self) -> usize {
+        return self.children.len() - 1; // the parent isn't a reply
+    }
+
+    pub fn add_child(&mut self, child: u64, limit: u16) -> Result<(), ThreadError> {
+        // children are kept in posting order, so a new child always has to be the newest post.
+        // the reply limit also keeps the list bounded, once it's hit the thread locks itself.
+        // cyclic threads are bounded by pruning instead, so they ignore it
+        if self.locked {
+            return Err(ThreadError::Locked);
+        }
+
+        let last: u64 = *self.children.last().unwrap();
+        if child <= last {
+            return Err(ThreadError::NotNewer { child, last });
+        }
+
+        if self.cyclic {
+            self.children.push(child);
+            return Ok(());
+        }
+
+        if self.get_reply_count() >= limit as usize {
+            self.locked = true;
+            return Err(ThreadError::ReplyLimit { limit });
+        }
+
+        self.children.push(child);
+        if self.get_reply_count() >= limit as usize {
+            self.locked = true;
+        }
+
+        return Ok(());
+    }
+
+    pub fn remove_child(&mut self, child: u64) -> bool {
+        // for purged replies. the parent stays, a thread only goes away as a whole
+        if child == self.get_parent() {
+            return false;
+        }
+
+        let count: usize = self.children.len();
+        self.children.retain(|existing| *existing != child);
+        return self.children.len() != count;
     }
 
-    pub fn add_child(&mut self, child: u64) {
-        self.children.push(child); // This allows a child post to be older than the thread.
-                                   // More so, this function technically represents a
-                                   // potential target for DoS attacks because our vectors
-                                   // are unbounded, re-allocation will eventually occur,
-                                   // giving a nice lovely target surface for re-allocation
-                                   // brute-forcing. Of course, this *hopefully* won't be
-                                   // an issue with a proper rate limiter in higher levels of
-                                   // the software.
+    pub fn prune_replies(&mut self, cap: u16) -> Vec<u64> {
+        // drops the oldest replies until at most cap are left, returning the ones dropped.
+        // the parent always stays
+        let excess: usize = self.get_reply_count().saturating_sub(cap as usize);
+        return self.children.drain(1..1 + excess).collect();
     }
 
     pub fn lock(&mut self) {
@@ -70,6 +149,14 @@ impl Thread {
     pub fn undelete(&mut self) {
         self.deleted = false;
     }
+
+    pub fn modify_cyclic(&mut self, cyclic: bool) {
+        self.cyclic = cyclic;
+    }
+
+    pub fn modify_bumped(&mut self, bumped: SystemTime) {
+        self.bumped = Some(bumped);
+    }
 }
 
 #[cfg(test)]
@@ -78,6 +165,7 @@ mod tests {
 
     const NAME: &str = "test post please ignore";
     const PARENT: u64 = 42069;
+    const LIMIT: u16 = 500;
 
     fn struct_init() -> Thread {
         let a_thread = Thread::new(NAME.to_string(), PARENT);
@@ -95,9 +183,11 @@ mod tests {
 
         assert_eq!(a.name, NAME);
         assert_eq!(a.children.len(), 1);
-        assert_eq!(*a.children.get(0).unwrap(), PARENT);
+        assert_eq!(*a.children.first().unwrap(), PARENT);
         assert!(!a.locked);
         assert!(!a.deleted);
+        assert!(!a.cyclic);
+        assert_eq!(a.bumped, None);
     }
 
     #[test]
@@ -116,7 +206,7 @@ mod tests {
     fn test_get_children() {
         let a = struct_init();
         assert_eq!(a.get_children().len(), 1);
-        assert_eq!(*a.get_children().get(0).unwrap(), PARENT);
+        assert_eq!(*a.get_children().first().unwrap(), PARENT);
     }
 
     #[test]
@@ -132,12 +222,32 @@ mod tests {
     }
 
     #[test]
-    fn test_modify_name() {
+    fn test_get_cyclic() {
+        let a = struct_init();
+        assert!(!a.get_cyclic());
+    }
+
+    #[test]
+    fn test_get_bumped() {
+        let a = struct_init();
+        assert_eq!(*a.get_bumped(), None);
+    }
+
+    #[test]
+    fn test_modify_bumped() {
         let mut a = struct_init();
-        let new_name = &"Not ignored real post";
 
-        a.modify_name(new_name.to_string());
-        assert_eq!(a.get_name(), new_name);
+        a.modify_bumped(SystemTime::UNIX_EPOCH);
+        assert_eq!(*a.get_bumped(), Some(SystemTime::UNIX_EPOCH));
+    }
+
+    #[test]
+    fn test_get_reply_count() {
+        let mut a = struct_init();
+        assert_eq!(a.get_reply_count(), 0);
+
+        a.children.push(PARENT + 1);
+        assert_eq!(a.get_reply_count(), 1);
     }
 
     #[test]
@@ -145,16 +255,120 @@ mod tests {
         let mut a = struct_init();
         let size: usize = 42;
         assert_eq!(a.get_children().len(), 1);
-        assert_eq!(*a.get_children().get(0).unwrap(), PARENT);
+        assert_eq!(*a.get_children().first().unwrap(), PARENT);
 
         for i in 1..size {
-            a.add_child(PARENT + i as u64);
+            a.add_child(PARENT + i as u64, LIMIT).unwrap();
             assert_eq!(a.get_children().len(), i + 1)
         }
 
         for i in 0..size {
             assert_eq!(*a.get_children().get(i).unwrap(), PARENT + i as u64);
         }
+        assert!(!a.get_locked());
+    }
+
+    #[test]
+    fn test_add_child_not_newer() {
+        let mut a = struct_init();
+        a.add_child(PARENT + 2, LIMIT).unwrap();
+
+        assert_eq!(
+            a.add_child(PARENT + 2, LIMIT),
+            Err(ThreadError::NotNewer {
+                child: PARENT + 2,
+                last: PARENT + 2
+            })
+        );
+        assert_eq!(
+            a.add_child(PARENT - 1, LIMIT),
+            Err(ThreadError::NotNewer {
+                child: PARENT - 1,
+                last: PARENT + 2
+            })
+        );
+        assert_eq!(a.get_children().len(), 2);
+    }
+
+    #[test]
+    fn test_add_child_reply_limit() {
+        let mut a = struct_init();
+
+        a.add_child(PARENT + 1, 2).unwrap();
+        assert!(!a.get_locked());
+        a.add_child(PARENT + 2, 2).unwrap();
+        assert!(a.get_locked());
+
+        assert_eq!(a.add_child(PARENT + 3, 2), Err(ThreadError::Locked));
+        assert_eq!(a.get_reply_count(), 2);
+    }
+
+    #[test]
+    fn test_add_child_limit_lowered() {
+        // a board's limit can drop below what a thread already has
+        let mut a = struct_init();
+        a.add_child(PARENT + 1, LIMIT).unwrap();
+        a.add_child(PARENT + 2, LIMIT).unwrap();
+
+        assert_eq!(
+            a.add_child(PARENT + 3, 1),
+            Err(ThreadError::ReplyLimit { limit: 1 })
+        );
+        assert!(a.get_locked());
+        assert_eq!(a.get_reply_count(), 2);
+    }
+
+    #[test]
+    fn test_add_child_locked() {
+        let mut a = struct_init();
+        a.lock();
+
+        assert_eq!(a.add_child(PARENT + 1, LIMIT), Err(ThreadError::Locked));
+        assert_eq!(a.get_reply_count(), 0);
+    }
+
+    #[test]
+    fn test_remove_child() {
+        let mut a = struct_init();
+        a.add_child(PARENT + 1, LIMIT).unwrap();
+        a.add_child(PARENT + 2, LIMIT).unwrap();
+
+        assert!(a.remove_child(PARENT + 1));
+        assert!(!a.remove_child(PARENT + 1));
+        assert!(!a.remove_child(PARENT));
+        assert_eq!(*a.get_children(), vec![PARENT, PARENT + 2]);
+
+        // removing a child doesn't let an older one back in
+        assert!(a.add_child(PARENT + 1, LIMIT).is_err());
+    }
+
+    #[test]
+    fn test_add_child_cyclic() {
+        // no limit applies, pruning keeps the list bounded instead
+        let mut a = struct_init();
+        a.modify_cyclic(true);
+
+        a.add_child(PARENT + 1, 1).unwrap();
+        a.add_child(PARENT + 2, 1).unwrap();
+        assert!(!a.get_locked());
+        assert_eq!(a.get_reply_count(), 2);
+    }
+
+    #[test]
+    fn test_prune_replies() {
+        let mut a = struct_init();
+        for i in 1..=5 {
+            a.add_child(PARENT + i, LIMIT).unwrap();
+        }
+
+        assert!(a.prune_replies(5).is_empty());
+        assert_eq!(a.prune_replies(3), vec![PARENT + 1, PARENT + 2]);
+        assert_eq!(
+            *a.get_children(),
+            vec![PARENT, PARENT + 3, PARENT + 4, PARENT + 5]
+        );
+        assert_eq!(a.prune_replies(0).len(), 3);
+        assert_eq!(*a.get_children(), vec![PARENT]);
     }
 
     #[test]
@@ -186,4 +400,11 @@ mod tests {
         a.undelete();
         assert!(!a.get_deleted());
     }
+
+    #[test]
+    fn test_modify_cyclic() {
+        let mut a = struct_init();
+        a.modify_cyclic(true);
+        assert!(a.get_cyclic());
+    }
 }
@@ -0,0 +1,166 @@
+// Tripcodes give posters a verifiable identity without needing an account.
+// A name field of `name#password` produces a classic tripcode, which is the traditional DES crypt
+// based code that is portable across sites. A name field of `name##password` produces a secure
+// tripcode, which is salted with a server secret so it can't be brute-forced offline.
+// Only the derived code is ever handed back to the caller; the password is dropped here.
+use base64::alphabet;
+use base64::engine::general_purpose::{GeneralPurpose, NO_PAD};
+use base64::Engine;
+use pwhash::unix_crypt;
+use sha2::{Digest, Sha256};
+use std::string::String;
+
+const SEPARATOR: char = '#';
+const CLASSIC_PREFIX: &str = "!";
+const SECURE_PREFIX: &str = "!!";
+const CODE_LENGTH: usize = 10;
+
+// salt characters outside of crypt's alphabet are remapped, same as every other imageboard does
+const SALT_FROM: &[u8] = b":;<=>?@[\\]^_`";
+const SALT_TO: &[u8] = b"ABCDEFGabcdef";
+
+// same character set as crypt(3), so classic and secure codes look alike
+const CRYPT_BASE64: GeneralPurpose = GeneralPurpose::new(&alphabet::CRYPT, NO_PAD);
+
+pub(crate) fn parse_name(
+    field: &str,
+    secure_salt: &[u8],
+    allowed: bool,
+) -> (String, Option<String>) {
+    // splits a raw name field into the display name and an optional tripcode.
+    // when tripcodes are not allowed, the password is still stripped so it never reaches storage.
+    let (name, password) = match field.split_once(SEPARATOR) {
+        Some(split) => split,
+        None => return (field.to_string(), None),
+    };
+
+    if !allowed {
+        return (name.to_string(), None);
+    }
+
+    let tripcode: Option<String> = match password.strip_prefix(SEPARATOR) {
+        Some("") => None,
+        Some(secure_password) => Some(secure(secure_password, secure_salt)),
+        None if password.is_empty() => None,
+        None => Some(classic(password)),
+    };
+
+    return (name.to_string(), tripcode);
+}
+
+pub(crate) fn classic(password: &str) -> String {
+    // the salt is the 2nd and 3rd characters of the password, padded out with "H."
+    let padded: Vec<u8> = [password.as_bytes(), b"H.."].concat();
+    let salt: String = padded[1..3].iter().map(|byte| salt_char(*byte)).collect();
+
+    // the salt is always two valid crypt characters, so hashing can't fail.
+    // DES crypt is deprecated for passwords, but it's the whole point of a classic tripcode
+    #[allow(deprecated)]
+    let hashed: String = unix_crypt::hash_with(&salt, password).unwrap();
+    let code: &str = &hashed[hashed.len() - CODE_LENGTH..];
+
+    return format!("{}{}", CLASSIC_PREFIX, code);
+}
+
+pub(crate) fn secure(password: &str, salt: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(password.as_bytes());
+    hasher.update(salt);
+
+    let encoded: String = CRYPT_BASE64.encode(hasher.finalize());
+    let code: &str = &encoded[..CODE_LENGTH];
+
+    return format!("{}{}", SECURE_PREFIX, code);
+}
+
+fn salt_char(byte: u8) -> char {
+    if !(b'.'..=b'z').contains(&byte) {
+        return '.';
+    }
+
+    return match SALT_FROM.iter().position(|from| *from == byte) {
+        Some(index) => SALT_TO[index] as char,
+        None => byte as char,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SALT: &[u8] = b"server secret";
+
+    #[test]
+    fn test_classic_known_values() {
+        // well known codes, these should match every other imageboard
+        assert_eq!(classic("a"), "!ZnBI2EKkq.");
+        assert_eq!(classic("test"), "!.CzKQna1OU");
+    }
+
+    #[test]
+    fn test_salt_char() {
+        assert_eq!(salt_char(b'a'), 'a');
+        assert_eq!(salt_char(b':'), 'A');
+        assert_eq!(salt_char(b'`'), 'f');
+        assert_eq!(salt_char(b'~'), '.');
+        assert_eq!(salt_char(0xCE), '.');
+    }
+
+    #[test]
+    fn test_secure() {
+        let code = secure("password", SALT);
+
+        assert!(code.starts_with(SECURE_PREFIX));
+        assert_eq!(code.len(), SECURE_PREFIX.len() + CODE_LENGTH);
+        assert_eq!(code, secure("password", SALT));
+        assert_ne!(code, secure("password", b"another secret"));
+        assert_ne!(code, secure("Password", SALT));
+    }
+
+    #[test]
+    fn test_parse_name_plain() {
+        assert_eq!(
+            parse_name("Anonymous", SALT, true),
+            ("Anonymous".to_string(), None)
+        );
+        assert_eq!(parse_name("", SALT, true), ("".to_string(), None));
+    }
+
+    #[test]
+    fn test_parse_name_classic() {
+        let (name, tripcode) = parse_name("me#a", SALT, true);
+
+        assert_eq!(name, "me");
+        assert_eq!(tripcode.unwrap(), "!ZnBI2EKkq.");
+    }
+
+    #[test]
+    fn test_parse_name_secure() {
+        let (name, tripcode) = parse_name("me##a", SALT, true);
+        let tripcode = tripcode.unwrap();
+
+        assert_eq!(name, "me");
+        assert!(tripcode.starts_with(SECURE_PREFIX));
+        assert_eq!(tripcode, secure("a", SALT));
+    }
+
+    #[test]
+    fn test_parse_name_empty_password() {
+        assert_eq!(parse_name("me#", SALT, true), ("me".to_string(), None));
+        assert_eq!(parse_name("me##", SALT, true), ("me".to_string(), None));
+    }
+
+    #[test]
+    fn test_parse_name_disallowed() {
+        assert_eq!(parse_name("me#a", SALT, false), ("me".to_string(), None));
+        assert_eq!(parse_name("me##a", SALT, false), ("me".to_string(), None));
+    }
+
+    #[test]
+    fn test_parse_name_drops_password() {
+        let (name, tripcode) = parse_name("me#hunter2", SALT, true);
+
+        assert!(!name.contains("hunter2"));
+        assert!(!tripcode.unwrap().contains("hunter2"));
+    }
+}
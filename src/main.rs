@@ -1,5 +1,580 @@
+// explicit returns are the house style
+#![allow(clippy::needless_return)]
+
 mod core;
+mod server;
+
+use crate::core::api_tokens;
+use crate::core::attachments;
+use crate::core::bans;
+use crate::core::deletion;
+use crate::core::media::perceptual;
+use crate::core::moving;
+use crate::core::revisions;
+use crate::core::serialization::{self, Form};
+use crate::core::storage::board_storage;
+use crate::core::storage::config::Config;
+use crate::core::storage::post_storage;
+use crate::core::storage::site_storage;
+use crate::core::storage::thread_storage;
+use crate::core::structs::api_token::Scope;
+use crate::core::structs::ban::Ban;
+use crate::core::structs::post::Post;
+use crate::core::structs::thread::Thread;
+use crate::server::ServerConfig;
+use std::env;
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+const DEFAULT_ROOT: &str = "./";
+const USAGE: &str = "usage: image_board [command]
+with no command, serves the site from the current directory
+commands:
+    serve [address] [workers] [data root]
+    regenerate-thumbnails [data root]
+    ban-image <board> <stored name> <reason> [data root]
+    unban-image <hash> [data root]
+    find-similar <board> <stored name> [data root]
+    post-history <board> <post> [data root]
+    export-thread <board> <thread> <public or admin> [data root]
+    export-board <board> [data root]
+    delete-post <board> <post> [data root]
+    restore-post <board> <post> [data root]
+    delete-file <board> <post> <stored name> [data root]
+    purge [data root]
+    move-thread <board> <thread> <destination board> [data root]
+    sticky <board> <thread> <priority> <hours, or 0 to never expire> [data root]
+    unsticky <board> <thread> [data root]
+    reorder-stickies <board> <thread,thread,...> [data root]
+    cyclic <board> <thread> [data root]
+    uncyclic <board> <thread> [data root]
+    ban <board> <post> <hours, or 0 to never expire> <reason> [data root]
+    unban <ip> [data root]
+    add-token <name> <scope,scope,...> <requests a minute, or 0 for no limit> [data root]
+    remove-token <name> [data root]
+the API scopes are post, lock, sticky, delete and ban
+commands that change a board wait for the server to finish any write it's in the middle of";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+
+    return match args.get(1).map(String::as_str) {
+        None => report("serve", serve(None, None, DEFAULT_ROOT)),
+        Some("serve") => {
+            let root: &str = args.get(4).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report(
+                "serve",
+                serve(
+                    args.get(2).map(String::as_str),
+                    args.get(3).map(String::as_str),
+                    root,
+                ),
+            )
+        }
+        Some("regenerate-thumbnails") => {
+            let root: &str = args.get(2).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report("regenerate thumbnails", regenerate_thumbnails(root))
+        }
+        Some("ban-image") if args.len() >= 5 => {
+            let root: &str = args.get(5).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report("ban image", ban_image(root, &args[2], &args[3], &args[4]))
+        }
+        Some("unban-image") if args.len() >= 3 => {
+            let root: &str = args.get(3).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report("unban image", unban_image(root, &args[2]))
+        }
+        Some("find-similar") if args.len() >= 4 => {
+            let root: &str = args.get(4).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report(
+                "find similar images",
+                find_similar(root, &args[2], &args[3]),
+            )
+        }
+        Some("post-history") if args.len() >= 4 => {
+            let root: &str = args.get(4).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report("read post history", post_history(root, &args[2], &args[3]))
+        }
+        Some("export-thread") if args.len() >= 5 => {
+            let root: &str = args.get(5).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report(
+                "export thread",
+                export_thread(root, &args[2], &args[3], &args[4]),
+            )
+        }
+        Some("export-board") if args.len() >= 3 => {
+            let root: &str = args.get(3).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report("export board", export_board(root, &args[2]))
+        }
+        Some("delete-post") if args.len() >= 4 => {
+            let root: &str = args.get(4).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report("delete post", set_deleted(root, &args[2], &args[3], true))
+        }
+        Some("restore-post") if args.len() >= 4 => {
+            let root: &str = args.get(4).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report("restore post", set_deleted(root, &args[2], &args[3], false))
+        }
+        Some("delete-file") if args.len() >= 5 => {
+            let root: &str = args.get(5).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report(
+                "delete file",
+                delete_file(root, &args[2], &args[3], &args[4]),
+            )
+        }
+        Some("purge") => {
+            let root: &str = args.get(2).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report("purge deleted posts", purge(root))
+        }
+        Some("move-thread") if args.len() >= 5 => {
+            let root: &str = args.get(5).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report(
+                "move thread",
+                move_thread(root, &args[2], &args[3], &args[4]),
+            )
+        }
+        Some("sticky") if args.len() >= 6 => {
+            let root: &str = args.get(6).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report(
+                "sticky thread",
+                sticky(root, &args[2], &args[3], &args[4], &args[5]),
+            )
+        }
+        Some("unsticky") if args.len() >= 4 => {
+            let root: &str = args.get(4).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report("unsticky thread", unsticky(root, &args[2], &args[3]))
+        }
+        Some("reorder-stickies") if args.len() >= 4 => {
+            let root: &str = args.get(4).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report(
+                "reorder stickies",
+                reorder_stickies(root, &args[2], &args[3]),
+            )
+        }
+        Some("cyclic") if args.len() >= 4 => {
+            let root: &str = args.get(4).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report(
+                "make thread cyclic",
+                set_cyclic(root, &args[2], &args[3], true),
+            )
+        }
+        Some("uncyclic") if args.len() >= 4 => {
+            let root: &str = args.get(4).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report(
+                "make thread not cyclic",
+                set_cyclic(root, &args[2], &args[3], false),
+            )
+        }
+        Some("ban") if args.len() >= 6 => {
+            let root: &str = args.get(6).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report(
+                "ban poster",
+                ban(root, &args[2], &args[3], &args[4], &args[5]),
+            )
+        }
+        Some("unban") if args.len() >= 3 => {
+            let root: &str = args.get(3).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report("unban", unban(root, &args[2]))
+        }
+        Some("add-token") if args.len() >= 5 => {
+            let root: &str = args.get(5).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report(
+                "add API token",
+                add_token(root, &args[2], &args[3], &args[4]),
+            )
+        }
+        Some("remove-token") if args.len() >= 3 => {
+            let root: &str = args.get(3).map(String::as_str).unwrap_or(DEFAULT_ROOT);
+            report("remove API token", remove_token(root, &args[2]))
+        }
+        Some(_) => {
+            eprintln!("{}", USAGE);
+            ExitCode::FAILURE
+        }
+    };
+}
+
+fn regenerate_thumbnails(root: &str) -> Result<(), Error> {
+    let config: Config = Config::new(root.to_string());
+
+    for board_name in site_storage::initialize(&config)? {
+        let board = board_storage::read_board(&config, &board_name)?;
+        let (regenerated, failed) = attachments::regenerate_thumbnails(&config, &board)?;
+
+        println!(
+            "/{}/: regenerated thumbnails for {} images",
+            board_name, regenerated
+        );
+        for stored_name in failed {
+            eprintln!("/{}/: could not decode {}", board_name, stored_name);
+        }
+    }
+
+    return Ok(());
+}
+
+fn serve(address: Option<&str>, workers: Option<&str>, root: &str) -> Result<(), Error> {
+    let workers: usize = match workers {
+        Some(workers) => workers
+            .parse()
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "the worker count is a number"))?,
+        None => server::DEFAULT_WORKERS,
+    };
+    let address: &str = address.unwrap_or(server::DEFAULT_ADDRESS);
+
+    return server::run(&ServerConfig::new(
+        address.to_string(),
+        workers,
+        root.to_string(),
+    ));
+}
+
+fn report(action: &str, result: Result<(), Error>) -> ExitCode {
+    return match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("failed to {}: {}", action, error);
+            ExitCode::FAILURE
+        }
+    };
+}
+
+fn read_hash(config: &Config, board: &str, stored_name: &str) -> Result<u64, Error> {
+    return attachments::get_perceptual_hash(config, board, stored_name)?.ok_or(Error::new(
+        ErrorKind::NotFound,
+        format!("/{}/ has no image named {}", board, stored_name),
+    ));
+}
+
+fn ban_image(root: &str, board: &str, stored_name: &str, reason: &str) -> Result<(), Error> {
+    let config: Config = Config::new(root.to_string());
+    let hash: u64 = read_hash(&config, board, stored_name)?;
+
+    let mut banned_images = site_storage::read_banned_images(&config)?;
+    match banned_images.add_hash(hash, reason.to_string()) {
+        true => println!("banned {}", perceptual::to_hex(hash)),
+        false => println!("{} was already banned", perceptual::to_hex(hash)),
+    }
+
+    return site_storage::write_banned_images(&config, &banned_images);
+}
+
+fn unban_image(root: &str, hash: &str) -> Result<(), Error> {
+    let config: Config = Config::new(root.to_string());
+    let hash: u64 = perceptual::from_hex(hash).ok_or(Error::new(
+        ErrorKind::InvalidInput,
+        "hashes are 16 hex digits",
+    ))?;
+
+    let mut banned_images = site_storage::read_banned_images(&config)?;
+    match banned_images.remove_hash(hash) {
+        true => println!("unbanned {}", perceptual::to_hex(hash)),
+        false => println!("{} was not banned", perceptual::to_hex(hash)),
+    }
+
+    return site_storage::write_banned_images(&config, &banned_images);
+}
+
+fn find_similar(root: &str, board: &str, stored_name: &str) -> Result<(), Error> {
+    // uses the banned image threshold, so this shows what a ban would catch
+    let config: Config = Config::new(root.to_string());
+    let hash: u64 = read_hash(&config, board, stored_name)?;
+    let threshold: u32 = site_storage::read_banned_images(&config)?.get_threshold();
+    let boards: Vec<String> = site_storage::read_boards(&config)?;
+
+    for (other_board, other_name, distance) in
+        attachments::find_similar(&config, &boards, hash, threshold)?
+    {
+        println!("/{}/{} (distance {})", other_board, other_name, distance);
+    }
+
+    return Ok(());
+}
+
+fn parse_id(post: &str) -> Result<u64, Error> {
+    return post
+        .parse()
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "post IDs are numbers"));
+}
+
+fn parse_hours(hours: &str) -> Result<u64, Error> {
+    return hours
+        .parse()
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "expiry is a number of hours"));
+}
+
+fn get_expiry(hours: u64, now: SystemTime) -> Result<Option<SystemTime>, Error> {
+    // 0 hours never expires
+    if hours == 0 {
+        return Ok(None);
+    }
+    let too_long = || Error::new(ErrorKind::InvalidInput, "that expiry is too far off");
+    let seconds: u64 = hours.checked_mul(60 * 60).ok_or_else(too_long)?;
+    return match now.checked_add(Duration::from_secs(seconds)) {
+        Some(expires) => Ok(Some(expires)),
+        None => Err(too_long()),
+    };
+}
+
+fn post_history(root: &str, board: &str, post: &str) -> Result<(), Error> {
+    // edit history is for moderators only, so it's only available from here for now
+    let config: Config = Config::new(root.to_string());
+    let id: u64 = parse_id(post)?;
+    let read = post_storage::read_post(&config, board, id)?;
+
+    match read.get_revisions().is_empty() {
+        true => println!("/{}/{} has never been edited", board, id),
+        false => print!("{}", revisions::format_history(&read)),
+    }
+
+    return Ok(());
+}
+
+fn export_thread(root: &str, board: &str, thread: &str, form: &str) -> Result<(), Error> {
+    // the thread as one JSON document on stdout. the public form is what anyone could see, a
+    // deleted thread has nothing public left in it
+    let config: Config = Config::new(root.to_string());
+    let form: Form = Form::parse(form).ok_or(Error::new(
+        ErrorKind::InvalidInput,
+        "the form is public or admin",
+    ))?;
+    let read: Thread = thread_storage::read_thread(&config, board, parse_id(thread)?)?;
+    if form == Form::Public && read.get_deleted() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            "that thread is deleted, only the admin form has it",
+        ));
+    }
+
+    // purged posts are gone for good, there's nothing left to export
+    let mut posts: Vec<(u64, Post)> = Vec::new();
+    for id in read.get_children() {
+        if post_storage::post_exists(&config, board, *id) {
+            posts.push((*id, post_storage::read_post(&config, board, *id)?));
+        }
+    }
+    println!(
+        "{}",
+        serialization::thread_export_to_json(&read, &posts, form)
+    );
+
+    return Ok(());
+}
+
+fn export_board(root: &str, board: &str) -> Result<(), Error> {
+    // the board's own state and settings, its threads go out one at a time with export-thread
+    let config: Config = Config::new(root.to_string());
+    let read = board_storage::read_board(&config, board)?;
+    println!("{}", serialization::board_to_json(&read));
+
+    return Ok(());
+}
+
+fn set_deleted(root: &str, board: &str, post: &str, deleted: bool) -> Result<(), Error> {
+    // restoring only works until the post is purged
+    let config: Config = Config::new(root.to_string());
+    let _locked: File = board_storage::lock_boards(&config)?;
+    let id: u64 = parse_id(post)?;
+    let mut read = post_storage::read_post(&config, board, id)?;
+
+    match (deleted, read.get_deleted()) {
+        (true, true) => println!("/{}/{} was already deleted", board, id),
+        (false, false) => println!("/{}/{} is not deleted", board, id),
+        (true, false) => {
+            deletion::delete_post(&config, board, id, &mut read)?;
+            println!("deleted /{}/{}", board, id);
+        }
+        (false, true) => {
+            deletion::restore_post(&config, board, id, &mut read)?;
+            println!("restored /{}/{}", board, id);
+        }
+    }
+
+    return Ok(());
+}
+
+fn delete_file(root: &str, board: &str, post: &str, stored_name: &str) -> Result<(), Error> {
+    // there's no restoring a file, unlike a post
+    let config: Config = Config::new(root.to_string());
+    let _locked: File = board_storage::lock_boards(&config)?;
+    let id: u64 = parse_id(post)?;
+    let mut read = post_storage::read_post(&config, board, id)?;
+
+    match deletion::delete_file(&config, board, id, &mut read, stored_name)? {
+        true => println!("deleted {} from /{}/{}", stored_name, board, id),
+        false => println!("/{}/{} has no file {}", board, id, stored_name),
+    }
+
+    return Ok(());
+}
+
+fn purge(root: &str) -> Result<(), Error> {
+    let config: Config = Config::new(root.to_string());
+    let boards: Vec<String> = site_storage::initialize(&config)?;
+    let _locked: File = board_storage::lock_boards(&config)?;
+
+    for board_name in boards {
+        let mut board = board_storage::read_board(&config, &board_name)?;
+        let purged: Vec<u64> = deletion::purge(&config, &mut board, SystemTime::now())?;
+        println!("/{}/: purged {} posts", board_name, purged.len());
+    }
+
+    return Ok(());
+}
+
+fn move_thread(root: &str, board: &str, thread: &str, destination: &str) -> Result<(), Error> {
+    let config: Config = Config::new(root.to_string());
+    let _locked: File = board_storage::lock_boards(&config)?;
+    let thread: u64 = parse_id(thread)?;
+    let mut from = board_storage::read_board(&config, board)?;
+    let mut to = board_storage::read_board(&config, destination)?;
+
+    let new_thread: u64 = moving::move_thread(&config, &mut from, thread, &mut to)?;
+    println!(
+        "moved /{}/{} to /{}/{}",
+        board, thread, destination, new_thread
+    );
+
+    return Ok(());
+}
+
+fn sticky(root: &str, board: &str, thread: &str, priority: &str, hours: &str) -> Result<(), Error> {
+    // stickying a thread again just changes its priority and expiry
+    let config: Config = Config::new(root.to_string());
+    let _locked: File = board_storage::lock_boards(&config)?;
+    let thread: u64 = parse_id(thread)?;
+    let priority: u16 = priority
+        .parse()
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "priorities are 0 to 65535"))?;
+    let expires: Option<SystemTime> = get_expiry(parse_hours(hours)?, SystemTime::now())?;
+
+    let mut read = board_storage::read_board(&config, board)?;
+    if !read.get_active().contains(&thread) {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("/{}/{} is not an active thread", board, thread),
+        ));
+    }
+    if !read.modify_sticky(thread, priority, expires) {
+        read.add_sticky(thread, priority, expires);
+    }
+    println!("stickied /{}/{}", board, thread);
+
+    return board_storage::write_board(&config, &read);
+}
+
+fn unsticky(root: &str, board: &str, thread: &str) -> Result<(), Error> {
+    let config: Config = Config::new(root.to_string());
+    let _locked: File = board_storage::lock_boards(&config)?;
+    let thread: u64 = parse_id(thread)?;
+    let mut read = board_storage::read_board(&config, board)?;
+
+    match read.remove_sticky(thread) {
+        true => println!("unstickied /{}/{}", board, thread),
+        false => println!("/{}/{} was not sticky", board, thread),
+    }
+
+    return board_storage::write_board(&config, &read);
+}
+
+fn reorder_stickies(root: &str, board: &str, order: &str) -> Result<(), Error> {
+    let config: Config = Config::new(root.to_string());
+    let _locked: File = board_storage::lock_boards(&config)?;
+    let order: Vec<u64> = order
+        .split(',')
+        .map(|thread| parse_id(thread.trim()))
+        .collect::<Result<Vec<u64>, Error>>()?;
+    let mut read = board_storage::read_board(&config, board)?;
+
+    if !read.reorder_stickies(&order) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "the new order has to list every sticky exactly once",
+        ));
+    }
+    println!("reordered the stickies on /{}/", board);
+
+    return board_storage::write_board(&config, &read);
+}
+
+fn set_cyclic(root: &str, board: &str, thread: &str, cyclic: bool) -> Result<(), Error> {
+    // pruning only happens as replies come in, turning it on doesn't prune anything by itself
+    let config: Config = Config::new(root.to_string());
+    let _locked: File = board_storage::lock_boards(&config)?;
+    let thread: u64 = parse_id(thread)?;
+    let mut read = thread_storage::read_thread(&config, board, thread)?;
+
+    read.modify_cyclic(cyclic);
+    match cyclic {
+        true => println!("/{}/{} is now cyclic", board, thread),
+        false => println!("/{}/{} is no longer cyclic", board, thread),
+    }
+
+    return thread_storage::write_thread(&config, board, &read);
+}
+
+fn ban(root: &str, board: &str, post: &str, hours: &str, reason: &str) -> Result<(), Error> {
+    // bans whoever made the post, without the IP ever being shown
+    let config: Config = Config::new(root.to_string());
+    let _locked: File = board_storage::lock_boards(&config)?;
+    let id: u64 = parse_id(post)?;
+    let hours: u64 = parse_hours(hours)?;
+    let now: SystemTime = SystemTime::now();
+    let expires: Option<SystemTime> = get_expiry(hours, now)?;
+
+    let ban: Ban = bans::ban_poster(&config, board, id, reason, expires, now)?;
+    match ban.get_expires() {
+        Some(_) => println!("banned the poster of /{}/{} for {} hours", board, id, hours),
+        None => println!("banned the poster of /{}/{}", board, id),
+    }
+
+    return Ok(());
+}
+
+fn unban(root: &str, ip: &str) -> Result<(), Error> {
+    let config: Config = Config::new(root.to_string());
+    let _locked: File = board_storage::lock_boards(&config)?;
+
+    match bans::unban(&config, ip, SystemTime::now())? {
+        true => println!("unbanned {}", ip),
+        false => println!("{} was not banned", ip),
+    }
+
+    return Ok(());
+}
+
+fn add_token(root: &str, name: &str, scopes: &str, rate_limit: &str) -> Result<(), Error> {
+    // the token is only ever printed here, only its hash is kept
+    let config: Config = Config::new(root.to_string());
+    let scopes: Vec<Scope> = scopes
+        .split(',')
+        .map(|scope| {
+            Scope::parse(scope.trim()).ok_or(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not an API scope", scope),
+            ))
+        })
+        .collect::<Result<Vec<Scope>, Error>>()?;
+    let rate_limit: u32 = rate_limit.parse().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            "rate limits are a number of requests a minute",
+        )
+    })?;
+
+    let token: String = api_tokens::add_token(&config, name, scopes, rate_limit)?;
+    println!("{}", token);
+
+    return Ok(());
+}
+
+fn remove_token(root: &str, name: &str) -> Result<(), Error> {
+    let config: Config = Config::new(root.to_string());
+
+    match api_tokens::remove_token(&config, name)? {
+        true => println!("removed the API token {}", name),
+        false => println!("there is no API token named {}", name),
+    }
 
-fn main() {
-    println!("Hello, world!");
+    return Ok(());
 }
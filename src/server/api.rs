@@ -0,0 +1,624 @@
+// The read-only JSON API, in the shape most imageboards share, so browser extensions and archivers
+// written for those work here too:
+// boards.json                 every board with its limits
+// /{board}/catalog.json       every active thread's OP with its newest replies, in pages
+// /{board}/threads.json       every active thread's number, reply count and last change, in pages
+// /{board}/thread/{id}.json   a whole thread
+// /{board}/archive.json       the numbers of every archived thread
+// Posts only carry one file in that shape, so any others go in extra_files, the way vichan does it.
+// tim is the stored name without its extension, which is how clients find the file and thumbnail:
+// /{board}/src/{tim}{ext} and /{board}/src/{tim}s.jpg.
+// Backlinks aren't in that shape either, they're added as backlinks, each the board and no of a
+// post quoting this one, and left out when there are none.
+// Times are unix seconds here, not milliseconds, since that's what clients expect.
+use super::pages;
+use crate::core::index::{self, PER_PAGE};
+use crate::core::markup::render::{self, LinkResolver};
+use crate::core::media::thumbnail::{self, ThumbnailSize, THUMBNAIL_EXTENSION};
+use crate::core::structs::attachment::Attachment;
+use crate::core::structs::board::Board;
+use crate::core::structs::board_config::BoardConfig;
+use crate::core::structs::post::Post;
+use crate::core::structs::thread::Thread;
+use serde::Serialize;
+use std::string::String;
+use std::time::SystemTime;
+use std::vec::Vec;
+
+const CATALOG_REPLIES: usize = 5; // replies shown under each thread in the catalog
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+// a thread with every post in it that's still up, the OP first
+pub(crate) struct ThreadPosts {
+    pub thread: Thread,
+    pub posts: Vec<(u64, Post)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ApiCooldowns {
+    pub threads: u32,
+    pub replies: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ApiBoard {
+    pub board: String,
+    pub title: String,
+    pub meta_description: String,
+    pub per_page: usize,
+    pub pages: usize,
+    pub max_filesize: u64,
+    pub max_comment_chars: u32,
+    pub bump_limit: u16, // the thread locks once it's reached, so it stops bumping too
+    pub cooldowns: ApiCooldowns,
+    pub spoilers: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forced_anon: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_ids: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_archived: Option<u8>,
+}
+
+impl ApiBoard {
+    pub fn new(board: &Board) -> ApiBoard {
+        let config: &BoardConfig = board.get_config();
+        let new_board: ApiBoard = ApiBoard {
+            board: board.get_name().clone(),
+            title: board.get_description().clone(),
+            meta_description: board.get_description().clone(),
+            per_page: PER_PAGE,
+            pages: index::get_page_count(config.get_active_count() as usize, PER_PAGE),
+            max_filesize: config.get_max_file_size(),
+            max_comment_chars: config.get_max_comment_length(),
+            bump_limit: config.get_max_replies(),
+            cooldowns: ApiCooldowns {
+                threads: config.get_thread_cooldown(),
+                replies: config.get_reply_cooldown(),
+            },
+            spoilers: 1,
+            forced_anon: flag(config.get_forced_anonymity()),
+            user_ids: flag(config.get_poster_ids()),
+            is_archived: flag(config.get_archive_count() > 0),
+        };
+
+        return new_board;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ApiFile {
+    pub tim: String,
+    pub filename: String,
+    pub ext: String,
+    pub fsize: u64,
+    // only images have dimensions and a thumbnail
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub w: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub h: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tn_w: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tn_h: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spoiler: Option<u8>,
+}
+
+impl ApiFile {
+    pub fn new(attachment: &Attachment, bound: u32) -> ApiFile {
+        let (tim, ext): (&str, &str) = split_extension(attachment.get_stored_name());
+        let (filename, _): (&str, &str) = split_extension(attachment.get_original_name());
+        let dimensions: Option<(u32, u32)> = attachment.get_dimensions();
+        let thumbnail: Option<(u32, u32)> =
+            dimensions.map(|(width, height)| thumbnail::get_dimensions(width, height, bound));
+
+        let new_file: ApiFile = ApiFile {
+            tim: tim.to_string(),
+            filename: filename.to_string(),
+            ext: ext.to_string(),
+            fsize: attachment.get_size(),
+            w: dimensions.map(|(width, _)| width),
+            h: dimensions.map(|(_, height)| height),
+            tn_w: thumbnail.map(|(width, _)| width),
+            tn_h: thumbnail.map(|(_, height)| height),
+            spoiler: flag(attachment.get_spoiler()),
+        };
+
+        return new_file;
+    }
+}
+
+// a post quoting this one, which can be on another board
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ApiBacklink {
+    pub board: String,
+    pub no: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub(crate) struct ApiPost {
+    pub no: u64,
+    pub resto: u64, // the thread, or 0 on the OP
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sticky: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closed: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archived: Option<u8>,
+    pub now: String,
+    pub time: u64,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub com: String,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub file: Option<ApiFile>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_files: Vec<ApiFile>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub backlinks: Vec<ApiBacklink>,
+    // the rest are only on OPs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replies: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub images: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub omitted_posts: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub omitted_images: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_replies: Option<Vec<ApiPost>>,
+}
+
+impl ApiPost {
+    pub fn new(
+        board: &Board,
+        thread: &Thread,
+        id: u64,
+        post: &Post,
+        links: &dyn LinkResolver,
+    ) -> ApiPost {
+        let parent: u64 = thread.get_parent();
+        let op: bool = id == parent;
+        let size: ThumbnailSize = match op {
+            true => ThumbnailSize::Op,
+            false => ThumbnailSize::Reply,
+        };
+        let bound: u32 = size.get_bound(board.get_config());
+        let mut files: Vec<ApiFile> = post
+            .get_attachments()
+            .iter()
+            .map(|attachment| ApiFile::new(attachment, bound))
+            .collect();
+        let file: Option<ApiFile> = match files.is_empty() {
+            true => None,
+            false => Some(files.remove(0)),
+        };
+
+        // poster IDs only while the board has them on, the same as the pages
+        let poster_id: Option<String> = match board.get_config().get_poster_ids() {
+            true => post.get_poster_id().clone(),
+            false => None,
+        };
+        let subject: Option<String> = match op && !thread.get_name().is_empty() {
+            true => Some(thread.get_name().clone()),
+            false => None,
+        };
+        let time: u64 = pages::get_seconds(post.get_created());
+
+        let new_post: ApiPost = ApiPost {
+            no: id,
+            resto: match op {
+                true => 0,
+                false => parent,
+            },
+            now: format_now(time),
+            time,
+            name: post.get_name().clone(),
+            trip: post.get_tripcode().clone(),
+            id: poster_id,
+            sub: subject,
+            com: render::render_text(post.get_text(), board.get_name(), parent, links),
+            file,
+            extra_files: files,
+            backlinks: post
+                .get_backlinks()
+                .iter()
+                .map(|(board, no)| ApiBacklink {
+                    board: board.clone(),
+                    no: *no,
+                })
+                .collect(),
+            ..ApiPost::default()
+        };
+
+        return new_post;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ApiThreads {
+    pub posts: Vec<ApiPost>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ApiPage<T> {
+    pub page: usize, // from 1
+    pub threads: Vec<T>,
+}
+
+// a thread as threads.json lists it
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ApiThreadEntry {
+    pub no: u64,
+    pub last_modified: u64,
+    pub replies: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ApiBoards {
+    pub boards: Vec<ApiBoard>,
+}
+
+pub(crate) fn boards(boards: &[Board]) -> String {
+    return to_json(&ApiBoards {
+        boards: boards.iter().map(ApiBoard::new).collect(),
+    });
+}
+
+pub(crate) fn thread(
+    board: &Board,
+    thread: &ThreadPosts,
+    links: &dyn LinkResolver,
+    now: SystemTime,
+) -> String {
+    let mut posts: Vec<ApiPost> = Vec::new();
+    for (id, post) in &thread.posts {
+        posts.push(ApiPost::new(board, &thread.thread, *id, post, links));
+    }
+    if let Some(op) = posts.first_mut() {
+        add_thread_fields(op, board, thread, now);
+    }
+
+    return to_json(&ApiThreads { posts });
+}
+
+pub(crate) fn catalog(
+    board: &Board,
+    threads: &[ThreadPosts],
+    links: &dyn LinkResolver,
+    now: SystemTime,
+) -> String {
+    let mut pages: Vec<ApiPage<ApiPost>> = Vec::new();
+    for (index, chunk) in threads.chunks(PER_PAGE).enumerate() {
+        let mut ops: Vec<ApiPost> = Vec::new();
+        for thread in chunk {
+            let Some((id, post)) = thread.posts.first() else {
+                continue;
+            };
+            let mut op: ApiPost = ApiPost::new(board, &thread.thread, *id, post, links);
+            add_thread_fields(&mut op, board, thread, now);
+
+            let replies: &[(u64, Post)] = &thread.posts[1..];
+            let shown: &[(u64, Post)] = &replies[replies.len().saturating_sub(CATALOG_REPLIES)..];
+            op.omitted_posts = Some(replies.len() - shown.len());
+            op.omitted_images = Some(count_files(replies) - count_files(shown));
+            op.last_replies = Some(
+                shown
+                    .iter()
+                    .map(|(id, post)| ApiPost::new(board, &thread.thread, *id, post, links))
+                    .collect(),
+            );
+            ops.push(op);
+        }
+
+        pages.push(ApiPage {
+            page: index + 1,
+            threads: ops,
+        });
+    }
+
+    return to_json(&pages);
+}
+
+pub(crate) fn threads(threads: &[ThreadPosts]) -> String {
+    let mut pages: Vec<ApiPage<ApiThreadEntry>> = Vec::new();
+    for (index, chunk) in threads.chunks(PER_PAGE).enumerate() {
+        pages.push(ApiPage {
+            page: index + 1,
+            threads: chunk
+                .iter()
+                .map(|thread| ApiThreadEntry {
+                    no: thread.thread.get_parent(),
+                    last_modified: last_modified(&thread.posts),
+                    replies: thread.posts.len().saturating_sub(1),
+                })
+                .collect(),
+        });
+    }
+
+    return to_json(&pages);
+}
+
+pub(crate) fn archive(parents: &[u64]) -> String {
+    return to_json(&parents);
+}
+
+fn add_thread_fields(op: &mut ApiPost, board: &Board, thread: &ThreadPosts, now: SystemTime) {
+    // counts only take in what's still up, the same as what the thread shows
+    let parent: u64 = thread.thread.get_parent();
+    let stuck: bool = board
+        .get_sticky()
+        .iter()
+        .any(|sticky| sticky.get_parent() == parent && !sticky.is_expired(now));
+    let replies: &[(u64, Post)] = thread.posts.get(1..).unwrap_or_default();
+
+    op.sticky = flag(stuck && board.get_active().contains(&parent));
+    op.closed = flag(thread.thread.get_locked());
+    op.archived = flag(board.get_archive().contains(&parent));
+    op.replies = Some(replies.len());
+    op.images = Some(count_files(replies));
+    op.last_modified = Some(last_modified(&thread.posts));
+}
+
+fn count_files(posts: &[(u64, Post)]) -> usize {
+    return posts
+        .iter()
+        .map(|(_, post)| post.get_attachments().len())
+        .sum();
+}
+
+fn last_modified(posts: &[(u64, Post)]) -> u64 {
+    // edits count, not just new replies
+    return posts
+        .iter()
+        .map(|(_, post)| pages::get_seconds(post.get_modified()))
+        .max()
+        .unwrap_or_default();
+}
+
+fn flag(set: bool) -> Option<u8> {
+    // flags are 1 when set and left out otherwise
+    return match set {
+        true => Some(1),
+        false => None,
+    };
+}
+
+fn split_extension(name: &str) -> (&str, &str) {
+    // the extension keeps its dot
+    return match name.rfind('.') {
+        Some(dot) => (&name[..dot], &name[dot..]),
+        None => (name, ""),
+    };
+}
+
+pub(crate) fn get_thumbnail_alias(tim: &str) -> String {
+    // where clients look for a thumbnail
+    return format!("{}s.{}", tim, THUMBNAIL_EXTENSION);
+}
+
+fn format_now(seconds: u64) -> String {
+    // like 10/19/26(Mon)00:00:00, in UTC
+    let (year, month, day): (i64, u32, u32) = pages::get_date(seconds);
+    let weekday: &str = WEEKDAYS[(seconds / 86400 + 4) as usize % 7]; // 1970 started on a Thursday
+
+    return format!(
+        "{:02}/{:02}/{:02}({}){:02}:{:02}:{:02}",
+        month,
+        day,
+        year.rem_euclid(100),
+        weekday,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    );
+}
+
+fn to_json<T: Serialize>(data: &T) -> String {
+    // nothing here has maps with non-string keys, which is the only way serializing can fail
+    return serde_json::to_string(data).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    const BOARD: &str = "test";
+
+    // every link is live, and always in thread 1
+    struct AllLinks;
+
+    impl LinkResolver for AllLinks {
+        fn find_thread(&self, _board: &str, _post: u64) -> Option<u64> {
+            return Some(1);
+        }
+
+        fn board_exists(&self, _board: &str) -> bool {
+            return true;
+        }
+    }
+
+    fn post(text: &str, files: usize) -> Post {
+        let mut post = Post::new(
+            "owner".to_string(),
+            "Anonymous".to_string(),
+            None,
+            text.to_string(),
+            1,
+        );
+        for index in 0..files {
+            post.add_attachment(
+                Attachment::new(
+                    format!("image {}.png", index),
+                    format!("1-{}.png", index),
+                    "image/png".to_string(),
+                    1024,
+                    Some((1000, 500)),
+                    "hash".to_string(),
+                    false,
+                ),
+                u8::MAX,
+            );
+        }
+        post.modify_poster_id(Some("abcdefgh".to_string()));
+        return post;
+    }
+
+    fn thread_posts(replies: u64) -> ThreadPosts {
+        let mut posts = vec![(1, post("op", 1))];
+        for id in 2..replies + 2 {
+            posts.push((id, post(">>1", (id % 2) as usize)));
+        }
+        return ThreadPosts {
+            thread: Thread::new("subject".to_string(), 1),
+            posts,
+        };
+    }
+
+    fn parse(json: &str) -> Value {
+        return serde_json::from_str(json).unwrap();
+    }
+
+    #[test]
+    fn test_boards() {
+        let mut board = Board::new(BOARD.to_string(), "Tests".to_string());
+        let mut board_config = board.get_config().clone();
+        board_config.modify_poster_ids(true);
+        board.modify_config(board_config);
+        let json = parse(&boards(&[board]));
+
+        let entry = &json["boards"][0];
+        assert_eq!(entry["board"], "test");
+        assert_eq!(entry["title"], "Tests");
+        assert_eq!(entry["per_page"], PER_PAGE);
+        assert_eq!(entry["user_ids"], 1);
+        assert!(entry.get("forced_anon").is_none());
+        assert!(entry["cooldowns"]["replies"].is_u64());
+    }
+
+    #[test]
+    fn test_thread() {
+        let mut board = Board::new(BOARD.to_string(), String::new());
+        board.add_active(1);
+        board.add_sticky(1, 0, None);
+        let mut thread_posts = thread_posts(3);
+        thread_posts.posts[0].1.add_backlink(BOARD.to_string(), 2);
+        thread_posts.posts[0].1.add_backlink("other".to_string(), 9);
+        let json = parse(&thread(&board, &thread_posts, &AllLinks, UNIX_EPOCH));
+        let posts = json["posts"].as_array().unwrap();
+
+        assert_eq!(posts.len(), 4);
+        let op = &posts[0];
+        assert_eq!(op["no"], 1);
+        assert_eq!(op["resto"], 0);
+        assert_eq!(op["sticky"], 1);
+        assert!(op.get("closed").is_none());
+        assert_eq!(op["sub"], "subject");
+        assert_eq!(op["replies"], 3);
+        assert_eq!(op["images"], 1);
+        assert!(op["last_modified"].is_u64());
+        assert_eq!(op["tim"], "1-0");
+        assert_eq!(op["filename"], "image 0");
+        assert_eq!(op["ext"], ".png");
+        assert_eq!(
+            (op["w"].clone(), op["h"].clone()),
+            (1000.into(), 500.into())
+        );
+        assert_eq!(op["tn_w"], board.get_config().get_op_thumbnail_bound());
+        assert!(op.get("id").is_none());
+        assert_eq!(
+            op["backlinks"],
+            serde_json::json!([{"board": "test", "no": 2}, {"board": "other", "no": 9}])
+        );
+
+        let reply = &posts[1];
+        assert_eq!(reply["resto"], 1);
+        assert!(reply.get("backlinks").is_none());
+        assert!(reply.get("sub").is_none());
+        assert!(reply.get("replies").is_none());
+        assert!(reply["com"].as_str().unwrap().contains("quotelink"));
+    }
+
+    #[test]
+    fn test_extra_files() {
+        let board = Board::new(BOARD.to_string(), String::new());
+        let thread_posts = ThreadPosts {
+            thread: Thread::new(String::new(), 1),
+            posts: vec![(1, post("", 3)), (2, post("", 0))],
+        };
+        let json = parse(&thread(&board, &thread_posts, &AllLinks, UNIX_EPOCH));
+
+        let op = &json["posts"][0];
+        assert_eq!(op["tim"], "1-0");
+        assert_eq!(op["extra_files"][1]["tim"], "1-2");
+        assert!(op.get("sub").is_none());
+        assert!(op.get("com").is_none());
+        assert!(op.get("archived").is_none());
+        assert!(json["posts"][1].get("tim").is_none());
+        assert!(json["posts"][1].get("extra_files").is_none());
+    }
+
+    #[test]
+    fn test_catalog() {
+        let board = Board::new(BOARD.to_string(), String::new());
+        let threads: Vec<ThreadPosts> = (0..PER_PAGE + 1).map(|_| thread_posts(8)).collect();
+        let json = parse(&catalog(&board, &threads, &AllLinks, UNIX_EPOCH));
+        let pages = json.as_array().unwrap();
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0]["page"], 1);
+        assert_eq!(pages[0]["threads"].as_array().unwrap().len(), PER_PAGE);
+        assert_eq!(pages[1]["threads"].as_array().unwrap().len(), 1);
+        let op = &pages[0]["threads"][0];
+        assert_eq!(op["replies"], 8);
+        assert_eq!(op["images"], 4);
+        assert_eq!(op["omitted_posts"], 3);
+        assert_eq!(op["omitted_images"], 1);
+        let last_replies = op["last_replies"].as_array().unwrap();
+        assert_eq!(last_replies.len(), CATALOG_REPLIES);
+        assert_eq!(last_replies[4]["no"], 9);
+    }
+
+    #[test]
+    fn test_threads() {
+        let json = parse(&threads(&[thread_posts(2)]));
+        assert_eq!(json[0]["page"], 1);
+        assert_eq!(json[0]["threads"][0]["no"], 1);
+        assert_eq!(json[0]["threads"][0]["replies"], 2);
+        assert!(json[0]["threads"][0]["last_modified"].is_u64());
+        assert_eq!(parse(&threads(&[])), Value::Array(Vec::new()));
+    }
+
+    #[test]
+    fn test_archive() {
+        assert_eq!(archive(&[3, 1]), "[3,1]");
+    }
+
+    #[test]
+    fn test_format_now() {
+        assert_eq!(format_now(0), "01/01/70(Thu)00:00:00");
+        let seconds = UNIX_EPOCH + Duration::from_secs(1792368000 + 3661);
+        assert_eq!(
+            format_now(pages::get_seconds(&seconds)),
+            "10/19/26(Mon)01:01:01"
+        );
+    }
+
+    #[test]
+    fn test_split_extension() {
+        assert_eq!(split_extension("12-0.png"), ("12-0", ".png"));
+        assert_eq!(split_extension("a.tar.gz"), ("a.tar", ".gz"));
+        assert_eq!(split_extension("none"), ("none", ""));
+    }
+}
@@ -0,0 +1,300 @@
+// Post form bodies. The post form is sent as multipart so it can carry files, but plain urlencoded
+// forms are taken too, for clients that never send files.
+// Multipart parsing only covers what browsers send for a form: one level of parts, each with a
+// form-data disposition, and no transfer encodings.
+use super::http;
+use std::collections::HashMap;
+use std::string::String;
+use std::vec::Vec;
+
+const NOKO: &str = "noko";
+const SAGE: &str = "sage";
+
+// one field or file out of a multipart body
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Part {
+    pub name: String,
+    pub filename: Option<String>, // only set for files
+    pub data: Vec<u8>,
+}
+
+// a file as it came in with the form
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FormFile {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+// everything the post form sends. options is the old email field: sage replies without bumping,
+// and noko stays in the thread after replying instead of going back to the board
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct PostForm {
+    pub thread: String,
+    pub name: String,
+    pub subject: String,
+    pub options: String,
+    pub comment: String,
+    pub password: String,
+    pub spoiler: bool,
+    pub files: Vec<FormFile>,
+}
+
+impl PostForm {
+    pub fn from_fields(fields: HashMap<String, String>) -> PostForm {
+        let mut form: PostForm = PostForm::default();
+        for (name, value) in fields {
+            form.set_field(&name, value);
+        }
+
+        return form;
+    }
+
+    pub fn from_parts(parts: Vec<Part>) -> PostForm {
+        // browsers send an empty file part when no file was picked
+        let mut form: PostForm = PostForm::default();
+        for part in parts {
+            match part.filename {
+                Some(_) if part.data.is_empty() => (),
+                Some(filename) => form.files.push(FormFile {
+                    name: filename,
+                    data: part.data,
+                }),
+                None => form.set_field(&part.name, String::from_utf8_lossy(&part.data).to_string()),
+            }
+        }
+
+        return form;
+    }
+
+    fn set_field(&mut self, name: &str, value: String) {
+        match name {
+            "thread" => self.thread = value,
+            "name" => self.name = value,
+            "subject" => self.subject = value,
+            "options" => self.options = value,
+            "comment" => self.comment = value,
+            "password" => self.password = value,
+            "spoiler" => self.spoiler = !value.is_empty(),
+            _ => (),
+        }
+    }
+
+    pub fn get_sage(&self) -> bool {
+        return self.has_option(SAGE);
+    }
+
+    pub fn get_noko(&self) -> bool {
+        return self.has_option(NOKO);
+    }
+
+    fn has_option(&self, option: &str) -> bool {
+        return self
+            .options
+            .split_whitespace()
+            .any(|given| given.eq_ignore_ascii_case(option));
+    }
+}
+
+pub(crate) fn parse(content_type: &str, body: &[u8]) -> Option<PostForm> {
+    // None if the body isn't what its content type says
+    let (mime, parameters): (&str, &str) =
+        content_type.split_once(';').unwrap_or((content_type, ""));
+
+    return match mime.trim().to_ascii_lowercase().as_str() {
+        "application/x-www-form-urlencoded" => Some(PostForm::from_fields(http::parse_form(body))),
+        "multipart/form-data" => {
+            let boundary: String = get_parameter(parameters, "boundary")?;
+            Some(PostForm::from_parts(parse_multipart(&boundary, body)?))
+        }
+        _ => None,
+    };
+}
+
+fn get_parameter(parameters: &str, name: &str) -> Option<String> {
+    // header parameters, like boundary=abc or name="abc"
+    for parameter in parameters.split(';') {
+        if let Some((key, value)) = parameter.trim().split_once('=') {
+            if key.trim().eq_ignore_ascii_case(name) {
+                return Some(value.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+
+    return None;
+}
+
+pub(crate) fn parse_multipart(boundary: &str, body: &[u8]) -> Option<Vec<Part>> {
+    let delimiter: Vec<u8> = format!("--{}", boundary).into_bytes();
+    let separator: Vec<u8> = format!("\r\n--{}", boundary).into_bytes();
+    if boundary.is_empty() {
+        return None;
+    }
+
+    let mut parts: Vec<Part> = Vec::new();
+    let mut position: usize = find(body, &delimiter, 0)? + delimiter.len();
+
+    loop {
+        // the last delimiter has -- after it, every other one a line break
+        let rest: &[u8] = &body[position..];
+        if rest.starts_with(b"--") {
+            return Some(parts);
+        }
+        position += rest.strip_prefix(b"\r\n").map(|_| 2)?;
+
+        let header_end: usize = find(body, b"\r\n\r\n", position)?;
+        let headers: &str = std::str::from_utf8(&body[position..header_end]).ok()?;
+        let data_start: usize = header_end + 4;
+        let data_end: usize = find(body, &separator, data_start)?;
+
+        parts.push(parse_part(headers, &body[data_start..data_end])?);
+        position = data_end + separator.len();
+    }
+}
+
+fn parse_part(headers: &str, data: &[u8]) -> Option<Part> {
+    for header in headers.split("\r\n") {
+        let Some((field, value)) = header.split_once(':') else {
+            continue;
+        };
+        if !field.trim().eq_ignore_ascii_case("content-disposition") {
+            continue;
+        }
+
+        // some browsers send the whole path, only the file's own name is wanted
+        let filename: Option<String> = get_parameter(value, "filename").map(|filename| {
+            filename
+                .rsplit(['/', '\\'])
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        });
+        return Some(Part {
+            name: get_parameter(value, "name")?,
+            filename,
+            data: data.to_vec(),
+        });
+    }
+
+    return None;
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    return haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|found| found + from);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARY: &str = "----boundary";
+
+    fn multipart(parts: &[(&str, Option<&str>, &[u8])]) -> Vec<u8> {
+        let mut body: Vec<u8> = Vec::new();
+        for (name, filename, data) in parts {
+            body.extend(format!("--{}\r\n", BOUNDARY).as_bytes());
+            match filename {
+                Some(filename) => body.extend(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+                         Content-Type: application/octet-stream\r\n\r\n",
+                        name, filename
+                    )
+                    .as_bytes(),
+                ),
+                None => body.extend(
+                    format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name).as_bytes(),
+                ),
+            }
+            body.extend(*data);
+            body.extend(b"\r\n");
+        }
+        body.extend(format!("--{}--\r\n", BOUNDARY).as_bytes());
+        return body;
+    }
+
+    #[test]
+    fn test_parse_multipart() {
+        let body = multipart(&[
+            ("comment", None, b"line\r\nline"),
+            ("file", Some("C:\\images\\a.png"), b"\x89PNG\r\n--not"),
+        ]);
+        let parts = parse_multipart(BOUNDARY, &body).unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "comment");
+        assert_eq!(parts[0].filename, None);
+        assert_eq!(parts[0].data, b"line\r\nline");
+        assert_eq!(parts[1].filename, Some("a.png".to_string()));
+        assert_eq!(parts[1].data, b"\x89PNG\r\n--not");
+    }
+
+    #[test]
+    fn test_parse_multipart_malformed() {
+        assert_eq!(parse_multipart(BOUNDARY, b"nothing"), None);
+        assert_eq!(parse_multipart("", b"--\r\n"), None);
+        // cut off before the closing delimiter
+        let body = multipart(&[("comment", None, b"text")]);
+        assert_eq!(parse_multipart(BOUNDARY, &body[..body.len() - 20]), None);
+        // a part without a name
+        let body = format!(
+            "--{}\r\nContent-Type: text/plain\r\n\r\ntext\r\n--{}--",
+            BOUNDARY, BOUNDARY
+        );
+        assert_eq!(parse_multipart(BOUNDARY, body.as_bytes()), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let body = multipart(&[
+            ("thread", None, b"12"),
+            ("name", None, b"anon"),
+            ("options", None, b"Sage noko"),
+            ("comment", None, b"text"),
+            ("password", None, b"hunter2"),
+            ("spoiler", None, b"on"),
+            ("file", Some("a.pdf"), b"%PDF-1.7\n"),
+            ("file", Some(""), b""),
+        ]);
+        let form = parse(
+            &format!("multipart/form-data; boundary=\"{}\"", BOUNDARY),
+            &body,
+        )
+        .unwrap();
+
+        assert_eq!(form.thread, "12");
+        assert_eq!(form.name, "anon");
+        assert_eq!(form.comment, "text");
+        assert_eq!(form.password, "hunter2");
+        assert!(form.spoiler);
+        assert!(form.get_sage());
+        assert!(form.get_noko());
+        assert_eq!(
+            form.files,
+            vec![FormFile {
+                name: "a.pdf".to_string(),
+                data: b"%PDF-1.7\n".to_vec()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_urlencoded() {
+        let form = parse(
+            "application/x-www-form-urlencoded",
+            b"subject=hi&comment=a+b&options=",
+        )
+        .unwrap();
+        assert_eq!(form.subject, "hi");
+        assert_eq!(form.comment, "a b");
+        assert!(!form.get_sage());
+        assert!(!form.get_noko());
+        assert!(form.files.is_empty());
+
+        assert_eq!(parse("text/plain", b"comment=text"), None);
+        assert_eq!(parse("multipart/form-data", b""), None);
+    }
+}
@@ -0,0 +1,274 @@
+// The bits of HTTP the routes need on top of tiny_http: building responses, reading the owner
+// cookie, reading and parsing form bodies and query strings, and turning request paths into decoded
+// segments.
+// Header values have to be ASCII, so anything going into one (redirects to boards with non-ASCII
+// names, mostly) is percent-encoded first.
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use std::collections::HashMap;
+use std::io::{Error, Read};
+use std::string::String;
+use std::vec::Vec;
+use tiny_http::{Header, Request, Response, ResponseBox};
+
+pub(crate) const OWNER_COOKIE: &str = "owner";
+const OWNER_LENGTH: usize = 16; // bytes, hex encoded in the cookie
+const OWNER_MAX_AGE: u64 = 2 * 365 * 24 * 60 * 60;
+
+// everything that can't appear as is in a single path segment
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+pub(crate) fn html(status: u16, body: String) -> ResponseBox {
+    return Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "text/html; charset=utf-8"))
+        .boxed();
+}
+
+pub(crate) fn json(status: u16, body: String) -> ResponseBox {
+    // the read API is public, so any site's scripts can read it
+    return Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+        .with_header(header("Access-Control-Allow-Origin", "*"))
+        .boxed();
+}
+
+pub(crate) fn private_json(status: u16, body: String) -> ResponseBox {
+    // the write API has no CORS header, so a browser won't let a script on another site send it a
+    // token or read what comes back. tokens are for bots and moderation tools, not other sites
+    return Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+        .boxed();
+}
+
+pub(crate) fn file(mime: &str, data: Vec<u8>) -> ResponseBox {
+    // uploads are served as exactly what they were detected as, browsers shouldn't guess
+    return Response::from_data(data)
+        .with_header(header("Content-Type", mime))
+        .with_header(header("X-Content-Type-Options", "nosniff"))
+        .boxed();
+}
+
+pub(crate) fn redirect(status: u16, location: &str) -> ResponseBox {
+    return Response::empty(status)
+        .with_header(header("Location", location))
+        .boxed();
+}
+
+pub(crate) fn board_location(board: &str) -> String {
+    return format!("/{}/", encode_segment(board));
+}
+
+pub(crate) fn page_location(board: &str, page: usize) -> String {
+    // the first page is the board itself
+    return match page {
+        0 | 1 => board_location(board),
+        page => format!("{}{}", board_location(board), page),
+    };
+}
+
+pub(crate) fn thread_location(board: &str, thread: u64, post: Option<u64>) -> String {
+    return match post {
+        Some(post) => format!("/{}/thread/{}#p{}", encode_segment(board), thread, post),
+        None => format!("/{}/thread/{}", encode_segment(board), thread),
+    };
+}
+
+pub(crate) fn encode_segment(segment: &str) -> String {
+    return utf8_percent_encode(segment, SEGMENT).to_string();
+}
+
+fn header(name: &str, value: &str) -> Header {
+    // only ever called with ASCII, anything from a request is encoded before it gets here
+    return Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap();
+}
+
+pub(crate) fn path_segments(url: &str) -> Option<Vec<String>> {
+    // the path after the leading slash, split and decoded. "/" is a single empty segment, and a
+    // trailing slash leaves an empty segment at the end. None if a segment isn't valid UTF-8
+    let path: &str = url.split(['?', '#']).next().unwrap_or_default();
+    let path: &str = path.strip_prefix('/').unwrap_or(path);
+
+    let mut segments: Vec<String> = Vec::new();
+    for segment in path.split('/') {
+        segments.push(percent_decode_str(segment).decode_utf8().ok()?.to_string());
+    }
+
+    return Some(segments);
+}
+
+pub(crate) fn get_ip(request: &Request) -> String {
+    return match request.remote_addr() {
+        Some(address) => address.ip().to_string(),
+        None => String::new(),
+    };
+}
+
+pub(crate) fn get_header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    return request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str());
+}
+
+pub(crate) fn get_cookie(request: &Request, name: &str) -> Option<String> {
+    for header in request.headers() {
+        if !header.field.equiv("Cookie") {
+            continue;
+        }
+
+        for pair in header.value.as_str().split(';') {
+            if let Some((key, value)) = pair.trim().split_once('=') {
+                if key == name {
+                    return Some(value.to_string());
+                }
+            }
+        }
+    }
+
+    return None;
+}
+
+pub(crate) fn get_owner(request: &Request) -> Option<String> {
+    // only cookies that look like ones handed out here, owners are stored with a ; separator
+    return get_cookie(request, OWNER_COOKIE).filter(|owner| {
+        owner.len() == OWNER_LENGTH * 2 && owner.chars().all(|c| c.is_ascii_hexdigit())
+    });
+}
+
+pub(crate) fn new_owner() -> Result<String, Error> {
+    let mut owner: [u8; OWNER_LENGTH] = [0; OWNER_LENGTH];
+    getrandom::fill(&mut owner).map_err(Error::other)?;
+    return Ok(hex::encode(owner));
+}
+
+pub(crate) fn with_owner(response: ResponseBox, owner: &str) -> ResponseBox {
+    // scripts never need the owner cookie, it's only ever sent back with forms
+    return response.with_header(header(
+        "Set-Cookie",
+        &format!(
+            "{}={}; Max-Age={}; Path=/; HttpOnly; SameSite=Lax",
+            OWNER_COOKIE, owner, OWNER_MAX_AGE
+        ),
+    ));
+}
+
+pub(crate) fn read_body(request: &mut Request, limit: usize) -> Result<Option<Vec<u8>>, Error> {
+    // None if the body is over the limit, which is checked as it's read so nothing big is kept
+    let mut body: Vec<u8> = Vec::new();
+    request
+        .as_reader()
+        .take(limit as u64 + 1)
+        .read_to_end(&mut body)?;
+
+    return match body.len() > limit {
+        true => Ok(None),
+        false => Ok(Some(body)),
+    };
+}
+
+pub(crate) fn parse_form(body: &[u8]) -> HashMap<String, String> {
+    // urlencoded fields, the last one wins if a field is sent twice
+    return form_urlencoded::parse(body).into_owned().collect();
+}
+
+pub(crate) fn parse_query(url: &str) -> HashMap<String, String> {
+    // the query string is urlencoded the same as a form
+    let query: &str = match url.split('#').next().unwrap_or_default().split_once('?') {
+        Some((_, query)) => query,
+        None => "",
+    };
+    return parse_form(query.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_segments() {
+        assert_eq!(path_segments("/"), Some(vec![String::new()]));
+        assert_eq!(
+            path_segments("/test/"),
+            Some(vec!["test".to_string(), String::new()])
+        );
+        assert_eq!(
+            path_segments("/test/thread/12?reply=1#p13"),
+            Some(vec![
+                "test".to_string(),
+                "thread".to_string(),
+                "12".to_string()
+            ])
+        );
+        assert_eq!(
+            path_segments("/%CE%B1/"),
+            Some(vec!["\u{3B1}".to_string(), String::new()])
+        );
+        assert_eq!(path_segments("/%FF/"), None);
+    }
+
+    #[test]
+    fn test_encode_segment() {
+        assert_eq!(encode_segment("test"), "test");
+        assert_eq!(encode_segment("\u{3B1}"), "%CE%B1");
+        assert_eq!(encode_segment("a/b?c"), "a%2Fb%3Fc");
+    }
+
+    #[test]
+    fn test_board_location() {
+        assert_eq!(board_location("\u{3B1}"), "/%CE%B1/");
+    }
+
+    #[test]
+    fn test_page_location() {
+        assert_eq!(page_location("test", 1), "/test/");
+        assert_eq!(page_location("test", 2), "/test/2");
+        assert_eq!(page_location("\u{3B1}", 3), "/%CE%B1/3");
+    }
+
+    #[test]
+    fn test_thread_location() {
+        assert_eq!(thread_location("test", 12, None), "/test/thread/12");
+        assert_eq!(
+            thread_location("\u{3B1}", 12, Some(13)),
+            "/%CE%B1/thread/12#p13"
+        );
+    }
+
+    #[test]
+    fn test_new_owner() {
+        let owner = new_owner().unwrap();
+        assert_eq!(owner.len(), OWNER_LENGTH * 2);
+        assert_ne!(owner, new_owner().unwrap());
+    }
+
+    #[test]
+    fn test_parse_form() {
+        let form = parse_form(b"name=anon%21&comment=a+b%0Ac&comment=last");
+        assert_eq!(form["name"], "anon!");
+        assert_eq!(form["comment"], "last");
+        assert_eq!(parse_form(b"").len(), 0);
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query("/test/catalog?sort=replies&q=two+words#top");
+        assert_eq!(query["sort"], "replies");
+        assert_eq!(query["q"], "two words");
+        assert_eq!(parse_query("/test/catalog").len(), 0);
+        assert_eq!(parse_query("/test/catalog#a?b=c").len(), 0);
+    }
+}
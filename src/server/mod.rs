@@ -0,0 +1,541 @@
+// The HTTP server. A fixed pool of worker threads takes requests off a single listener, and every
+// page is read straight out of storage, so there's no cache to go stale.
+// Writes are the exception: post IDs come from each board's counter, so posts are only ever made
+// one at a time, under the same lock as the posting cooldowns. Moderator actions from the write API
+// take that lock too, so they can't cross paths with a post.
+mod api;
+mod form;
+mod http;
+mod pages;
+mod routes;
+mod templates;
+mod write_api;
+
+use crate::core::api_tokens::RateLimits;
+use crate::core::posting::Cooldowns;
+use crate::core::storage::config::Config;
+use crate::core::storage::site_storage;
+use std::collections::HashMap;
+use std::io::Error;
+use std::string::String;
+use std::sync::Mutex;
+use std::thread;
+use std::vec::Vec;
+use templates::Templates;
+use tiny_http::Server;
+
+pub(crate) const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+pub(crate) const DEFAULT_WORKERS: usize = 4;
+
+pub(crate) struct ServerConfig {
+    address: String,
+    workers: usize,
+    root: String,
+}
+
+impl ServerConfig {
+    pub fn new(address: String, workers: usize, root: String) -> ServerConfig {
+        let server_config: ServerConfig = ServerConfig {
+            address,
+            workers: workers.max(1),
+            root,
+        };
+
+        return server_config;
+    }
+
+    pub fn get_address(&self) -> &String {
+        return &self.address;
+    }
+
+    pub fn get_workers(&self) -> usize {
+        return self.workers;
+    }
+
+    pub fn get_root(&self) -> &String {
+        return &self.root;
+    }
+}
+
+// everything the workers share
+pub(crate) struct State {
+    config: Config,
+    boards: Vec<String>,
+    templates: Templates,
+    cooldowns: Mutex<HashMap<String, Cooldowns>>, // by board
+    rate_limits: Mutex<RateLimits>,               // for API tokens
+}
+
+impl State {
+    pub fn new(config: Config, boards: Vec<String>, templates: Templates) -> State {
+        let state: State = State {
+            config,
+            boards,
+            templates,
+            cooldowns: Mutex::new(HashMap::new()),
+            rate_limits: Mutex::new(RateLimits::new()),
+        };
+
+        return state;
+    }
+
+    pub fn get_config(&self) -> &Config {
+        return &self.config;
+    }
+
+    pub fn get_boards(&self) -> &Vec<String> {
+        // the boards from the site config at startup, adding one takes a restart
+        return &self.boards;
+    }
+
+    pub fn get_templates(&self) -> &Templates {
+        return &self.templates;
+    }
+
+    pub fn get_cooldowns(&self) -> &Mutex<HashMap<String, Cooldowns>> {
+        // also the lock every write is made under, along with board_storage::lock_boards() for
+        // the command line
+        return &self.cooldowns;
+    }
+
+    pub fn get_rate_limits(&self) -> &Mutex<RateLimits> {
+        return &self.rate_limits;
+    }
+}
+
+pub(crate) fn run(server_config: &ServerConfig) -> Result<(), Error> {
+    // only returns if the server can't be started
+    let config: Config = Config::new(server_config.get_root().clone());
+    let boards: Vec<String> = site_storage::initialize(&config)?;
+    let templates: Templates = Templates::load(&config)?;
+    let server: Server = Server::http(server_config.get_address()).map_err(Error::other)?;
+
+    println!(
+        "serving {} boards on {} with {} workers",
+        boards.len(),
+        server_config.get_address(),
+        server_config.get_workers()
+    );
+    serve(
+        &server,
+        &State::new(config, boards, templates),
+        server_config.get_workers(),
+    );
+
+    return Ok(());
+}
+
+pub(crate) fn serve(server: &Server, state: &State, workers: usize) {
+    // each worker stops once the server is unblocked, this returns when they all have
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                for request in server.incoming_requests() {
+                    routes::handle(state, request);
+                }
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::api_tokens;
+    use crate::core::storage::board_storage;
+    use crate::core::storage::fixtures::TestRoot;
+    use crate::core::storage::post_storage;
+    use crate::core::storage::thread_storage;
+    use crate::core::structs::api_token::Scope;
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+    const BOUNDARY: &str = "----boundary";
+
+    fn test_config(name: &str) -> TestRoot {
+        return TestRoot::new("server", name);
+    }
+
+    fn send(address: SocketAddr, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        stream.write_all(request).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        return response;
+    }
+
+    fn get(address: SocketAddr, path: &str) -> String {
+        return send(
+            address,
+            &format!(
+                "GET {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n",
+                path
+            )
+            .into_bytes(),
+        );
+    }
+
+    fn post(address: SocketAddr, content_type: &str, body: &[u8]) -> String {
+        let mut request: Vec<u8> = format!(
+            "POST /test/post HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\
+             Content-Type: {}\r\nContent-Length: {}\r\n\r\n",
+            content_type,
+            body.len()
+        )
+        .into_bytes();
+        request.extend(body);
+        return send(address, &request);
+    }
+
+    fn form(address: SocketAddr, path: &str, owner: &str, body: &str) -> String {
+        return send(
+            address,
+            &format!(
+                "POST {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\
+                 Cookie: owner={}\r\nContent-Type: application/x-www-form-urlencoded\r\n\
+                 Content-Length: {}\r\n\r\n{}",
+                path,
+                owner,
+                body.len(),
+                body
+            )
+            .into_bytes(),
+        );
+    }
+
+    fn api(address: SocketAddr, action: &str, token: &str, body: &str) -> String {
+        return send(
+            address,
+            &format!(
+                "POST /test/api/{} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\
+                 Authorization: Bearer {}\r\nContent-Type: application/json\r\n\
+                 Content-Length: {}\r\n\r\n{}",
+                action,
+                token,
+                body.len(),
+                body
+            )
+            .into_bytes(),
+        );
+    }
+
+    fn multipart(fields: &[(&str, &str)], file: &[u8]) -> Vec<u8> {
+        let mut body: Vec<u8> = Vec::new();
+        for (name, value) in fields {
+            body.extend(
+                format!(
+                    "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                    BOUNDARY, name, value
+                )
+                .as_bytes(),
+            );
+        }
+        body.extend(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.pdf\"\r\n\
+                 Content-Type: application/pdf\r\n\r\n",
+                BOUNDARY
+            )
+            .as_bytes(),
+        );
+        body.extend(file);
+        body.extend(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+        return body;
+    }
+
+    #[test]
+    fn server_config_init() {
+        let server_config = ServerConfig::new(DEFAULT_ADDRESS.to_string(), 0, "./".to_string());
+        assert_eq!(server_config.get_address(), DEFAULT_ADDRESS);
+        assert_eq!(server_config.get_workers(), 1);
+        assert_eq!(server_config.get_root(), "./");
+    }
+
+    #[test]
+    fn test_serve() {
+        let config = test_config("serve");
+        let boards = site_storage::initialize(&config).unwrap();
+        // the first thread goes up without a file, and everything is posted from the same address
+        let mut board = board_storage::read_board(&config, "test").unwrap();
+        let mut board_config = board.get_config().clone();
+        board_config.modify_op_requires_image(false);
+        board_config.modify_reply_cooldown(0);
+        board_config.modify_thread_cooldown(0);
+        board.modify_config(board_config);
+        board_storage::write_board(&config, &board).unwrap();
+
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        // the state gets a config of its own for the same root, which is removed with the fixture
+        let root = Config::new(config.get_root().to_string_lossy().to_string());
+        let state = State::new(root, boards, Templates::new());
+        let multipart_type = format!("multipart/form-data; boundary={}", BOUNDARY);
+
+        // everything is checked after the server stops, so a failure can't leave it running
+        let responses: Vec<String> = thread::scope(|scope| {
+            scope.spawn(|| serve(&server, &state, 1));
+            let responses = vec![
+                get(address, "/"),
+                get(address, "/test"),
+                get(address, "/nope/"),
+                post(
+                    address,
+                    "application/x-www-form-urlencoded",
+                    b"subject=hello&comment=first+post",
+                ),
+                post(
+                    address,
+                    &multipart_type,
+                    &multipart(
+                        &[("thread", "1"), ("options", "sage"), ("password", "pw")],
+                        b"%PDF-1.7\n",
+                    ),
+                ),
+                post(
+                    address,
+                    &multipart_type,
+                    &multipart(
+                        &[("thread", "1"), ("options", "noko"), ("comment", ">>1 hi")],
+                        b"",
+                    ),
+                ),
+                post(
+                    address,
+                    &multipart_type,
+                    &multipart(&[("thread", "1"), ("name", "keep me")], b"not a pdf"),
+                ),
+                get(address, "/test/thread/1"),
+                get(address, "/test/"),
+                get(address, "/test/catalog"),
+                get(address, "/boards.json"),
+                get(address, "/test/thread/1.json"),
+                get(address, "/test/catalog.json"),
+                get(address, "/test/threads.json"),
+                get(address, "/test/archive.json"),
+                get(address, "/test/thread/2.json"),
+                get(address, "/test/catalog?sort=replies&q=FIRST"),
+                get(address, "/test/catalog?q=nothing+like+it"),
+                get(address, "/test/2"),
+            ];
+            server.unblock();
+            return responses;
+        });
+
+        let [landing, redirect, missing, posted, file, noko, rejected, thread, index, catalog, boards_json, thread_json, catalog_json, threads_json, archive_json, reply_json, sorted, filtered, past_last] =
+            responses.as_slice()
+        else {
+            panic!("expected nineteen responses");
+        };
+        assert!(landing.starts_with("HTTP/1.1 200"));
+        assert!(landing.contains("<a href=\"/test/\">/test/</a>"));
+        assert!(redirect.starts_with("HTTP/1.1 301"));
+        assert!(missing.starts_with("HTTP/1.1 404"));
+        assert!(posted.starts_with("HTTP/1.1 303"));
+        assert!(posted.contains("Location: /test/thread/1#p1"));
+        assert!(posted.contains("Set-Cookie: owner="));
+        assert!(file.contains("Location: /test/\r\n"));
+        assert!(noko.contains("Location: /test/thread/1#p3"));
+        assert!(rejected.starts_with("HTTP/1.1 400"));
+        assert!(rejected.contains("value=\"keep me\""));
+        assert!(thread.starts_with("HTTP/1.1 200"));
+        assert!(thread.contains("first post"));
+        assert!(thread.contains("<a href=\"/test/src/2-0.pdf\">a.pdf</a>"));
+        assert!(index.contains("hello"));
+        assert!(index.contains("[<b>1</b>]"));
+        assert!(catalog.contains("first post"));
+        assert!(boards_json.contains("Content-Type: application/json"));
+        assert!(boards_json.contains("Access-Control-Allow-Origin: *"));
+        assert!(boards_json.contains("{\"board\":\"test\","));
+        assert!(thread_json.contains("\"sub\":\"hello\""));
+        assert!(thread_json.contains("\"tim\":\"2-0\",\"filename\":\"a\",\"ext\":\".pdf\""));
+        assert!(thread_json.contains("\"replies\":2,\"images\":1"));
+        assert!(thread_json.contains("\"backlinks\":[{\"board\":\"test\",\"no\":3}]"));
+        assert!(catalog_json.contains("\"omitted_posts\":0"));
+        assert!(threads_json.contains("[{\"page\":1,\"threads\":[{\"no\":1,"));
+        assert!(archive_json.ends_with("\r\n\r\n[]"));
+        assert!(reply_json.starts_with("HTTP/1.1 404"));
+        assert!(sorted.contains("<option value=\"replies\" selected>"));
+        assert!(sorted.contains("R: 2 / I: 1"));
+        assert!(filtered.contains("No threads found."));
+        assert!(past_last.starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn test_own_posts() {
+        let config = test_config("own_posts");
+        let boards = site_storage::initialize(&config).unwrap();
+        let mut board = board_storage::read_board(&config, "test").unwrap();
+        let mut board_config = board.get_config().clone();
+        board_config.modify_op_requires_image(false);
+        board_config.modify_reply_cooldown(0);
+        board_config.modify_thread_cooldown(0);
+        board_config.modify_max_comment_length(20);
+        board.modify_config(board_config);
+        board_storage::write_board(&config, &board).unwrap();
+
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        // the state gets a config of its own for the same root, which is removed with the fixture
+        let root = Config::new(config.get_root().to_string_lossy().to_string());
+        let state = State::new(root, boards, Templates::new());
+        let mine = "0123456789abcdef0123456789abcdef";
+        let theirs = "fedcba9876543210fedcba9876543210";
+
+        let responses: Vec<String> = thread::scope(|scope| {
+            scope.spawn(|| serve(&server, &state, 1));
+            let responses = vec![
+                form(address, "/test/post", mine, "subject=s&comment=op"),
+                form(
+                    address,
+                    "/test/post",
+                    mine,
+                    "thread=1&comment=typo&password=pw",
+                ),
+                form(address, "/test/edit", mine, "post=2&comment=fixed"),
+                form(address, "/test/edit", theirs, "post=2&comment=stolen"),
+                form(
+                    address,
+                    "/test/edit",
+                    mine,
+                    "post=2&comment=far+too+long+for+this+board",
+                ),
+                get(address, "/test/edit/2"),
+                form(address, "/test/delete", theirs, "post=2&password=pw"),
+                get(address, "/test/thread/1"),
+                get(address, "/test/edit/2"),
+            ];
+            server.unblock();
+            return responses;
+        });
+
+        let [_, _, edited, stolen, too_long, edit_form, deleted, thread, gone] =
+            responses.as_slice()
+        else {
+            panic!("expected nine responses");
+        };
+        assert!(edited.starts_with("HTTP/1.1 303"));
+        assert!(edited.contains("Location: /test/thread/1#p2"));
+        assert!(stolen.starts_with("HTTP/1.1 403"));
+        assert!(too_long.starts_with("HTTP/1.1 400"));
+        assert!(too_long.contains(">far too long for this board</textarea>"));
+        assert!(edit_form.contains(">fixed</textarea>"));
+        assert!(deleted.starts_with("HTTP/1.1 303"));
+        assert!(deleted.contains("Location: /test/thread/1\r\n"));
+        assert!(thread.contains("op"));
+        assert!(!thread.contains("id=\"p2\""));
+        assert!(gone.starts_with("HTTP/1.1 404"));
+
+        let reply = post_storage::read_post(state.get_config(), "test", 2).unwrap();
+        assert_eq!(reply.get_revisions()[0].get_text(), "typo");
+    }
+
+    #[test]
+    fn test_write_api() {
+        let config = test_config("write_api");
+        let boards = site_storage::initialize(&config).unwrap();
+        let mut board = board_storage::read_board(&config, "test").unwrap();
+        let mut board_config = board.get_config().clone();
+        board_config.modify_op_requires_image(false);
+        board_config.modify_reply_cooldown(0);
+        board_config.modify_thread_cooldown(0);
+        board.modify_config(board_config);
+        board_storage::write_board(&config, &board).unwrap();
+        let moderator = api_tokens::add_token(
+            &config,
+            "moderator",
+            vec![Scope::Post, Scope::Lock, Scope::Delete, Scope::Ban],
+            0,
+        )
+        .unwrap();
+        let poster = api_tokens::add_token(&config, "poster", vec![Scope::Post], 1).unwrap();
+
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        // the state gets a config of its own for the same root, which is removed with the fixture
+        let root = Config::new(config.get_root().to_string_lossy().to_string());
+        let state = State::new(root, boards, Templates::new());
+
+        let responses: Vec<String> = thread::scope(|scope| {
+            scope.spawn(|| serve(&server, &state, 1));
+            let responses = vec![
+                api(address, "post", "guess", r#"{"comment": "hi"}"#),
+                api(
+                    address,
+                    "post",
+                    &moderator,
+                    r#"{"subject": "bot thread", "comment": "hi"}"#,
+                ),
+                api(
+                    address,
+                    "post",
+                    &moderator,
+                    r#"{"thread": 1, "files": [{"name": "a.pdf", "data": "JVBERi0xLjcK"}]}"#,
+                ),
+                api(address, "post", &moderator, r#"{"thread": 1}"#),
+                api(address, "post", &poster, r#"{"comment": "one"}"#),
+                api(address, "post", &poster, r#"{"comment": "two"}"#),
+                api(address, "lock", &poster, r#"{"thread": 1}"#),
+                api(address, "lock", &moderator, r#"{"thread": 1}"#),
+                api(
+                    address,
+                    "post",
+                    &moderator,
+                    r#"{"thread": 1, "comment": "hi"}"#,
+                ),
+                api(address, "delete", &moderator, r#"{"post": 2}"#),
+                api(address, "delete", &moderator, r#"{"post": 9}"#),
+                api(
+                    address,
+                    "ban",
+                    &moderator,
+                    r#"{"post": 1, "reason": "spam"}"#,
+                ),
+                api(address, "post", &moderator, r#"{"comment": "hi"}"#),
+                api(address, "sticky", &moderator, r#"{"thread": 1}"#),
+            ];
+            server.unblock();
+            return responses;
+        });
+
+        let [unauthorized, thread, reply, empty, first, limited, forbidden, locked, closed, deleted, missing, banned, rejected, no_scope] =
+            responses.as_slice()
+        else {
+            panic!("expected fourteen responses");
+        };
+        assert!(unauthorized.starts_with("HTTP/1.1 401"));
+        assert!(unauthorized.contains("\"error\":\"unauthorized\""));
+        assert!(thread.starts_with("HTTP/1.1 201"));
+        assert!(thread.ends_with("{\"thread\":1,\"post\":1}"));
+        assert!(!thread.contains("Access-Control-Allow-Origin"));
+        assert!(!unauthorized.contains("Access-Control-Allow-Origin"));
+        assert!(reply.ends_with("{\"thread\":1,\"post\":2}"));
+        assert!(empty.starts_with("HTTP/1.1 400"));
+        assert!(empty.contains("\"error\":\"empty\""));
+        assert!(first.ends_with("{\"thread\":3,\"post\":3}"));
+        assert!(limited.starts_with("HTTP/1.1 429"));
+        assert!(limited.contains("\"error\":\"rate_limited\""));
+        assert!(limited.contains("\"retry_after\":"));
+        assert!(forbidden.starts_with("HTTP/1.1 403"));
+        assert!(forbidden.contains("\"error\":\"forbidden\""));
+        assert!(locked.ends_with("{\"thread\":1,\"locked\":true}"));
+        assert!(closed.contains("\"error\":\"thread_locked\""));
+        assert!(deleted.ends_with("{\"post\":2,\"deleted\":true}"));
+        assert!(missing.contains("\"error\":\"post_not_found\""));
+        assert!(banned.starts_with("HTTP/1.1 200"));
+        assert!(rejected.starts_with("HTTP/1.1 403"));
+        assert!(rejected.contains("\"error\":\"banned\""));
+        assert!(!rejected.contains("\"expires\""));
+        assert!(no_scope.contains("\"error\":\"forbidden\""));
+
+        let config = state.get_config();
+        assert!(thread_storage::read_thread(config, "test", 1)
+            .unwrap()
+            .get_locked());
+        assert!(post_storage::read_post(config, "test", 2)
+            .unwrap()
+            .get_deleted());
+    }
+}
@@ -0,0 +1,688 @@
+// HTML for every page on the site, filled into the templates from the core structs. Anything that
+// came from a poster or an admin is escaped by the templates, and post text goes through the
+// markup renderer, which does its own escaping.
+// Times are shown in UTC, so pages look the same to everyone and nothing needs a time zone database.
+use super::form::PostForm;
+use super::http;
+use super::templates::Templates;
+use crate::core::catalog::{CatalogSort, CatalogThread, ALL_SORTS};
+use crate::core::index::IndexPage;
+use crate::core::markup::render::{self, LinkResolver};
+use crate::core::media::thumbnail::ThumbnailSize;
+use crate::core::storage::attachment_storage;
+use crate::core::structs::attachment::Attachment;
+use crate::core::structs::board::Board;
+use crate::core::structs::post::Post;
+use crate::core::structs::thread::Thread;
+use std::string::String;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+
+const EXCERPT_LENGTH: usize = 120; // characters of the OP shown in the catalog and archive
+const SORT_KEYS: [&str; 4] = [
+    "sort_bump",
+    "sort_created",
+    "sort_replies",
+    "sort_last_reply",
+];
+
+// a thread as it shows up in the archive, just the OP
+pub(crate) struct ThreadSummary {
+    pub thread: Thread,
+    pub op: Post,
+}
+
+pub(crate) fn landing(templates: &Templates, boards: &[Board]) -> String {
+    let mut list: String = String::new();
+    for board in boards {
+        list.push_str(&templates.render(
+            "landing_board",
+            &[
+                ("board", board.get_name()),
+                ("description", board.get_description()),
+            ],
+        ));
+    }
+
+    let body: String = templates.render("landing", &[("boards", &list)]);
+    return layout(templates, "Boards", &body);
+}
+
+pub(crate) fn index(
+    templates: &Templates,
+    board: &Board,
+    index_page: &IndexPage,
+    links: &dyn LinkResolver,
+) -> String {
+    let mut threads: String = String::new();
+    for preview in &index_page.threads {
+        let parent: u64 = preview.thread.get_parent();
+        let mut op: String = String::new();
+        let mut replies: String = String::new();
+        for (id, post) in &preview.posts {
+            let rendered: String = render_post(templates, board, &preview.thread, *id, post, links);
+            match *id == parent {
+                true => op.push_str(&rendered),
+                false => replies.push_str(&rendered),
+            }
+        }
+
+        let omitted: String = match preview.omitted {
+            0 => String::new(),
+            omitted => omitted.to_string(),
+        };
+        threads.push_str(&templates.render(
+            "index_thread",
+            &[
+                ("board", board.get_name()),
+                ("parent", &parent.to_string()),
+                ("op", &op),
+                ("omitted", &omitted),
+                ("replies", &replies),
+            ],
+        ));
+    }
+
+    let mut page_links: String = String::new();
+    for page in 1..=index_page.page_count {
+        let current: &str = match page == index_page.page {
+            true => "current",
+            false => "",
+        };
+        page_links.push_str(&templates.render(
+            "index_page_link",
+            &[
+                ("page", &page.to_string()),
+                ("location", &http::page_location(board.get_name(), page)),
+                ("current", current),
+            ],
+        ));
+    }
+
+    let body: String = templates.render(
+        "index",
+        &[
+            ("board", board.get_name()),
+            ("description", board.get_description()),
+            ("form", &post_form(templates, board, &PostForm::default())),
+            ("threads", &threads),
+            ("pages", &page_links),
+        ],
+    );
+    return layout(templates, &format!("/{}/", board.get_name()), &body);
+}
+
+pub(crate) fn thread(
+    templates: &Templates,
+    board: &Board,
+    thread: &Thread,
+    posts: &[(u64, Post)],
+    links: &dyn LinkResolver,
+) -> String {
+    let mut rendered: String = String::new();
+    for (id, post) in posts {
+        rendered.push_str(&render_post(templates, board, thread, *id, post, links));
+    }
+
+    let locked: &str = match thread.get_locked() {
+        true => "locked",
+        false => "",
+    };
+    let form: PostForm = PostForm {
+        thread: thread.get_parent().to_string(),
+        ..PostForm::default()
+    };
+    let body: String = templates.render(
+        "thread",
+        &[
+            ("board", board.get_name()),
+            ("description", board.get_description()),
+            ("subject", thread.get_name()),
+            ("parent", &thread.get_parent().to_string()),
+            ("locked", locked),
+            ("form", &post_form(templates, board, &form)),
+            ("posts", &rendered),
+            (
+                "delete_form",
+                &templates.render("delete_form", &[("board", board.get_name())]),
+            ),
+        ],
+    );
+
+    let title: String = match thread.get_name().is_empty() {
+        true => format!("/{}/ - {}", board.get_name(), thread.get_parent()),
+        false => format!("/{}/ - {}", board.get_name(), thread.get_name()),
+    };
+    return layout(templates, &title, &body);
+}
+
+pub(crate) fn catalog(
+    templates: &Templates,
+    board: &Board,
+    catalog_threads: &[CatalogThread],
+    sort: CatalogSort,
+    filter: &str,
+) -> String {
+    let mut threads: String = String::new();
+    for catalog_thread in catalog_threads {
+        // the catalog always uses the reply sized thumbnail, it's a grid of small ones
+        let op: &Post = catalog_thread.get_op();
+        let thumbnail: String = match op.get_attachments().first() {
+            Some(attachment) => thumbnail_url(board.get_name(), attachment, ThumbnailSize::Reply),
+            None => String::new(),
+        };
+        threads.push_str(&templates.render(
+            "catalog_thread",
+            &[
+                ("board", board.get_name()),
+                ("parent", &catalog_thread.thread.get_parent().to_string()),
+                ("thumbnail", &thumbnail),
+                ("subject", catalog_thread.thread.get_name()),
+                ("excerpt", &excerpt(op.get_text())),
+                ("replies", &catalog_thread.get_reply_count().to_string()),
+                ("images", &catalog_thread.get_image_count().to_string()),
+                ("bumped", &format_time(&catalog_thread.get_bumped())),
+            ],
+        ));
+    }
+
+    // the sort that's in use is the one selected in the form
+    let mut values: Vec<(&str, &str)> = vec![
+        ("board", board.get_name()),
+        ("filter", filter),
+        ("threads", &threads),
+    ];
+    for (key, other) in SORT_KEYS.iter().zip(ALL_SORTS) {
+        values.push((key, if other == sort { "selected" } else { "" }));
+    }
+
+    let body: String = templates.render("catalog", &values);
+    return layout(
+        templates,
+        &format!("/{}/ - Catalog", board.get_name()),
+        &body,
+    );
+}
+
+pub(crate) fn archive(templates: &Templates, board: &Board, summaries: &[ThreadSummary]) -> String {
+    let mut threads: String = String::new();
+    for summary in summaries {
+        threads.push_str(&templates.render(
+            "archive_thread",
+            &[
+                ("board", board.get_name()),
+                ("parent", &summary.thread.get_parent().to_string()),
+                ("subject", summary.thread.get_name()),
+                ("excerpt", &excerpt(summary.op.get_text())),
+            ],
+        ));
+    }
+
+    let body: String = templates.render(
+        "archive",
+        &[("board", board.get_name()), ("threads", &threads)],
+    );
+    return layout(
+        templates,
+        &format!("/{}/ - Archive", board.get_name()),
+        &body,
+    );
+}
+
+pub(crate) fn error(templates: &Templates, title: &str, message: &str) -> String {
+    let body: String = templates.render("error", &[("title", title), ("message", message)]);
+    return layout(templates, title, &body);
+}
+
+pub(crate) fn post_rejected(
+    templates: &Templates,
+    board: &Board,
+    message: &str,
+    form: &PostForm,
+) -> String {
+    // the form comes back filled in, so nothing has to be typed again
+    let body: String = templates.render(
+        "post_rejected",
+        &[
+            ("board", board.get_name()),
+            ("message", message),
+            ("form", &post_form(templates, board, form)),
+        ],
+    );
+    return layout(templates, "Post rejected", &body);
+}
+
+pub(crate) fn edit(
+    templates: &Templates,
+    board: &Board,
+    id: u64,
+    parent: u64,
+    comment: &str,
+    message: &str,
+) -> String {
+    // the post's text to start with, or what was sent if the edit was rejected
+    let body: String = templates.render(
+        "edit",
+        &[
+            ("board", board.get_name()),
+            ("id", &id.to_string()),
+            ("parent", &parent.to_string()),
+            ("comment", comment),
+            ("message", message),
+        ],
+    );
+    return layout(templates, &format!("Edit No. {}", id), &body);
+}
+
+fn layout(templates: &Templates, title: &str, body: &str) -> String {
+    return templates.render("layout", &[("title", title), ("body", body)]);
+}
+
+fn post_form(templates: &Templates, board: &Board, form: &PostForm) -> String {
+    // an empty thread makes a new one. the password and files are never put back
+    let anonymous: &str = match board.get_config().get_forced_anonymity() {
+        true => "anonymous",
+        false => "",
+    };
+
+    return templates.render(
+        "post_form",
+        &[
+            ("board", board.get_name()),
+            ("thread", &form.thread),
+            ("anonymous", anonymous),
+            ("name", &form.name),
+            ("subject", &form.subject),
+            ("options", &form.options),
+            ("comment", &form.comment),
+        ],
+    );
+}
+
+fn render_post(
+    templates: &Templates,
+    board: &Board,
+    thread: &Thread,
+    id: u64,
+    post: &Post,
+    links: &dyn LinkResolver,
+) -> String {
+    let board_name: &str = board.get_name();
+    let op: bool = id == thread.get_parent();
+    let size: ThumbnailSize = match op {
+        true => ThumbnailSize::Op,
+        false => ThumbnailSize::Reply,
+    };
+
+    let mut files: String = String::new();
+    for attachment in post.get_attachments() {
+        files.push_str(&render_attachment(templates, board_name, attachment, size));
+    }
+
+    // poster IDs are only shown while the board has them on, even if older posts have one
+    let poster_id: &str = match (board.get_config().get_poster_ids(), post.get_poster_id()) {
+        (true, Some(poster_id)) => poster_id,
+        _ => "",
+    };
+    let (class, subject): (&str, &str) = match op {
+        true => ("op", thread.get_name()),
+        false => ("reply", ""),
+    };
+    // posts in a locked thread can't be changed, so there's no point offering
+    let editable: &str = match thread.get_locked() {
+        true => "",
+        false => "editable",
+    };
+
+    return templates.render(
+        "post",
+        &[
+            ("board", board_name),
+            ("class", class),
+            ("id", &id.to_string()),
+            ("editable", editable),
+            ("subject", subject),
+            ("name", post.get_name()),
+            (
+                "tripcode",
+                post.get_tripcode().as_deref().unwrap_or_default(),
+            ),
+            ("poster_id", poster_id),
+            ("time", &format_time(post.get_created())),
+            (
+                "backlinks",
+                &render::render_backlinks(
+                    post.get_backlinks(),
+                    board_name,
+                    thread.get_parent(),
+                    links,
+                ),
+            ),
+            ("files", &files),
+            (
+                "text",
+                &render::render_text(post.get_text(), board_name, thread.get_parent(), links),
+            ),
+        ],
+    );
+}
+
+fn render_attachment(
+    templates: &Templates,
+    board: &str,
+    attachment: &Attachment,
+    size: ThumbnailSize,
+) -> String {
+    // spoilered images don't show their thumbnail
+    let (thumbnail, spoiler): (String, &str) = match attachment.get_spoiler() {
+        true => (String::new(), "spoiler"),
+        false => (thumbnail_url(board, attachment, size), ""),
+    };
+
+    return templates.render(
+        "attachment",
+        &[
+            (
+                "source",
+                &format!("/{}/src/{}", board, attachment.get_stored_name()),
+            ),
+            ("original_name", attachment.get_original_name()),
+            ("size", &format_size(attachment.get_size())),
+            ("thumbnail", &thumbnail),
+            ("spoiler", spoiler),
+        ],
+    );
+}
+
+fn thumbnail_url(board: &str, attachment: &Attachment, size: ThumbnailSize) -> String {
+    // only images have thumbnails
+    if attachment.get_dimensions().is_none() || attachment.get_spoiler() {
+        return String::new();
+    }
+
+    return format!(
+        "/{}/thumb/{}",
+        board,
+        attachment_storage::get_thumbnail_name(attachment.get_stored_name(), size)
+    );
+}
+
+fn excerpt(text: &str) -> String {
+    // the start of a post's text, on one line
+    let flattened: String = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    return match flattened.char_indices().nth(EXCERPT_LENGTH) {
+        Some((end, _)) => format!("{}...", &flattened[..end]),
+        None => flattened,
+    };
+}
+
+fn format_size(bytes: u64) -> String {
+    return match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1048576 => format!("{} KB", bytes / 1024),
+        _ => format!("{:.1} MB", bytes as f64 / 1048576.0),
+    };
+}
+
+pub(crate) fn format_time(time: &SystemTime) -> String {
+    let seconds: u64 = get_seconds(time);
+    let (year, month, day): (i64, u32, u32) = get_date(seconds);
+
+    return format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    );
+}
+
+pub(crate) fn get_seconds(time: &SystemTime) -> u64 {
+    // unix seconds, anything before the epoch is the epoch
+    return time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+}
+
+pub(crate) fn get_date(seconds: u64) -> (i64, u32, u32) {
+    // the date from days since 1970, after Howard Hinnant's civil_from_days
+    let days: i64 = (seconds / 86400) as i64 + 719468;
+    let era: i64 = days.div_euclid(146097);
+    let day_of_era: i64 = days.rem_euclid(146097);
+    let year_of_era: i64 =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index: i64 = (5 * day_of_year + 2) / 153;
+    let day: i64 = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month: i64 = match month_index < 10 {
+        true => month_index + 3,
+        false => month_index - 9,
+    };
+    let year: i64 = year_of_era + era * 400 + i64::from(month <= 2);
+
+    return (year, month as u32, day as u32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::index::IndexThread;
+    use std::time::Duration;
+
+    const BOARD: &str = "test";
+
+    // every link is live, and always in thread 1
+    struct AllLinks;
+
+    impl LinkResolver for AllLinks {
+        fn find_thread(&self, _board: &str, _post: u64) -> Option<u64> {
+            return Some(1);
+        }
+
+        fn board_exists(&self, _board: &str) -> bool {
+            return true;
+        }
+    }
+
+    fn post(text: &str) -> Post {
+        let mut post = Post::new(
+            "owner".to_string(),
+            "<b>name</b>".to_string(),
+            None,
+            text.to_string(),
+            1,
+        );
+        post.modify_poster_id(Some("abcdefgh".to_string()));
+        return post;
+    }
+
+    fn summary(subject: &str, text: &str) -> ThreadSummary {
+        return ThreadSummary {
+            thread: Thread::new(subject.to_string(), 1),
+            op: post(text),
+        };
+    }
+
+    #[test]
+    fn test_landing() {
+        let boards = vec![Board::new(BOARD.to_string(), "<i>tests</i>".to_string())];
+        let html = landing(&Templates::new(), &boards);
+        assert!(html.contains("<a href=\"/test/\">/test/</a>"));
+        assert!(html.contains("&lt;i&gt;tests&lt;/i&gt;"));
+    }
+
+    #[test]
+    fn test_thread() {
+        let templates = Templates::new();
+        let board = Board::new(BOARD.to_string(), String::new());
+        let mut locked = Thread::new("<subject>".to_string(), 1);
+        let posts = vec![(1, post("op")), (2, post(">>1 <script>"))];
+        let html = thread(&templates, &board, &locked, &posts, &AllLinks);
+
+        assert!(html.contains("&lt;subject&gt;"));
+        assert!(html.contains("&lt;b&gt;name&lt;/b&gt;"));
+        assert!(html.contains("id=\"p2\""));
+        assert!(html.contains("<a href=\"#p1\" class=\"quotelink\">&gt;&gt;1</a> &lt;script&gt;"));
+        assert!(html.contains("name=\"thread\" value=\"1\""));
+        assert!(html.contains("<a href=\"/test/edit/2\" class=\"edit\">Edit</a>"));
+        assert!(html.contains("action=\"/test/delete\""));
+        assert!(!html.contains("<script>"));
+
+        locked.lock();
+        let html = thread(&templates, &board, &locked, &posts, &AllLinks);
+        assert!(!html.contains("<form"));
+        assert!(!html.contains("Edit</a>"));
+        assert!(html.contains("This thread is locked."));
+    }
+
+    #[test]
+    fn test_thread_poster_ids() {
+        let templates = Templates::new();
+        let mut board = Board::new(BOARD.to_string(), String::new());
+        let posts = vec![(1, post("op"))];
+        let shown = Thread::new(String::new(), 1);
+        assert!(!thread(&templates, &board, &shown, &posts, &AllLinks).contains("abcdefgh"));
+
+        let mut board_config = board.get_config().clone();
+        board_config.modify_poster_ids(true);
+        board.modify_config(board_config);
+        assert!(thread(&templates, &board, &shown, &posts, &AllLinks).contains("ID: abcdefgh"));
+    }
+
+    #[test]
+    fn test_index() {
+        let board = Board::new(BOARD.to_string(), String::new());
+        let index_page = IndexPage {
+            threads: vec![IndexThread {
+                thread: Thread::new("subject".to_string(), 1),
+                posts: vec![(1, post("op")), (5, post("reply"))],
+                omitted: 3,
+            }],
+            page: 2,
+            page_count: 3,
+        };
+        let html = index(&Templates::new(), &board, &index_page, &AllLinks);
+
+        assert!(html.contains("<a href=\"/test/thread/1\">Reply</a>"));
+        assert!(html.contains("3 replies omitted."));
+        assert!(html.contains("name=\"subject\""));
+        // the OP comes before the reply link, and the replies after it
+        assert!(html.find("id=\"p1\"") < html.find("Reply</a>"));
+        assert!(html.find("Reply</a>") < html.find("id=\"p5\""));
+        assert!(html.contains("[<a href=\"/test/\">1</a>]"));
+        assert!(html.contains("[<b>2</b>]"));
+        assert!(html.contains("[<a href=\"/test/3\">3</a>]"));
+    }
+
+    #[test]
+    fn test_catalog() {
+        let board = Board::new(BOARD.to_string(), String::new());
+        let mut op = post("op text");
+        op.restore_timestamps(UNIX_EPOCH, UNIX_EPOCH);
+        let catalog_thread = CatalogThread {
+            thread: Thread::new("<subject>".to_string(), 1),
+            posts: vec![(1, op), (2, post("reply"))],
+            sticky: false,
+        };
+        let html = catalog(
+            &Templates::new(),
+            &board,
+            &[catalog_thread],
+            CatalogSort::Replies,
+            "<q>",
+        );
+        assert!(html.contains("<a href=\"/test/thread/1\">No. 1</a>"));
+        assert!(html.contains("&lt;subject&gt;"));
+        assert!(html.contains("op text"));
+        assert!(html.contains("R: 1 / I: 0"));
+        assert!(html.contains("<option value=\"replies\" selected>"));
+        assert!(html.contains("<option value=\"bump\">"));
+        assert!(html.contains("value=\"&lt;q&gt;\""));
+
+        let empty = catalog(&Templates::new(), &board, &[], CatalogSort::Bump, "");
+        assert!(empty.contains("No threads found."));
+    }
+
+    #[test]
+    fn test_archive() {
+        let board = Board::new(BOARD.to_string(), String::new());
+        let html = archive(&Templates::new(), &board, &[summary("", "<op>")]);
+        assert!(html.contains("<td>1</td>"));
+        assert!(html.contains("&lt;op&gt;"));
+        assert!(html.contains("<a href=\"/test/thread/1\">View</a>"));
+    }
+
+    #[test]
+    fn test_post_rejected() {
+        let board = Board::new(BOARD.to_string(), String::new());
+        let form = PostForm {
+            thread: "12".to_string(),
+            name: "anon".to_string(),
+            options: "sage".to_string(),
+            comment: "</textarea><script>".to_string(),
+            password: "hunter2".to_string(),
+            ..PostForm::default()
+        };
+        let html = post_rejected(&Templates::new(), &board, "Too fast.", &form);
+
+        assert!(html.contains("Too fast."));
+        assert!(html.contains("name=\"thread\" value=\"12\""));
+        assert!(html.contains("value=\"anon\""));
+        assert!(html.contains("value=\"sage\""));
+        assert!(html.contains("&lt;/textarea&gt;&lt;script&gt;</textarea>"));
+        assert!(!html.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_edit() {
+        let board = Board::new(BOARD.to_string(), String::new());
+        let html = edit(&Templates::new(), &board, 5, 1, "</textarea>", "");
+        assert!(html.contains("<a href=\"/test/thread/1#p5\">Return</a>"));
+        assert!(html.contains("name=\"post\" value=\"5\""));
+        assert!(html.contains("&lt;/textarea&gt;</textarea>"));
+        assert!(!html.contains("class=\"error\""));
+
+        let rejected = edit(&Templates::new(), &board, 5, 1, "", "Too long.");
+        assert!(rejected.contains("<p class=\"error\">Too long.</p>"));
+    }
+
+    #[test]
+    fn test_error() {
+        let html = error(&Templates::new(), "Bad request", "<nope>");
+        assert!(html.contains("<title>Bad request</title>"));
+        assert!(html.contains("&lt;nope&gt;"));
+    }
+
+    #[test]
+    fn test_excerpt() {
+        assert_eq!(excerpt("short\n\ntext"), "short text");
+        let long = "\u{3B1}".repeat(EXCERPT_LENGTH + 1);
+        assert_eq!(
+            excerpt(&long),
+            format!("{}...", "\u{3B1}".repeat(EXCERPT_LENGTH))
+        );
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(12), "12 B");
+        assert_eq!(format_size(2048), "2 KB");
+        assert_eq!(format_size(3 * 1048576 / 2), "1.5 MB");
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(&UNIX_EPOCH), "1970-01-01 00:00:00 UTC");
+        assert_eq!(
+            format_time(&(UNIX_EPOCH + Duration::from_secs(951782400 + 3661))),
+            "2000-02-29 01:01:01 UTC"
+        );
+        assert_eq!(
+            format_time(&(UNIX_EPOCH + Duration::from_secs(1792368000))),
+            "2026-10-19 00:00:00 UTC"
+        );
+    }
+}
@@ -0,0 +1,907 @@
+// Every URL the site answers to, and what it does with it:
+// GET  /                          the landing page, listing every board
+// GET  /{board}/                  the board index, active threads with their newest replies, a page
+//                                 at a time. /{board}/{page} for the pages after the first
+// GET  /{board}/thread/{post}     a whole thread. replies and moved posts redirect to where they are
+// GET  /{board}/catalog           every active thread, OP only. takes ?sort= and ?q=, see catalog.rs
+// GET  /{board}/archive           every archived thread, OP only
+// GET  /{board}/src/{stored}      an attachment
+// GET  /{board}/thumb/{thumbnail} an attachment's thumbnail
+// POST /{board}/post              makes a thread or a reply, from the post form
+// POST /{board}/delete            a poster deleting their own post, or just its files
+// GET  /{board}/edit/{post}       the form for a poster to edit their own post
+// POST /{board}/edit              saves the edit. both take the post's deletion password, or go
+//                                 by the owner cookie without one, see own_posts.rs
+// GET  /boards.json and /{board}/{catalog,threads,archive}.json and /{board}/thread/{post}.json
+//                                 the JSON API, see api.rs
+// POST /{board}/api/{action}      the write API, for posting and moderating with a token, see
+//                                 write_api.rs
+// Attachments of deleted posts aren't served, even though the files stay around until the purge.
+// Thumbnails can also be had as /{board}/src/{stem}s.jpg, which is where API clients look for them.
+use super::api::{self, ThreadPosts};
+use super::form::{self, PostForm};
+use super::http;
+use super::pages::{self, ThreadSummary};
+use super::write_api;
+use super::State;
+use crate::core::attachments::UploadedFile;
+use crate::core::backlinks::StorageLinks;
+use crate::core::catalog::{self, CatalogSort, CatalogThread};
+use crate::core::creation::{self, CreateError, NewPost};
+use crate::core::index::{self, IndexPage};
+use crate::core::media::thumbnail::{ThumbnailSize, ALL_SIZES};
+use crate::core::own_posts::{self, Credential, OwnPostError};
+use crate::core::posting::{Cooldowns, Submission};
+use crate::core::storage::attachment_storage;
+use crate::core::storage::board_storage;
+use crate::core::storage::post_storage;
+use crate::core::storage::thread_storage;
+use crate::core::structs::api_token::Scope;
+use crate::core::structs::board::Board;
+use crate::core::structs::board_config::BoardConfig;
+use crate::core::structs::post::Post;
+use crate::core::structs::thread::Thread;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::string::String;
+use std::sync::PoisonError;
+use std::time::{Duration, SystemTime};
+use std::vec::Vec;
+use tiny_http::{Method, Request, ResponseBox};
+
+const MAX_FORM_SIZE: usize = 64 * 1024; // everything but the files
+const THUMBNAIL_MIME: &str = "image/jpeg"; // thumbnails are always JPEG
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Route {
+    Landing,
+    BoardRedirect(String), // a board without the trailing slash
+    Index(String, usize),  // the page, from 1
+    Thread(String, u64),
+    Catalog(String),
+    Archive(String),
+    Source(String, String),
+    Thumbnail(String, String),
+    Post(String),
+    DeletePost(String),
+    EditForm(String, u64),
+    EditPost(String),
+    BoardsJson,
+    CatalogJson(String),
+    ThreadsJson(String),
+    ThreadJson(String, u64),
+    ArchiveJson(String),
+    Api(String, Scope),
+    NotFound,
+}
+
+pub(crate) fn parse(method: &Method, segments: &[String], boards: &[String]) -> Route {
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let board: String = match segments.first() {
+        Some(board) if boards.iter().any(|known| known == board) => board.to_string(),
+        Some(&"") if *method == Method::Get && segments.len() == 1 => return Route::Landing,
+        Some(&"boards.json") if *method == Method::Get && segments.len() == 1 => {
+            return Route::BoardsJson
+        }
+        _ => return Route::NotFound,
+    };
+
+    return match (method, &segments[1..]) {
+        (Method::Get, []) => Route::BoardRedirect(board),
+        (Method::Get, [""]) => Route::Index(board, 1),
+        (Method::Get, ["thread", post]) => match post.strip_suffix(".json") {
+            Some(post) => match post.parse() {
+                Ok(post) => Route::ThreadJson(board, post),
+                Err(_) => Route::NotFound,
+            },
+            None => match post.parse() {
+                Ok(post) => Route::Thread(board, post),
+                Err(_) => Route::NotFound,
+            },
+        },
+        (Method::Get, ["catalog"]) => Route::Catalog(board),
+        (Method::Get, ["archive"]) => Route::Archive(board),
+        (Method::Get, ["src", name]) => Route::Source(board, name.to_string()),
+        (Method::Get, ["thumb", name]) => Route::Thumbnail(board, name.to_string()),
+        (Method::Post, ["post"]) => Route::Post(board),
+        (Method::Post, ["delete"]) => Route::DeletePost(board),
+        (Method::Get, ["edit", post]) => match post.parse() {
+            Ok(post) => Route::EditForm(board, post),
+            Err(_) => Route::NotFound,
+        },
+        (Method::Post, ["edit"]) => Route::EditPost(board),
+        (Method::Get, ["catalog.json"]) => Route::CatalogJson(board),
+        (Method::Get, ["threads.json"]) => Route::ThreadsJson(board),
+        (Method::Get, ["archive.json"]) => Route::ArchiveJson(board),
+        (Method::Post, ["api", action]) => match Scope::parse(action) {
+            Some(scope) => Route::Api(board, scope),
+            None => Route::NotFound,
+        },
+        (Method::Get, [page]) => match page.parse() {
+            // the first page is only ever the board itself
+            Ok(page) if page > 1 => Route::Index(board, page),
+            _ => Route::NotFound,
+        },
+        _ => Route::NotFound,
+    };
+}
+
+pub(crate) fn handle(state: &State, mut request: Request) {
+    let route: Route = match http::path_segments(request.url()) {
+        Some(segments) => parse(request.method(), &segments, state.get_boards()),
+        None => Route::NotFound,
+    };
+    let now: SystemTime = SystemTime::now();
+
+    let response: Result<ResponseBox, Error> = match route {
+        Route::Landing => landing(state),
+        Route::BoardRedirect(board) => Ok(http::redirect(301, &http::board_location(&board))),
+        Route::Index(board, page) => index(state, &board, page, now),
+        Route::Thread(board, post) => thread(state, &board, post),
+        Route::Catalog(board) => catalog(state, &board, request.url(), now),
+        Route::Archive(board) => archive(state, &board),
+        Route::Source(board, name) => source(state, &board, &name),
+        Route::Thumbnail(board, name) => thumbnail(state, &board, &name),
+        Route::Post(board) => post(state, &mut request, &board, now),
+        Route::DeletePost(board) => delete_post(state, &mut request, &board, now),
+        Route::EditForm(board, post) => edit_form(state, &board, post),
+        Route::EditPost(board) => edit_post(state, &mut request, &board, now),
+        Route::BoardsJson => boards_json(state),
+        Route::CatalogJson(board) => catalog_json(state, &board, request.url(), now),
+        Route::ThreadsJson(board) => threads_json(state, &board, now),
+        Route::ThreadJson(board, post) => thread_json(state, &board, post, now),
+        Route::ArchiveJson(board) => archive_json(state, &board),
+        Route::Api(board, scope) => Ok(write_api::handle(state, &mut request, &board, scope, now)),
+        Route::NotFound => Ok(not_found(state)),
+    };
+
+    let response: ResponseBox = match response {
+        Ok(response) => response,
+        Err(error) if error.kind() == ErrorKind::NotFound => not_found(state),
+        Err(error) => {
+            eprintln!("{} {}: {}", request.method(), request.url(), error);
+            error_page(
+                state,
+                500,
+                "Server error",
+                "Something went wrong, please try again.",
+            )
+        }
+    };
+
+    // a client hanging up before getting its response isn't worth reporting
+    let _ = request.respond(response);
+}
+
+fn error_page(state: &State, status: u16, title: &str, message: &str) -> ResponseBox {
+    return http::html(status, pages::error(state.get_templates(), title, message));
+}
+
+fn not_found(state: &State) -> ResponseBox {
+    return error_page(state, 404, "Not found", "There's nothing here.");
+}
+
+fn landing(state: &State) -> Result<ResponseBox, Error> {
+    return Ok(http::html(
+        200,
+        pages::landing(state.get_templates(), &read_boards(state)?),
+    ));
+}
+
+fn read_boards(state: &State) -> Result<Vec<Board>, Error> {
+    let mut boards: Vec<Board> = Vec::new();
+    for board in state.get_boards() {
+        boards.push(board_storage::read_board(state.get_config(), board)?);
+    }
+
+    return Ok(boards);
+}
+
+fn index(
+    state: &State,
+    board_name: &str,
+    page: usize,
+    now: SystemTime,
+) -> Result<ResponseBox, Error> {
+    let board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    let index_page: IndexPage = match index::read_page(
+        state.get_config(),
+        &board,
+        page,
+        index::PER_PAGE,
+        index::INDEX_REPLIES,
+        now,
+    )? {
+        Some(index_page) => index_page,
+        None => return Ok(not_found(state)),
+    };
+
+    let links: StorageLinks = StorageLinks::new(state.get_config());
+    return Ok(http::html(
+        200,
+        pages::index(state.get_templates(), &board, &index_page, &links),
+    ));
+}
+
+fn thread(state: &State, board_name: &str, post: u64) -> Result<ResponseBox, Error> {
+    if !post_storage::post_exists(state.get_config(), board_name, post) {
+        // posts moved off the board point to where they went
+        return match thread_storage::read_redirects(state.get_config(), board_name)?.get(&post) {
+            Some((board, moved)) => Ok(http::redirect(
+                301,
+                &http::thread_location(board, *moved, None),
+            )),
+            None => Ok(not_found(state)),
+        };
+    }
+
+    let read: Post = post_storage::read_post(state.get_config(), board_name, post)?;
+    if read.get_deleted() {
+        return Ok(not_found(state));
+    }
+    if read.get_parent() != post {
+        return Ok(http::redirect(
+            302,
+            &http::thread_location(board_name, read.get_parent(), Some(post)),
+        ));
+    }
+
+    let board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    let thread: Thread = thread_storage::read_thread(state.get_config(), board_name, post)?;
+    let posts: Vec<(u64, Post)> = read_posts(state, board_name, thread.get_children())?;
+
+    let links: StorageLinks = StorageLinks::new(state.get_config());
+    return Ok(http::html(
+        200,
+        pages::thread(state.get_templates(), &board, &thread, &posts, &links),
+    ));
+}
+
+fn catalog(
+    state: &State,
+    board_name: &str,
+    url: &str,
+    now: SystemTime,
+) -> Result<ResponseBox, Error> {
+    let board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    let (sort, filter): (CatalogSort, String) = read_catalog_query(url);
+    let threads: Vec<CatalogThread> =
+        catalog::read_catalog(state.get_config(), &board, sort, &filter, now)?;
+
+    return Ok(http::html(
+        200,
+        pages::catalog(state.get_templates(), &board, &threads, sort, &filter),
+    ));
+}
+
+fn read_catalog_query(url: &str) -> (CatalogSort, String) {
+    // an unknown sort is bump order, the same as none
+    let mut query: HashMap<String, String> = http::parse_query(url);
+    let sort: CatalogSort = query
+        .get("sort")
+        .and_then(|sort| CatalogSort::parse(sort))
+        .unwrap_or(CatalogSort::Bump);
+    let filter: String = query.remove("q").unwrap_or_default().trim().to_string();
+
+    return (sort, filter);
+}
+
+fn archive(state: &State, board_name: &str) -> Result<ResponseBox, Error> {
+    let board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    let summaries: Vec<ThreadSummary> = read_summaries(state, board_name, board.get_archive())?;
+
+    return Ok(http::html(
+        200,
+        pages::archive(state.get_templates(), &board, &summaries),
+    ));
+}
+
+fn read_summaries(
+    state: &State,
+    board: &str,
+    parents: &[u64],
+) -> Result<Vec<ThreadSummary>, Error> {
+    // deleted threads are left out
+    let mut summaries: Vec<ThreadSummary> = Vec::new();
+    for parent in parents {
+        let thread: Thread = thread_storage::read_thread(state.get_config(), board, *parent)?;
+        if thread.get_deleted() {
+            continue;
+        }
+        let op: Post = post_storage::read_post(state.get_config(), board, *parent)?;
+        summaries.push(ThreadSummary { thread, op });
+    }
+
+    return Ok(summaries);
+}
+
+fn read_posts(state: &State, board: &str, ids: &[u64]) -> Result<Vec<(u64, Post)>, Error> {
+    // deleted replies are left out, rather than shown as a gap
+    let mut posts: Vec<(u64, Post)> = Vec::new();
+    for id in ids {
+        let post: Post = post_storage::read_post(state.get_config(), board, *id)?;
+        if !post.get_deleted() {
+            posts.push((*id, post));
+        }
+    }
+
+    return Ok(posts);
+}
+
+fn read_threads(state: &State, board: &str, parents: &[u64]) -> Result<Vec<ThreadPosts>, Error> {
+    // whole threads, for the API's counts. deleted threads are left out
+    let mut threads: Vec<ThreadPosts> = Vec::new();
+    for parent in parents {
+        let thread: Thread = thread_storage::read_thread(state.get_config(), board, *parent)?;
+        if thread.get_deleted() {
+            continue;
+        }
+        let posts: Vec<(u64, Post)> = read_posts(state, board, thread.get_children())?;
+        threads.push(ThreadPosts { thread, posts });
+    }
+
+    return Ok(threads);
+}
+
+fn live_post(state: &State, board: &str, stored_name: &str) -> Result<Option<(u64, Post)>, Error> {
+    // the post an attachment belongs to, if it's still up. stored names start with the post ID
+    let id: u64 = match stored_name.split('-').next().map(str::parse) {
+        Some(Ok(id)) => id,
+        _ => return Ok(None),
+    };
+    if !post_storage::post_exists(state.get_config(), board, id) {
+        return Ok(None);
+    }
+
+    let post: Post = post_storage::read_post(state.get_config(), board, id)?;
+    return match post.get_deleted() {
+        true => Ok(None),
+        false => Ok(Some((id, post))),
+    };
+}
+
+fn source(state: &State, board: &str, name: &str) -> Result<ResponseBox, Error> {
+    let (id, post): (u64, Post) = match live_post(state, board, name)? {
+        Some(found) => found,
+        None => return Ok(not_found(state)),
+    };
+    if let Some(attachment) = post
+        .get_attachments()
+        .iter()
+        .find(|attachment| attachment.get_stored_name() == name)
+    {
+        let data: Vec<u8> = attachment_storage::read_attachment(state.get_config(), board, name)?;
+        return Ok(http::file(attachment.get_mime(), data));
+    }
+
+    // the API's thumbnail names, sized the same as on the post's pages
+    let size: ThumbnailSize = match post.get_parent() == id {
+        true => ThumbnailSize::Op,
+        false => ThumbnailSize::Reply,
+    };
+    let thumbnail: Option<&str> = post.get_attachments().iter().find_map(|attachment| {
+        let stored: &str = attachment.get_stored_name();
+        let stem: &str = stored.split_once('.').map_or(stored, |(stem, _)| stem);
+        match api::get_thumbnail_alias(stem) == name && attachment.get_dimensions().is_some() {
+            true => Some(stored),
+            false => None,
+        }
+    });
+
+    return match thumbnail {
+        Some(stored) => {
+            let data: Vec<u8> =
+                attachment_storage::read_thumbnail(state.get_config(), board, stored, size)?;
+            Ok(http::file(THUMBNAIL_MIME, data))
+        }
+        None => Ok(not_found(state)),
+    };
+}
+
+fn thumbnail(state: &State, board: &str, name: &str) -> Result<ResponseBox, Error> {
+    let post: Post = match live_post(state, board, name)? {
+        Some((_, post)) => post,
+        None => return Ok(not_found(state)),
+    };
+    let found: bool = post.get_attachments().iter().any(|attachment| {
+        ALL_SIZES.iter().any(|size| {
+            attachment_storage::get_thumbnail_name(attachment.get_stored_name(), *size) == name
+        })
+    });
+    if !found {
+        return Ok(not_found(state));
+    }
+
+    let data: Vec<u8> = attachment_storage::read_attachment(state.get_config(), board, name)?;
+    return Ok(http::file(THUMBNAIL_MIME, data));
+}
+
+fn post(
+    state: &State,
+    request: &mut Request,
+    board_name: &str,
+    now: SystemTime,
+) -> Result<ResponseBox, Error> {
+    // the board is read again under the lock, this one is only for the size limit
+    let board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    let content_type: String = http::get_header(request, "Content-Type")
+        .unwrap_or_default()
+        .to_string();
+
+    let form: PostForm = match http::read_body(request, get_body_limit(board.get_config()))? {
+        Some(body) => match form::parse(&content_type, &body) {
+            Some(form) => form,
+            None => {
+                return Ok(error_page(
+                    state,
+                    400,
+                    "Bad request",
+                    "That post couldn't be read.",
+                ))
+            }
+        },
+        None => {
+            return Ok(error_page(
+                state,
+                413,
+                "Too large",
+                "That post is too large to take.",
+            ))
+        }
+    };
+
+    let (owner, new_owner): (String, bool) = match http::get_owner(request) {
+        Some(owner) => (owner, false),
+        None => (http::new_owner()?, true),
+    };
+    let ip: String = http::get_ip(request);
+    let created: Result<(u64, u64), CreateError> =
+        submit(state, board_name, &form, &ip, &ip, &owner, now);
+
+    // new threads always open, replies only stay in the thread with noko
+    let response: ResponseBox = match created {
+        Ok((thread, id)) if thread == id || form.get_noko() => {
+            http::redirect(303, &http::thread_location(board_name, thread, Some(id)))
+        }
+        Ok(_) => http::redirect(303, &http::board_location(board_name)),
+        Err(CreateError::Storage(kind)) => return Err(Error::from(kind)),
+        Err(CreateError::ThreadNotFound) => not_found(state),
+        Err(error) => http::html(
+            400,
+            pages::post_rejected(state.get_templates(), &board, &error.to_string(), &form),
+        ),
+    };
+
+    return match new_owner {
+        true => Ok(http::with_owner(response, &owner)),
+        false => Ok(response),
+    };
+}
+
+fn read_fields(request: &mut Request) -> Result<Option<HashMap<String, String>>, Error> {
+    // the forms without files are small and urlencoded. None if it's too large to take
+    return Ok(http::read_body(request, MAX_FORM_SIZE)?.map(|body| http::parse_form(&body)));
+}
+
+fn read_post_field(fields: &HashMap<String, String>) -> Option<u64> {
+    return fields.get("post").and_then(|post| post.trim().parse().ok());
+}
+
+fn get_credential<'a>(
+    fields: &'a HashMap<String, String>,
+    owner: &'a Option<String>,
+) -> Credential<'a> {
+    // the password if one was given, otherwise whoever the owner cookie says they are
+    return match fields.get("password").map(String::as_str) {
+        Some(password) if !password.is_empty() => Credential::Password(password),
+        _ => Credential::Owner(owner.as_deref().unwrap_or_default()),
+    };
+}
+
+fn own_post_rejected(state: &State, error: OwnPostError) -> Result<ResponseBox, Error> {
+    let status: u16 = match error {
+        OwnPostError::Storage(kind) => return Err(Error::from(kind)),
+        OwnPostError::NotFound => return Ok(not_found(state)),
+        OwnPostError::Posting(_) => 400,
+        _ => 403,
+    };
+    return Ok(error_page(
+        state,
+        status,
+        "Post not changed",
+        &error.to_string(),
+    ));
+}
+
+fn delete_post(
+    state: &State,
+    request: &mut Request,
+    board_name: &str,
+    now: SystemTime,
+) -> Result<ResponseBox, Error> {
+    let owner: Option<String> = http::get_owner(request);
+    let fields: HashMap<String, String> = match read_fields(request)? {
+        Some(fields) => fields,
+        None => {
+            return Ok(error_page(
+                state,
+                413,
+                "Too large",
+                "That form is too large.",
+            ))
+        }
+    };
+    let Some(id) = read_post_field(&fields) else {
+        return Ok(not_found(state));
+    };
+    let files_only: bool = fields
+        .get("files_only")
+        .is_some_and(|files_only| !files_only.is_empty());
+
+    let _writing = state
+        .get_cooldowns()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let _locked: File = board_storage::lock_boards(state.get_config())?;
+    let board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    let credential: Credential = get_credential(&fields, &owner);
+    if let Err(error) =
+        own_posts::delete_own_post(state.get_config(), &board, id, &credential, files_only, now)
+    {
+        return own_post_rejected(state, error);
+    }
+
+    // back to the post, or where it was. a deleted OP took its thread with it
+    let post: Post = post_storage::read_post(state.get_config(), board_name, id)?;
+    let parent: u64 = post.get_parent();
+    let location: String = match (files_only, parent == id) {
+        (true, _) => http::thread_location(board_name, parent, Some(id)),
+        (false, true) => http::board_location(board_name),
+        (false, false) => http::thread_location(board_name, parent, None),
+    };
+    return Ok(http::redirect(303, &location));
+}
+
+fn edit_form(state: &State, board_name: &str, id: u64) -> Result<ResponseBox, Error> {
+    // anyone can have the form, the edit itself is what's checked
+    if !post_storage::post_exists(state.get_config(), board_name, id) {
+        return Ok(not_found(state));
+    }
+    let post: Post = post_storage::read_post(state.get_config(), board_name, id)?;
+    if post.get_deleted() {
+        return Ok(not_found(state));
+    }
+
+    let board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    return Ok(http::html(
+        200,
+        pages::edit(
+            state.get_templates(),
+            &board,
+            id,
+            post.get_parent(),
+            post.get_text(),
+            "",
+        ),
+    ));
+}
+
+fn edit_post(
+    state: &State,
+    request: &mut Request,
+    board_name: &str,
+    now: SystemTime,
+) -> Result<ResponseBox, Error> {
+    let owner: Option<String> = http::get_owner(request);
+    let ip: String = http::get_ip(request);
+    let fields: HashMap<String, String> = match read_fields(request)? {
+        Some(fields) => fields,
+        None => {
+            return Ok(error_page(
+                state,
+                413,
+                "Too large",
+                "That form is too large.",
+            ))
+        }
+    };
+    let Some(id) = read_post_field(&fields) else {
+        return Ok(not_found(state));
+    };
+    let comment: String = fields.get("comment").cloned().unwrap_or_default();
+
+    let _writing = state
+        .get_cooldowns()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let _locked: File = board_storage::lock_boards(state.get_config())?;
+    let board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    let credential: Credential = get_credential(&fields, &owner);
+    let edited: Result<(), OwnPostError> = own_posts::edit_own_post(
+        state.get_config(),
+        &board,
+        id,
+        &credential,
+        comment.clone(),
+        &ip,
+        now,
+    );
+
+    return match edited {
+        Ok(()) => {
+            let post: Post = post_storage::read_post(state.get_config(), board_name, id)?;
+            let location: String = http::thread_location(board_name, post.get_parent(), Some(id));
+            Ok(http::redirect(303, &location))
+        }
+        // the form comes back with what was sent, so nothing has to be typed again
+        Err(OwnPostError::Posting(error)) => {
+            let post: Post = post_storage::read_post(state.get_config(), board_name, id)?;
+            Ok(http::html(
+                400,
+                pages::edit(
+                    state.get_templates(),
+                    &board,
+                    id,
+                    post.get_parent(),
+                    &comment,
+                    &error.to_string(),
+                ),
+            ))
+        }
+        Err(error) => own_post_rejected(state, error),
+    };
+}
+
+fn boards_json(state: &State) -> Result<ResponseBox, Error> {
+    return Ok(http::json(200, api::boards(&read_boards(state)?)));
+}
+
+fn catalog_json(
+    state: &State,
+    board_name: &str,
+    url: &str,
+    now: SystemTime,
+) -> Result<ResponseBox, Error> {
+    // sorted and filtered the same as the catalog page
+    let board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    let (sort, filter): (CatalogSort, String) = read_catalog_query(url);
+    let threads: Vec<ThreadPosts> =
+        catalog::read_catalog(state.get_config(), &board, sort, &filter, now)?
+            .into_iter()
+            .map(|catalog_thread| ThreadPosts {
+                thread: catalog_thread.thread,
+                posts: catalog_thread.posts,
+            })
+            .collect();
+
+    let links: StorageLinks = StorageLinks::new(state.get_config());
+    return Ok(http::json(200, api::catalog(&board, &threads, &links, now)));
+}
+
+fn threads_json(state: &State, board_name: &str, now: SystemTime) -> Result<ResponseBox, Error> {
+    let board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    let threads: Vec<ThreadPosts> = read_threads(state, board_name, &board.get_thread_order(now))?;
+
+    return Ok(http::json(200, api::threads(&threads)));
+}
+
+fn thread_json(
+    state: &State,
+    board_name: &str,
+    post: u64,
+    now: SystemTime,
+) -> Result<ResponseBox, Error> {
+    // only thread IDs, unlike the page there are no redirects for replies or moved threads
+    if !post_storage::post_exists(state.get_config(), board_name, post) {
+        return Ok(not_found(state));
+    }
+    let read: Post = post_storage::read_post(state.get_config(), board_name, post)?;
+    if read.get_deleted() || read.get_parent() != post {
+        return Ok(not_found(state));
+    }
+
+    let board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    let threads: Vec<ThreadPosts> = read_threads(state, board_name, &[post])?;
+    let Some(thread) = threads.first() else {
+        return Ok(not_found(state));
+    };
+
+    let links: StorageLinks = StorageLinks::new(state.get_config());
+    return Ok(http::json(200, api::thread(&board, thread, &links, now)));
+}
+
+fn archive_json(state: &State, board_name: &str) -> Result<ResponseBox, Error> {
+    let board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    let mut parents: Vec<u64> = Vec::new();
+    for parent in board.get_archive() {
+        let thread: Thread = thread_storage::read_thread(state.get_config(), board_name, *parent)?;
+        if !thread.get_deleted() {
+            parents.push(*parent);
+        }
+    }
+
+    return Ok(http::json(200, api::archive(&parents)));
+}
+
+pub(crate) fn get_body_limit(board_config: &BoardConfig) -> usize {
+    // the fields, and as many files as a post can have, all at the largest size allowed
+    let files: u64 = board_config.get_max_files() as u64 * board_config.get_max_file_size();
+    return MAX_FORM_SIZE + files as usize;
+}
+
+pub(crate) fn submit(
+    state: &State,
+    board_name: &str,
+    form: &PostForm,
+    ip: &str,
+    poster: &str,
+    cookie: &str,
+    now: SystemTime,
+) -> Result<(u64, u64), CreateError> {
+    // makes a post from a form, for the post form and the write API alike. poster is what
+    // cooldowns are counted against, cookie is who owns the post
+    let thread: Option<u64> = match form.thread.as_str() {
+        "" => None, // an empty or missing thread makes a new one
+        thread => Some(thread.parse().map_err(|_| CreateError::ThreadNotFound)?),
+    };
+    let files: Vec<UploadedFile> = form
+        .files
+        .iter()
+        .map(|file| UploadedFile {
+            original_name: &file.name,
+            data: &file.data,
+            spoiler: form.spoiler,
+        })
+        .collect();
+
+    let new_post: NewPost = NewPost {
+        submission: Submission {
+            poster,
+            name: &form.name,
+            subject: &form.subject,
+            text: &form.comment,
+            files: &files,
+            new_thread: thread.is_none(),
+        },
+        thread: thread.unwrap_or_default(),
+        ip,
+        cookie,
+        password: &form.password,
+        sage: form.get_sage(),
+    };
+
+    // the locks are held for the whole post, which also keeps post IDs from being handed out twice.
+    // the file lock keeps the command line out while the server is writing
+    let mut cooldowns = state
+        .get_cooldowns()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let cooldowns: &mut Cooldowns = cooldowns
+        .entry(board_name.to_string())
+        .or_insert_with(Cooldowns::new);
+    let _locked: File = board_storage::lock_boards(state.get_config())?;
+    let mut board: Board = board_storage::read_board(state.get_config(), board_name)?;
+
+    // posters past the longest cooldown are forgotten, so the map only holds recent posters
+    let board_config: &BoardConfig = board.get_config();
+    let longest: u32 = board_config
+        .get_reply_cooldown()
+        .max(board_config.get_thread_cooldown());
+    if let Some(before) = now.checked_sub(Duration::from_secs(longest as u64)) {
+        cooldowns.prune(before);
+    }
+    return creation::create_post(state.get_config(), &mut board, cooldowns, &new_post, now);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(method: Method, url: &str) -> Route {
+        let boards = vec!["test".to_string(), "\u{3B1}".to_string()];
+        return parse(&method, &http::path_segments(url).unwrap(), &boards);
+    }
+
+    #[test]
+    fn test_parse() {
+        let board = || "test".to_string();
+        assert_eq!(route(Method::Get, "/"), Route::Landing);
+        assert_eq!(route(Method::Get, "/test"), Route::BoardRedirect(board()));
+        assert_eq!(route(Method::Get, "/test/"), Route::Index(board(), 1));
+        assert_eq!(route(Method::Get, "/test/2"), Route::Index(board(), 2));
+        assert_eq!(route(Method::Get, "/test/1"), Route::NotFound);
+        assert_eq!(route(Method::Get, "/test/0"), Route::NotFound);
+        assert_eq!(
+            route(Method::Get, "/%CE%B1/"),
+            Route::Index("\u{3B1}".to_string(), 1)
+        );
+        assert_eq!(
+            route(Method::Get, "/test/thread/12"),
+            Route::Thread(board(), 12)
+        );
+        assert_eq!(
+            route(Method::Get, "/test/src/12-0.png"),
+            Route::Source(board(), "12-0.png".to_string())
+        );
+        assert_eq!(
+            route(Method::Get, "/test/thumb/12-0.op.jpg"),
+            Route::Thumbnail(board(), "12-0.op.jpg".to_string())
+        );
+        assert_eq!(route(Method::Get, "/test/catalog"), Route::Catalog(board()));
+        assert_eq!(route(Method::Get, "/test/archive"), Route::Archive(board()));
+        assert_eq!(route(Method::Post, "/test/post"), Route::Post(board()));
+        assert_eq!(
+            route(Method::Post, "/test/delete"),
+            Route::DeletePost(board())
+        );
+        assert_eq!(
+            route(Method::Get, "/test/edit/12"),
+            Route::EditForm(board(), 12)
+        );
+        assert_eq!(route(Method::Get, "/test/edit/x"), Route::NotFound);
+        assert_eq!(route(Method::Post, "/test/edit"), Route::EditPost(board()));
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let board = || "test".to_string();
+        assert_eq!(route(Method::Get, "/boards.json"), Route::BoardsJson);
+        assert_eq!(
+            route(Method::Get, "/test/catalog.json"),
+            Route::CatalogJson(board())
+        );
+        assert_eq!(
+            route(Method::Get, "/test/threads.json"),
+            Route::ThreadsJson(board())
+        );
+        assert_eq!(
+            route(Method::Get, "/test/thread/12.json"),
+            Route::ThreadJson(board(), 12)
+        );
+        assert_eq!(
+            route(Method::Get, "/test/archive.json"),
+            Route::ArchiveJson(board())
+        );
+        assert_eq!(route(Method::Get, "/test/boards.json"), Route::NotFound);
+        assert_eq!(route(Method::Get, "/test/thread/x.json"), Route::NotFound);
+        assert_eq!(route(Method::Post, "/boards.json"), Route::NotFound);
+    }
+
+    #[test]
+    fn test_read_catalog_query() {
+        assert_eq!(
+            read_catalog_query("/test/catalog?sort=last_reply&q=+a+b+"),
+            (CatalogSort::LastReply, "a b".to_string())
+        );
+        assert_eq!(
+            read_catalog_query("/test/catalog?sort=nope"),
+            (CatalogSort::Bump, String::new())
+        );
+    }
+
+    #[test]
+    fn test_parse_api() {
+        let board = || "test".to_string();
+        assert_eq!(
+            route(Method::Post, "/test/api/post"),
+            Route::Api(board(), Scope::Post)
+        );
+        assert_eq!(
+            route(Method::Post, "/test/api/ban"),
+            Route::Api(board(), Scope::Ban)
+        );
+        assert_eq!(route(Method::Post, "/test/api/nuke"), Route::NotFound);
+        assert_eq!(route(Method::Get, "/test/api/post"), Route::NotFound);
+    }
+
+    #[test]
+    fn test_parse_not_found() {
+        assert_eq!(route(Method::Get, "/other/"), Route::NotFound);
+        assert_eq!(route(Method::Get, "/test/thread/abc"), Route::NotFound);
+        assert_eq!(route(Method::Get, "/test/thread/12/more"), Route::NotFound);
+        assert_eq!(route(Method::Get, "/test/post"), Route::NotFound);
+        assert_eq!(route(Method::Post, "/"), Route::NotFound);
+        assert_eq!(route(Method::Post, "/test/"), Route::NotFound);
+    }
+}
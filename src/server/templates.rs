@@ -0,0 +1,202 @@
+// HTML templates for every page. The defaults are compiled in, so the server works out of the box,
+// and any of them can be replaced by a file of the same name in the data directory's templates
+// folder. Replacements are read when the server starts.
+// Templates are plain HTML with a few mustache-like tags:
+// {{key}}              the value, escaped
+// {{{key}}}            the value as is, only ever used for HTML the server built itself
+// {{#key}}...{{/key}}  only shown if the value isn't empty
+// {{^key}}...{{/key}}  only shown if the value is empty
+// Keys without a value are empty. A section can't hold another section with the same key.
+use crate::core::markup::render::escape;
+use crate::core::storage::config::Config;
+use crate::core::storage::site_storage;
+use std::collections::HashMap;
+use std::io::Error;
+use std::string::String;
+
+const DEFAULTS: [(&str, &str); 18] = [
+    ("layout", include_str!("templates/layout.html")),
+    ("landing", include_str!("templates/landing.html")),
+    (
+        "landing_board",
+        include_str!("templates/landing_board.html"),
+    ),
+    ("index", include_str!("templates/index.html")),
+    ("index_thread", include_str!("templates/index_thread.html")),
+    (
+        "index_page_link",
+        include_str!("templates/index_page_link.html"),
+    ),
+    ("thread", include_str!("templates/thread.html")),
+    ("post", include_str!("templates/post.html")),
+    ("attachment", include_str!("templates/attachment.html")),
+    ("post_form", include_str!("templates/post_form.html")),
+    (
+        "post_rejected",
+        include_str!("templates/post_rejected.html"),
+    ),
+    ("catalog", include_str!("templates/catalog.html")),
+    (
+        "catalog_thread",
+        include_str!("templates/catalog_thread.html"),
+    ),
+    ("archive", include_str!("templates/archive.html")),
+    (
+        "archive_thread",
+        include_str!("templates/archive_thread.html"),
+    ),
+    ("delete_form", include_str!("templates/delete_form.html")),
+    ("edit", include_str!("templates/edit.html")),
+    ("error", include_str!("templates/error.html")),
+];
+
+pub(crate) struct Templates {
+    templates: HashMap<String, String>,
+}
+
+impl Templates {
+    pub fn new() -> Templates {
+        // only the compiled in defaults
+        let templates: Templates = Templates {
+            templates: DEFAULTS
+                .iter()
+                .map(|(name, template)| (name.to_string(), template.to_string()))
+                .collect(),
+        };
+
+        return templates;
+    }
+
+    pub fn load(config: &Config) -> Result<Templates, Error> {
+        // the defaults, with whatever the operator replaced
+        let mut templates: Templates = Templates::new();
+        for (name, _) in DEFAULTS {
+            if let Some(template) = site_storage::read_template(config, name)? {
+                templates.templates.insert(name.to_string(), template);
+            }
+        }
+
+        return Ok(templates);
+    }
+
+    pub fn render(&self, name: &str, values: &[(&str, &str)]) -> String {
+        // every template name is compiled in, so a missing one is a bug
+        let template: &String = &self.templates[name];
+        let values: HashMap<&str, &str> = values.iter().copied().collect();
+        return fill(template, &values);
+    }
+}
+
+fn fill(template: &str, values: &HashMap<&str, &str>) -> String {
+    let value = |key: &str| -> &str { values.get(key.trim()).copied().unwrap_or_default() };
+    let mut filled: String = String::with_capacity(template.len());
+    let mut rest: &str = template;
+
+    while let Some(start) = rest.find("{{") {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(tag) = rest.strip_prefix("{{{") {
+            let Some(end) = tag.find("}}}") else {
+                break;
+            };
+            filled.push_str(value(&tag[..end]));
+            rest = &tag[end + 3..];
+            continue;
+        }
+
+        let tag: &str = &rest[2..];
+        let Some(end) = tag.find("}}") else {
+            break;
+        };
+        let key: &str = tag[..end].trim();
+        rest = &tag[end + 2..];
+
+        let shown: bool = match (key.strip_prefix('#'), key.strip_prefix('^')) {
+            (Some(section), _) => !value(section).is_empty(),
+            (_, Some(section)) => value(section).is_empty(),
+            _ => {
+                // stray closing tags are dropped
+                if !key.starts_with('/') {
+                    filled.push_str(&escape(value(key)));
+                }
+                continue;
+            }
+        };
+
+        let close: String = format!("{{{{/{}}}}}", key[1..].trim());
+        let (inner, after): (&str, &str) = match rest.find(&close) {
+            Some(end) => (&rest[..end], &rest[end + close.len()..]),
+            None => (rest, ""), // an unclosed section runs to the end
+        };
+        if shown {
+            filled.push_str(&fill(inner, values));
+        }
+        rest = after;
+    }
+
+    filled.push_str(rest);
+    return filled;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::storage::fixtures::TestRoot;
+    use std::fs::{create_dir_all, write};
+
+    fn fill_with(template: &str, values: &[(&str, &str)]) -> String {
+        return fill(template, &values.iter().copied().collect());
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(fill_with("no tags", &[]), "no tags");
+        assert_eq!(fill_with("<p>{{ a }}</p>", &[("a", "1")]), "<p>1</p>");
+        assert_eq!(fill_with("{{a}}", &[("a", "<b>")]), "&lt;b&gt;");
+        assert_eq!(fill_with("{{{a}}}", &[("a", "<b>")]), "<b>");
+        assert_eq!(fill_with("[{{missing}}]", &[]), "[]");
+        assert_eq!(fill_with("unclosed {{a", &[("a", "1")]), "unclosed {{a");
+    }
+
+    #[test]
+    fn test_fill_sections() {
+        let template = "{{#a}}a is {{a}}{{/a}}{{^a}}no a{{/a}}";
+        assert_eq!(fill_with(template, &[("a", "1")]), "a is 1");
+        assert_eq!(fill_with(template, &[("a", "")]), "no a");
+        assert_eq!(fill_with(template, &[]), "no a");
+        assert_eq!(
+            fill_with("{{#a}}{{#b}}both{{/b}}{{/a}}", &[("a", "1"), ("b", "1")]),
+            "both"
+        );
+        assert_eq!(fill_with("{{#a}}open", &[("a", "1")]), "open");
+        assert_eq!(fill_with("stray{{/a}}", &[]), "stray");
+    }
+
+    #[test]
+    fn test_render() {
+        let templates = Templates::new();
+        let html = templates.render("error", &[("title", "Oops"), ("message", "<nope>")]);
+        assert!(html.contains("<h1>Oops</h1>"));
+        assert!(html.contains("&lt;nope&gt;"));
+    }
+
+    #[test]
+    fn test_load() {
+        let config = TestRoot::new("templates", "load");
+        let root = config.get_root();
+        create_dir_all(root.join("templates")).unwrap();
+        write(
+            root.join("templates").join("error.html"),
+            "custom {{title}}",
+        )
+        .unwrap();
+
+        let templates = Templates::load(&config).unwrap();
+        assert_eq!(
+            templates.render("error", &[("title", "Oops")]),
+            "custom Oops"
+        );
+        assert!(templates.render("landing", &[]).contains("<h1>Boards</h1>"));
+    }
+}
@@ -0,0 +1,707 @@
+// The write API, for bots and integrations. Every request is a POST to /{board}/api/{action}, with
+// an API token in an `Authorization: Bearer` header, and the action has to be one of the token's
+// scopes:
+// post    a thread or a reply. takes the post form as it is, or JSON with the files base64 encoded:
+//         {"thread", "name", "subject", "options", "comment", "password", "spoiler",
+//          "files": [{"name", "data"}]}
+// lock    {"thread", "locked"}
+// sticky  {"thread", "sticky", "priority", "hours"} with 0 hours for a sticky that never expires
+// delete  {"post", "deleted"} where deleted false restores the post
+// ban     {"post", "reason", "hours"} bans whoever made the post, 0 hours never expires
+// The flags default to true, and everything else that's left out is empty or 0.
+// Rejections are JSON too: {"error": code, "message": text}, with retry_after in seconds for
+// cooldowns and rate limits, and expires in unix seconds for temporary bans. The codes are stable,
+// the messages are only meant for people. Unlike the read API, nothing here is open to scripts
+// on other sites.
+// API posts are owned by their token rather than an owner cookie, and cooldowns are counted per
+// token, so bots sharing an address don't hold each other up. Bans still go by IP.
+use super::form::{self, FormFile, PostForm};
+use super::http;
+use super::pages;
+use super::routes;
+use super::State;
+use crate::core::api_tokens;
+use crate::core::bans;
+use crate::core::creation::CreateError;
+use crate::core::deletion;
+use crate::core::media::validation::UploadError;
+use crate::core::posting::PostingError;
+use crate::core::storage::board_storage;
+use crate::core::storage::post_storage;
+use crate::core::storage::site_storage;
+use crate::core::storage::thread_storage;
+use crate::core::structs::api_token::{ApiToken, Scope};
+use crate::core::structs::ban::Ban;
+use crate::core::structs::board::Board;
+use crate::core::structs::post::Post;
+use crate::core::structs::thread::{Thread, ThreadError};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::string::String;
+use std::sync::PoisonError;
+use std::time::{Duration, SystemTime};
+use std::vec::Vec;
+use tiny_http::{Request, ResponseBox};
+
+const MAX_ACTION_SIZE: usize = 64 * 1024; // everything but posts
+const SECONDS_PER_HOUR: u64 = 60 * 60;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct ApiError {
+    #[serde(skip)]
+    pub status: u16,
+    pub error: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+}
+
+impl ApiError {
+    pub fn new(status: u16, error: &'static str, message: &str) -> ApiError {
+        let api_error: ApiError = ApiError {
+            status,
+            error,
+            message: message.to_string(),
+            retry_after: None,
+            expires: None,
+        };
+
+        return api_error;
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> ApiError {
+        return match error.kind() {
+            ErrorKind::NotFound => ApiError::new(404, "not_found", "That doesn't exist."),
+            _ => {
+                eprintln!("write API: {}", error);
+                ApiError::new(500, "storage", "Something went wrong, please try again.")
+            }
+        };
+    }
+}
+
+impl From<CreateError> for ApiError {
+    fn from(error: CreateError) -> ApiError {
+        let message: String = error.to_string();
+        let (status, code): (u16, &'static str) = match &error {
+            CreateError::Banned(_) => (403, "banned"),
+            CreateError::Posting(PostingError::Cooldown { .. }) => (429, "cooldown"),
+            CreateError::Posting(error) => (400, get_posting_code(error)),
+            CreateError::Upload(UploadError::Storage(kind)) => {
+                return ApiError::from(Error::from(*kind))
+            }
+            CreateError::Upload(error) => (400, get_upload_code(error)),
+            CreateError::ThreadNotFound => (404, "thread_not_found"),
+            CreateError::Thread(ThreadError::Locked) => (403, "thread_locked"),
+            CreateError::Thread(ThreadError::ReplyLimit { .. }) => (403, "reply_limit"),
+            CreateError::Thread(ThreadError::NotNewer { .. }) => {
+                return ApiError::from(Error::new(ErrorKind::InvalidData, message))
+            }
+            CreateError::Storage(kind) => return ApiError::from(Error::from(*kind)),
+        };
+
+        let mut api_error: ApiError = ApiError::new(status, code, &message);
+        match error {
+            CreateError::Posting(PostingError::Cooldown { remaining }) => {
+                api_error.retry_after = Some(remaining);
+            }
+            CreateError::Banned(ban) => {
+                api_error.expires = ban.get_expires().as_ref().map(pages::get_seconds);
+            }
+            _ => (),
+        }
+
+        return api_error;
+    }
+}
+
+fn get_posting_code(error: &PostingError) -> &'static str {
+    return match error {
+        PostingError::Empty => "empty",
+        PostingError::TextTooLong { .. } => "text_too_long",
+        PostingError::NameTooLong { .. } => "name_too_long",
+        PostingError::SubjectTooLong { .. } => "subject_too_long",
+        PostingError::TooManyLines { .. } => "too_many_lines",
+        PostingError::TooManyFiles { .. } => "too_many_files",
+        PostingError::ImageRequired => "image_required",
+        PostingError::SubjectRequired => "subject_required",
+        PostingError::Cooldown { .. } => "cooldown",
+    };
+}
+
+fn get_upload_code(error: &UploadError) -> &'static str {
+    return match error {
+        UploadError::Empty => "file_empty",
+        UploadError::UnknownType => "file_unknown_type",
+        UploadError::MissingExtension => "file_missing_extension",
+        UploadError::ExtensionMismatch { .. } => "file_extension_mismatch",
+        UploadError::TypeNotAllowed(_) => "file_type_not_allowed",
+        UploadError::TooLarge { .. } => "file_too_large",
+        UploadError::DimensionsTooLarge { .. } => "file_dimensions_too_large",
+        UploadError::Unreadable(_) => "file_unreadable",
+        UploadError::TooManyFiles { .. } => "too_many_files",
+        UploadError::Banned => "file_banned",
+        UploadError::Storage(_) => "storage",
+    };
+}
+
+// a file in a JSON post
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub(crate) struct ApiUpload {
+    pub name: String,
+    pub data: String, // base64
+}
+
+// a post sent as JSON, the same fields as the post form
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub(crate) struct ApiPostRequest {
+    pub thread: Option<u64>,
+    pub name: String,
+    pub subject: String,
+    pub options: String,
+    pub comment: String,
+    pub password: String,
+    pub spoiler: bool,
+    pub files: Vec<ApiUpload>,
+}
+
+impl ApiPostRequest {
+    pub fn into_form(self) -> Option<PostForm> {
+        // None if a file isn't valid base64
+        let mut files: Vec<FormFile> = Vec::new();
+        for file in self.files {
+            files.push(FormFile {
+                name: file.name,
+                data: STANDARD.decode(file.data).ok()?,
+            });
+        }
+
+        let form: PostForm = PostForm {
+            thread: self
+                .thread
+                .map(|thread| thread.to_string())
+                .unwrap_or_default(),
+            name: self.name,
+            subject: self.subject,
+            options: self.options,
+            comment: self.comment,
+            password: self.password,
+            spoiler: self.spoiler,
+            files,
+        };
+
+        return Some(form);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+struct LockRequest {
+    thread: u64,
+    #[serde(default = "yes")]
+    locked: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+struct StickyRequest {
+    thread: u64,
+    #[serde(default = "yes")]
+    sticky: bool,
+    #[serde(default)]
+    priority: u16,
+    #[serde(default)]
+    hours: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+struct DeleteRequest {
+    post: u64,
+    #[serde(default = "yes")]
+    deleted: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+struct BanRequest {
+    post: u64,
+    #[serde(default)]
+    reason: String,
+    #[serde(default)]
+    hours: u64,
+}
+
+fn yes() -> bool {
+    return true;
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct Posted {
+    thread: u64,
+    post: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct Locked {
+    thread: u64,
+    locked: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct Stickied {
+    thread: u64,
+    sticky: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct Deleted {
+    post: u64,
+    deleted: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct Banned {
+    post: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires: Option<u64>,
+}
+
+pub(crate) fn handle(
+    state: &State,
+    request: &mut Request,
+    board: &str,
+    scope: Scope,
+    now: SystemTime,
+) -> ResponseBox {
+    let response: Result<ResponseBox, ApiError> =
+        authorize(state, request, scope, now).and_then(|token| match scope {
+            Scope::Post => post(state, request, board, &token, now),
+            Scope::Lock => lock(state, request, board),
+            Scope::Sticky => sticky(state, request, board, now),
+            Scope::Delete => delete(state, request, board),
+            Scope::Ban => ban(state, request, board, now),
+        });
+
+    return match response {
+        Ok(response) => response,
+        Err(error) => http::private_json(error.status, to_json(&error)),
+    };
+}
+
+fn authorize(
+    state: &State,
+    request: &Request,
+    scope: Scope,
+    now: SystemTime,
+) -> Result<ApiToken, ApiError> {
+    let given: &str = http::get_header(request, "Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .ok_or(ApiError::new(
+            401,
+            "unauthorized",
+            "This needs an API token.",
+        ))?;
+    let tokens: Vec<ApiToken> = site_storage::read_api_tokens(state.get_config())?;
+    let token: ApiToken = api_tokens::find_token(&tokens, given)
+        .ok_or(ApiError::new(
+            401,
+            "unauthorized",
+            "That API token isn't valid.",
+        ))?
+        .clone();
+
+    if !token.has_scope(scope) {
+        return Err(ApiError::new(
+            403,
+            "forbidden",
+            &format!("That API token doesn't have the {} scope.", scope),
+        ));
+    }
+
+    let limited: Result<(), u64> = state
+        .get_rate_limits()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .check(&token, now);
+    if let Err(retry_after) = limited {
+        let mut error: ApiError = ApiError::new(
+            429,
+            "rate_limited",
+            &format!(
+                "That API token is over its rate limit, please wait {} more seconds.",
+                retry_after
+            ),
+        );
+        error.retry_after = Some(retry_after);
+        return Err(error);
+    }
+
+    return Ok(token);
+}
+
+fn post(
+    state: &State,
+    request: &mut Request,
+    board_name: &str,
+    token: &ApiToken,
+    now: SystemTime,
+) -> Result<ResponseBox, ApiError> {
+    let board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    let content_type: String = http::get_header(request, "Content-Type")
+        .unwrap_or_default()
+        .to_string();
+    let body: Vec<u8> = read_body(request, routes::get_body_limit(board.get_config()))?;
+
+    let unreadable = || ApiError::new(400, "bad_request", "That post couldn't be read.");
+    let form: PostForm = match is_json(&content_type) {
+        true => serde_json::from_slice::<ApiPostRequest>(&body)
+            .map_err(|error| ApiError::new(400, "bad_request", &error.to_string()))?
+            .into_form()
+            .ok_or_else(unreadable)?,
+        false => form::parse(&content_type, &body).ok_or_else(unreadable)?,
+    };
+
+    let client: String = format!("api:{}", token.get_name());
+    let ip: String = http::get_ip(request);
+    let (thread, post): (u64, u64) =
+        routes::submit(state, board_name, &form, &ip, &client, &client, now)?;
+
+    return Ok(http::private_json(201, to_json(&Posted { thread, post })));
+}
+
+fn lock(state: &State, request: &mut Request, board: &str) -> Result<ResponseBox, ApiError> {
+    let lock_request: LockRequest = read_json(request)?;
+    let _writing = state
+        .get_cooldowns()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let _locked: File = board_storage::lock_boards(state.get_config())?;
+
+    let mut thread: Thread = read_live_thread(state, board, lock_request.thread)?;
+    match lock_request.locked {
+        true => thread.lock(),
+        false => thread.unlock(),
+    }
+    thread_storage::write_thread(state.get_config(), board, &thread)?;
+
+    return Ok(http::private_json(
+        200,
+        to_json(&Locked {
+            thread: lock_request.thread,
+            locked: lock_request.locked,
+        }),
+    ));
+}
+
+fn sticky(
+    state: &State,
+    request: &mut Request,
+    board_name: &str,
+    now: SystemTime,
+) -> Result<ResponseBox, ApiError> {
+    // stickying a thread again just changes its priority and expiry
+    let sticky_request: StickyRequest = read_json(request)?;
+    let thread: u64 = sticky_request.thread;
+    let expires: Option<SystemTime> = get_expiry(sticky_request.hours, now)?;
+    let _writing = state
+        .get_cooldowns()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let _locked: File = board_storage::lock_boards(state.get_config())?;
+
+    let mut board: Board = board_storage::read_board(state.get_config(), board_name)?;
+    if !board.get_active().contains(&thread) {
+        return Err(ApiError::new(
+            404,
+            "thread_not_found",
+            "That isn't an active thread.",
+        ));
+    }
+    match sticky_request.sticky {
+        true => {
+            if !board.modify_sticky(thread, sticky_request.priority, expires) {
+                board.add_sticky(thread, sticky_request.priority, expires);
+            }
+        }
+        false => {
+            board.remove_sticky(thread);
+        }
+    }
+    board_storage::write_board(state.get_config(), &board)?;
+
+    return Ok(http::private_json(
+        200,
+        to_json(&Stickied {
+            thread,
+            sticky: sticky_request.sticky,
+        }),
+    ));
+}
+
+fn delete(state: &State, request: &mut Request, board: &str) -> Result<ResponseBox, ApiError> {
+    // deleting a deleted post, or restoring one that's up, changes nothing
+    let delete_request: DeleteRequest = read_json(request)?;
+    let id: u64 = delete_request.post;
+    let _writing = state
+        .get_cooldowns()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let _locked: File = board_storage::lock_boards(state.get_config())?;
+
+    let mut post: Post = read_post(state, board, id)?;
+    match (delete_request.deleted, post.get_deleted()) {
+        (true, false) => deletion::delete_post(state.get_config(), board, id, &mut post)?,
+        (false, true) => deletion::restore_post(state.get_config(), board, id, &mut post)?,
+        _ => (),
+    }
+
+    return Ok(http::private_json(
+        200,
+        to_json(&Deleted {
+            post: id,
+            deleted: delete_request.deleted,
+        }),
+    ));
+}
+
+fn ban(
+    state: &State,
+    request: &mut Request,
+    board: &str,
+    now: SystemTime,
+) -> Result<ResponseBox, ApiError> {
+    // the poster's IP stays private, even to the token doing the banning
+    let ban_request: BanRequest = read_json(request)?;
+    let expires: Option<SystemTime> = get_expiry(ban_request.hours, now)?;
+    let _writing = state
+        .get_cooldowns()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let _locked: File = board_storage::lock_boards(state.get_config())?;
+
+    read_post(state, board, ban_request.post)?;
+    let ban: Ban = match bans::ban_poster(
+        state.get_config(),
+        board,
+        ban_request.post,
+        &ban_request.reason,
+        expires,
+        now,
+    ) {
+        Ok(ban) => ban,
+        Err(error) if error.kind() == ErrorKind::InvalidData => {
+            return Err(ApiError::new(
+                409,
+                "no_ip",
+                "That post is too old to have an IP to ban.",
+            ))
+        }
+        Err(error) => return Err(ApiError::from(error)),
+    };
+
+    return Ok(http::private_json(
+        200,
+        to_json(&Banned {
+            post: ban_request.post,
+            expires: ban.get_expires().as_ref().map(pages::get_seconds),
+        }),
+    ));
+}
+
+fn read_live_thread(state: &State, board: &str, thread: u64) -> Result<Thread, ApiError> {
+    let not_found = || ApiError::new(404, "thread_not_found", "That thread doesn't exist.");
+    if !thread_storage::thread_exists(state.get_config(), board, thread) {
+        return Err(not_found());
+    }
+
+    let read: Thread = thread_storage::read_thread(state.get_config(), board, thread)?;
+    return match read.get_deleted() {
+        true => Err(not_found()),
+        false => Ok(read),
+    };
+}
+
+fn read_post(state: &State, board: &str, id: u64) -> Result<Post, ApiError> {
+    // deleted posts are still there to be restored or banned over
+    if !post_storage::post_exists(state.get_config(), board, id) {
+        return Err(ApiError::new(
+            404,
+            "post_not_found",
+            "That post doesn't exist.",
+        ));
+    }
+
+    return Ok(post_storage::read_post(state.get_config(), board, id)?);
+}
+
+fn read_body(request: &mut Request, limit: usize) -> Result<Vec<u8>, ApiError> {
+    return http::read_body(request, limit)?.ok_or(ApiError::new(
+        413,
+        "too_large",
+        "That request is too large to take.",
+    ));
+}
+
+fn read_json<T: DeserializeOwned>(request: &mut Request) -> Result<T, ApiError> {
+    let content_type: &str = http::get_header(request, "Content-Type").unwrap_or_default();
+    if !is_json(content_type) {
+        return Err(ApiError::new(
+            415,
+            "bad_content_type",
+            "Moderator actions are sent as JSON.",
+        ));
+    }
+
+    let body: Vec<u8> = read_body(request, MAX_ACTION_SIZE)?;
+    return serde_json::from_slice(&body)
+        .map_err(|error| ApiError::new(400, "bad_request", &error.to_string()));
+}
+
+fn get_expiry(hours: u64, now: SystemTime) -> Result<Option<SystemTime>, ApiError> {
+    // 0 hours never expires
+    if hours == 0 {
+        return Ok(None);
+    }
+    let too_long = || ApiError::new(400, "bad_request", "That expiry is too far off.");
+    let seconds: u64 = hours.checked_mul(SECONDS_PER_HOUR).ok_or_else(too_long)?;
+    return match now.checked_add(Duration::from_secs(seconds)) {
+        Some(expires) => Ok(Some(expires)),
+        None => Err(too_long()),
+    };
+}
+
+fn is_json(content_type: &str) -> bool {
+    let mime: &str = content_type.split(';').next().unwrap_or_default();
+    return mime.trim().eq_ignore_ascii_case("application/json");
+}
+
+fn to_json<T: Serialize>(data: &T) -> String {
+    // none of the responses have maps with non-string keys, which is the only way this can fail
+    return serde_json::to_string(data).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_form() {
+        let request: ApiPostRequest = serde_json::from_str(
+            r#"{"thread": 12, "comment": "hi", "options": "sage",
+                "files": [{"name": "a.pdf", "data": "JVBERi0xLjcK"}]}"#,
+        )
+        .unwrap();
+        let form = request.into_form().unwrap();
+
+        assert_eq!(form.thread, "12");
+        assert_eq!(form.comment, "hi");
+        assert!(form.get_sage());
+        assert!(!form.spoiler);
+        assert_eq!(form.files[0].name, "a.pdf");
+        assert_eq!(form.files[0].data, b"%PDF-1.7\n");
+
+        let new_thread: ApiPostRequest = serde_json::from_str(r#"{"subject": "s"}"#).unwrap();
+        assert_eq!(new_thread.into_form().unwrap().thread, "");
+
+        let bad_file = ApiPostRequest {
+            files: vec![ApiUpload {
+                name: "a.pdf".to_string(),
+                data: "not base64!".to_string(),
+            }],
+            ..ApiPostRequest::default()
+        };
+        assert_eq!(bad_file.into_form(), None);
+    }
+
+    #[test]
+    fn test_requests_defaults() {
+        let lock: LockRequest = serde_json::from_str(r#"{"thread": 1}"#).unwrap();
+        assert!(lock.locked);
+        let sticky: StickyRequest = serde_json::from_str(r#"{"thread": 1}"#).unwrap();
+        assert!(sticky.sticky);
+        assert_eq!((sticky.priority, sticky.hours), (0, 0));
+        let delete: DeleteRequest =
+            serde_json::from_str(r#"{"post": 1, "deleted": false}"#).unwrap();
+        assert!(!delete.deleted);
+        assert!(serde_json::from_str::<BanRequest>(r#"{"reason": "spam"}"#).is_err());
+    }
+
+    #[test]
+    fn test_get_expiry() {
+        let now = SystemTime::UNIX_EPOCH;
+        assert_eq!(get_expiry(0, now).unwrap(), None);
+        assert_eq!(
+            get_expiry(2, now).unwrap(),
+            Some(now + Duration::from_secs(2 * SECONDS_PER_HOUR))
+        );
+
+        // too many seconds to count, and too far off for the clock
+        for hours in [u64::MAX, u64::MAX / SECONDS_PER_HOUR] {
+            let error = get_expiry(hours, now).unwrap_err();
+            assert_eq!((error.status, error.error), (400, "bad_request"));
+        }
+    }
+
+    #[test]
+    fn test_create_errors() {
+        let cooldown = ApiError::from(CreateError::Posting(PostingError::Cooldown {
+            remaining: 12,
+        }));
+        assert_eq!((cooldown.status, cooldown.error), (429, "cooldown"));
+        assert_eq!(cooldown.retry_after, Some(12));
+
+        let empty = ApiError::from(CreateError::Posting(PostingError::Empty));
+        assert_eq!((empty.status, empty.error), (400, "empty"));
+        assert_eq!(empty.message, PostingError::Empty.to_string());
+
+        let banned = ApiError::from(CreateError::Banned(Ban::new(
+            "192.0.2.1".to_string(),
+            "spam".to_string(),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(60)),
+        )));
+        assert_eq!((banned.status, banned.error), (403, "banned"));
+        assert_eq!(banned.expires, Some(60));
+
+        let upload = ApiError::from(CreateError::Upload(UploadError::TooLarge {
+            size: 2,
+            limit: 1,
+        }));
+        assert_eq!(upload.error, "file_too_large");
+        let locked = ApiError::from(CreateError::Thread(ThreadError::Locked));
+        assert_eq!((locked.status, locked.error), (403, "thread_locked"));
+        let missing = ApiError::from(CreateError::ThreadNotFound);
+        assert_eq!((missing.status, missing.error), (404, "thread_not_found"));
+        let storage = ApiError::from(CreateError::Storage(ErrorKind::PermissionDenied));
+        assert_eq!((storage.status, storage.error), (500, "storage"));
+        let gone = ApiError::from(CreateError::Upload(UploadError::Storage(
+            ErrorKind::NotFound,
+        )));
+        assert_eq!((gone.status, gone.error), (404, "not_found"));
+    }
+
+    #[test]
+    fn test_error_json() {
+        let mut error = ApiError::new(429, "rate_limited", "Slow down.");
+        error.retry_after = Some(3);
+        assert_eq!(
+            to_json(&error),
+            r#"{"error":"rate_limited","message":"Slow down.","retry_after":3}"#
+        );
+    }
+
+    #[test]
+    fn test_is_json() {
+        assert!(is_json("application/json"));
+        assert!(is_json("Application/JSON; charset=utf-8"));
+        assert!(!is_json("multipart/form-data; boundary=x"));
+        assert!(!is_json(""));
+    }
+}